@@ -0,0 +1,296 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma};
+
+use crate::api::imageproc_ops::LumeRect;
+use crate::helpers::{self, Image};
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// Exposure summary. Percentages are 0-100 of all pixels; `mean_luminance`
+/// is on the 0-255 scale.
+pub struct LumeExposure {
+    pub clipped_highlights_pct: f64,
+    pub clipped_shadows_pct: f64,
+    pub mean_luminance: f64,
+}
+
+// ---------------------------------------------------------------------------
+// Saliency
+// ---------------------------------------------------------------------------
+
+/// Side of the square the spectral residual is computed on. The method works
+/// on a coarse version of the image by design.
+const SALIENCY_SIZE: usize = 64;
+
+/// In-place 2D DFT of a square row-major complex grid (`inverse` flips the
+/// sign of the exponent and scales by 1/n²). A naive transform is plenty at
+/// `SALIENCY_SIZE`.
+fn dft2(re: &mut [f32], im: &mut [f32], n: usize, inverse: bool) {
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let twiddles: Vec<(f32, f32)> = (0..n)
+        .map(|k| {
+            let angle = sign * 2.0 * std::f32::consts::PI * k as f32 / n as f32;
+            (angle.cos(), angle.sin())
+        })
+        .collect();
+    let mut line_re = vec![0.0; n];
+    let mut line_im = vec![0.0; n];
+    // Rows, then columns.
+    for pass in 0..2 {
+        for line in 0..n {
+            let index = |i: usize| {
+                if pass == 0 {
+                    line * n + i
+                } else {
+                    i * n + line
+                }
+            };
+            for (k, (out_re, out_im)) in line_re.iter_mut().zip(line_im.iter_mut()).enumerate() {
+                let (mut sr, mut si) = (0.0, 0.0);
+                for t in 0..n {
+                    let (c, s) = twiddles[(k * t) % n];
+                    let (xr, xi) = (re[index(t)], im[index(t)]);
+                    sr += xr * c - xi * s;
+                    si += xr * s + xi * c;
+                }
+                *out_re = sr;
+                *out_im = si;
+            }
+            for i in 0..n {
+                re[index(i)] = line_re[i];
+                im[index(i)] = line_im[i];
+            }
+        }
+    }
+    if inverse {
+        let scale = 1.0 / (n * n) as f32;
+        re.iter_mut().chain(im.iter_mut()).for_each(|v| *v *= scale);
+    }
+}
+
+/// Spectral residual saliency at `SALIENCY_SIZE` x `SALIENCY_SIZE`, scaled
+/// so the most salient point is 255.
+pub(crate) fn saliency_map(img: &DynamicImage) -> GrayImage {
+    let n = SALIENCY_SIZE;
+    let small = img
+        .resize_exact(n as u32, n as u32, FilterType::Triangle)
+        .to_luma8();
+
+    let mut re: Vec<f32> = small.pixels().map(|p| p.0[0] as f32 / 255.0).collect();
+    let mut im = vec![0.0; n * n];
+    dft2(&mut re, &mut im, n, false);
+
+    // Spectral residual: log amplitude minus its local (3x3) average.
+    let log_amp: Vec<f32> = re
+        .iter()
+        .zip(&im)
+        .map(|(r, i)| (r.hypot(*i) + 1e-9).ln())
+        .collect();
+    let phase: Vec<f32> = re.iter().zip(&im).map(|(r, i)| i.atan2(*r)).collect();
+    for y in 0..n {
+        for x in 0..n {
+            let mut sum = 0.0;
+            for dy in [n - 1, 0, 1] {
+                for dx in [n - 1, 0, 1] {
+                    sum += log_amp[((y + dy) % n) * n + (x + dx) % n];
+                }
+            }
+            let residual = (log_amp[y * n + x] - sum / 9.0).exp();
+            re[y * n + x] = residual * phase[y * n + x].cos();
+            im[y * n + x] = residual * phase[y * n + x].sin();
+        }
+    }
+    dft2(&mut re, &mut im, n, true);
+
+    let energy: Image<Luma<f32>> = ImageBuffer::from_fn(n as u32, n as u32, |x, y| {
+        let i = (y as usize) * n + x as usize;
+        Luma([re[i] * re[i] + im[i] * im[i]])
+    });
+    let smoothed = imageproc::filter::gaussian_blur_f32(&energy, 2.5);
+    let max = smoothed
+        .pixels()
+        .map(|p| p.0[0])
+        .fold(0.0, f32::max)
+        .max(1e-12);
+    GrayImage::from_fn(n as u32, n as u32, |x, y| {
+        Luma([(smoothed.get_pixel(x, y).0[0] / max * 255.0).round() as u8])
+    })
+}
+
+/// Saliency map using the spectral residual method (Hou & Zhang, 2007):
+/// bright areas are the ones that stand out from the image's statistically
+/// expected content. Useful for smart cropping and attention heatmaps.
+#[flutter_rust_bridge::frb(sync)]
+pub fn saliency(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let map = saliency_map(&img);
+    let out = image::imageops::resize(&map, img.width(), img.height(), FilterType::Triangle);
+    helpers::encode(&DynamicImage::ImageLuma8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Quality scores
+// ---------------------------------------------------------------------------
+
+/// Focus measure: variance of the 3x3 Laplacian over `region` (or the whole
+/// image). Higher is sharper; the scale depends on content, so compare scores
+/// of similar shots (burst frames) rather than against a fixed threshold.
+#[flutter_rust_bridge::frb(sync)]
+pub fn sharpness_score(image_bytes: Vec<u8>, region: Option<LumeRect>) -> Result<f64> {
+    let gray = helpers::load(&image_bytes)?.to_luma8();
+    let (w, h) = gray.dimensions();
+    let (x, y, rw, rh) = match region {
+        Some(r) => helpers::clip_rect(r.x, r.y, r.width, r.height, w, h),
+        None => helpers::clip_rect(0, 0, w, h, w, h),
+    }
+    .ok_or_else(|| anyhow::anyhow!("Region is outside the image"))?;
+    let area = image::imageops::crop_imm(&gray, x, y, rw, rh).to_image();
+    if rw < 3 || rh < 3 {
+        return Ok(0.0);
+    }
+
+    let at = |x: u32, y: u32| area.get_pixel(x, y).0[0] as f64;
+    let mut sum = 0.0;
+    let mut sum_sq = 0.0;
+    for y in 1..rh - 1 {
+        for x in 1..rw - 1 {
+            let lap = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y);
+            sum += lap;
+            sum_sq += lap * lap;
+        }
+    }
+    let n = ((rw - 2) * (rh - 2)) as f64;
+    let mean = sum / n;
+    Ok(sum_sq / n - mean * mean)
+}
+
+/// Share of blown highlights (any channel at 255 or luma >= 250), crushed
+/// shadows (luma <= 5) and the mean luminance, for "too dark" / "too bright"
+/// warnings before upload.
+#[flutter_rust_bridge::frb(sync)]
+pub fn analyze_exposure(image_bytes: Vec<u8>) -> Result<LumeExposure> {
+    let rgb = helpers::load(&image_bytes)?.to_rgb8();
+    let total = (rgb.width() as u64 * rgb.height() as u64).max(1) as f64;
+    let mut highlights = 0u64;
+    let mut shadows = 0u64;
+    let mut luma_sum = 0.0;
+    for p in rgb.pixels() {
+        let [r, g, b] = p.0;
+        let luma = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+        luma_sum += luma;
+        if r == 255 || g == 255 || b == 255 || luma >= 250.0 {
+            highlights += 1;
+        } else if luma <= 5.0 {
+            shadows += 1;
+        }
+    }
+    Ok(LumeExposure {
+        clipped_highlights_pct: highlights as f64 * 100.0 / total,
+        clipped_shadows_pct: shadows as f64 * 100.0 / total,
+        mean_luminance: luma_sum / total,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Motion
+// ---------------------------------------------------------------------------
+
+/// Blur applied to both frames before differencing, so sensor noise and
+/// compression artifacts do not read as motion.
+const MOTION_BLUR_SIGMA: f32 = 2.0;
+/// Changed pixels are grown by this many pixels so a moving object's
+/// fragments merge into one area.
+const MOTION_MERGE_RADIUS: u8 = 3;
+
+/// Bounds of the changed pixels of one connected area, and their count.
+#[derive(Clone)]
+struct ChangedArea {
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
+    pixels: u32,
+}
+
+/// Areas that changed between two frames of a fixed camera, largest first:
+/// pixels whose blurred luma differs by more than `threshold` (0-255) are
+/// grouped into connected areas, and areas of fewer than `min_area` changed
+/// pixels are dropped. Both frames must have the same size.
+#[flutter_rust_bridge::frb(sync)]
+pub fn detect_motion(
+    prev_bytes: Vec<u8>,
+    next_bytes: Vec<u8>,
+    threshold: u8,
+    min_area: u32,
+) -> Result<Vec<LumeRect>> {
+    let prev = helpers::load(&prev_bytes)?.to_luma8();
+    let next = helpers::load(&next_bytes)?.to_luma8();
+    if prev.dimensions() != next.dimensions() {
+        return Err(anyhow::anyhow!(
+            "Frame sizes differ: {}x{} and {}x{}",
+            prev.width(),
+            prev.height(),
+            next.width(),
+            next.height()
+        ));
+    }
+    let prev = imageproc::filter::gaussian_blur_f32(&prev, MOTION_BLUR_SIGMA);
+    let next = imageproc::filter::gaussian_blur_f32(&next, MOTION_BLUR_SIGMA);
+    let changed = GrayImage::from_fn(prev.width(), prev.height(), |x, y| {
+        let d = prev.get_pixel(x, y).0[0].abs_diff(next.get_pixel(x, y).0[0]);
+        Luma([if d > threshold { 255 } else { 0 }])
+    });
+    let merged = imageproc::morphology::dilate(
+        &changed,
+        imageproc::distance_transform::Norm::LInf,
+        MOTION_MERGE_RADIUS,
+    );
+    let labels = imageproc::region_labelling::connected_components(
+        &merged,
+        imageproc::region_labelling::Connectivity::Eight,
+        Luma([0]),
+    );
+
+    let mut areas: Vec<Option<ChangedArea>> = Vec::new();
+    for (x, y, p) in changed.enumerate_pixels() {
+        if p.0[0] == 0 {
+            continue;
+        }
+        let label = labels.get_pixel(x, y).0[0] as usize;
+        if areas.len() <= label {
+            areas.resize(label + 1, None);
+        }
+        let area = areas[label].get_or_insert(ChangedArea {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+            pixels: 0,
+        });
+        area.min_x = area.min_x.min(x);
+        area.min_y = area.min_y.min(y);
+        area.max_x = area.max_x.max(x);
+        area.max_y = area.max_y.max(y);
+        area.pixels += 1;
+    }
+    let mut found: Vec<(u32, LumeRect)> = areas
+        .into_iter()
+        .flatten()
+        .filter(|a| a.pixels >= min_area)
+        .map(|a| {
+            let rect = LumeRect {
+                x: a.min_x as i32,
+                y: a.min_y as i32,
+                width: a.max_x - a.min_x + 1,
+                height: a.max_y - a.min_y + 1,
+            };
+            (a.pixels, rect)
+        })
+        .collect();
+    found.sort_by_key(|a| std::cmp::Reverse(a.0));
+    Ok(found.into_iter().map(|(_, rect)| rect).collect())
+}
@@ -0,0 +1,144 @@
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use qrcode::{EcLevel, QrCode};
+
+use crate::api::image_ops::LumeColor;
+use crate::api::imageproc_ops::LumePoint;
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// A decoded barcode. `format` names the symbology ("qr_code"); `corners`
+/// are in image coordinates, clockwise from the code's own top-left corner.
+pub struct LumeBarcode {
+    pub format: String,
+    pub text: String,
+    pub corners: Vec<LumePoint>,
+}
+
+// ---------------------------------------------------------------------------
+// Decoding
+// ---------------------------------------------------------------------------
+
+/// Finds and decodes every barcode in a still image. Only QR codes are
+/// recognized for now; 1D codes (EAN, Code 128, ...) and other 2D symbologies
+/// (Data Matrix, Aztec, PDF417) are not, so check `format` rather than assume
+/// it. Codes that are detected but fail to decode are skipped. Light-on-dark codes are found by retrying
+/// on the inverted image when nothing decodes at first.
+#[cfg(feature = "qr")]
+#[flutter_rust_bridge::frb(sync)]
+pub fn decode_barcodes(image_bytes: Vec<u8>) -> Result<Vec<LumeBarcode>> {
+    let mut gray = helpers::load(&image_bytes)?.to_luma8();
+    let mut codes = detect_qr(&gray);
+    if codes.is_empty() {
+        image::imageops::invert(&mut gray);
+        codes = detect_qr(&gray);
+    }
+    Ok(codes)
+}
+
+#[cfg(feature = "qr")]
+fn detect_qr(gray: &image::GrayImage) -> Vec<LumeBarcode> {
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        gray.width() as usize,
+        gray.height() as usize,
+        |x, y| gray.get_pixel(x as u32, y as u32).0[0],
+    );
+    prepared
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| {
+            let (_, text) = grid.decode().ok()?;
+            Some(LumeBarcode {
+                format: "qr_code".to_string(),
+                text,
+                corners: grid
+                    .bounds
+                    .iter()
+                    .map(|p| LumePoint { x: p.x, y: p.y })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Generation
+// ---------------------------------------------------------------------------
+
+/// Quiet zone around the symbol, in modules, as required by the QR spec.
+const QUIET_ZONE: u32 = 4;
+
+/// Renders `text` as a `size` x `size` PNG QR code. `error_correction` is
+/// "l", "m", "q" or "h". An optional `logo_bytes` image is scaled into the
+/// center on a `bg_color` pad; the logo's size follows the error correction
+/// level so the code stays readable, and "h" is recommended with a logo.
+#[flutter_rust_bridge::frb(sync)]
+pub fn generate_qr(
+    text: String,
+    size: u32,
+    fg_color: LumeColor,
+    bg_color: LumeColor,
+    error_correction: String,
+    logo_bytes: Option<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let (level, logo_fraction) = match error_correction.to_lowercase().as_str() {
+        "l" | "low" => (EcLevel::L, 0.12),
+        "m" | "medium" => (EcLevel::M, 0.16),
+        "q" | "quartile" => (EcLevel::Q, 0.2),
+        "h" | "high" => (EcLevel::H, 0.25),
+        other => return Err(anyhow::anyhow!("Unsupported error correction: {}", other)),
+    };
+    let code = QrCode::with_error_correction_level(text.as_bytes(), level)
+        .map_err(|e| anyhow::anyhow!("Cannot encode QR code: {}", e))?;
+    let modules = code.width() as u32;
+    let module_px = size / (modules + 2 * QUIET_ZONE);
+    if module_px == 0 {
+        return Err(anyhow::anyhow!(
+            "Size must be at least {} px for this QR code",
+            modules + 2 * QUIET_ZONE
+        ));
+    }
+
+    let fg = Rgba([fg_color.r, fg_color.g, fg_color.b, fg_color.a]);
+    let bg = Rgba([bg_color.r, bg_color.g, bg_color.b, bg_color.a]);
+    let symbol_px = modules * module_px;
+    let offset = (size - symbol_px) / 2;
+    let mut out = RgbaImage::from_pixel(size, size, bg);
+    for my in 0..modules {
+        for mx in 0..modules {
+            if code[(mx as usize, my as usize)] == qrcode::Color::Dark {
+                for y in 0..module_px {
+                    for x in 0..module_px {
+                        out.put_pixel(offset + mx * module_px + x, offset + my * module_px + y, fg);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(bytes) = logo_bytes {
+        let logo = helpers::load(&bytes)?;
+        let max_side = ((symbol_px as f32 * logo_fraction) as u32).max(1);
+        let logo = logo
+            .resize(max_side, max_side, image::imageops::FilterType::Lanczos3)
+            .to_rgba8();
+        // Pad the logo with a module of background so it does not touch dark modules.
+        let (pad_w, pad_h) = (logo.width() + 2 * module_px, logo.height() + 2 * module_px);
+        let (pad_x, pad_y) = ((size - pad_w) / 2, (size - pad_h) / 2);
+        for y in pad_y..pad_y + pad_h {
+            for x in pad_x..pad_x + pad_w {
+                out.put_pixel(x, y, bg);
+            }
+        }
+        image::imageops::overlay(
+            &mut out,
+            &logo,
+            (pad_x + module_px) as i64,
+            (pad_y + module_px) as i64,
+        );
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(out), ImageFormat::Png)
+}
@@ -0,0 +1,221 @@
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::{helpers, metrics};
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// What to measure. Empty `operations` / `widths` and a zero `iterations`
+/// fall back to the defaults below. Images are generated at 4:3.
+pub struct LumeBenchmarkSpec {
+    pub operations: Vec<String>,
+    pub widths: Vec<u32>,
+    pub iterations: u32,
+}
+
+pub struct LumeBenchmarkResult {
+    pub operation: String,
+    pub width: u32,
+    pub height: u32,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+}
+
+pub struct LumeBenchmarkReport {
+    pub results: Vec<LumeBenchmarkResult>,
+    pub total_ms: f64,
+}
+
+/// Where the time of the calls between `metrics_begin` and `metrics_end`
+/// went. `process_ms` is everything but decoding and encoding.
+pub struct LumeMetrics {
+    pub total_ms: f64,
+    pub decode_ms: f64,
+    pub process_ms: f64,
+    pub encode_ms: f64,
+    pub decodes: u32,
+    pub encodes: u32,
+    /// Highest heap use above the level at `metrics_begin`, in bytes. Only
+    /// measured when the crate is built with the `metrics` feature.
+    pub peak_bytes: Option<u64>,
+}
+
+const DEFAULT_OPERATIONS: [&str; 9] = [
+    "decode_png",
+    "encode_png",
+    "encode_jpeg",
+    "resize",
+    "grayscale",
+    "gaussian_blur",
+    "median_filter",
+    "bilateral_filter",
+    "canny",
+];
+const DEFAULT_WIDTHS: [u32; 3] = [640, 1280, 1920];
+const DEFAULT_ITERATIONS: u32 = 3;
+/// Widest synthetic image; 16384 x 12288 RGBA is already 800 MB.
+const MAX_WIDTH: u32 = 16384;
+
+// ---------------------------------------------------------------------------
+// Benchmark
+// ---------------------------------------------------------------------------
+
+/// Deterministic test image with smooth gradients, hard edges and some
+/// high-frequency texture, so filters do representative work.
+fn synthetic_image(width: u32, height: u32) -> DynamicImage {
+    let mut state: u32 = 0x9E37_79B9;
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let noise = (state & 0x1F) as u8;
+        let checker = if ((x / 32) + (y / 32)) % 2 == 0 {
+            60
+        } else {
+            0
+        };
+        image::Rgba([
+            ((x * 255 / width.max(1)) as u8).saturating_add(noise),
+            ((y * 255 / height.max(1)) as u8).saturating_add(checker),
+            128u8.saturating_add(noise),
+            255,
+        ])
+    }))
+}
+
+fn run_operation(name: &str, img: &DynamicImage, png: &[u8]) -> Result<()> {
+    // black_box keeps the optimizer from discarding results nobody reads.
+    match name {
+        "decode_png" => {
+            black_box(helpers::load(png)?);
+        }
+        "encode_png" => {
+            black_box(helpers::encode(img, ImageFormat::Png)?);
+        }
+        "encode_jpeg" => {
+            let rgb = DynamicImage::ImageRgb8(img.to_rgb8());
+            black_box(helpers::encode(&rgb, ImageFormat::Jpeg)?);
+        }
+        "resize" => {
+            let filter = image::imageops::FilterType::Lanczos3;
+            black_box(img.resize(img.width() / 2, img.height() / 2, filter));
+        }
+        "grayscale" => {
+            black_box(img.grayscale());
+        }
+        "gaussian_blur" => {
+            black_box(imageproc::filter::gaussian_blur_f32(&img.to_rgba8(), 3.0));
+        }
+        "median_filter" => {
+            black_box(imageproc::filter::median_filter(&img.to_luma8(), 2, 2));
+        }
+        "bilateral_filter" => {
+            let gray = img.to_luma8();
+            black_box(imageproc::filter::bilateral_filter(&gray, 5, 10.0, 10.0));
+        }
+        "canny" => {
+            black_box(imageproc::edges::canny(&img.to_luma8(), 50.0, 150.0));
+        }
+        other => {
+            return Err(anyhow::anyhow!(
+                "Unsupported benchmark operation: {}",
+                other
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Times representative operations on synthetic images at several sizes on the
+/// current device, so apps can pick quality settings adaptively. Unlike the
+/// rest of this file it is not `sync`: a run takes seconds and would block
+/// the calling isolate, so Dart gets a `Future`.
+pub fn run_benchmark(spec: LumeBenchmarkSpec) -> Result<LumeBenchmarkReport> {
+    let operations: Vec<String> = if spec.operations.is_empty() {
+        DEFAULT_OPERATIONS.iter().map(|s| s.to_string()).collect()
+    } else {
+        spec.operations.iter().map(|s| s.to_lowercase()).collect()
+    };
+    let widths = if spec.widths.is_empty() {
+        DEFAULT_WIDTHS.to_vec()
+    } else {
+        spec.widths
+    };
+    if let Some(width) = widths.iter().find(|&&w| w > MAX_WIDTH) {
+        return Err(anyhow::anyhow!(
+            "Benchmark width {} is larger than {}",
+            width,
+            MAX_WIDTH
+        ));
+    }
+    let iterations = if spec.iterations == 0 {
+        DEFAULT_ITERATIONS
+    } else {
+        spec.iterations
+    };
+
+    let started = Instant::now();
+    let mut results = Vec::new();
+    for width in widths {
+        let height = (width * 3 / 4).max(1);
+        let img = synthetic_image(width.max(1), height);
+        let png = helpers::encode(&img, ImageFormat::Png)?;
+        for op in &operations {
+            let mut timings = Vec::with_capacity(iterations as usize);
+            for _ in 0..iterations {
+                let t = Instant::now();
+                run_operation(op, &img, &png)?;
+                timings.push(t.elapsed().as_secs_f64() * 1000.0);
+            }
+            results.push(LumeBenchmarkResult {
+                operation: op.clone(),
+                width: img.width(),
+                height,
+                mean_ms: timings.iter().sum::<f64>() / timings.len() as f64,
+                min_ms: timings.iter().cloned().fold(f64::INFINITY, f64::min),
+                max_ms: timings.iter().cloned().fold(0.0, f64::max),
+            });
+        }
+    }
+
+    Ok(LumeBenchmarkReport {
+        results,
+        total_ms: started.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Metrics
+// ---------------------------------------------------------------------------
+
+/// Starts recording call metrics, e.g. around one pipeline run in a
+/// production build to find its bottleneck. Recording is per thread: only
+/// sync calls made from the same isolate until `metrics_end` count, and work
+/// other threads do for them (async calls, parallel filters) is not seen.
+#[flutter_rust_bridge::frb(sync)]
+pub fn metrics_begin() {
+    metrics::begin();
+}
+
+/// Stops recording and reports what happened since `metrics_begin`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn metrics_end() -> Result<LumeMetrics> {
+    let snapshot =
+        metrics::end().ok_or_else(|| anyhow::anyhow!("Metrics are not being recorded"))?;
+    let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+    let (total, decode, encode) = (ms(snapshot.total), ms(snapshot.decode), ms(snapshot.encode));
+    Ok(LumeMetrics {
+        total_ms: total,
+        decode_ms: decode,
+        process_ms: (total - decode - encode).max(0.0),
+        encode_ms: encode,
+        decodes: snapshot.decodes,
+        encodes: snapshot.encodes,
+        peak_bytes: snapshot.peak_bytes,
+    })
+}
@@ -0,0 +1,1145 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageFormat, Luma, Rgba};
+use imageproc::contours::BorderType;
+use imageproc::geometric_transformations::{Interpolation, Projection};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::api::registration::solve;
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// A point with sub-pixel precision, in image coordinates.
+pub struct LumeFloatPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A detected fiducial marker. `corners` are clockwise from the marker's own
+/// top-left corner, so they give its pose even when the image is rotated.
+pub struct LumeMarker {
+    pub id: u32,
+    pub corners: Vec<LumeFloatPoint>,
+}
+
+/// A chessboard calibration target: its inner corner counts, as passed to
+/// `find_chessboard_corners`, and the side of one square in the unit the
+/// camera position should come out in (e.g. millimeters).
+pub struct LumeBoardSpec {
+    pub cols: u32,
+    pub rows: u32,
+    pub square_size: f32,
+}
+
+/// Pinhole intrinsics and Brown-Conrady distortion in OpenCV's conventions
+/// (`k1`, `k2`, `k3` radial, `p1`, `p2` tangential), for images of
+/// `image_width` x `image_height`. `rms_error` is the reprojection error of
+/// the calibration, in pixels; below 1 is usually a good calibration.
+pub struct LumeCameraParams {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub k1: f64,
+    pub k2: f64,
+    pub p1: f64,
+    pub p2: f64,
+    pub k3: f64,
+    pub rms_error: f64,
+}
+
+// ---------------------------------------------------------------------------
+// Sub-pixel corners
+// ---------------------------------------------------------------------------
+
+/// A grayscale image as floats, for bilinear sampling.
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Plane {
+    fn new(img: &GrayImage) -> Plane {
+        Plane {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            data: img.pixels().map(|p| p.0[0] as f32).collect(),
+        }
+    }
+
+    /// Bilinear sample, clamped to the edges.
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let x = x.clamp(0.0, (self.width - 1) as f32);
+        let y = y.clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let at = |x: usize, y: usize| self.data[y * self.width + x];
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+/// Moves `corner` to the point that every gradient in the `radius` window
+/// around it points away from, as OpenCV's `cornerSubPix` does: edges
+/// meeting at a corner all pass through it. Keeps the original position if
+/// the estimate wanders out of the window.
+fn refine_corner(img: &Plane, corner: (f32, f32), radius: i32) -> (f32, f32) {
+    let mut c = corner;
+    let spread = (radius * radius) as f64;
+    for _ in 0..20 {
+        let (mut a, mut b, mut d, mut b1, mut b2) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let (px, py) = (c.0 + dx as f32, c.1 + dy as f32);
+                let gx = ((img.sample(px + 1.0, py) - img.sample(px - 1.0, py)) / 2.0) as f64;
+                let gy = ((img.sample(px, py + 1.0) - img.sample(px, py - 1.0)) / 2.0) as f64;
+                let weight = (-((dx * dx + dy * dy) as f64) / spread).exp();
+                let (gxx, gxy, gyy) = (gx * gx * weight, gx * gy * weight, gy * gy * weight);
+                a += gxx;
+                b += gxy;
+                d += gyy;
+                b1 += gxx * px as f64 + gxy * py as f64;
+                b2 += gxy * px as f64 + gyy * py as f64;
+            }
+        }
+        let det = a * d - b * b;
+        if det.abs() < 1e-9 {
+            break;
+        }
+        let next = (
+            ((d * b1 - b * b2) / det) as f32,
+            ((a * b2 - b * b1) / det) as f32,
+        );
+        let moved = (next.0 - c.0).hypot(next.1 - c.1);
+        c = next;
+        if moved < 0.01 {
+            break;
+        }
+    }
+    if (c.0 - corner.0).hypot(c.1 - corner.1) > radius as f32 {
+        corner
+    } else {
+        c
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Chessboard
+// ---------------------------------------------------------------------------
+
+/// Radius of the ChESS sampling ring.
+const RING_RADIUS: i32 = 5;
+/// The ring: 16 points at radius 5, counter-clockwise in image coordinates.
+const RING: [(i32, i32); 16] = [
+    (5, 0),
+    (5, 2),
+    (4, 4),
+    (2, 5),
+    (0, 5),
+    (-2, 5),
+    (-4, 4),
+    (-5, 2),
+    (-5, 0),
+    (-5, -2),
+    (-4, -4),
+    (-2, -5),
+    (0, -5),
+    (2, -5),
+    (4, -4),
+    (5, -2),
+];
+/// Longest side the chessboard search first runs at.
+const CHESS_WORK_SIDE: u32 = 1024;
+
+/// ChESS response (Bennett and Lasenby, 2014): high where the ring around a
+/// pixel sees two dark and two light quadrants, as at the X-junction of four
+/// chessboard squares, and low on edges and blobs.
+fn chess_response(img: &GrayImage) -> Vec<f32> {
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    let mut response = vec![0.0; (w * h) as usize];
+    let at = |x: i32, y: i32| img.get_pixel(x as u32, y as u32).0[0] as f32;
+    for y in RING_RADIUS..h - RING_RADIUS {
+        for x in RING_RADIUS..w - RING_RADIUS {
+            let s: Vec<f32> = RING.iter().map(|&(dx, dy)| at(x + dx, y + dy)).collect();
+            let sum: f32 = (0..4)
+                .map(|n| (s[n] + s[n + 8] - s[n + 4] - s[n + 12]).abs())
+                .sum();
+            let diff: f32 = (0..8).map(|n| (s[n] - s[n + 8]).abs()).sum();
+            let ring_mean = s.iter().sum::<f32>() / 16.0;
+            let local =
+                (at(x, y) + at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1)) / 5.0;
+            response[(y * w + x) as usize] = sum - diff - 16.0 * (ring_mean - local).abs();
+        }
+    }
+    response
+}
+
+/// Local maxima of the ChESS response, strongest first.
+fn chess_candidates(img: &GrayImage, limit: usize) -> Vec<(f32, f32, f32)> {
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    let response = chess_response(img);
+    let strongest = response.iter().cloned().fold(0.0, f32::max);
+    let threshold = strongest * 0.05;
+    let mut found = Vec::new();
+    for y in RING_RADIUS..h - RING_RADIUS {
+        for x in RING_RADIUS..w - RING_RADIUS {
+            let r = response[(y * w + x) as usize];
+            if r <= threshold {
+                continue;
+            }
+            let is_max = (-RING_RADIUS..=RING_RADIUS).all(|dy| {
+                (-RING_RADIUS..=RING_RADIUS).all(|dx| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= w || ny >= h || (dx, dy) == (0, 0) {
+                        return true;
+                    }
+                    let n = response[(ny * w + nx) as usize];
+                    // Ties go to the first pixel in scan order.
+                    n < r || (n == r && (dy, dx) > (0, 0))
+                })
+            });
+            if is_max {
+                found.push((x as f32, y as f32, r));
+            }
+        }
+    }
+    found.sort_by(|a, b| b.2.total_cmp(&a.2));
+    found.truncate(limit);
+    found
+}
+
+type Grid = HashMap<(i32, i32), usize>;
+
+/// Step from `cell` to its neighbor in direction `dir`, from corners already
+/// on the grid: the step that led to `cell`, or the same step in a
+/// neighboring row or column.
+fn grid_step(
+    grid: &Grid,
+    points: &[(f32, f32, f32)],
+    cell: (i32, i32),
+    dir: (i32, i32),
+) -> Option<(f32, f32)> {
+    let delta = |from: (i32, i32), to: (i32, i32)| {
+        let (a, b) = (points[*grid.get(&from)?], points[*grid.get(&to)?]);
+        Some((b.0 - a.0, b.1 - a.1))
+    };
+    let back = (cell.0 - dir.0, cell.1 - dir.1);
+    delta(back, cell).or_else(|| {
+        [-1, 1].iter().find_map(|&side| {
+            let beside = if dir.0 != 0 {
+                (cell.0, cell.1 + side)
+            } else {
+                (cell.0 + side, cell.1)
+            };
+            delta(beside, (beside.0 + dir.0, beside.1 + dir.1))
+        })
+    })
+}
+
+/// Grows a grid of corners outwards from `seed`, predicting each neighbor
+/// from the steps already known, which follows perspective and mild lens
+/// distortion.
+fn grow_grid(points: &[(f32, f32, f32)], seed: usize) -> Option<Grid> {
+    let dist = |a: usize, b: usize| (points[a].0 - points[b].0).hypot(points[a].1 - points[b].1);
+    let mut near: Vec<usize> = (0..points.len()).filter(|&i| i != seed).collect();
+    near.sort_by(|&a, &b| dist(seed, a).total_cmp(&dist(seed, b)));
+    let u = *near.first()?;
+    let du = (points[u].0 - points[seed].0, points[u].1 - points[seed].1);
+    let lu = du.0.hypot(du.1);
+    // The second grid direction: a close neighbor roughly across the first.
+    let v = *near.iter().take(8).find(|&&i| {
+        let dv = (points[i].0 - points[seed].0, points[i].1 - points[seed].1);
+        let lv = dv.0.hypot(dv.1);
+        ((du.0 * dv.0 + du.1 * dv.1) / (lu * lv)).abs() < 0.5 && lv < 2.0 * lu
+    })?;
+    let dv = (points[v].0 - points[seed].0, points[v].1 - points[seed].1);
+
+    let mut grid = Grid::new();
+    let mut used = vec![false; points.len()];
+    let mut queue = VecDeque::new();
+    for (cell, index) in [((0, 0), seed), ((1, 0), u), ((0, 1), v)] {
+        grid.insert(cell, index);
+        used[index] = true;
+        queue.push_back(cell);
+    }
+    while let Some(cell) = queue.pop_front() {
+        let here = points[grid[&cell]];
+        for dir in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let target = (cell.0 + dir.0, cell.1 + dir.1);
+            if grid.contains_key(&target) {
+                continue;
+            }
+            let step = grid_step(&grid, points, cell, dir).unwrap_or(if dir.0 != 0 {
+                (du.0 * dir.0 as f32, du.1 * dir.0 as f32)
+            } else {
+                (dv.0 * dir.1 as f32, dv.1 * dir.1 as f32)
+            });
+            let predicted = (here.0 + step.0, here.1 + step.1);
+            let tolerance = 0.35 * step.0.hypot(step.1);
+            let closest = (0..points.len())
+                .filter(|&i| !used[i])
+                .map(|i| {
+                    (
+                        i,
+                        (points[i].0 - predicted.0).hypot(points[i].1 - predicted.1),
+                    )
+                })
+                .filter(|&(_, d)| d < tolerance)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((index, _)) = closest {
+                grid.insert(target, index);
+                used[index] = true;
+                queue.push_back(target);
+            }
+        }
+    }
+    Some(grid)
+}
+
+/// The complete `cols` x `rows` window of `grid` with the strongest corners,
+/// as rows of point indices. Corners along the board edge (T-junctions)
+/// may extend the grid beyond the pattern; they respond much less than
+/// inner corners. Comparably strong corners outside the window mean the
+/// board is larger than the pattern asked for, and give `None`.
+fn pattern_window(
+    grid: &Grid,
+    points: &[(f32, f32, f32)],
+    cols: i32,
+    rows: i32,
+) -> Option<Vec<Vec<usize>>> {
+    let (min_i, max_i) = (
+        grid.keys().map(|c| c.0).min()?,
+        grid.keys().map(|c| c.0).max()?,
+    );
+    let (min_j, max_j) = (
+        grid.keys().map(|c| c.1).min()?,
+        grid.keys().map(|c| c.1).max()?,
+    );
+    let mut best: Option<(f32, Vec<Vec<usize>>)> = None;
+    // `transposed`: grid columns run along the pattern's rows.
+    for transposed in [false, true] {
+        let (wi, wj) = if transposed {
+            (rows, cols)
+        } else {
+            (cols, rows)
+        };
+        for i0 in min_i..=max_i - wi + 1 {
+            for j0 in min_j..=max_j - wj + 1 {
+                let cells: Option<Vec<Vec<usize>>> = (0..rows)
+                    .map(|r| {
+                        (0..cols)
+                            .map(|c| {
+                                let cell = if transposed {
+                                    (i0 + r, j0 + c)
+                                } else {
+                                    (i0 + c, j0 + r)
+                                };
+                                grid.get(&cell).copied()
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let Some(cells) = cells else {
+                    continue;
+                };
+                let score = cells.iter().flatten().map(|&i| points[i].2).sum::<f32>();
+                if best.as_ref().is_none_or(|b| score > b.0) {
+                    best = Some((score, cells));
+                }
+            }
+        }
+        if cols == rows {
+            break;
+        }
+    }
+    let (_, cells) = best?;
+    let weakest = cells
+        .iter()
+        .flatten()
+        .map(|&i| points[i].2)
+        .fold(f32::MAX, f32::min);
+    let inside: HashSet<usize> = cells.iter().flatten().copied().collect();
+    let rival = grid
+        .values()
+        .any(|i| !inside.contains(i) && points[*i].2 > weakest / 2.0);
+    (!rival).then_some(cells)
+}
+
+/// Puts the corners in reading order: rows run left to right and follow
+/// each other top to bottom as seen in the image, starting from the corner
+/// closest to the top-left.
+fn normalize_order(mut g: Vec<Vec<(f32, f32)>>) -> Vec<Vec<(f32, f32)>> {
+    let (rows, cols) = (g.len(), g[0].len());
+    let cross = |g: &Vec<Vec<(f32, f32)>>| {
+        let (o, a, b) = (g[0][0], g[0][1], g[1][0]);
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    if cross(&g) < 0.0 {
+        for row in &mut g {
+            row.reverse();
+        }
+    }
+    let rotate_180 = |g: &Vec<Vec<(f32, f32)>>| {
+        g.iter()
+            .rev()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect::<Vec<Vec<_>>>()
+    };
+    let mut options = vec![rotate_180(&g)];
+    if rows == cols {
+        let rotate_90 = |g: &Vec<Vec<(f32, f32)>>| {
+            (0..rows)
+                .map(|r| (0..cols).map(|c| g[rows - 1 - c][r]).collect())
+                .collect::<Vec<Vec<_>>>()
+        };
+        let quarter = rotate_90(&g);
+        options.push(rotate_180(&quarter));
+        options.push(quarter);
+    }
+    options.push(g);
+    options
+        .into_iter()
+        .min_by(|a, b| (a[0][0].0 + a[0][0].1).total_cmp(&(b[0][0].0 + b[0][0].1)))
+        .expect("at least one ordering")
+}
+
+/// Finds the inner corners of a chessboard with `cols` x `rows` inner
+/// corners (squares per row and column minus one, as in OpenCV) and
+/// returns them with sub-pixel precision, row by row, or `None` when the
+/// whole pattern is not visible. The board may be seen in perspective and
+/// rotated; the first corner is the one nearest the image's top-left.
+#[flutter_rust_bridge::frb(sync)]
+pub fn find_chessboard_corners(
+    image_bytes: Vec<u8>,
+    cols: u32,
+    rows: u32,
+) -> Result<Option<Vec<LumeFloatPoint>>> {
+    if cols < 2 || rows < 2 {
+        return Err(anyhow::anyhow!(
+            "A chessboard needs at least 2x2 inner corners"
+        ));
+    }
+    let gray = helpers::load(&image_bytes)?.to_luma8();
+    let (w, h) = gray.dimensions();
+    let full = Plane::new(&gray);
+    let longest = w.max(h);
+    let mut scales = vec![1.0];
+    if longest > CHESS_WORK_SIDE {
+        scales.insert(0, CHESS_WORK_SIDE as f32 / longest as f32);
+    }
+    let count = (cols * rows) as usize;
+    for scale in scales {
+        let work = if scale < 1.0 {
+            let (sw, sh) = (
+                (w as f32 * scale).round() as u32,
+                (h as f32 * scale).round() as u32,
+            );
+            image::imageops::resize(&gray, sw, sh, FilterType::Triangle)
+        } else {
+            gray.clone()
+        };
+        let work = imageproc::filter::gaussian_blur_f32(&work, 1.0);
+        let points = chess_candidates(&work, count * 4 + 20);
+        if points.len() < count {
+            continue;
+        }
+        for seed in 0..points.len().min(10) {
+            let Some(grid) = grow_grid(&points, seed) else {
+                continue;
+            };
+            let Some(window) = pattern_window(&grid, &points, cols as i32, rows as i32) else {
+                continue;
+            };
+            let corners: Vec<Vec<(f32, f32)>> = window
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|&i| (points[i].0 / scale, points[i].1 / scale))
+                        .collect()
+                })
+                .collect();
+            let step = (corners[0][1].0 - corners[0][0].0).hypot(corners[0][1].1 - corners[0][0].1);
+            let radius = ((step / 4.0).round() as i32).clamp(2, 10);
+            let ordered = normalize_order(corners);
+            return Ok(Some(
+                ordered
+                    .into_iter()
+                    .flatten()
+                    .map(|c| {
+                        let (x, y) = refine_corner(&full, c, radius);
+                        LumeFloatPoint { x, y }
+                    })
+                    .collect(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+// ---------------------------------------------------------------------------
+// Markers
+// ---------------------------------------------------------------------------
+//
+// Markers follow the original ArUco design (OpenCV's DICT_ARUCO_ORIGINAL):
+// a black border one cell wide around 5x5 cells. Each row carries 2 bits of
+// the id as one of four 5-bit codewords, so ids go from 0 to 1023 and the
+// codewords tell the marker's orientation and reject most false squares.
+
+/// Row codewords for the 2-bit values 0-3 (1 = white cell).
+const MARKER_CODEWORDS: [u8; 4] = [0b10000, 0b10111, 0b01001, 0b01110];
+/// Cells across a marker, border included.
+const MARKER_CELLS: u32 = 7;
+const MARKER_IDS: u32 = 1024;
+
+/// Pixels darker than their surroundings by a small margin, as 255.
+fn dark_regions(gray: &GrayImage, radius: u32) -> GrayImage {
+    let mean = imageproc::filter::box_filter(gray, radius, radius);
+    GrayImage::from_fn(gray.width(), gray.height(), |x, y| {
+        let (p, m) = (gray.get_pixel(x, y).0[0], mean.get_pixel(x, y).0[0]);
+        Luma([if (p as i32) + 7 < m as i32 { 255 } else { 0 }])
+    })
+}
+
+/// Four-cornered convex outlines of dark areas, clockwise.
+fn dark_quads(dark: &GrayImage) -> Vec<[(f32, f32); 4]> {
+    let (w, h) = (dark.width() as i32, dark.height() as i32);
+    let mut quads = Vec::new();
+    for contour in imageproc::contours::find_contours::<i32>(dark) {
+        if contour.border_type != BorderType::Outer || contour.points.len() < 40 {
+            continue;
+        }
+        let touches_edge = contour
+            .points
+            .iter()
+            .any(|p| p.x <= 0 || p.y <= 0 || p.x >= w - 1 || p.y >= h - 1);
+        if touches_edge {
+            continue;
+        }
+        let epsilon = contour.points.len() as f64 * 0.05;
+        let poly = imageproc::geometry::approximate_polygon_dp(&contour.points, epsilon, true);
+        if poly.len() != 4 {
+            continue;
+        }
+        let q: Vec<(f32, f32)> = poly.iter().map(|p| (p.x as f32, p.y as f32)).collect();
+        let turns: Vec<f32> = (0..4)
+            .map(|i| {
+                let (a, b, c) = (q[i], q[(i + 1) % 4], q[(i + 2) % 4]);
+                (b.0 - a.0) * (c.1 - b.1) - (b.1 - a.1) * (c.0 - b.0)
+            })
+            .collect();
+        let convex = turns.iter().all(|&t| t > 0.0) || turns.iter().all(|&t| t < 0.0);
+        let shortest = (0..4)
+            .map(|i| (q[(i + 1) % 4].0 - q[i].0).hypot(q[(i + 1) % 4].1 - q[i].1))
+            .fold(f32::MAX, f32::min);
+        if !convex || shortest < 10.0 {
+            continue;
+        }
+        let mut quad = [q[0], q[1], q[2], q[3]];
+        // Clockwise as seen, i.e. positive turns with y pointing down.
+        if turns[0] < 0.0 {
+            quad.reverse();
+        }
+        quads.push(quad);
+    }
+    quads
+}
+
+/// Mean brightness of every cell of the marker grid spanned by `corners`.
+fn sample_cells(gray: &Plane, corners: &[(f32, f32); 4]) -> Option<Vec<f32>> {
+    let n = MARKER_CELLS as f32;
+    let projection =
+        Projection::from_control_points([(0.0, 0.0), (n, 0.0), (n, n), (0.0, n)], *corners)?;
+    let mut cells = Vec::with_capacity((MARKER_CELLS * MARKER_CELLS) as usize);
+    for row in 0..MARKER_CELLS {
+        for col in 0..MARKER_CELLS {
+            // A few samples around the center, away from the cell edges.
+            let mut sum = 0.0;
+            for sy in [0.3, 0.5, 0.7] {
+                for sx in [0.3, 0.5, 0.7] {
+                    let (x, y) = projection * (col as f32 + sx, row as f32 + sy);
+                    sum += gray.sample(x, y);
+                }
+            }
+            cells.push(sum / 9.0);
+        }
+    }
+    Some(cells)
+}
+
+/// The id of a marker whose cell brightness is `cells`, read in the
+/// orientation the corners were given in.
+fn decode_cells(cells: &[f32]) -> Option<u32> {
+    let (low, high) = cells
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(l, h), &v| (l.min(v), h.max(v)));
+    if high - low < 30.0 {
+        return None;
+    }
+    let threshold = (low + high) / 2.0;
+    let n = MARKER_CELLS as usize;
+    let white = |row: usize, col: usize| cells[row * n + col] > threshold;
+    let border_black =
+        (0..n).all(|i| !white(0, i) && !white(n - 1, i) && !white(i, 0) && !white(i, n - 1));
+    if !border_black {
+        return None;
+    }
+    let mut id = 0;
+    for row in 1..n - 1 {
+        let word = (1..n - 1).fold(0u8, |w, col| (w << 1) | white(row, col) as u8);
+        let value = MARKER_CODEWORDS.iter().position(|&c| c == word)?;
+        id = (id << 2) | value as u32;
+    }
+    Some(id)
+}
+
+/// Finds ArUco markers of the original dictionary (ids 0-1023, as printed by
+/// `generate_marker` or OpenCV's `DICT_ARUCO_ORIGINAL`) and returns their
+/// ids and sub-pixel corners, e.g. to anchor AR content or estimate a pose.
+#[flutter_rust_bridge::frb(sync)]
+pub fn detect_markers(image_bytes: Vec<u8>) -> Result<Vec<LumeMarker>> {
+    let gray = helpers::load(&image_bytes)?.to_luma8();
+    let plane = Plane::new(&gray);
+    // Thin borders of small markers need a small window, thick ones a large.
+    let mut radii = vec![3, 7, 15, gray.width().min(gray.height()) / 40];
+    radii.sort_unstable();
+    radii.dedup();
+
+    let mut markers: Vec<(u32, [(f32, f32); 4])> = Vec::new();
+    for radius in radii.into_iter().filter(|&r| r >= 1) {
+        for quad in dark_quads(&dark_regions(&gray, radius)) {
+            let found = (0..4).find_map(|turn| {
+                let corners = [
+                    quad[turn],
+                    quad[(turn + 1) % 4],
+                    quad[(turn + 2) % 4],
+                    quad[(turn + 3) % 4],
+                ];
+                let id = decode_cells(&sample_cells(&plane, &corners)?)?;
+                Some((id, corners))
+            });
+            let Some((id, corners)) = found else {
+                continue;
+            };
+            let center = |q: &[(f32, f32); 4]| {
+                (
+                    q.iter().map(|p| p.0).sum::<f32>() / 4.0,
+                    q.iter().map(|p| p.1).sum::<f32>() / 4.0,
+                )
+            };
+            let c = center(&corners);
+            let side = (corners[1].0 - corners[0].0).hypot(corners[1].1 - corners[0].1);
+            let duplicate = markers.iter().any(|(other, q)| {
+                let o = center(q);
+                *other == id && (o.0 - c.0).hypot(o.1 - c.1) < side / 2.0
+            });
+            if !duplicate {
+                markers.push((id, corners));
+            }
+        }
+    }
+
+    Ok(markers
+        .into_iter()
+        .map(|(id, corners)| {
+            let side = (corners[1].0 - corners[0].0).hypot(corners[1].1 - corners[0].1);
+            let radius = ((side / 20.0).round() as i32).clamp(2, 5);
+            LumeMarker {
+                id,
+                corners: corners
+                    .iter()
+                    .map(|&c| {
+                        let (x, y) = refine_corner(&plane, c, radius);
+                        LumeFloatPoint { x, y }
+                    })
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+/// Renders marker `id` (0-1023) for printing as a `size` x `size` grayscale
+/// PNG, with a white margin of one cell around the black border so it can
+/// be found on any background.
+#[flutter_rust_bridge::frb(sync)]
+pub fn generate_marker(id: u32, size: u32) -> Result<Vec<u8>> {
+    if id >= MARKER_IDS {
+        return Err(anyhow::anyhow!("Marker id must be below {}", MARKER_IDS));
+    }
+    let cells = MARKER_CELLS + 2;
+    if size < cells {
+        return Err(anyhow::anyhow!("Marker size must be at least {}", cells));
+    }
+    let img = GrayImage::from_fn(size, size, |x, y| {
+        let (col, row) = (x * cells / size, y * cells / size);
+        let white = if col == 0 || row == 0 || col == cells - 1 || row == cells - 1 {
+            true
+        } else if col == 1 || row == 1 || col == cells - 2 || row == cells - 2 {
+            false
+        } else {
+            let value = (id >> (2 * (6 - row))) & 3;
+            (MARKER_CODEWORDS[value as usize] >> (6 - col)) & 1 == 1
+        };
+        Luma([if white { 255 } else { 0 }])
+    });
+    helpers::encode(&DynamicImage::ImageLuma8(img), ImageFormat::Png)
+}
+
+// ---------------------------------------------------------------------------
+// Camera calibration
+// ---------------------------------------------------------------------------
+//
+// Zhang's method, "A Flexible New Technique for Camera Calibration" (2000):
+// a homography per view gives a closed-form first guess of the focal
+// lengths and board poses, then Levenberg-Marquardt refines all parameters,
+// distortion included, against the reprojection error.
+
+type Vec3 = [f64; 3];
+type Mat3 = [[f64; 3]; 3];
+
+/// Intrinsics in optimization order: fx, fy, cx, cy, k1, k2, p1, p2, k3.
+const INTRINSICS: usize = 9;
+/// Per-view pose: rotation (Rodrigues vector) and translation.
+const POSE: usize = 6;
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn norm(a: Vec3) -> f64 {
+    (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt()
+}
+
+fn scale(a: Vec3, s: f64) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+/// Rotation matrix of a Rodrigues vector (axis times angle).
+fn rodrigues(r: &[f64]) -> Mat3 {
+    let theta = norm([r[0], r[1], r[2]]);
+    if theta < 1e-12 {
+        return [[1.0, -r[2], r[1]], [r[2], 1.0, -r[0]], [-r[1], r[0], 1.0]];
+    }
+    let k = [r[0] / theta, r[1] / theta, r[2] / theta];
+    let (c, s) = (theta.cos(), theta.sin());
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (1.0 - c) * k[i] * k[j] + if i == j { c } else { 0.0 };
+        }
+    }
+    m[0][1] -= s * k[2];
+    m[0][2] += s * k[1];
+    m[1][0] += s * k[2];
+    m[1][2] -= s * k[0];
+    m[2][0] -= s * k[1];
+    m[2][1] += s * k[0];
+    m
+}
+
+/// Rodrigues vector of a rotation matrix.
+fn rodrigues_vector(m: &Mat3) -> Vec3 {
+    let cos = ((m[0][0] + m[1][1] + m[2][2] - 1.0) / 2.0).clamp(-1.0, 1.0);
+    let theta = cos.acos();
+    let skew = [m[2][1] - m[1][2], m[0][2] - m[2][0], m[1][0] - m[0][1]];
+    if theta < 1e-9 {
+        return scale(skew, 0.5);
+    }
+    if theta.sin() > 1e-6 {
+        return scale(skew, theta / (2.0 * theta.sin()));
+    }
+    // Half a turn: the axis comes from the symmetric part.
+    let mut axis = [0.0; 3];
+    for (i, a) in axis.iter_mut().enumerate() {
+        *a = ((m[i][i] + 1.0) / 2.0).max(0.0).sqrt();
+    }
+    if m[0][1] < 0.0 {
+        axis[1] = -axis[1];
+    }
+    if m[0][2] < 0.0 {
+        axis[2] = -axis[2];
+    }
+    scale(axis, theta)
+}
+
+/// Image position of board point (`x`, `y`, 0) seen from `pose`.
+fn project(intrinsics: &[f64], pose: &[f64], x: f64, y: f64) -> (f64, f64) {
+    let r = rodrigues(&pose[..3]);
+    let p = [
+        r[0][0] * x + r[0][1] * y + pose[3],
+        r[1][0] * x + r[1][1] * y + pose[4],
+        r[2][0] * x + r[2][1] * y + pose[5],
+    ];
+    let (xn, yn) = (p[0] / p[2], p[1] / p[2]);
+    let (xd, yd) = distort(intrinsics, xn, yn);
+    (
+        intrinsics[0] * xd + intrinsics[2],
+        intrinsics[1] * yd + intrinsics[3],
+    )
+}
+
+/// Applies the lens distortion to normalized image coordinates.
+fn distort(intrinsics: &[f64], x: f64, y: f64) -> (f64, f64) {
+    let [k1, k2, p1, p2, k3] = [
+        intrinsics[4],
+        intrinsics[5],
+        intrinsics[6],
+        intrinsics[7],
+        intrinsics[8],
+    ];
+    let r2 = x * x + y * y;
+    let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+    (
+        x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+        y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y,
+    )
+}
+
+/// Homography from board to image points, by DLT on normalized points.
+fn homography(board: &[(f64, f64)], image: &[(f64, f64)]) -> Option<Mat3> {
+    // Hartley normalization: centered, mean distance sqrt(2).
+    let normalizer = |pts: &[(f64, f64)]| {
+        let n = pts.len() as f64;
+        let (mx, my) = pts
+            .iter()
+            .fold((0.0, 0.0), |a, p| (a.0 + p.0 / n, a.1 + p.1 / n));
+        let spread = pts.iter().map(|p| (p.0 - mx).hypot(p.1 - my)).sum::<f64>() / n;
+        let s = std::f64::consts::SQRT_2 / spread.max(1e-12);
+        (mx, my, s)
+    };
+    let (bx, by, bs) = normalizer(board);
+    let (ix, iy, is) = normalizer(image);
+    let mut a = vec![vec![0.0; 8]; 8];
+    let mut rhs = vec![0.0; 8];
+    for (p, q) in board.iter().zip(image) {
+        let (x, y) = ((p.0 - bx) * bs, (p.1 - by) * bs);
+        let (u, v) = ((q.0 - ix) * is, (q.1 - iy) * is);
+        for (row, target) in [
+            ([x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y], u),
+            ([0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y], v),
+        ] {
+            for i in 0..8 {
+                for j in 0..8 {
+                    a[i][j] += row[i] * row[j];
+                }
+                rhs[i] += row[i] * target;
+            }
+        }
+    }
+    let h = solve(a, rhs)?;
+    let hn = [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], 1.0]];
+    // Undo the normalizations: H = Ti^-1 * Hn * Tb.
+    let tb = [[bs, 0.0, -bs * bx], [0.0, bs, -bs * by], [0.0, 0.0, 1.0]];
+    let ti_inv = [[1.0 / is, 0.0, ix], [0.0, 1.0 / is, iy], [0.0, 0.0, 1.0]];
+    let mul = |a: &Mat3, b: &Mat3| {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+            }
+        }
+        m
+    };
+    Some(mul(&mul(&ti_inv, &hn), &tb))
+}
+
+/// Focal lengths from the homographies with the principal point at
+/// (`cx`, `cy`) and no skew: the images of the board axes must be
+/// orthogonal and of equal length once the intrinsics are removed.
+fn initial_focal(homographies: &[Mat3], cx: f64, cy: f64) -> Option<(f64, f64)> {
+    let mut a = vec![vec![0.0; 2]; 2];
+    let mut rhs = vec![0.0; 2];
+    for h in homographies {
+        let col = |j: usize| [h[0][j] - cx * h[2][j], h[1][j] - cy * h[2][j], h[2][j]];
+        let (c1, c2) = (col(0), col(1));
+        for (row, target) in [
+            ([c1[0] * c2[0], c1[1] * c2[1]], -c1[2] * c2[2]),
+            (
+                [c1[0] * c1[0] - c2[0] * c2[0], c1[1] * c1[1] - c2[1] * c2[1]],
+                -(c1[2] * c1[2] - c2[2] * c2[2]),
+            ),
+        ] {
+            for i in 0..2 {
+                for j in 0..2 {
+                    a[i][j] += row[i] * row[j];
+                }
+                rhs[i] += row[i] * target;
+            }
+        }
+    }
+    let x = solve(a, rhs)?;
+    (x[0] > 0.0 && x[1] > 0.0).then(|| (1.0 / x[0].sqrt(), 1.0 / x[1].sqrt()))
+}
+
+/// Board pose of a view from its homography and the intrinsics.
+fn initial_pose(h: &Mat3, intrinsics: &[f64]) -> [f64; POSE] {
+    let (fx, fy, cx, cy) = (intrinsics[0], intrinsics[1], intrinsics[2], intrinsics[3]);
+    let col = |j: usize| {
+        [
+            (h[0][j] - cx * h[2][j]) / fx,
+            (h[1][j] - cy * h[2][j]) / fy,
+            h[2][j],
+        ]
+    };
+    let mut lambda = 1.0 / norm(col(0));
+    // The board is in front of the camera.
+    if col(2)[2] * lambda < 0.0 {
+        lambda = -lambda;
+    }
+    let r1 = scale(col(0), lambda);
+    let t = scale(col(2), lambda);
+    let r1 = scale(r1, 1.0 / norm(r1));
+    let r2 = scale(col(1), lambda);
+    let dot = r1[0] * r2[0] + r1[1] * r2[1] + r1[2] * r2[2];
+    let r2 = [
+        r2[0] - dot * r1[0],
+        r2[1] - dot * r1[1],
+        r2[2] - dot * r1[2],
+    ];
+    let r2 = scale(r2, 1.0 / norm(r2));
+    let r3 = cross(r1, r2);
+    let m = [
+        [r1[0], r2[0], r3[0]],
+        [r1[1], r2[1], r3[1]],
+        [r1[2], r2[2], r3[2]],
+    ];
+    let r = rodrigues_vector(&m);
+    [r[0], r[1], r[2], t[0], t[1], t[2]]
+}
+
+/// Reprojection residuals (x and y per corner) of one view.
+fn view_residuals(
+    intrinsics: &[f64],
+    pose: &[f64],
+    board: &[(f64, f64)],
+    corners: &[(f64, f64)],
+    out: &mut [f64],
+) {
+    for (i, (b, c)) in board.iter().zip(corners).enumerate() {
+        let (u, v) = project(intrinsics, pose, b.0, b.1);
+        out[2 * i] = u - c.0;
+        out[2 * i + 1] = v - c.1;
+    }
+}
+
+/// Levenberg-Marquardt on intrinsics and poses together, with numerical
+/// derivatives. Returns the final sum of squared residuals.
+fn refine_calibration(params: &mut [f64], board: &[(f64, f64)], views: &[Vec<(f64, f64)>]) -> f64 {
+    let per_view = 2 * board.len();
+    let count = params.len();
+    let residuals = |p: &[f64]| {
+        let mut r = vec![0.0; per_view * views.len()];
+        for (v, corners) in views.iter().enumerate() {
+            let pose = &p[INTRINSICS + v * POSE..INTRINSICS + (v + 1) * POSE];
+            let out = &mut r[v * per_view..(v + 1) * per_view];
+            view_residuals(&p[..INTRINSICS], pose, board, corners, out);
+        }
+        r
+    };
+    let cost = |r: &[f64]| r.iter().map(|v| v * v).sum::<f64>();
+
+    let mut r = residuals(params);
+    let mut current = cost(&r);
+    let mut damping = 1e-3;
+    for _ in 0..100 {
+        // Jacobian columns; a pose only affects its own view's residuals.
+        let mut jacobian = vec![vec![0.0; r.len()]; count];
+        for (k, column) in jacobian.iter_mut().enumerate() {
+            let step = 1e-6 * params[k].abs().max(1.0);
+            let mut plus = params.to_vec();
+            let mut minus = params.to_vec();
+            plus[k] += step;
+            minus[k] -= step;
+            if k < INTRINSICS {
+                let (rp, rm) = (residuals(&plus), residuals(&minus));
+                for (c, (a, b)) in column.iter_mut().zip(rp.iter().zip(&rm)) {
+                    *c = (a - b) / (2.0 * step);
+                }
+            } else {
+                let v = (k - INTRINSICS) / POSE;
+                let pose = INTRINSICS + v * POSE..INTRINSICS + (v + 1) * POSE;
+                let mut rp = vec![0.0; per_view];
+                let mut rm = vec![0.0; per_view];
+                view_residuals(
+                    &plus[..INTRINSICS],
+                    &plus[pose.clone()],
+                    board,
+                    &views[v],
+                    &mut rp,
+                );
+                view_residuals(
+                    &minus[..INTRINSICS],
+                    &minus[pose],
+                    board,
+                    &views[v],
+                    &mut rm,
+                );
+                let out = &mut column[v * per_view..(v + 1) * per_view];
+                for (c, (a, b)) in out.iter_mut().zip(rp.iter().zip(&rm)) {
+                    *c = (a - b) / (2.0 * step);
+                }
+            }
+        }
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>();
+        let mut jtj = vec![vec![0.0; count]; count];
+        for i in 0..count {
+            for j in i..count {
+                let v = dot(&jacobian[i], &jacobian[j]);
+                jtj[i][j] = v;
+                jtj[j][i] = v;
+            }
+        }
+        let gradient: Vec<f64> = jacobian.iter().map(|c| -dot(c, &r)).collect();
+
+        let mut improved = false;
+        while damping < 1e10 {
+            let mut a = jtj.clone();
+            for (i, row) in a.iter_mut().enumerate() {
+                row[i] += damping * jtj[i][i].max(1e-12);
+            }
+            let Some(delta) = solve(a, gradient.clone()) else {
+                damping *= 10.0;
+                continue;
+            };
+            let candidate: Vec<f64> = params.iter().zip(&delta).map(|(p, d)| p + d).collect();
+            let rc = residuals(&candidate);
+            let c = cost(&rc);
+            if c.is_finite() && c < current {
+                let gain = (current - c) / current.max(1e-300);
+                params.copy_from_slice(&candidate);
+                r = rc;
+                current = c;
+                damping = (damping / 10.0).max(1e-12);
+                improved = gain > 1e-10;
+                break;
+            }
+            damping *= 10.0;
+        }
+        if !improved {
+            break;
+        }
+    }
+    current
+}
+
+/// Estimates the camera intrinsics and lens distortion from chessboard
+/// corners found in several photos of `board` (from
+/// `find_chessboard_corners`, one set per photo, all `image_width` x
+/// `image_height`). Use at least 3 views, with the board tilted differently
+/// in each and covering the frame corners too, for a stable result.
+#[flutter_rust_bridge::frb(sync)]
+pub fn calibrate_camera(
+    corner_sets: Vec<Vec<LumeFloatPoint>>,
+    board: LumeBoardSpec,
+    image_width: u32,
+    image_height: u32,
+) -> Result<LumeCameraParams> {
+    if corner_sets.len() < 3 {
+        return Err(anyhow::anyhow!("Calibration needs at least 3 views"));
+    }
+    let count = (board.cols * board.rows) as usize;
+    if count < 4 || board.square_size <= 0.0 {
+        return Err(anyhow::anyhow!("Invalid board specification"));
+    }
+    if let Some(set) = corner_sets.iter().find(|s| s.len() != count) {
+        return Err(anyhow::anyhow!(
+            "Expected {} corners per view, got {}",
+            count,
+            set.len()
+        ));
+    }
+    let square = board.square_size as f64;
+    let board_points: Vec<(f64, f64)> = (0..count)
+        .map(|i| {
+            let (col, row) = (i as u32 % board.cols, i as u32 / board.cols);
+            (col as f64 * square, row as f64 * square)
+        })
+        .collect();
+    let views: Vec<Vec<(f64, f64)>> = corner_sets
+        .iter()
+        .map(|s| s.iter().map(|p| (p.x as f64, p.y as f64)).collect())
+        .collect();
+
+    let homographies = views
+        .iter()
+        .map(|v| homography(&board_points, v))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| anyhow::anyhow!("Degenerate corner layout"))?;
+    let (cx, cy) = (image_width as f64 / 2.0, image_height as f64 / 2.0);
+    // Views all facing the camera do not constrain the focal length; start
+    // from a normal lens then.
+    let fallback = image_width.max(image_height) as f64;
+    let (fx, fy) = initial_focal(&homographies, cx, cy).unwrap_or((fallback, fallback));
+
+    let mut params = vec![fx, fy, cx, cy, 0.0, 0.0, 0.0, 0.0, 0.0];
+    for h in &homographies {
+        params.extend(initial_pose(h, &params[..INTRINSICS]));
+    }
+    let error = refine_calibration(&mut params, &board_points, &views);
+    Ok(LumeCameraParams {
+        image_width,
+        image_height,
+        fx: params[0],
+        fy: params[1],
+        cx: params[2],
+        cy: params[3],
+        k1: params[4],
+        k2: params[5],
+        p1: params[6],
+        p2: params[7],
+        k3: params[8],
+        rms_error: (error / (count * views.len()) as f64).sqrt(),
+    })
+}
+
+/// Removes the lens distortion described by `params` (from
+/// `calibrate_camera`), keeping the same intrinsics so straight lines come
+/// out straight. Areas with no source pixels become transparent (black in
+/// formats without alpha).
+#[flutter_rust_bridge::frb(sync)]
+pub fn undistort(image_bytes: Vec<u8>, params: LumeCameraParams) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    // Params were estimated at another resolution: scale the intrinsics.
+    let (sx, sy) = (
+        img.width() as f64 / params.image_width.max(1) as f64,
+        img.height() as f64 / params.image_height.max(1) as f64,
+    );
+    let intrinsics = [
+        params.fx * sx,
+        params.fy * sy,
+        params.cx * sx,
+        params.cy * sy,
+        params.k1,
+        params.k2,
+        params.p1,
+        params.p2,
+        params.k3,
+    ];
+    let out = imageproc::geometric_transformations::warp_with(
+        &img,
+        |u, v| {
+            let x = (u as f64 - intrinsics[2]) / intrinsics[0];
+            let y = (v as f64 - intrinsics[3]) / intrinsics[1];
+            let (xd, yd) = distort(&intrinsics, x, y);
+            (
+                (intrinsics[0] * xd + intrinsics[2]) as f32,
+                (intrinsics[1] * yd + intrinsics[3]) as f32,
+            )
+        },
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+    );
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
@@ -0,0 +1,1296 @@
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use anyhow::Result;
+use image::{DynamicImage, GrayImage, Luma, Rgba};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
+
+use crate::api::image_ops::LumeColor;
+use crate::api::imageproc_ops::{LumeContour, LumePoint, LumeRect};
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// A shape for `draw_shapes`, in pixel coordinates.
+pub enum LumeShape {
+    Line {
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+    },
+    Rect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    Circle {
+        cx: i32,
+        cy: i32,
+        radius: i32,
+    },
+    Ellipse {
+        cx: i32,
+        cy: i32,
+        width_radius: i32,
+        height_radius: i32,
+    },
+    Polygon {
+        points: Vec<LumePoint>,
+    },
+    /// Connected line segments, e.g. a freehand stroke. Only a `closed`
+    /// polyline can be filled.
+    Polyline {
+        points: Vec<LumePoint>,
+        closed: bool,
+    },
+    Path {
+        segments: Vec<LumePathSegment>,
+    },
+    /// A line from (x1, y1) with a filled triangular head of length
+    /// `head_size` at (x2, y2).
+    Arrow {
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        head_size: f32,
+    },
+    /// Part of a circle's edge. Angles are in degrees, clockwise from the
+    /// positive x axis (3 o'clock); a `sweep` of 360 or more is the whole
+    /// circle. Arcs have no inside, so they are always stroked.
+    Arc {
+        cx: f32,
+        cy: f32,
+        radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+    /// A pie slice: the arc plus the two radii closing it.
+    Pie {
+        cx: f32,
+        cy: f32,
+        radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+    /// A segment of a donut between two radii, e.g. a progress ring.
+    Ring {
+        cx: f32,
+        cy: f32,
+        inner_radius: f32,
+        outer_radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+}
+
+/// One step of a `LumeShape::Path`, as in SVG or Flutter's `Path`. Each
+/// `MoveTo` starts a new subpath; filling treats every subpath as closed
+/// and uses the even-odd rule, so inner subpaths cut holes.
+pub enum LumePathSegment {
+    MoveTo {
+        x: f32,
+        y: f32,
+    },
+    LineTo {
+        x: f32,
+        y: f32,
+    },
+    QuadTo {
+        cx: f32,
+        cy: f32,
+        x: f32,
+        y: f32,
+    },
+    CubicTo {
+        c1x: f32,
+        c1y: f32,
+        c2x: f32,
+        c2y: f32,
+        x: f32,
+        y: f32,
+    },
+    Close,
+}
+
+/// Look of a `draw_callout` bubble. The text is drawn with the TrueType or
+/// OpenType font in `font_bytes` (no font is bundled).
+pub struct LumeCalloutStyle {
+    pub background: LumeColor,
+    pub border_color: LumeColor,
+    pub border_width: f32,
+    pub text_color: LumeColor,
+    pub font_bytes: Vec<u8>,
+    pub font_size: f32,
+    pub corner_radius: f32,
+    pub padding: u32,
+}
+
+/// A color stop of a gradient; `offset` is 0 at the gradient's start and 1
+/// at its end.
+pub struct LumeGradientStop {
+    pub offset: f32,
+    pub color: LumeColor,
+}
+
+/// Paint for the inside of filled shapes. Gradients are defined in image
+/// coordinates and repeat their end colors beyond their ends; `Pattern`
+/// tiles an encoded image from (`offset_x`, `offset_y`).
+pub enum LumeFill {
+    LinearGradient {
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        stops: Vec<LumeGradientStop>,
+    },
+    RadialGradient {
+        cx: f32,
+        cy: f32,
+        radius: f32,
+        stops: Vec<LumeGradientStop>,
+    },
+    Pattern {
+        image_bytes: Vec<u8>,
+        offset_x: i32,
+        offset_y: i32,
+    },
+}
+
+/// How `draw_shapes` paints. `opacity` (0-1) scales the color's alpha for
+/// the whole call. Lines are never filled. With `fill` set, filled shapes
+/// are painted with it instead of `color`, which is still used for lines.
+///
+/// Outlines and lines are `stroke_width` pixels wide, centered on the shape's
+/// edge, with rounded corners. `cap` ("butt", "round" or "square") shapes
+/// the ends of open lines and of dashes. `dash` alternates drawn and skipped
+/// lengths in pixels (e.g. `[12, 6]`); an empty list draws a solid line.
+pub struct LumeDrawStyle {
+    pub color: LumeColor,
+    pub opacity: f32,
+    pub filled: bool,
+    pub stroke_width: f32,
+    pub cap: String,
+    pub dash: Vec<f32>,
+    pub fill: Option<LumeFill>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cap {
+    Butt,
+    Round,
+    Square,
+}
+
+struct Stroke {
+    width: f32,
+    cap: Cap,
+    dash: Vec<f32>,
+}
+
+impl Stroke {
+    fn from_style(style: &LumeDrawStyle) -> Result<Stroke> {
+        let cap = match style.cap.to_lowercase().as_str() {
+            "butt" => Cap::Butt,
+            "round" => Cap::Round,
+            "square" => Cap::Square,
+            other => return Err(anyhow::anyhow!("Unsupported line cap: {}", other)),
+        };
+        if style.dash.iter().any(|&d| !(d >= 0.0 && d.is_finite())) {
+            return Err(anyhow::anyhow!("Dash lengths must be zero or positive"));
+        }
+        // An odd-length pattern is repeated, so drawn and skipped lengths
+        // alternate on each pass (as in SVG).
+        let mut dash = style.dash.clone();
+        if dash.len() % 2 == 1 {
+            dash.extend_from_within(..);
+        }
+        if dash.iter().sum::<f32>() <= 0.0 {
+            dash.clear();
+        }
+        Ok(Stroke {
+            width: style.stroke_width.max(1.0),
+            cap,
+            dash,
+        })
+    }
+}
+
+type Pt = (f32, f32);
+
+/// Gradient colors sorted by offset, ready to be sampled.
+pub(crate) struct Gradient(Vec<(f32, Rgba<u8>)>);
+
+impl Gradient {
+    pub(crate) fn new(stops: &[LumeGradientStop]) -> Result<Gradient> {
+        if stops.is_empty() {
+            return Err(anyhow::anyhow!("A gradient needs at least one color stop"));
+        }
+        let mut sorted: Vec<(f32, Rgba<u8>)> = stops
+            .iter()
+            .map(|s| {
+                let c = &s.color;
+                (s.offset.clamp(0.0, 1.0), Rgba([c.r, c.g, c.b, c.a]))
+            })
+            .collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Gradient(sorted))
+    }
+
+    /// Color at `t`, clamped to the first and last stops.
+    pub(crate) fn at(&self, t: f32) -> Rgba<u8> {
+        let stops = &self.0;
+        let next = stops.partition_point(|s| s.0 <= t);
+        if next == 0 {
+            return stops[0].1;
+        }
+        if next == stops.len() {
+            return stops[next - 1].1;
+        }
+        let ((t0, a), (t1, b)) = (stops[next - 1], stops[next]);
+        helpers::lerp_rgba(&a, &b, (t - t0) / (t1 - t0))
+    }
+}
+
+enum Paint {
+    Linear {
+        from: Pt,
+        dir: Pt,
+        gradient: Gradient,
+    },
+    Radial {
+        center: Pt,
+        radius: f32,
+        gradient: Gradient,
+    },
+    Pattern {
+        tile: image::RgbaImage,
+        offset: (i32, i32),
+    },
+}
+
+impl Paint {
+    fn new(fill: &LumeFill) -> Result<Paint> {
+        Ok(match *fill {
+            LumeFill::LinearGradient {
+                x1,
+                y1,
+                x2,
+                y2,
+                ref stops,
+            } => {
+                // Scaled so the projection on `dir` is 0 at the start and 1
+                // at the end.
+                let len2 = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).max(f32::EPSILON);
+                Paint::Linear {
+                    from: (x1, y1),
+                    dir: ((x2 - x1) / len2, (y2 - y1) / len2),
+                    gradient: Gradient::new(stops)?,
+                }
+            }
+            LumeFill::RadialGradient {
+                cx,
+                cy,
+                radius,
+                ref stops,
+            } => Paint::Radial {
+                center: (cx, cy),
+                radius: radius.max(f32::EPSILON),
+                gradient: Gradient::new(stops)?,
+            },
+            LumeFill::Pattern {
+                ref image_bytes,
+                offset_x,
+                offset_y,
+            } => {
+                let tile = helpers::load(image_bytes)?.to_rgba8();
+                if tile.width() == 0 || tile.height() == 0 {
+                    return Err(anyhow::anyhow!("Pattern image is empty"));
+                }
+                Paint::Pattern {
+                    tile,
+                    offset: (offset_x, offset_y),
+                }
+            }
+        })
+    }
+
+    fn at(&self, x: u32, y: u32) -> Rgba<u8> {
+        let (fx, fy) = (x as f32, y as f32);
+        match self {
+            Paint::Linear {
+                from,
+                dir,
+                gradient,
+            } => gradient.at((fx - from.0) * dir.0 + (fy - from.1) * dir.1),
+            Paint::Radial {
+                center,
+                radius,
+                gradient,
+            } => gradient.at((fx - center.0).hypot(fy - center.1) / radius),
+            Paint::Pattern { tile, offset } => {
+                let tx = (x as i64 - offset.0 as i64).rem_euclid(tile.width() as i64);
+                let ty = (y as i64 - offset.1 as i64).rem_euclid(tile.height() as i64);
+                *tile.get_pixel(tx as u32, ty as u32)
+            }
+        }
+    }
+}
+
+const INK: Luma<u8> = Luma([255]);
+
+// ---------------------------------------------------------------------------
+// Rasterization
+// ---------------------------------------------------------------------------
+//
+// Coordinates are pixel indices: pixel (x, y) is the unit square centered on
+// (x, y), as in imageproc.
+
+fn fill_polygon(mask: &mut GrayImage, pts: &[Pt]) {
+    fill_rings(mask, &[pts]);
+}
+
+/// Fills polygons together with the even-odd rule by covering every pixel
+/// whose center is inside.
+fn fill_rings(mask: &mut GrayImage, rings: &[&[Pt]]) {
+    let rings: Vec<&[Pt]> = rings.iter().copied().filter(|r| r.len() > 2).collect();
+    if rings.is_empty() {
+        return;
+    }
+    let (w, h) = (mask.width() as i64, mask.height() as i64);
+    let all = || rings.iter().flat_map(|r| r.iter());
+    let min_y = all().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let max_y = all().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+    let y0 = (min_y.ceil() as i64).max(0);
+    let y1 = (max_y.floor() as i64).min(h - 1);
+    let mut crossings = Vec::new();
+    for y in y0..=y1 {
+        let fy = y as f32;
+        crossings.clear();
+        for pts in &rings {
+            for (i, &(ax, ay)) in pts.iter().enumerate() {
+                let (bx, by) = pts[(i + 1) % pts.len()];
+                // Half-open in y so shared vertices are counted once.
+                if (ay <= fy) != (by <= fy) {
+                    crossings.push(ax + (fy - ay) / (by - ay) * (bx - ax));
+                }
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            let x0 = (span[0].ceil() as i64).max(0);
+            let x1 = (span[1].ceil() as i64).min(w);
+            for x in x0..x1 {
+                mask.put_pixel(x as u32, y as u32, INK);
+            }
+        }
+    }
+}
+
+fn fill_disc(mask: &mut GrayImage, (cx, cy): Pt, radius: f32) {
+    let (w, h) = (mask.width() as i64, mask.height() as i64);
+    let r2 = radius * radius;
+    let y0 = ((cy - radius).ceil() as i64).max(0);
+    let y1 = ((cy + radius).floor() as i64).min(h - 1);
+    for y in y0..=y1 {
+        let dy = y as f32 - cy;
+        let half = (r2 - dy * dy).max(0.0).sqrt();
+        let x0 = ((cx - half).ceil() as i64).max(0);
+        let x1 = ((cx + half).floor() as i64).min(w - 1);
+        for x in x0..=x1 {
+            mask.put_pixel(x as u32, y as u32, INK);
+        }
+    }
+}
+
+/// Splits a polyline into its drawn dashes.
+fn dash_path(path: &[Pt], pattern: &[f32]) -> Vec<Vec<Pt>> {
+    let mut pieces = Vec::new();
+    let mut current = vec![path[0]];
+    let (mut index, mut on) = (0, true);
+    let mut left = pattern[0];
+    for seg in path.windows(2) {
+        let ((ax, ay), (bx, by)) = (seg[0], seg[1]);
+        let len = (bx - ax).hypot(by - ay);
+        let mut t = 0.0;
+        while len - t > left {
+            t += left;
+            let p = (ax + (bx - ax) * t / len, ay + (by - ay) * t / len);
+            if on {
+                current.push(p);
+                pieces.push(std::mem::take(&mut current));
+            } else {
+                current = vec![p];
+            }
+            on = !on;
+            index = (index + 1) % pattern.len();
+            left = pattern[index];
+        }
+        left -= len - t;
+        if on {
+            current.push((bx, by));
+        }
+    }
+    if on {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// Strokes one continuous polyline. `closed` paths (first point repeated
+/// at the end) get a join instead of caps at their start.
+fn stroke_piece(mask: &mut GrayImage, piece: &[Pt], stroke: &Stroke, closed: bool) {
+    let mut pts = piece.to_vec();
+    pts.dedup();
+    if stroke.width <= 1.0 {
+        if pts.len() == 1 {
+            pts.push(pts[0]);
+        }
+        for seg in pts.windows(2) {
+            imageproc::drawing::draw_line_segment_mut(mask, seg[0], seg[1], INK);
+        }
+        return;
+    }
+    let half = stroke.width / 2.0;
+    if pts.len() == 1 {
+        let (x, y) = pts[0];
+        match stroke.cap {
+            Cap::Butt => {}
+            Cap::Round => fill_disc(mask, pts[0], half),
+            Cap::Square => fill_polygon(
+                mask,
+                &[
+                    (x - half, y - half),
+                    (x + half, y - half),
+                    (x + half, y + half),
+                    (x - half, y + half),
+                ],
+            ),
+        }
+        return;
+    }
+    let last = pts.len() - 1;
+    if !closed && stroke.cap == Cap::Square {
+        let extend = |from: Pt, to: Pt| {
+            let len = (to.0 - from.0).hypot(to.1 - from.1);
+            (
+                to.0 + (to.0 - from.0) / len * half,
+                to.1 + (to.1 - from.1) / len * half,
+            )
+        };
+        pts[0] = extend(pts[1], pts[0]);
+        pts[last] = extend(pts[last - 1], pts[last]);
+    }
+    for seg in pts.windows(2) {
+        let ((ax, ay), (bx, by)) = (seg[0], seg[1]);
+        let len = (bx - ax).hypot(by - ay);
+        let (nx, ny) = (-(by - ay) / len * half, (bx - ax) / len * half);
+        fill_polygon(
+            mask,
+            &[
+                (ax + nx, ay + ny),
+                (bx + nx, by + ny),
+                (bx - nx, by - ny),
+                (ax - nx, ay - ny),
+            ],
+        );
+    }
+    // Round joins; a closed path also joins at its start.
+    let (first, end) = if closed { (0, last) } else { (1, last - 1) };
+    for &p in pts.iter().take(end + 1).skip(first) {
+        fill_disc(mask, p, half);
+    }
+    if !closed && stroke.cap == Cap::Round {
+        fill_disc(mask, pts[0], half);
+        fill_disc(mask, pts[last], half);
+    }
+}
+
+fn stroke_path(mask: &mut GrayImage, points: &[Pt], closed: bool, stroke: &Stroke) {
+    if points.is_empty() {
+        return;
+    }
+    let mut path = points.to_vec();
+    if closed && path.len() > 2 {
+        path.push(path[0]);
+    }
+    if stroke.dash.is_empty() {
+        stroke_piece(mask, &path, stroke, closed && path.len() > 2);
+    } else {
+        for piece in dash_path(&path, &stroke.dash) {
+            stroke_piece(mask, &piece, stroke, false);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Shapes
+// ---------------------------------------------------------------------------
+
+/// Points around an ellipse, about 2 pixels apart.
+fn ellipse_points(cx: f32, cy: f32, rx: f32, ry: f32) -> Vec<Pt> {
+    let n = (std::f32::consts::PI * (rx + ry) / 2.0)
+        .ceil()
+        .clamp(12.0, 4096.0) as usize;
+    (0..n)
+        .map(|i| {
+            let t = i as f32 / n as f32 * std::f32::consts::TAU;
+            (cx + rx * t.cos(), cy + ry * t.sin())
+        })
+        .collect()
+}
+
+/// Points along an arc, about 2 pixels apart, both ends included.
+fn arc_points(cx: f32, cy: f32, radius: f32, start_angle: f32, sweep: f32) -> Vec<Pt> {
+    let sweep = sweep.clamp(-360.0, 360.0);
+    let n = (sweep.abs().to_radians() * radius / 2.0)
+        .ceil()
+        .clamp(2.0, 4096.0) as usize;
+    (0..=n)
+        .map(|i| {
+            let t = (start_angle + sweep * i as f32 / n as f32).to_radians();
+            (cx + radius * t.cos(), cy + radius * t.sin())
+        })
+        .collect()
+}
+
+/// A full circle as a closed polyline (without repeating the first point).
+fn circle_points(cx: f32, cy: f32, radius: f32) -> Vec<Pt> {
+    let mut pts = arc_points(cx, cy, radius, 0.0, 360.0);
+    pts.pop();
+    pts
+}
+
+/// Number of segments approximating a curve whose control polygon is
+/// `length` pixels long, about 2 pixels each.
+fn curve_steps(length: f32) -> usize {
+    (length / 2.0).ceil().clamp(4.0, 1024.0) as usize
+}
+
+/// Flattens path segments into polylines, one per subpath, each with
+/// whether it was closed.
+fn flatten_path(segments: &[LumePathSegment]) -> Vec<(Vec<Pt>, bool)> {
+    let dist = |a: Pt, b: Pt| (b.0 - a.0).hypot(b.1 - a.1);
+    let mut subpaths = Vec::new();
+    let mut current: Vec<Pt> = Vec::new();
+    let mut pen = (0.0, 0.0);
+    for segment in segments {
+        match *segment {
+            LumePathSegment::MoveTo { x, y } => {
+                if current.len() > 1 {
+                    subpaths.push((std::mem::take(&mut current), false));
+                }
+                pen = (x, y);
+                current = vec![pen];
+            }
+            LumePathSegment::LineTo { x, y } => {
+                if current.is_empty() {
+                    current.push(pen);
+                }
+                pen = (x, y);
+                current.push(pen);
+            }
+            LumePathSegment::QuadTo { cx, cy, x, y } => {
+                if current.is_empty() {
+                    current.push(pen);
+                }
+                let (p0, c, p1) = (pen, (cx, cy), (x, y));
+                let n = curve_steps(dist(p0, c) + dist(c, p1));
+                for i in 1..=n {
+                    let t = i as f32 / n as f32;
+                    let (a, b, d) = ((1.0 - t) * (1.0 - t), 2.0 * (1.0 - t) * t, t * t);
+                    current.push((a * p0.0 + b * c.0 + d * p1.0, a * p0.1 + b * c.1 + d * p1.1));
+                }
+                pen = p1;
+            }
+            LumePathSegment::CubicTo {
+                c1x,
+                c1y,
+                c2x,
+                c2y,
+                x,
+                y,
+            } => {
+                if current.is_empty() {
+                    current.push(pen);
+                }
+                let (p0, c1, c2, p1) = (pen, (c1x, c1y), (c2x, c2y), (x, y));
+                let n = curve_steps(dist(p0, c1) + dist(c1, c2) + dist(c2, p1));
+                for i in 1..=n {
+                    let t = i as f32 / n as f32;
+                    let u = 1.0 - t;
+                    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                    current.push((
+                        a * p0.0 + b * c1.0 + c * c2.0 + d * p1.0,
+                        a * p0.1 + b * c1.1 + c * c2.1 + d * p1.1,
+                    ));
+                }
+                pen = p1;
+            }
+            LumePathSegment::Close => {
+                if let Some(&start) = current.first() {
+                    subpaths.push((std::mem::take(&mut current), true));
+                    pen = start;
+                }
+            }
+        }
+    }
+    if current.len() > 1 {
+        subpaths.push((current, false));
+    }
+    subpaths
+}
+
+fn to_points(points: &[LumePoint]) -> Vec<Pt> {
+    points.iter().map(|p| (p.x as f32, p.y as f32)).collect()
+}
+
+/// The shape's edges as polylines, each with whether it is closed.
+fn outline(shape: &LumeShape) -> Vec<(Vec<Pt>, bool)> {
+    let single = match *shape {
+        LumeShape::Line { x1, y1, x2, y2 } => (vec![(x1, y1), (x2, y2)], false),
+        LumeShape::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            if width == 0 || height == 0 {
+                return Vec::new();
+            }
+            let (x0, y0) = (x as f32, y as f32);
+            let (x1, y1) = (x0 + width as f32 - 1.0, y0 + height as f32 - 1.0);
+            (vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)], true)
+        }
+        LumeShape::Circle { cx, cy, radius } => (
+            ellipse_points(cx as f32, cy as f32, radius as f32, radius as f32),
+            true,
+        ),
+        LumeShape::Ellipse {
+            cx,
+            cy,
+            width_radius,
+            height_radius,
+        } => (
+            ellipse_points(
+                cx as f32,
+                cy as f32,
+                width_radius as f32,
+                height_radius as f32,
+            ),
+            true,
+        ),
+        LumeShape::Polygon { ref points } => {
+            let mut pts = to_points(points);
+            if pts.len() > 1 && pts.first() == pts.last() {
+                pts.pop();
+            }
+            (pts, true)
+        }
+        LumeShape::Polyline { ref points, closed } => (to_points(points), closed),
+        LumeShape::Path { ref segments } => return flatten_path(segments),
+        LumeShape::Arrow { x1, y1, x2, y2, .. } => (vec![(x1, y1), (x2, y2)], false),
+        LumeShape::Arc {
+            cx,
+            cy,
+            radius,
+            start_angle,
+            sweep,
+        } => {
+            if sweep.abs() >= 360.0 {
+                (circle_points(cx, cy, radius), true)
+            } else {
+                (arc_points(cx, cy, radius, start_angle, sweep), false)
+            }
+        }
+        LumeShape::Pie {
+            cx,
+            cy,
+            radius,
+            start_angle,
+            sweep,
+        } => {
+            if sweep.abs() >= 360.0 {
+                (circle_points(cx, cy, radius), true)
+            } else {
+                let mut pts = vec![(cx, cy)];
+                pts.extend(arc_points(cx, cy, radius, start_angle, sweep));
+                (pts, true)
+            }
+        }
+        LumeShape::Ring {
+            cx,
+            cy,
+            inner_radius,
+            outer_radius,
+            start_angle,
+            sweep,
+        } => {
+            if sweep.abs() >= 360.0 {
+                return vec![
+                    (circle_points(cx, cy, outer_radius), true),
+                    (circle_points(cx, cy, inner_radius), true),
+                ];
+            }
+            let mut pts = arc_points(cx, cy, outer_radius, start_angle, sweep);
+            let mut inner = arc_points(cx, cy, inner_radius, start_angle, sweep);
+            inner.reverse();
+            pts.extend(inner);
+            (pts, true)
+        }
+    };
+    vec![single]
+}
+
+/// Fills the inside of `shape`. Returns false for shapes without an inside.
+fn fill_shape(mask: &mut GrayImage, shape: &LumeShape) -> bool {
+    use imageproc::drawing::*;
+    match *shape {
+        LumeShape::Line { .. } | LumeShape::Arrow { .. } | LumeShape::Arc { .. } => return false,
+        LumeShape::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            if width > 0 && height > 0 {
+                draw_filled_rect_mut(mask, Rect::at(x, y).of_size(width, height), INK);
+            }
+        }
+        LumeShape::Circle { cx, cy, radius } => draw_filled_circle_mut(mask, (cx, cy), radius, INK),
+        LumeShape::Ellipse {
+            cx,
+            cy,
+            width_radius,
+            height_radius,
+        } => draw_filled_ellipse_mut(mask, (cx, cy), width_radius, height_radius, INK),
+        LumeShape::Polygon { ref points } => {
+            let mut pts: Vec<Point<i32>> = points.iter().map(|p| Point::new(p.x, p.y)).collect();
+            // imageproc rejects an explicitly closed polygon.
+            if pts.len() > 1 && pts.first() == pts.last() {
+                pts.pop();
+            }
+            if pts.len() > 2 {
+                draw_polygon_mut(mask, &pts, INK);
+            } else {
+                return false;
+            }
+        }
+        LumeShape::Polyline { ref points, closed } => {
+            if !closed {
+                return false;
+            }
+            fill_polygon(mask, &to_points(points));
+        }
+        LumeShape::Path { ref segments } => {
+            let subpaths = flatten_path(segments);
+            let rings: Vec<&[Pt]> = subpaths.iter().map(|(pts, _)| pts.as_slice()).collect();
+            fill_rings(mask, &rings);
+        }
+        LumeShape::Pie { .. } | LumeShape::Ring { .. } => {
+            let subpaths = outline(shape);
+            let rings: Vec<&[Pt]> = subpaths.iter().map(|(pts, _)| pts.as_slice()).collect();
+            fill_rings(mask, &rings);
+        }
+    }
+    true
+}
+
+/// Strokes the shaft of an arrow and fills its head. The head is at least
+/// twice as wide as the shaft so the shaft never shows past it.
+fn stroke_arrow(mask: &mut GrayImage, from: Pt, tip: Pt, head_size: f32, stroke: &Stroke) {
+    let (dx, dy) = (tip.0 - from.0, tip.1 - from.1);
+    let len = dx.hypot(dy);
+    if len == 0.0 {
+        return;
+    }
+    let (ux, uy) = (dx / len, dy / len);
+    let head = head_size.max(0.0).min(len);
+    let half = (head / 2.0).max(stroke.width);
+    let base = (tip.0 - ux * head, tip.1 - uy * head);
+    // The shaft runs halfway into the head so no seam shows at its base.
+    let shaft_end = (base.0 + ux * head / 2.0, base.1 + uy * head / 2.0);
+    stroke_path(mask, &[from, shaft_end], false, stroke);
+    fill_polygon(
+        mask,
+        &[
+            tip,
+            (base.0 - uy * half, base.1 + ux * half),
+            (base.0 + uy * half, base.1 - ux * half),
+        ],
+    );
+}
+
+/// Draws `shapes` in one pass. The shapes are combined into a single layer
+/// that is blended over the image once, so overlapping shapes do not build
+/// up opacity and a translucent highlight box or scrim looks even.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_shapes(
+    image_bytes: Vec<u8>,
+    shapes: Vec<LumeShape>,
+    style: LumeDrawStyle,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let stroke = Stroke::from_style(&style)?;
+    let paint = style.fill.as_ref().map(Paint::new).transpose()?;
+    let mut mask = GrayImage::new(img.width(), img.height());
+    let mut fill_mask = GrayImage::new(img.width(), img.height());
+    for shape in &shapes {
+        if let LumeShape::Arrow {
+            x1,
+            y1,
+            x2,
+            y2,
+            head_size,
+        } = *shape
+        {
+            stroke_arrow(&mut mask, (x1, y1), (x2, y2), head_size, &stroke);
+            continue;
+        }
+        let target = if paint.is_some() {
+            &mut fill_mask
+        } else {
+            &mut mask
+        };
+        if style.filled && fill_shape(target, shape) {
+            continue;
+        }
+        for (points, closed) in outline(shape) {
+            stroke_path(&mut mask, &points, closed, &stroke);
+        }
+    }
+    if let Some(paint) = &paint {
+        helpers::paint_over(&mut img, &fill_mask, style.opacity, |x, y| paint.at(x, y));
+    }
+    let c = &style.color;
+    let color = Rgba([c.r, c.g, c.b, c.a]);
+    helpers::paint_over(&mut img, &mask, style.opacity, |_, _| color);
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
+
+/// Strokes a polyline with round caps and joins, e.g. to commit a freehand
+/// annotation in one call. `closed` connects the last point to the first.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_polyline(
+    image_bytes: Vec<u8>,
+    points: Vec<LumePoint>,
+    color: LumeColor,
+    width: f32,
+    closed: bool,
+) -> Result<Vec<u8>> {
+    draw_shapes(
+        image_bytes,
+        vec![LumeShape::Polyline { points, closed }],
+        LumeDrawStyle {
+            color,
+            opacity: 1.0,
+            filled: false,
+            stroke_width: width,
+            cap: "round".to_string(),
+            dash: Vec::new(),
+            fill: None,
+        },
+    )
+}
+
+/// Draws an arrow from (x1, y1) pointing at (x2, y2), e.g. to point out a
+/// detail in a screenshot.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_arrow(
+    image_bytes: Vec<u8>,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    head_size: f32,
+    width: f32,
+    color: LumeColor,
+) -> Result<Vec<u8>> {
+    draw_shapes(
+        image_bytes,
+        vec![LumeShape::Arrow {
+            x1,
+            y1,
+            x2,
+            y2,
+            head_size,
+        }],
+        LumeDrawStyle {
+            color,
+            opacity: 1.0,
+            filled: false,
+            stroke_width: width,
+            cap: "round".to_string(),
+            dash: Vec::new(),
+            fill: None,
+        },
+    )
+}
+
+/// Strokes part of a circle, e.g. a progress indicator; see
+/// `LumeShape::Arc` for the angles.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_arc(
+    image_bytes: Vec<u8>,
+    cx: f32,
+    cy: f32,
+    radius: f32,
+    start_angle: f32,
+    sweep: f32,
+    width: f32,
+    color: LumeColor,
+) -> Result<Vec<u8>> {
+    draw_shapes(
+        image_bytes,
+        vec![LumeShape::Arc {
+            cx,
+            cy,
+            radius,
+            start_angle,
+            sweep,
+        }],
+        LumeDrawStyle {
+            color,
+            opacity: 1.0,
+            filled: false,
+            stroke_width: width,
+            cap: "round".to_string(),
+            dash: Vec::new(),
+            fill: None,
+        },
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Markers & contours
+// ---------------------------------------------------------------------------
+
+/// Draws the same 1px marker centered on each of `points`, e.g. to plot
+/// detected keypoints. `marker` is "cross", "circle" or "square", and `size`
+/// is its width in pixels. All markers go through one decode and encode.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_markers(
+    image_bytes: Vec<u8>,
+    points: Vec<LumePoint>,
+    marker: String,
+    size: u32,
+    color: LumeColor,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let mut mask = GrayImage::new(img.width(), img.height());
+    let half = (size / 2) as i32;
+    let side = half as u32 * 2 + 1;
+    let draw: fn(&mut GrayImage, i32, i32, i32, u32) = match marker.to_lowercase().as_str() {
+        "cross" => |mask, x, y, half, _| {
+            let (x, y, half) = (x as f32, y as f32, half as f32);
+            imageproc::drawing::draw_line_segment_mut(mask, (x - half, y), (x + half, y), INK);
+            imageproc::drawing::draw_line_segment_mut(mask, (x, y - half), (x, y + half), INK);
+        },
+        "circle" => |mask, x, y, half, _| {
+            imageproc::drawing::draw_hollow_circle_mut(mask, (x, y), half, INK);
+        },
+        "square" => |mask, x, y, half, side| {
+            let rect = Rect::at(x - half, y - half).of_size(side, side);
+            imageproc::drawing::draw_hollow_rect_mut(mask, rect, INK);
+        },
+        other => return Err(anyhow::anyhow!("Unsupported marker: {}", other)),
+    };
+    for p in &points {
+        draw(&mut mask, p.x, p.y, half, side);
+    }
+    let color = Rgba([color.r, color.g, color.b, color.a]);
+    helpers::paint_over(&mut img, &mask, 1.0, |_, _| color);
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
+
+/// Draws contours from `find_contours` over the image. A positive
+/// `thickness` strokes them; 0 fills the regions they enclose, leaving hole
+/// contours see-through unless `fill_holes` is set.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_contours(
+    image_bytes: Vec<u8>,
+    contours: Vec<LumeContour>,
+    color: LumeColor,
+    thickness: f32,
+    fill_holes: bool,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let mut mask = GrayImage::new(img.width(), img.height());
+    let paths: Vec<Vec<Pt>> = contours.iter().map(|c| to_points(&c.points)).collect();
+    if thickness > 0.0 {
+        let stroke = Stroke {
+            width: thickness.max(1.0),
+            cap: Cap::Round,
+            dash: Vec::new(),
+        };
+        for path in &paths {
+            stroke_path(&mut mask, path, true, &stroke);
+        }
+    } else {
+        // Contour points are the region's own border pixels, which filling
+        // at pixel centers only partly covers, so they are drawn on top.
+        if fill_holes {
+            for (contour, path) in contours.iter().zip(&paths) {
+                if contour.border_type != "hole" {
+                    fill_polygon(&mut mask, path);
+                }
+            }
+        } else {
+            // Holes nest inside outer borders, so the even-odd rule cuts them out.
+            let rings: Vec<&[Pt]> = paths.iter().map(Vec::as_slice).collect();
+            fill_rings(&mut mask, &rings);
+        }
+        let outline = Stroke {
+            width: 1.0,
+            cap: Cap::Butt,
+            dash: Vec::new(),
+        };
+        for path in &paths {
+            stroke_path(&mut mask, path, true, &outline);
+        }
+    }
+    let color = Rgba([color.r, color.g, color.b, color.a]);
+    helpers::paint_over(&mut img, &mask, 1.0, |_, _| color);
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Guides
+// ---------------------------------------------------------------------------
+
+/// Draws 1px composition guides, e.g. for camera preview snapshots or crop
+/// UI exports. `kind` is:
+/// - "thirds": rule-of-thirds lines;
+/// - "golden": lines at the golden ratio (about 38% and 62%);
+/// - "squares": a grid of `spacing`-pixel cells from the top-left corner.
+///
+/// `spacing` is only used by "squares". The lines are blended at `opacity`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_grid(
+    image_bytes: Vec<u8>,
+    kind: String,
+    spacing: u32,
+    color: LumeColor,
+    opacity: f32,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = img.dimensions();
+    let at = |size: u32, fractions: &[f32]| -> Vec<u32> {
+        fractions
+            .iter()
+            .map(|f| ((size as f32 * f).round() as u32).min(size.saturating_sub(1)))
+            .collect()
+    };
+    let (cols, rows) = match kind.to_lowercase().as_str() {
+        "thirds" => {
+            let f = [1.0 / 3.0, 2.0 / 3.0];
+            (at(w, &f), at(h, &f))
+        }
+        "golden" => {
+            let minor = 1.0 - 1.0 / ((1.0 + 5f32.sqrt()) / 2.0);
+            let f = [minor, 1.0 - minor];
+            (at(w, &f), at(h, &f))
+        }
+        "squares" => {
+            if spacing == 0 {
+                return Err(anyhow::anyhow!("Grid spacing must be positive"));
+            }
+            let step = spacing as usize;
+            (
+                (spacing..w).step_by(step).collect(),
+                (spacing..h).step_by(step).collect(),
+            )
+        }
+        other => return Err(anyhow::anyhow!("Unsupported grid: {}", other)),
+    };
+    let mut mask = GrayImage::new(w, h);
+    for x in cols {
+        for y in 0..h {
+            mask.put_pixel(x, y, INK);
+        }
+    }
+    for y in rows {
+        for x in 0..w {
+            mask.put_pixel(x, y, INK);
+        }
+    }
+    let color = Rgba([color.r, color.g, color.b, color.a]);
+    helpers::paint_over(&mut img, &mask, opacity, |_, _| color);
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Callouts
+// ---------------------------------------------------------------------------
+
+fn corner(pts: &mut Vec<Pt>, (cx, cy): Pt, radius: f32, start_deg: f32) {
+    let steps = ((radius / 2.0).ceil() as usize).clamp(1, 64);
+    for i in 0..=steps {
+        let t = (start_deg + 90.0 * i as f32 / steps as f32).to_radians();
+        pts.push((cx + radius * t.cos(), cy + radius * t.sin()));
+    }
+}
+
+/// Outline of a rounded rectangle with a pointed tail towards `anchor`,
+/// leaving from the side the anchor lies beyond. No tail when the anchor is
+/// inside the rectangle.
+fn bubble_outline(x0: f32, y0: f32, x1: f32, y1: f32, radius: f32, anchor: Pt) -> Vec<Pt> {
+    let r = radius.clamp(0.0, (x1 - x0).min(y1 - y0) / 2.0);
+    let (ax, ay) = anchor;
+    let out_x = (x0 - ax).max(ax - x1).max(0.0);
+    let out_y = (y0 - ay).max(ay - y1).max(0.0);
+    // 0 = top, 1 = right, 2 = bottom, 3 = left, in drawing order.
+    let side = if out_y >= out_x && out_y > 0.0 {
+        Some(if ay < y0 { 0 } else { 2 })
+    } else if out_x > 0.0 {
+        Some(if ax > x1 { 1 } else { 3 })
+    } else {
+        None
+    };
+    let half = ((x1 - x0).min(y1 - y0) / 4.0).clamp(3.0, 16.0);
+    let base = |lo: f32, hi: f32, at: f32| {
+        let (lo, hi) = (lo + r + half, hi - r - half);
+        if lo <= hi {
+            at.clamp(lo, hi)
+        } else {
+            (lo + hi) / 2.0
+        }
+    };
+
+    let mut pts = Vec::new();
+    corner(&mut pts, (x0 + r, y0 + r), r, 180.0);
+    if side == Some(0) {
+        let c = base(x0, x1, ax);
+        pts.extend([(c - half, y0), anchor, (c + half, y0)]);
+    }
+    corner(&mut pts, (x1 - r, y0 + r), r, 270.0);
+    if side == Some(1) {
+        let c = base(y0, y1, ay);
+        pts.extend([(x1, c - half), anchor, (x1, c + half)]);
+    }
+    corner(&mut pts, (x1 - r, y1 - r), r, 0.0);
+    if side == Some(2) {
+        let c = base(x0, x1, ax);
+        pts.extend([(c + half, y1), anchor, (c - half, y1)]);
+    }
+    corner(&mut pts, (x0 + r, y1 - r), r, 90.0);
+    if side == Some(3) {
+        let c = base(y0, y1, ay);
+        pts.extend([(x0, c + half), anchor, (x0, c - half)]);
+    }
+    pts
+}
+
+/// Greedy word wrap; explicit newlines are kept. Words wider than
+/// `max_width` get a line of their own.
+fn wrap_text(font: &FontRef, scale: PxScale, text: &str, max_width: f32) -> Vec<String> {
+    let width = |s: &str| imageproc::drawing::text_size(scale, font, s).0 as f32;
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if !line.is_empty() && width(&candidate) > max_width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Draws a speech-bubble label: a rounded box over `label_rect` with a tail
+/// pointing at `anchor` and `text` wrapped and centered inside. The box
+/// keeps its size; text that does not fit overflows it.
+#[flutter_rust_bridge::frb(sync)]
+pub fn draw_callout(
+    image_bytes: Vec<u8>,
+    anchor: LumePoint,
+    label_rect: LumeRect,
+    text: String,
+    style: LumeCalloutStyle,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let font = if text.trim().is_empty() {
+        None
+    } else if style.font_bytes.is_empty() {
+        return Err(anyhow::anyhow!("A font is needed to draw text"));
+    } else {
+        Some(
+            FontRef::try_from_slice(&style.font_bytes)
+                .map_err(|e| anyhow::anyhow!("Invalid font: {}", e))?,
+        )
+    };
+    let color = |c: &LumeColor| Rgba([c.r, c.g, c.b, c.a]);
+    let (w, h) = img.dimensions();
+
+    // Pixel edges of the rectangle, so the fill covers exactly its pixels.
+    let x0 = label_rect.x as f32 - 0.5;
+    let y0 = label_rect.y as f32 - 0.5;
+    let x1 = x0 + label_rect.width as f32;
+    let y1 = y0 + label_rect.height as f32;
+    let outline = bubble_outline(
+        x0,
+        y0,
+        x1,
+        y1,
+        style.corner_radius,
+        (anchor.x as f32, anchor.y as f32),
+    );
+
+    let mut mask = GrayImage::new(w, h);
+    fill_polygon(&mut mask, &outline);
+    helpers::paint_over(&mut img, &mask, 1.0, |_, _| color(&style.background));
+    if style.border_width > 0.0 {
+        let mut mask = GrayImage::new(w, h);
+        let stroke = Stroke {
+            width: style.border_width,
+            cap: Cap::Butt,
+            dash: Vec::new(),
+        };
+        stroke_path(&mut mask, &outline, true, &stroke);
+        helpers::paint_over(&mut img, &mask, 1.0, |_, _| color(&style.border_color));
+    }
+
+    if let Some(font) = font {
+        let scale = PxScale::from(style.font_size.max(1.0));
+        let padding = style.padding as f32;
+        let lines = wrap_text(&font, scale, &text, x1 - x0 - 2.0 * padding);
+        let scaled = font.as_scaled(scale);
+        let line_height = scaled.height() + scaled.line_gap();
+        let total = line_height * lines.len() as f32 - scaled.line_gap();
+        let center_x = (x0 + x1) / 2.0;
+        let mut top = (y0 + y1) / 2.0 - total / 2.0;
+        let mut mask = GrayImage::new(w, h);
+        for line in &lines {
+            let line_width = imageproc::drawing::text_size(scale, &font, line).0 as f32;
+            let left = (center_x - line_width / 2.0).round() as i32;
+            imageproc::drawing::draw_text_mut(
+                &mut mask,
+                INK,
+                left,
+                top.round() as i32,
+                scale,
+                &font,
+                line,
+            );
+            top += line_height;
+        }
+        helpers::paint_over(&mut img, &mask, 1.0, |_, _| color(&style.text_color));
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
@@ -0,0 +1,729 @@
+use anyhow::Result;
+use image::{DynamicImage, GrayImage, ImageFormat, Rgba, RgbaImage};
+
+use crate::api::imageproc_ops::LumeRect;
+use crate::api::mask::{self, LumeMask};
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Tilt-shift
+// ---------------------------------------------------------------------------
+
+/// Miniature-faking blur: rows inside the band of `focus_band_height` pixels
+/// centered on `focus_y` stay sharp, and the blur grows linearly towards the
+/// top and bottom edges until it reaches `max_sigma`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn tilt_shift(
+    image_bytes: Vec<u8>,
+    focus_y: u32,
+    focus_band_height: u32,
+    max_sigma: f32,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    if max_sigma <= 0.0 {
+        return helpers::encode(&DynamicImage::ImageRgba8(img), fmt);
+    }
+
+    // Several blur levels interpolated piecewise look far smoother than a
+    // single sharp/blurred cross-fade.
+    const LEVELS: usize = 4;
+    let mut levels = vec![img.clone()];
+    for i in 1..LEVELS {
+        let sigma = max_sigma * i as f32 / (LEVELS - 1) as f32;
+        levels.push(imageproc::filter::gaussian_blur_f32(&img, sigma));
+    }
+
+    let height = img.height() as f32;
+    let band_top = focus_y as f32 - focus_band_height as f32 / 2.0;
+    let band_bottom = focus_y as f32 + focus_band_height as f32 / 2.0;
+    let out = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let y = y as f32;
+        let t = if y < band_top {
+            (band_top - y) / band_top.max(1.0)
+        } else if y > band_bottom {
+            (y - band_bottom) / (height - band_bottom).max(1.0)
+        } else {
+            0.0
+        }
+        .clamp(0.0, 1.0);
+        let pos = t * (LEVELS - 1) as f32;
+        let lower = (pos.floor() as usize).min(LEVELS - 2);
+        helpers::lerp_rgba(
+            levels[lower].get_pixel(x, y as u32),
+            levels[lower + 1].get_pixel(x, y as u32),
+            pos - lower as f32,
+        )
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Lens blur
+// ---------------------------------------------------------------------------
+
+/// Aperture-shaped kernel: a disc when `sides < 3`, otherwise a regular
+/// polygon with that many blades. Edge pixels get partial coverage.
+fn bokeh_kernel(radius: u32, sides: u32) -> Vec<f32> {
+    let size = 2 * radius + 1;
+    let r = radius as f32;
+    let mut data = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (dx, dy) = (x as f32 - r, y as f32 - r);
+            let dist = (dx * dx + dy * dy).sqrt();
+            let edge = if sides < 3 {
+                r
+            } else {
+                let sector = std::f32::consts::TAU / sides as f32;
+                let theta = dy.atan2(dx).rem_euclid(sector) - sector / 2.0;
+                r * (sector / 2.0).cos() / theta.cos()
+            };
+            data.push((edge - dist + 0.5).clamp(0.0, 1.0));
+        }
+    }
+    let sum: f32 = data.iter().sum();
+    data.iter_mut().for_each(|v| *v /= sum);
+    data
+}
+
+/// Realistic defocus blur. Each pixel is spread over an aperture-shaped
+/// kernel, and `highlight_boost` (0 = none) raises the weight of bright
+/// pixels so specular highlights bloom into visible bokeh shapes.
+#[flutter_rust_bridge::frb(sync)]
+pub fn lens_blur(
+    image_bytes: Vec<u8>,
+    radius: u32,
+    bokeh_sides: u32,
+    highlight_boost: f32,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    if radius == 0 {
+        return helpers::encode(&DynamicImage::ImageRgba8(img), fmt);
+    }
+
+    // Blurring in a power space (v^k) lets bright values dominate the
+    // average, mimicking how real optics accumulate light.
+    let k = 1.0 + highlight_boost.max(0.0);
+    let expanded: image::Rgba32FImage =
+        image::ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            let [r, g, b, a] = img.get_pixel(x, y).0;
+            let f = |c: u8| (c as f32 / 255.0).powf(k);
+            Rgba([f(r), f(g), f(b), a as f32 / 255.0])
+        });
+
+    let size = 2 * radius + 1;
+    let data = bokeh_kernel(radius, bokeh_sides);
+    let kernel = imageproc::filter::Kernel::new(&data, size, size);
+    let blurred: image::Rgba32FImage = kernel.filter(&expanded, |channel, acc| *channel = acc);
+
+    let out = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = blurred.get_pixel(x, y).0;
+        let f = |v: f32| (v.max(0.0).powf(1.0 / k) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Rgba([
+            f(r),
+            f(g),
+            f(b),
+            (a * 255.0).round().clamp(0.0, 255.0) as u8,
+        ])
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Kuwahara
+// ---------------------------------------------------------------------------
+
+/// Summed-area table with one extra leading row and column of zeros.
+struct SummedArea {
+    width: usize,
+    data: Vec<[f64; 5]>,
+}
+
+impl SummedArea {
+    /// Accumulates R, G, B, luma and luma² of `img`.
+    fn new(img: &RgbaImage) -> Self {
+        let (w, h) = (img.width() as usize, img.height() as usize);
+        let width = w + 1;
+        let mut data = vec![[0f64; 5]; width * (h + 1)];
+        for y in 0..h {
+            let mut row = [0f64; 5];
+            for x in 0..w {
+                let [r, g, b, _] = img.get_pixel(x as u32, y as u32).0;
+                let l = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+                for (acc, v) in row.iter_mut().zip([r as f64, g as f64, b as f64, l, l * l]) {
+                    *acc += v;
+                }
+                let above = data[y * width + x + 1];
+                let cell = &mut data[(y + 1) * width + x + 1];
+                for i in 0..5 {
+                    cell[i] = above[i] + row[i];
+                }
+            }
+        }
+        SummedArea { width, data }
+    }
+
+    /// Sums over the inclusive pixel rectangle [x0, x1] × [y0, y1].
+    fn sum(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> [f64; 5] {
+        let at = |x: usize, y: usize| self.data[y * self.width + x];
+        let (a, b, c, d) = (
+            at(x0, y0),
+            at(x1 + 1, y0),
+            at(x0, y1 + 1),
+            at(x1 + 1, y1 + 1),
+        );
+        std::array::from_fn(|i| d[i] - b[i] - c[i] + a[i])
+    }
+}
+
+/// Classic Kuwahara filter: every pixel takes the mean color of whichever of
+/// its four overlapping (radius + 1)² quadrants has the lowest luminance
+/// variance, flattening texture into painterly patches while keeping edges.
+#[flutter_rust_bridge::frb(sync)]
+pub fn kuwahara(image_bytes: Vec<u8>, radius: u32) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    if radius == 0 || w == 0 || h == 0 {
+        return helpers::encode(&DynamicImage::ImageRgba8(img), fmt);
+    }
+    let table = SummedArea::new(&img);
+    let r = radius as i64;
+    let out = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let mut best = (f64::INFINITY, [0f64; 3]);
+        for (qx, qy) in [(x - r, y - r), (x, y - r), (x - r, y), (x, y)] {
+            let x0 = qx.clamp(0, w - 1) as usize;
+            let y0 = qy.clamp(0, h - 1) as usize;
+            let x1 = (qx + r).clamp(0, w - 1) as usize;
+            let y1 = (qy + r).clamp(0, h - 1) as usize;
+            let n = ((x1 - x0 + 1) * (y1 - y0 + 1)) as f64;
+            let s = table.sum(x0, y0, x1, y1);
+            let mean_l = s[3] / n;
+            let variance = s[4] / n - mean_l * mean_l;
+            if variance < best.0 {
+                best = (variance, [s[0] / n, s[1] / n, s[2] / n]);
+            }
+        }
+        let [rr, gg, bb] = best.1.map(|v| v.round().clamp(0.0, 255.0) as u8);
+        Rgba([rr, gg, bb, img.get_pixel(x as u32, y as u32).0[3]])
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+/// Per-pixel orientation (radians) and anisotropy in [0, 1] from the
+/// smoothed structure tensor of the luma channel.
+fn local_orientation(img: &RgbaImage) -> (Vec<f32>, Vec<f32>) {
+    let gray = DynamicImage::ImageRgba8(img.clone()).to_luma8();
+    let gx = imageproc::gradients::horizontal_sobel(&gray);
+    let gy = imageproc::gradients::vertical_sobel(&gray);
+    let tensor: image::Rgb32FImage =
+        image::ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            let dx = gx.get_pixel(x, y).0[0] as f32 / 1020.0;
+            let dy = gy.get_pixel(x, y).0[0] as f32 / 1020.0;
+            image::Rgb([dx * dx, dx * dy, dy * dy])
+        });
+    let tensor = imageproc::filter::gaussian_blur_f32(&tensor, 2.0);
+    let mut angle = Vec::with_capacity(tensor.len() / 3);
+    let mut anisotropy = Vec::with_capacity(tensor.len() / 3);
+    for p in tensor.pixels() {
+        let [e, f, g] = p.0;
+        let root = ((e - g).powi(2) + 4.0 * f * f).sqrt();
+        let (l1, l2) = ((e + g + root) / 2.0, (e + g - root) / 2.0);
+        // Direction of least change, i.e. along the edge.
+        let (tx, ty) = (l1 - e, -f);
+        angle.push(if tx == 0.0 && ty == 0.0 {
+            0.0
+        } else {
+            ty.atan2(tx)
+        });
+        anisotropy.push(if l1 + l2 > 0.0 {
+            (l1 - l2) / (l1 + l2)
+        } else {
+            0.0
+        });
+    }
+    (angle, anisotropy)
+}
+
+/// Anisotropic Kuwahara filter (Kyprianidis et al.). The filter window is an
+/// ellipse aligned with the local structure and split into eight sectors whose
+/// means are blended by inverse variance, giving brush-stroke-like output that
+/// follows edges. `sharpness` (typically 8) controls how strongly low-variance
+/// sectors dominate.
+#[flutter_rust_bridge::frb(sync)]
+pub fn anisotropic_kuwahara(image_bytes: Vec<u8>, radius: u32, sharpness: f32) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    if radius == 0 || w == 0 || h == 0 {
+        return helpers::encode(&DynamicImage::ImageRgba8(img), fmt);
+    }
+    const SECTORS: usize = 8;
+    const ALPHA: f32 = 1.0;
+    let (angle, anisotropy) = local_orientation(&img);
+    let radius = radius as f32;
+    let sector_width = std::f32::consts::TAU / SECTORS as f32;
+
+    let out = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let i = (y as i64 * w + x as i64) as usize;
+        let a = radius * (ALPHA + anisotropy[i]) / ALPHA;
+        let b = radius * ALPHA / (ALPHA + anisotropy[i]);
+        let (sin, cos) = angle[i].sin_cos();
+        let extent = a.ceil() as i64;
+
+        let mut mean = [[0f32; 3]; SECTORS];
+        let mut square = [[0f32; 3]; SECTORS];
+        let mut weight = [0f32; SECTORS];
+        for dy in -extent..=extent {
+            for dx in -extent..=extent {
+                // Map the offset into the unit disk of the rotated ellipse.
+                let u = (cos * dx as f32 + sin * dy as f32) / a;
+                let v = (-sin * dx as f32 + cos * dy as f32) / b;
+                let d2 = u * u + v * v;
+                if d2 > 1.0 {
+                    continue;
+                }
+                let sx = (x as i64 + dx).clamp(0, w - 1) as u32;
+                let sy = (y as i64 + dy).clamp(0, h - 1) as u32;
+                let p = img.get_pixel(sx, sy).0;
+                let k = ((v.atan2(u).rem_euclid(std::f32::consts::TAU) / sector_width) as usize)
+                    .min(SECTORS - 1);
+                let g = (-2.0 * d2).exp();
+                for c in 0..3 {
+                    let value = p[c] as f32 / 255.0;
+                    mean[k][c] += value * g;
+                    square[k][c] += value * value * g;
+                }
+                weight[k] += g;
+            }
+        }
+
+        let mut acc = [0f32; 3];
+        let mut total = 0f32;
+        for k in 0..SECTORS {
+            if weight[k] <= 0.0 {
+                continue;
+            }
+            let m: [f32; 3] = std::array::from_fn(|c| mean[k][c] / weight[k]);
+            let variance: f32 = (0..3)
+                .map(|c| (square[k][c] / weight[k] - m[c] * m[c]).abs())
+                .sum();
+            let alpha = 1.0 / (1.0 + (255.0 * variance).powf(0.5 * sharpness));
+            for c in 0..3 {
+                acc[c] += m[c] * alpha;
+            }
+            total += alpha;
+        }
+        let center = *img.get_pixel(x, y);
+        if total <= 0.0 {
+            return center;
+        }
+        let f = |v: f32| (v / total * 255.0).round().clamp(0.0, 255.0) as u8;
+        Rgba([f(acc[0]), f(acc[1]), f(acc[2]), center.0[3]])
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Cartoon
+// ---------------------------------------------------------------------------
+
+/// Cartoon stylization: edge-preserving smoothing, per-channel posterization
+/// to `color_levels` levels and dark outlines from Canny edges.
+/// `edge_strength` in [0, 1] controls both how many edges are kept and how
+/// thick they are drawn; 0 disables outlines.
+#[flutter_rust_bridge::frb(sync)]
+pub fn cartoonify(image_bytes: Vec<u8>, edge_strength: f32, color_levels: u8) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+
+    let mut smooth = img.clone();
+    for _ in 0..2 {
+        smooth = crate::api::imageproc_ops::joint_bilateral(&smooth, 7, 30.0, 3.0);
+    }
+
+    let levels = color_levels.max(2) as f32;
+    let step = 255.0 / (levels - 1.0);
+    for p in smooth.pixels_mut() {
+        for c in 0..3 {
+            p.0[c] = ((p.0[c] as f32 / step).round() * step).clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    let strength = edge_strength.clamp(0.0, 1.0);
+    if strength > 0.0 {
+        let gray = DynamicImage::ImageRgba8(img).to_luma8();
+        let gray = imageproc::filter::median_filter(&gray, 2, 2);
+        let high = 200.0 - 150.0 * strength;
+        let mut edges = imageproc::edges::canny(&gray, high / 2.0, high);
+        let thickness = (strength * 2.0).round() as u8;
+        if thickness > 0 {
+            edges = imageproc::morphology::dilate(
+                &edges,
+                imageproc::distance_transform::Norm::L1,
+                thickness,
+            );
+        }
+        for (p, e) in smooth.pixels_mut().zip(edges.pixels()) {
+            if e.0[0] > 0 {
+                p.0[0] = 0;
+                p.0[1] = 0;
+                p.0[2] = 0;
+            }
+        }
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(smooth), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Pixelate
+// ---------------------------------------------------------------------------
+
+/// Replaces every `block_size` × `block_size` cell of the given area with its
+/// average color. Blocks are aligned to the area's top-left corner.
+fn pixelate_area(img: &mut RgbaImage, block_size: u32, x0: u32, y0: u32, w: u32, h: u32) {
+    let block = block_size.max(1);
+    for by in (y0..y0 + h).step_by(block as usize) {
+        for bx in (x0..x0 + w).step_by(block as usize) {
+            let bw = block.min(x0 + w - bx);
+            let bh = block.min(y0 + h - by);
+            let mut sum = [0u64; 4];
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    for (s, v) in sum.iter_mut().zip(img.get_pixel(x, y).0) {
+                        *s += v as u64;
+                    }
+                }
+            }
+            let n = (bw * bh) as u64;
+            let avg = Rgba(sum.map(|s| ((s + n / 2) / n) as u8));
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    img.put_pixel(x, y, avg);
+                }
+            }
+        }
+    }
+}
+
+/// Mosaic effect. With a `region` only that rectangle is pixelated, which is
+/// the usual way to censor faces or license plates.
+#[flutter_rust_bridge::frb(sync)]
+pub fn pixelate(
+    image_bytes: Vec<u8>,
+    block_size: u32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = img.dimensions();
+    let area = match region {
+        Some(r) => helpers::clip_rect(r.x, r.y, r.width, r.height, w, h),
+        None if w > 0 && h > 0 => Some((0, 0, w, h)),
+        None => None,
+    };
+    if let Some((x, y, aw, ah)) = area {
+        pixelate_area(&mut img, block_size, x, y, aw, ah);
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
+
+/// Pixelates the whole image and blends it in through a grayscale mask
+/// (white = fully pixelated, black = untouched).
+#[flutter_rust_bridge::frb(sync)]
+pub fn pixelate_masked(
+    image_bytes: Vec<u8>,
+    block_size: u32,
+    mask_bytes: Vec<u8>,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let mask = helpers::load_mask(&mask_bytes, img.width(), img.height())?;
+    pixelate_through_mask(&img, block_size, &mask, fmt)
+}
+
+/// `pixelate_masked` with a `LumeMask`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn pixelate_with_mask(
+    image_bytes: Vec<u8>,
+    block_size: u32,
+    mask: LumeMask,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let mask = mask::to_gray_sized(&mask, img.width(), img.height())?;
+    pixelate_through_mask(&img, block_size, &mask, fmt)
+}
+
+fn pixelate_through_mask(
+    img: &RgbaImage,
+    block_size: u32,
+    mask: &GrayImage,
+    fmt: ImageFormat,
+) -> Result<Vec<u8>> {
+    let (w, h) = img.dimensions();
+    let mut pixelated = img.clone();
+    if w > 0 && h > 0 {
+        pixelate_area(&mut pixelated, block_size, 0, 0, w, h);
+    }
+    let out = helpers::blend_with_mask(img, &pixelated, mask);
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Film grain
+// ---------------------------------------------------------------------------
+
+fn overlay_channel(base: f32, blend: f32) -> f32 {
+    if base < 0.5 {
+        2.0 * base * blend
+    } else {
+        1.0 - 2.0 * (1.0 - base) * (1.0 - blend)
+    }
+}
+
+/// Photographic grain: noise is generated at 1/`grain_size` resolution,
+/// upscaled and softened so grains cover several pixels, then mixed in with
+/// an overlay blend (which affects midtones most, like real film).
+/// `intensity` in [0, 1]; `monochrome` uses the same grain for all channels.
+#[flutter_rust_bridge::frb(sync)]
+pub fn film_grain(
+    image_bytes: Vec<u8>,
+    intensity: f32,
+    grain_size: f32,
+    monochrome: bool,
+) -> Result<Vec<u8>> {
+    const SEED: u64 = 0x5EED_F11A;
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = img.dimensions();
+    if w == 0 || h == 0 || intensity <= 0.0 {
+        return helpers::encode(&DynamicImage::ImageRgba8(img), fmt);
+    }
+
+    let size = grain_size.max(1.0);
+    let nw = ((w as f32 / size).ceil() as u32).max(1);
+    let nh = ((h as f32 / size).ceil() as u32).max(1);
+    let flat = image::RgbImage::from_pixel(nw, nh, image::Rgb([128, 128, 128]));
+    let mut noise = imageproc::noise::gaussian_noise(&flat, 0.0, 40.0, SEED);
+    if monochrome {
+        for p in noise.pixels_mut() {
+            p.0 = [p.0[0]; 3];
+        }
+    }
+    let noise = image::imageops::resize(&noise, w, h, image::imageops::FilterType::CatmullRom);
+    let noise = imageproc::filter::gaussian_blur_f32(&noise, (size * 0.25).max(0.5));
+
+    let amount = intensity.clamp(0.0, 1.0);
+    let out = RgbaImage::from_fn(w, h, |x, y| {
+        let p = img.get_pixel(x, y).0;
+        let n = noise.get_pixel(x, y).0;
+        let mut o = p;
+        for c in 0..3 {
+            let base = p[c] as f32 / 255.0;
+            let mixed = overlay_channel(base, n[c] as f32 / 255.0);
+            let v = base + (mixed - base) * amount;
+            o[c] = (v * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+        Rgba(o)
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Halftone
+// ---------------------------------------------------------------------------
+
+/// Converts tones into black dots on a white grid rotated by `angle` degrees,
+/// with cells of `dot_size` pixels. Dot area follows the local darkness.
+/// `shape` is one of "circle", "square", "diamond" or "line". Alpha is kept.
+#[flutter_rust_bridge::frb(sync)]
+pub fn halftone(image_bytes: Vec<u8>, dot_size: u32, angle: f32, shape: String) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let shape = shape.to_lowercase();
+    if !matches!(shape.as_str(), "circle" | "square" | "diamond" | "line") {
+        return Err(anyhow::anyhow!("Unsupported halftone shape: {}", shape));
+    }
+    let (w, h) = img.dimensions();
+    let cell = dot_size.max(2) as f32;
+    // Averaging over roughly one cell makes the sampled tone stable.
+    let gray = DynamicImage::ImageRgba8(img.clone()).to_luma8();
+    let gray = imageproc::filter::box_filter(&gray, dot_size / 2, dot_size / 2);
+    let (sin, cos) = angle.to_radians().sin_cos();
+
+    let out = RgbaImage::from_fn(w, h, |x, y| {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        // Image → grid space.
+        let u = px * cos + py * sin;
+        let v = -px * sin + py * cos;
+        let (cu, cv) = (
+            ((u / cell).floor() + 0.5) * cell,
+            ((v / cell).floor() + 0.5) * cell,
+        );
+        // Grid cell center → image space, to sample its tone.
+        let sx = (cu * cos - cv * sin).clamp(0.0, (w - 1) as f32) as u32;
+        let sy = (cu * sin + cv * cos).clamp(0.0, (h - 1) as f32) as u32;
+        let darkness = 1.0 - gray.get_pixel(sx, sy).0[0] as f32 / 255.0;
+        let (du, dv) = (u - cu, v - cv);
+        // Signed distance from the dot edge (positive = inside), sized so the
+        // inked area fraction matches `darkness`.
+        let inside = match shape.as_str() {
+            "circle" => {
+                cell * (darkness / std::f32::consts::PI).sqrt() - (du * du + dv * dv).sqrt()
+            }
+            "square" => cell * darkness.sqrt() / 2.0 - du.abs().max(dv.abs()),
+            "diamond" => cell * (darkness / 2.0).sqrt() - (du.abs() + dv.abs()),
+            _ => cell * darkness / 2.0 - dv.abs(),
+        };
+        let ink = (inside + 0.5).clamp(0.0, 1.0);
+        let level = ((1.0 - ink) * 255.0).round() as u8;
+        Rgba([level, level, level, img.get_pixel(x, y).0[3]])
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Emboss
+// ---------------------------------------------------------------------------
+
+/// Luma derivative along the light direction, `angle` degrees counter-clockwise
+/// from the x axis, scaled by `depth`.
+fn relief(img: &RgbaImage, angle: f32, depth: f32) -> Vec<f32> {
+    let gray = DynamicImage::ImageRgba8(img.clone()).to_luma8();
+    let gx = imageproc::gradients::horizontal_sobel(&gray);
+    let gy = imageproc::gradients::vertical_sobel(&gray);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    gx.pixels()
+        .zip(gy.pixels())
+        // Sobel responses are 4× the pixel difference; y grows downwards.
+        .map(|(h, v)| depth * (h.0[0] as f32 * cos - v.0[0] as f32 * sin) / 4.0)
+        .collect()
+}
+
+/// Classic gray relief effect lit from `angle` degrees; `depth` scales the
+/// height of the relief (1.0 is a good default).
+#[flutter_rust_bridge::frb(sync)]
+pub fn emboss(image_bytes: Vec<u8>, angle: f32, depth: f32) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let heights = relief(&img, angle, depth);
+    let mut out = img;
+    for (p, d) in out.pixels_mut().zip(heights) {
+        let v = (128.0 + d).round().clamp(0.0, 255.0) as u8;
+        p.0 = [v, v, v, p.0[3]];
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+/// Emboss that keeps the original colors: only luminance is shifted by the
+/// relief, so the result looks like a textured print of the photo.
+#[flutter_rust_bridge::frb(sync)]
+pub fn emboss_color(image_bytes: Vec<u8>, angle: f32, depth: f32) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let heights = relief(&img, angle, depth);
+    let mut out = img;
+    for (p, d) in out.pixels_mut().zip(heights) {
+        for c in 0..3 {
+            p.0[c] = (p.0[c] as f32 + d).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Glitch
+// ---------------------------------------------------------------------------
+
+fn sample_bilinear(img: &RgbaImage, x: f32, y: f32, channel: usize) -> u8 {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let x = x.clamp(0.0, w - 1.0);
+    let y = y.clamp(0.0, h - 1.0);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(img.width() - 1),
+        (y0 + 1).min(img.height() - 1),
+    );
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let v = |x, y| img.get_pixel(x, y).0[channel] as f32;
+    let top = v(x0, y0) + (v(x1, y0) - v(x0, y0)) * fx;
+    let bottom = v(x0, y1) + (v(x1, y1) - v(x0, y1)) * fx;
+    (top + (bottom - top) * fy).round().clamp(0.0, 255.0) as u8
+}
+
+/// Lens-style color fringing: red is scaled outwards and blue inwards from
+/// the center so the channels separate by `shift` pixels at the corners.
+#[flutter_rust_bridge::frb(sync)]
+pub fn chromatic_aberration(image_bytes: Vec<u8>, shift: f32) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = img.dimensions();
+    let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+    let corner = (cx * cx + cy * cy).sqrt().max(1.0);
+    let scale = shift / corner;
+    let out = RgbaImage::from_fn(w, h, |x, y| {
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let p = img.get_pixel(x, y).0;
+        let red = sample_bilinear(&img, cx + dx * (1.0 - scale), cy + dy * (1.0 - scale), 0);
+        let blue = sample_bilinear(&img, cx + dx * (1.0 + scale), cy + dy * (1.0 + scale), 2);
+        Rgba([red, p[1], blue, p[3]])
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+/// VHS/digital glitch: RGB channel offsets, randomly displaced horizontal
+/// slices and darkened scanlines. `intensity` in [0, 1]; the same `seed`
+/// always produces the same result.
+#[flutter_rust_bridge::frb(sync)]
+pub fn glitch(image_bytes: Vec<u8>, intensity: f32, seed: u64) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = img.dimensions();
+    let amount = intensity.clamp(0.0, 1.0);
+    if w == 0 || h == 0 || amount == 0.0 {
+        return helpers::encode(&DynamicImage::ImageRgba8(img), fmt);
+    }
+    let mut rng = helpers::SplitMix64::new(seed);
+    let max_shift = (w as f32 * 0.05 * amount).max(1.0);
+
+    // Per-row horizontal displacement, constant within randomly sized slices.
+    let mut offsets = vec![0i64; h as usize];
+    let mut y = 0;
+    while y < h as usize {
+        let slice = 1 + (rng.next_f32() * h as f32 * 0.08) as usize;
+        let offset = if rng.next_f32() < 0.3 * amount {
+            ((rng.next_f32() * 2.0 - 1.0) * max_shift * 3.0) as i64
+        } else {
+            0
+        };
+        for o in offsets.iter_mut().skip(y).take(slice) {
+            *o = offset;
+        }
+        y += slice;
+    }
+    let red_shift = ((rng.next_f32() * 2.0 - 1.0) * max_shift) as i64;
+    let blue_shift = ((rng.next_f32() * 2.0 - 1.0) * max_shift) as i64;
+
+    let fetch = |x: i64, y: u32, c: usize| img.get_pixel(x.rem_euclid(w as i64) as u32, y).0[c];
+    let out = RgbaImage::from_fn(w, h, |x, y| {
+        let sx = x as i64 + offsets[y as usize];
+        let mut p = [
+            fetch(sx + red_shift, y, 0),
+            fetch(sx, y, 1),
+            fetch(sx + blue_shift, y, 2),
+            fetch(sx, y, 3),
+        ];
+        if y % 2 == 1 {
+            let dim = 1.0 - 0.25 * amount;
+            for v in p.iter_mut().take(3) {
+                *v = (*v as f32 * dim) as u8;
+            }
+        }
+        Rgba(p)
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
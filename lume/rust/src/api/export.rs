@@ -0,0 +1,904 @@
+use anyhow::Result;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ExtendedColorType, ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+
+use crate::api::analysis;
+use crate::api::image_ops::LumeColor;
+use crate::api::registration;
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// One generated file. `name` is a relative path such as
+/// "mipmap-xhdpi/ic_launcher.png".
+pub struct LumeExportFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Look of the line between the two halves of `compare_split`. `handle`
+/// adds a round slider knob with arrows at the middle of the line.
+pub struct LumeDividerStyle {
+    pub width: u32,
+    pub color: LumeColor,
+    pub handle: bool,
+}
+
+/// Encoder settings for `encode_with_options`. An empty `format` keeps the
+/// source format.
+pub struct LumeEncodeOptions {
+    pub format: String,
+    pub quality: u8,
+    pub progressive: bool,
+    pub interlaced: bool,
+}
+
+/// One tile of a `LumeTileSet`.
+pub struct LumeTile {
+    pub level: u32,
+    pub column: u32,
+    pub row: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Deep Zoom (DZI) pyramid of an image. Level `max_level` is the full-size
+/// image and each level below halves the one above (rounding up), down to
+/// 1x1 at level 0. Tiles are `tile_size` square, smaller along the right and
+/// bottom edges, without overlap. Stored as `<name>_files/<level>/<column>_
+/// <row>.<extension>` next to `<name>.dzi` containing `dzi`, they can be
+/// served to any DZI viewer (OpenSeadragon, ...).
+pub struct LumeTileSet {
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: u32,
+    pub max_level: u32,
+    /// File extension of the tiles ("jpg", "png", ...).
+    pub extension: String,
+    /// The .dzi XML descriptor.
+    pub dzi: String,
+    pub tiles: Vec<LumeTile>,
+}
+
+// ---------------------------------------------------------------------------
+// App icons
+// ---------------------------------------------------------------------------
+
+/// Scales `img` into a `size` x `size` square, centered on transparency when
+/// it is not square.
+fn square_icon(img: &DynamicImage, size: u32) -> RgbaImage {
+    let fitted = img.resize(size, size, FilterType::Lanczos3).to_rgba8();
+    let mut out = RgbaImage::from_pixel(size, size, Rgba([0, 0, 0, 0]));
+    let x = (size - fitted.width()) / 2;
+    let y = (size - fitted.height()) / 2;
+    image::imageops::overlay(&mut out, &fitted, x as i64, y as i64);
+    out
+}
+
+fn png_file(name: &str, img: DynamicImage) -> Result<LumeExportFile> {
+    Ok(LumeExportFile {
+        name: name.to_string(),
+        bytes: helpers::encode(&img, ImageFormat::Png)?,
+    })
+}
+
+/// Multi-resolution ICO with one PNG-compressed frame per size.
+fn ico_file(name: &str, img: &DynamicImage, sizes: &[u32]) -> Result<LumeExportFile> {
+    let icons: Vec<RgbaImage> = sizes.iter().map(|&s| square_icon(img, s)).collect();
+    let frames = icons
+        .iter()
+        .map(|icon| {
+            IcoFrame::as_png(
+                icon.as_raw(),
+                icon.width(),
+                icon.height(),
+                ExtendedColorType::Rgba8,
+            )
+        })
+        .collect::<image::ImageResult<Vec<_>>>()?;
+    let mut bytes = Vec::new();
+    IcoEncoder::new(&mut bytes).encode_images(&frames)?;
+    Ok(LumeExportFile {
+        name: name.to_string(),
+        bytes,
+    })
+}
+
+/// Generates every icon file a platform expects from one source image
+/// (ideally square, at least 1024 px). `profile` is one of:
+/// - "ios": AppIcon PNGs for iPhone, iPad and the App Store, flattened onto
+///   white because iOS rejects icons with transparency;
+/// - "android": launcher icons for each mipmap density plus the 512 px Play
+///   Store icon;
+/// - "windows": a multi-resolution app.ico and the tile logos;
+/// - "favicon": favicon.ico plus the PNG sizes browsers and home screens use.
+#[flutter_rust_bridge::frb(sync)]
+pub fn generate_icon_set(image_bytes: Vec<u8>, profile: String) -> Result<Vec<LumeExportFile>> {
+    let img = helpers::load(&image_bytes)?;
+    let mut files = Vec::new();
+    match profile.to_lowercase().as_str() {
+        "ios" => {
+            const IOS: [(&str, u32); 15] = [
+                ("AppIcon-20.png", 20),
+                ("AppIcon-20@2x.png", 40),
+                ("AppIcon-20@3x.png", 60),
+                ("AppIcon-29.png", 29),
+                ("AppIcon-29@2x.png", 58),
+                ("AppIcon-29@3x.png", 87),
+                ("AppIcon-40.png", 40),
+                ("AppIcon-40@2x.png", 80),
+                ("AppIcon-40@3x.png", 120),
+                ("AppIcon-60@2x.png", 120),
+                ("AppIcon-60@3x.png", 180),
+                ("AppIcon-76.png", 76),
+                ("AppIcon-76@2x.png", 152),
+                ("AppIcon-83.5@2x.png", 167),
+                ("AppIcon-1024.png", 1024),
+            ];
+            for (name, size) in IOS {
+                let mut flat = RgbaImage::from_pixel(size, size, Rgba([255, 255, 255, 255]));
+                image::imageops::overlay(&mut flat, &square_icon(&img, size), 0, 0);
+                files.push(png_file(
+                    name,
+                    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(flat).to_rgb8()),
+                )?);
+            }
+        }
+        "android" => {
+            const ANDROID: [(&str, u32); 6] = [
+                ("mipmap-mdpi/ic_launcher.png", 48),
+                ("mipmap-hdpi/ic_launcher.png", 72),
+                ("mipmap-xhdpi/ic_launcher.png", 96),
+                ("mipmap-xxhdpi/ic_launcher.png", 144),
+                ("mipmap-xxxhdpi/ic_launcher.png", 192),
+                ("playstore-icon.png", 512),
+            ];
+            for (name, size) in ANDROID {
+                files.push(png_file(
+                    name,
+                    DynamicImage::ImageRgba8(square_icon(&img, size)),
+                )?);
+            }
+        }
+        "windows" => {
+            files.push(ico_file("app.ico", &img, &[16, 24, 32, 48, 64, 256])?);
+            const WINDOWS: [(&str, u32); 4] = [
+                ("Square44x44Logo.png", 44),
+                ("StoreLogo.png", 50),
+                ("Square150x150Logo.png", 150),
+                ("Square310x310Logo.png", 310),
+            ];
+            for (name, size) in WINDOWS {
+                files.push(png_file(
+                    name,
+                    DynamicImage::ImageRgba8(square_icon(&img, size)),
+                )?);
+            }
+        }
+        "favicon" => {
+            files.push(ico_file("favicon.ico", &img, &[16, 32, 48])?);
+            const FAVICON: [(&str, u32); 5] = [
+                ("favicon-16x16.png", 16),
+                ("favicon-32x32.png", 32),
+                ("apple-touch-icon.png", 180),
+                ("android-chrome-192x192.png", 192),
+                ("android-chrome-512x512.png", 512),
+            ];
+            for (name, size) in FAVICON {
+                files.push(png_file(
+                    name,
+                    DynamicImage::ImageRgba8(square_icon(&img, size)),
+                )?);
+            }
+        }
+        other => return Err(anyhow::anyhow!("Unsupported icon profile: {}", other)),
+    }
+    Ok(files)
+}
+
+// ---------------------------------------------------------------------------
+// Tile pyramids
+// ---------------------------------------------------------------------------
+
+/// Cuts `img` into `tile_size` tiles for pyramid level `level`, row by row.
+fn level_tiles(
+    img: &DynamicImage,
+    level: u32,
+    tile_size: u32,
+    fmt: ImageFormat,
+) -> Result<Vec<LumeTile>> {
+    let mut tiles = Vec::new();
+    for row in 0..img.height().div_ceil(tile_size) {
+        for column in 0..img.width().div_ceil(tile_size) {
+            let (x, y) = (column * tile_size, row * tile_size);
+            let tile = img.crop_imm(
+                x,
+                y,
+                tile_size.min(img.width() - x),
+                tile_size.min(img.height() - y),
+            );
+            tiles.push(LumeTile {
+                level,
+                column,
+                row,
+                bytes: helpers::encode(&tile, fmt)?,
+            });
+        }
+    }
+    Ok(tiles)
+}
+
+/// Generates a Deep Zoom tile pyramid, so viewers can pan and zoom through
+/// very large images loading only the visible tiles at the current zoom.
+/// `format` is the tile format ("jpeg" or "png" for the widest viewer
+/// support); an empty `format` keeps the source format. JPEG tiles drop the
+/// alpha channel.
+#[flutter_rust_bridge::frb(sync)]
+pub fn generate_pyramid(
+    image_bytes: Vec<u8>,
+    tile_size: u32,
+    format: String,
+) -> Result<LumeTileSet> {
+    if tile_size == 0 {
+        return Err(anyhow::anyhow!("tile_size must be greater than zero"));
+    }
+    let fmt = if format.is_empty() {
+        helpers::detect_format(&image_bytes)?
+    } else {
+        helpers::string_to_format(&format)?
+    };
+    let mut img = helpers::load(&image_bytes)?;
+    if fmt == ImageFormat::Jpeg {
+        img = DynamicImage::ImageRgb8(img.to_rgb8());
+    }
+    let (width, height) = (img.width(), img.height());
+    let max_level = width.max(height).next_power_of_two().trailing_zeros();
+    let extension = fmt
+        .extensions_str()
+        .first()
+        .copied()
+        .unwrap_or("img")
+        .to_string();
+
+    // Levels are built from the one above rather than from the original, so
+    // each halving only reads four times its own pixel count.
+    let mut levels = Vec::new();
+    for level in (0..=max_level).rev() {
+        if level < max_level {
+            let (w, h) = (img.width().div_ceil(2), img.height().div_ceil(2));
+            img = img.resize_exact(w, h, FilterType::Triangle);
+        }
+        levels.push(level_tiles(&img, level, tile_size, fmt)?);
+    }
+    let tiles = levels.into_iter().rev().flatten().collect();
+
+    let dzi = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" TileSize=\"{}\" \
+         Overlap=\"0\" Format=\"{}\">\n  <Size Width=\"{}\" Height=\"{}\"/>\n</Image>\n",
+        tile_size, extension, width, height
+    );
+    Ok(LumeTileSet {
+        width,
+        height,
+        tile_size,
+        max_level,
+        extension,
+        dzi,
+        tiles,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Export presets
+// ---------------------------------------------------------------------------
+
+/// Smallest share of the source width or height a preset crop may keep. When
+/// matching the target aspect ratio would cut away more, the image is cropped
+/// this far and the rest is padded instead.
+const MIN_CROP_KEEP: f32 = 0.6;
+
+/// Start of the `window`-long span of `profile` with the largest sum.
+fn best_window(profile: &[f32], window: usize) -> usize {
+    let window = window.clamp(1, profile.len());
+    let mut sum: f32 = profile[..window].iter().sum();
+    let (mut best, mut best_sum) = (0, sum);
+    for start in 1..=profile.len() - window {
+        sum += profile[start + window - 1] - profile[start - 1];
+        if sum > best_sum {
+            best = start;
+            best_sum = sum;
+        }
+    }
+    best
+}
+
+/// Crops `img` towards the `target_w`:`target_h` aspect ratio, keeping the
+/// most salient part of the image and at least `MIN_CROP_KEEP` of the
+/// cropped axis.
+fn smart_crop(img: &RgbImage, target_w: u32, target_h: u32) -> RgbImage {
+    let (w, h) = img.dimensions();
+    let target_aspect = target_w as f32 / target_h as f32;
+    let aspect = w as f32 / h as f32;
+    if (aspect - target_aspect).abs() < 1e-3 {
+        return img.clone();
+    }
+    let map = analysis::saliency_map(&DynamicImage::ImageRgb8(img.clone()));
+    let n = map.width() as usize;
+    let wide = aspect > target_aspect;
+    let (len, keep) = if wide {
+        (w, (target_aspect / aspect).max(MIN_CROP_KEEP))
+    } else {
+        (h, (aspect / target_aspect).max(MIN_CROP_KEEP))
+    };
+    let crop_len = ((len as f32 * keep).round() as u32).clamp(1, len);
+    let mut profile = vec![0.0; n];
+    for (x, y, p) in map.enumerate_pixels() {
+        profile[if wide { x } else { y } as usize] += p.0[0] as f32;
+    }
+    let window = (crop_len as f32 / len as f32 * n as f32).round() as usize;
+    let start = (best_window(&profile, window) as u32 * len / n as u32).min(len - crop_len);
+    if wide {
+        image::imageops::crop_imm(img, start, 0, crop_len, h).to_image()
+    } else {
+        image::imageops::crop_imm(img, 0, start, w, crop_len).to_image()
+    }
+}
+
+/// Fits `img` into a `width` x `height` canvas. Any leftover space is filled
+/// with a blurred, zoomed copy of the image rather than flat bars.
+fn fit_with_backdrop(img: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let source = DynamicImage::ImageRgb8(img.clone());
+    let fitted = source.resize(width, height, FilterType::Lanczos3).to_rgb8();
+    if fitted.dimensions() == (width, height) {
+        return fitted;
+    }
+    // Blurring a small copy and scaling it up is much cheaper than a wide
+    // blur at full size, and looks the same.
+    let small = source.resize_to_fill(64, (64 * height / width).max(1), FilterType::Triangle);
+    let blurred = imageproc::filter::gaussian_blur_f32(&small.to_rgb8(), 3.0);
+    let mut out = image::imageops::resize(&blurred, width, height, FilterType::Triangle);
+    let x = (width - fitted.width()) / 2;
+    let y = (height - fitted.height()) / 2;
+    image::imageops::replace(&mut out, &fitted, x as i64, y as i64);
+    out
+}
+
+/// Prepares an image for a social or web target in one call: smart-crops
+/// towards the target aspect ratio (following the saliency map), pads what
+/// cannot be cropped with a blurred backdrop, resizes and encodes a JPEG at
+/// the target's recommended quality. Transparency is flattened onto white.
+/// `preset` is one of:
+/// - "instagram_square": 1080x1080;
+/// - "instagram_portrait": 1080x1350;
+/// - "instagram_story": 1080x1920 (also reels);
+/// - "youtube_thumbnail": 1280x720;
+/// - "opengraph": 1200x630 link preview image.
+#[flutter_rust_bridge::frb(sync)]
+pub fn export_preset(image_bytes: Vec<u8>, preset: String) -> Result<Vec<u8>> {
+    let (width, height, quality) = match preset.to_lowercase().as_str() {
+        "instagram_square" => (1080, 1080, 90),
+        "instagram_portrait" => (1080, 1350, 90),
+        "instagram_story" | "instagram_reel" => (1080, 1920, 90),
+        "youtube_thumbnail" => (1280, 720, 90),
+        "opengraph" | "og" => (1200, 630, 85),
+        other => return Err(anyhow::anyhow!("Unsupported export preset: {}", other)),
+    };
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let flat = RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y).0;
+        let a = p[3] as u32;
+        Rgb([0, 1, 2].map(|c| ((p[c] as u32 * a + 255 * (255 - a) + 127) / 255) as u8))
+    });
+    let cropped = smart_crop(&flat, width, height);
+    let out = fit_with_backdrop(&cropped, width, height);
+
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(&out)?;
+    Ok(bytes)
+}
+
+// ---------------------------------------------------------------------------
+// Optimization
+// ---------------------------------------------------------------------------
+
+/// Quality used when `LumeEncodeOptions::quality` is 0, same as the default
+/// JPEG encoder.
+const DEFAULT_JPEG_QUALITY: u8 = 75;
+
+/// JPEG encoders take RGB or grayscale samples only.
+fn jpeg_samples(img: &DynamicImage) -> DynamicImage {
+    if img.color().has_color() {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    } else {
+        DynamicImage::ImageLuma8(img.to_luma8())
+    }
+}
+
+#[cfg(feature = "mozjpeg")]
+fn mozjpeg_encode(img: &DynamicImage, quality: u8, progressive: bool) -> Result<Vec<u8>> {
+    let samples = jpeg_samples(img);
+    let color_space = match samples {
+        DynamicImage::ImageLuma8(_) => mozjpeg::ColorSpace::JCS_GRAYSCALE,
+        _ => mozjpeg::ColorSpace::JCS_RGB,
+    };
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let quality = quality.clamp(1, 100) as f32;
+    // libjpeg reports errors by unwinding, so they surface here as panics.
+    std::panic::catch_unwind(move || -> std::io::Result<Vec<u8>> {
+        let mut comp = mozjpeg::Compress::new(color_space);
+        comp.set_size(width, height);
+        comp.set_quality(quality);
+        comp.set_optimize_coding(true);
+        if progressive {
+            comp.set_progressive_mode();
+            comp.set_optimize_scans(true);
+        } else {
+            // Clears the progressive scan script mozjpeg sets up by default.
+            comp.set_optimize_scans(false);
+        }
+        let mut started = comp.start_compress(Vec::new())?;
+        started.write_scanlines(samples.as_bytes())?;
+        started.finish()
+    })
+    .map_err(|_| anyhow::anyhow!("mozjpeg failed to encode the image"))?
+    .map_err(Into::into)
+}
+
+#[cfg(not(feature = "mozjpeg"))]
+fn mozjpeg_encode(_img: &DynamicImage, _quality: u8, _progressive: bool) -> Result<Vec<u8>> {
+    Err(anyhow::anyhow!(
+        "Progressive JPEG encoding needs the mozjpeg feature"
+    ))
+}
+
+#[cfg(feature = "oxipng")]
+fn oxipng_encode(png: &[u8], level: u8, strip: bool, interlaced: Option<bool>) -> Result<Vec<u8>> {
+    let mut options = oxipng::Options::from_preset(level.min(6));
+    if strip {
+        options.strip = oxipng::StripChunks::Safe;
+    }
+    options.interlace = interlaced.map(|adam7| {
+        if adam7 {
+            oxipng::Interlacing::Adam7
+        } else {
+            oxipng::Interlacing::None
+        }
+    });
+    oxipng::optimize_from_memory(png, &options)
+        .map_err(|e| anyhow::anyhow!("Cannot optimize PNG: {}", e))
+}
+
+#[cfg(not(feature = "oxipng"))]
+fn oxipng_encode(
+    _png: &[u8],
+    _level: u8,
+    _strip: bool,
+    _interlaced: Option<bool>,
+) -> Result<Vec<u8>> {
+    Err(anyhow::anyhow!(
+        "Interlaced PNG encoding needs the oxipng feature"
+    ))
+}
+
+/// Re-encodes an image as a compact JPEG with mozjpeg (trellis quantization,
+/// optimized Huffman tables), typically 10-20 % smaller than the default
+/// encoder at the same visual quality. `quality` is 1-100; 75-85 suits most
+/// photos. `progressive` writes progressive scans, which also tend to be
+/// smaller. Metadata is not carried over and transparency is dropped.
+#[cfg(feature = "mozjpeg")]
+#[flutter_rust_bridge::frb(sync)]
+pub fn optimize_jpeg(image_bytes: Vec<u8>, quality: u8, progressive: bool) -> Result<Vec<u8>> {
+    mozjpeg_encode(&helpers::load(&image_bytes)?, quality, progressive)
+}
+
+/// Losslessly recompresses an image as a smaller PNG with oxipng. Tries
+/// better filters, bit depth and palette reductions and stronger deflate, and
+/// strips ancillary chunks that do not affect how the image displays (text,
+/// timestamps, EXIF), while keeping color management chunks. `level` is the
+/// oxipng preset, 0 (fast) to 6 (smallest, slowest); 2 is a good default.
+/// Non-PNG input is converted to PNG first. Interlacing is left as it is.
+#[cfg(feature = "oxipng")]
+#[flutter_rust_bridge::frb(sync)]
+pub fn optimize_png(image_bytes: Vec<u8>, level: u8) -> Result<Vec<u8>> {
+    let png = if helpers::detect_format(&image_bytes)? == ImageFormat::Png {
+        image_bytes
+    } else {
+        helpers::encode(&helpers::load(&image_bytes)?, ImageFormat::Png)?
+    };
+    oxipng_encode(&png, level, true, None)
+}
+
+/// Encodes an image with explicit encoder settings:
+/// - `quality` (1-100, 0 for the default) applies to JPEG;
+/// - `progressive` writes a progressive JPEG, which renders coarse-to-fine
+///   while downloading (needs the mozjpeg feature);
+/// - `interlaced` writes an Adam7 interlaced PNG, the PNG equivalent (needs
+///   the oxipng feature).
+///
+/// Options that do not apply to the chosen format are ignored.
+#[flutter_rust_bridge::frb(sync)]
+pub fn encode_with_options(image_bytes: Vec<u8>, options: LumeEncodeOptions) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?;
+    let fmt = if options.format.is_empty() {
+        helpers::detect_format(&image_bytes)?
+    } else {
+        helpers::string_to_format(&options.format)?
+    };
+    let quality = match options.quality {
+        0 => DEFAULT_JPEG_QUALITY,
+        q => q.min(100),
+    };
+    match fmt {
+        ImageFormat::Jpeg if options.progressive => mozjpeg_encode(&img, quality, true),
+        ImageFormat::Jpeg => {
+            let mut bytes = Vec::new();
+            JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(&jpeg_samples(&img))?;
+            Ok(bytes)
+        }
+        ImageFormat::Png if options.interlaced => {
+            // Level 0 only re-encodes; no point searching filters here.
+            oxipng_encode(
+                &helpers::encode(&img, ImageFormat::Png)?,
+                0,
+                false,
+                Some(true),
+            )
+        }
+        _ => helpers::encode(&img, fmt),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Thumbnails
+// ---------------------------------------------------------------------------
+
+/// Size of `width` x `height` scaled down to fit `max_width` x
+/// `max_height`, never scaled up.
+fn thumbnail_size(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    let scale = (max_width as f32 / width as f32)
+        .min(max_height as f32 / height as f32)
+        .min(1.0);
+    let side = |v: u32| ((v as f32 * scale).round() as u32).max(1);
+    (side(width), side(height))
+}
+
+/// Renders one thumbnail per entry of `sizes`, each fitted inside its
+/// (width, height) box with the aspect ratio kept and never enlarged, from a
+/// single decode. Sizes are rendered from largest to smallest, halving the
+/// working image (box filter) while it is at least twice the next size and
+/// finishing with Lanczos3, which is faster than resizing the original each
+/// time and avoids aliasing on large reductions. Results follow the order of
+/// `sizes`. An empty `format` keeps the source format; `quality` applies to
+/// JPEG (0 for the encoder default), and JPEG drops the alpha channel.
+#[flutter_rust_bridge::frb(sync)]
+pub fn thumbnails(
+    image_bytes: Vec<u8>,
+    sizes: Vec<(u32, u32)>,
+    format: String,
+    quality: u8,
+) -> Result<Vec<Vec<u8>>> {
+    if sizes.iter().any(|&(w, h)| w == 0 || h == 0) {
+        return Err(anyhow::anyhow!("Thumbnail sizes must be greater than zero"));
+    }
+    let img = helpers::load(&image_bytes)?;
+    let fmt = if format.is_empty() {
+        helpers::detect_format(&image_bytes)?
+    } else {
+        helpers::string_to_format(&format)?
+    };
+    let quality = match quality {
+        0 => DEFAULT_JPEG_QUALITY,
+        q => q.min(100),
+    };
+    let targets: Vec<(u32, u32)> = sizes
+        .iter()
+        .map(|&(w, h)| thumbnail_size(img.width(), img.height(), w, h))
+        .collect();
+    let mut order: Vec<usize> = (0..targets.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(targets[i].0 as u64 * targets[i].1 as u64));
+
+    let mut working = img;
+    let mut out = vec![Vec::new(); targets.len()];
+    for i in order {
+        let (w, h) = targets[i];
+        while working.width() >= 2 * w && working.height() >= 2 * h {
+            let (hw, hh) = (working.width().div_ceil(2), working.height().div_ceil(2));
+            working = working.resize_exact(hw, hh, FilterType::Triangle);
+        }
+        let thumb = if (working.width(), working.height()) == (w, h) {
+            working.clone()
+        } else {
+            working.resize_exact(w, h, FilterType::Lanczos3)
+        };
+        out[i] = match fmt {
+            ImageFormat::Jpeg => {
+                let mut bytes = Vec::new();
+                JpegEncoder::new_with_quality(&mut bytes, quality)
+                    .encode_image(&jpeg_samples(&thumb))?;
+                bytes
+            }
+            _ => helpers::encode(&thumb, fmt)?,
+        };
+    }
+    Ok(out)
+}
+
+// ---------------------------------------------------------------------------
+// Before/after comparison
+// ---------------------------------------------------------------------------
+
+/// Draws the slider knob: a disc centered on (`cx`, `cy`) with two arrows
+/// pointing along the slider's axis.
+fn draw_handle(canvas: &mut RgbaImage, cx: i32, cy: i32, vertical: bool, style: &LumeDividerStyle) {
+    let color = Rgba([style.color.r, style.color.g, style.color.b, style.color.a]);
+    let radius = (style.width as i32 * 4).max(14);
+    imageproc::drawing::draw_filled_circle_mut(canvas, (cx, cy), radius, color);
+    // Arrows in the inverted color so they show on any divider color.
+    let arrow = Rgba([255 - color.0[0], 255 - color.0[1], 255 - color.0[2], 255]);
+    let (tip, base, half) = (radius * 3 / 4, radius / 4, radius / 3);
+    for sign in [-1, 1] {
+        let points = [(sign * tip, 0), (sign * base, -half), (sign * base, half)].map(|(a, b)| {
+            let (dx, dy) = if vertical { (a, b) } else { (b, a) };
+            imageproc::point::Point::new(cx + dx, cy + dy)
+        });
+        imageproc::drawing::draw_polygon_mut(canvas, &points, arrow);
+    }
+}
+
+/// Composites a before/after image for sharing. `mode` is one of:
+/// - "vertical": the original on the left, the edit on the right, split at
+///   `position` (0-1) of the width;
+/// - "horizontal": the original on top, the edit below, split at `position`
+///   of the height;
+/// - "side_by_side": both images in full, next to each other, with the
+///   divider as a gap between them.
+///
+/// `edited` is scaled to the original's size when they differ. The result is
+/// encoded in the original's format.
+#[flutter_rust_bridge::frb(sync)]
+pub fn compare_split(
+    original_bytes: Vec<u8>,
+    edited_bytes: Vec<u8>,
+    mode: String,
+    position: f32,
+    divider_style: LumeDividerStyle,
+) -> Result<Vec<u8>> {
+    let before = helpers::load(&original_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&original_bytes)?;
+    let mut after = helpers::load(&edited_bytes)?.to_rgba8();
+    let (w, h) = before.dimensions();
+    if after.dimensions() != (w, h) {
+        after = image::imageops::resize(&after, w, h, FilterType::Lanczos3);
+    }
+    let color = Rgba([
+        divider_style.color.r,
+        divider_style.color.g,
+        divider_style.color.b,
+        divider_style.color.a,
+    ]);
+    let line = divider_style.width;
+    let position = position.clamp(0.0, 1.0);
+
+    let out = match mode.to_lowercase().as_str() {
+        "vertical" | "horizontal" => {
+            let vertical = mode.eq_ignore_ascii_case("vertical");
+            let split = if vertical {
+                (w as f32 * position).round() as u32
+            } else {
+                (h as f32 * position).round() as u32
+            };
+            let mut out = RgbaImage::from_fn(w, h, |x, y| {
+                let first = if vertical { x < split } else { y < split };
+                if first {
+                    *before.get_pixel(x, y)
+                } else {
+                    *after.get_pixel(x, y)
+                }
+            });
+            if line > 0 {
+                let start = split.saturating_sub(line / 2) as i32;
+                let rect = if vertical {
+                    imageproc::rect::Rect::at(start, 0).of_size(line, h)
+                } else {
+                    imageproc::rect::Rect::at(0, start).of_size(w, line)
+                };
+                imageproc::drawing::draw_filled_rect_mut(&mut out, rect, color);
+            }
+            if divider_style.handle {
+                let (cx, cy) = if vertical {
+                    (split as i32, h as i32 / 2)
+                } else {
+                    (w as i32 / 2, split as i32)
+                };
+                draw_handle(&mut out, cx, cy, vertical, &divider_style);
+            }
+            out
+        }
+        "side_by_side" => {
+            let mut out = RgbaImage::from_pixel(w * 2 + line, h, color);
+            image::imageops::replace(&mut out, &before, 0, 0);
+            image::imageops::replace(&mut out, &after, (w + line) as i64, 0);
+            out
+        }
+        other => return Err(anyhow::anyhow!("Unsupported compare mode: {}", other)),
+    };
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Timelapse
+// ---------------------------------------------------------------------------
+
+/// Appends a RIFF chunk, padded to an even length.
+fn riff_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+fn u24(v: u32) -> [u8; 3] {
+    let b = v.to_le_bytes();
+    [b[0], b[1], b[2]]
+}
+
+/// The `VP8L` chunk (header included) of a still lossless WebP.
+fn vp8l_chunk(webp: &[u8]) -> Result<&[u8]> {
+    let mut pos = 12;
+    while pos + 8 <= webp.len() {
+        let size = u32::from_le_bytes([webp[pos + 4], webp[pos + 5], webp[pos + 6], webp[pos + 7]]);
+        let end = pos + 8 + size as usize;
+        if &webp[pos..pos + 4] == b"VP8L" && end <= webp.len() {
+            return Ok(&webp[pos..end]);
+        }
+        pos = end + size as usize % 2;
+    }
+    Err(anyhow::anyhow!("Encoded WebP frame has no VP8L chunk"))
+}
+
+/// Collects frames into an animated WebP. The `image` crate only writes
+/// still WebP, so each frame is encoded losslessly on its own and its
+/// bitstream wrapped in an `ANMF` chunk of the extended format.
+struct WebpAnimation {
+    width: u32,
+    height: u32,
+    frames: Vec<u8>,
+}
+
+impl WebpAnimation {
+    fn push(&mut self, frame: &RgbaImage, duration_ms: u32) -> Result<()> {
+        let mut still = Vec::new();
+        image::codecs::webp::WebPEncoder::new_lossless(&mut still).encode(
+            frame.as_raw(),
+            frame.width(),
+            frame.height(),
+            ExtendedColorType::Rgba8,
+        )?;
+        let mut anmf = Vec::new();
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(frame.width() - 1));
+        anmf.extend_from_slice(&u24(frame.height() - 1));
+        anmf.extend_from_slice(&u24(duration_ms.min(0xFF_FFFF)));
+        // Replace the canvas instead of blending, no disposal.
+        anmf.push(0b10);
+        let vp8l = vp8l_chunk(&still)?;
+        anmf.extend_from_slice(vp8l);
+        // The frame data is a chunk of its own and keeps its padding.
+        if vp8l.len() % 2 == 1 {
+            anmf.push(0);
+        }
+        riff_chunk(&mut self.frames, b"ANMF", &anmf);
+        Ok(())
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut body = b"WEBP".to_vec();
+        let mut vp8x = vec![0x10 | 0x02, 0, 0, 0];
+        vp8x.extend_from_slice(&u24(self.width - 1));
+        vp8x.extend_from_slice(&u24(self.height - 1));
+        riff_chunk(&mut body, b"VP8X", &vp8x);
+        // Transparent background, loop forever.
+        riff_chunk(&mut body, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+        body.extend_from_slice(&self.frames);
+        let mut out = b"RIFF".to_vec();
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+}
+
+/// Builds a looping animation from image files, read and encoded one at a
+/// time so only the current frame is ever decoded. Frames are scaled to fit
+/// `max_dimension` (0 keeps the size) and then to the first frame's size;
+/// with `align`, each frame is registered onto the first one (see
+/// `align_images`, "feature" method) to remove tripod shake. `format` is
+/// "gif" (256 colors per frame) or "webp" (lossless, larger files).
+pub fn build_timelapse(
+    paths: Vec<String>,
+    fps: f32,
+    max_dimension: u32,
+    format: String,
+    align: bool,
+) -> Result<Vec<u8>> {
+    if paths.is_empty() {
+        return Err(anyhow::anyhow!("No frames for the timelapse"));
+    }
+    if fps.is_nan() || fps <= 0.0 {
+        return Err(anyhow::anyhow!("Frame rate must be greater than zero"));
+    }
+    let webp = match format.to_lowercase().as_str() {
+        "gif" => false,
+        "webp" => true,
+        other => return Err(anyhow::anyhow!("Unsupported timelapse format: {}", other)),
+    };
+    let duration = std::time::Duration::try_from_secs_f32(1.0 / fps)
+        .map_err(|_| anyhow::anyhow!("Frame rate {} is too low", fps))?;
+
+    let mut gif_bytes = Vec::new();
+    let mut gif = if webp {
+        None
+    } else {
+        let mut encoder = image::codecs::gif::GifEncoder::new_with_speed(&mut gif_bytes, 10);
+        encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+        Some(encoder)
+    };
+    let mut animation: Option<WebpAnimation> = None;
+    let mut first_size: Option<(u32, u32)> = None;
+    let mut reference: Option<image::GrayImage> = None;
+    for path in &paths {
+        let bytes =
+            std::fs::read(path).map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path, e))?;
+        let mut frame = helpers::load(&bytes)?;
+        if max_dimension > 0 && frame.width().max(frame.height()) > max_dimension {
+            frame = frame.resize(max_dimension, max_dimension, FilterType::Triangle);
+        }
+        let frame = match first_size {
+            None => {
+                first_size = Some((frame.width(), frame.height()));
+                if align {
+                    reference = Some(frame.to_luma8());
+                }
+                frame.to_rgba8()
+            }
+            Some((w, h)) => {
+                if (frame.width(), frame.height()) != (w, h) {
+                    frame = frame.resize_exact(w, h, FilterType::Triangle);
+                }
+                match &reference {
+                    Some(first) => {
+                        let t =
+                            registration::estimate_alignment(first, &frame.to_luma8(), "feature")?;
+                        registration::warp_to_reference(&frame.to_rgba8(), t, w, h)?
+                    }
+                    None => frame.to_rgba8(),
+                }
+            }
+        };
+
+        if webp {
+            let anim = animation.get_or_insert(WebpAnimation {
+                width: frame.width(),
+                height: frame.height(),
+                frames: Vec::new(),
+            });
+            anim.push(&frame, duration.as_millis().min(u32::MAX as u128) as u32)?;
+        } else if let Some(encoder) = gif.as_mut() {
+            let delay = image::Delay::from_saturating_duration(duration);
+            encoder.encode_frame(image::Frame::from_parts(frame, 0, 0, delay))?;
+        }
+    }
+    Ok(match animation {
+        Some(anim) => anim.finish(),
+        None => {
+            drop(gif);
+            gif_bytes
+        }
+    })
+}
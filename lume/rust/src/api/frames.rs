@@ -0,0 +1,909 @@
+use anyhow::Result;
+use image::imageops::{self, colorops};
+use image::{DynamicImage, ImageBuffer, Pixel, Rgba, RgbaImage};
+
+use crate::api::image_ops;
+use crate::api::pipeline::{self, LumeOp};
+use crate::helpers::{self, Taps};
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// One plane of a YUV camera frame, as camera plugins hand them out. A
+/// stride of 0 means tightly packed: one byte per sample for Y and planar
+/// chroma, two for interleaved chroma.
+pub struct LumeYuvPlane {
+    pub bytes: Vec<u8>,
+    /// Bytes from one row to the next.
+    pub row_stride: u32,
+    /// Bytes from one sample to the next within a row.
+    pub pixel_stride: u32,
+}
+
+/// A pipeline set up once for a stream of same-sized raw RGBA frames, e.g.
+/// live camera preview filters. Ops are checked and compiled for the frame
+/// size when the processor is created: lookup tables, resampling weights and
+/// blur kernels are computed once, and the buffers that steps which cannot
+/// work in place write to are kept from one frame to the next.
+#[flutter_rust_bridge::frb(opaque)]
+pub struct LumeFrameProcessor {
+    width: u32,
+    height: u32,
+    stages: Vec<Stage>,
+    output: LumeFrameSize,
+    /// Second frame buffer, for stages that cannot work in place.
+    scratch: Vec<u8>,
+    /// One float per sample, between the passes of separable filters.
+    floats: Vec<f32>,
+}
+
+pub struct LumeFrameSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Pixels laid out for a Flutter `Texture` or `ui.decodeImageFromPixels`
+/// with `PixelFormat.bgra8888`: B, G, R, A bytes, rows `stride` bytes apart.
+pub struct LumePixelBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub premultiplied: bool,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum YuvLayout {
+    /// Y plane, then interleaved V/U.
+    Nv21,
+    /// Y plane, then interleaved U/V.
+    Nv12,
+    /// Y, U and V planes.
+    I420,
+}
+
+impl YuvLayout {
+    fn parse(name: &str) -> Result<YuvLayout> {
+        match name.to_lowercase().as_str() {
+            "nv21" => Ok(YuvLayout::Nv21),
+            "nv12" => Ok(YuvLayout::Nv12),
+            "i420" => Ok(YuvLayout::I420),
+            other => Err(anyhow::anyhow!("Unsupported YUV layout: {}", other)),
+        }
+    }
+}
+
+/// Samples of one plane: `cols` x `rows` of them starting at `offset`.
+struct PlaneView<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    row_stride: usize,
+    pixel_stride: usize,
+}
+
+impl<'a> PlaneView<'a> {
+    fn new(
+        name: &str,
+        bytes: &'a [u8],
+        offset: usize,
+        (row_stride, pixel_stride): (usize, usize),
+        (cols, rows): (u32, u32),
+    ) -> Result<PlaneView<'a>> {
+        if cols > 0 && rows > 0 {
+            let last =
+                offset + (rows as usize - 1) * row_stride + (cols as usize - 1) * pixel_stride;
+            if last >= bytes.len() {
+                return Err(anyhow::anyhow!("{} plane is too small for the frame", name));
+            }
+        }
+        Ok(PlaneView {
+            bytes,
+            offset,
+            row_stride,
+            pixel_stride,
+        })
+    }
+
+    fn at(&self, x: u32, y: u32) -> i32 {
+        self.bytes[self.offset + y as usize * self.row_stride + x as usize * self.pixel_stride]
+            as i32
+    }
+}
+
+/// Strides of `plane`, with 0 replaced by the tightly packed defaults.
+fn strides(plane: &LumeYuvPlane, cols: u32, default_pixel_stride: u32) -> (usize, usize) {
+    let pixel_stride = match plane.pixel_stride {
+        0 => default_pixel_stride,
+        n => n,
+    } as usize;
+    let row_stride = match plane.row_stride {
+        0 => cols as usize * pixel_stride,
+        n => n as usize,
+    };
+    (row_stride, pixel_stride)
+}
+
+// ---------------------------------------------------------------------------
+// YUV conversion
+// ---------------------------------------------------------------------------
+//
+// 4:2:0 frames with BT.601 coefficients, as produced by Android and iOS
+// cameras. Video ("limited") range puts black at Y = 16 and white at 235;
+// full range uses all 256 levels (JFIF, Android's NV21 camera preview).
+
+fn to_rgb(y: i32, u: i32, v: i32, full_range: bool) -> [u8; 3] {
+    let (d, e) = (u - 128, v - 128);
+    let (c, r, g1, g2, b) = if full_range {
+        (y << 8, 359, 88, 183, 454)
+    } else {
+        (298 * (y - 16), 409, 100, 208, 516)
+    };
+    let clamp = |v: i32| ((v + 128) >> 8).clamp(0, 255) as u8;
+    [
+        clamp(c + r * e),
+        clamp(c - g1 * d - g2 * e),
+        clamp(c + b * d),
+    ]
+}
+
+fn to_yuv([r, g, b]: [i32; 3], full_range: bool) -> (i32, i32, i32) {
+    let y = |cr: i32, cg: i32, cb: i32| (cr * r + cg * g + cb * b + 128) >> 8;
+    if full_range {
+        (
+            y(77, 150, 29),
+            y(-43, -85, 128) + 128,
+            y(128, -107, -21) + 128,
+        )
+    } else {
+        (
+            y(66, 129, 25) + 16,
+            y(-38, -74, 112) + 128,
+            y(112, -94, -18) + 128,
+        )
+    }
+}
+
+/// Converts a 4:2:0 YUV frame to tightly packed RGBA, ready for `from_raw`
+/// or `handle_from_raw`. `planes` is either:
+/// - Y, U and V with their own strides (Android's YUV_420_888, or I420);
+/// - Y and interleaved chroma, V first for "nv21", U first for "nv12"
+///   (iOS bi-planar frames);
+/// - a single tightly packed buffer holding the whole frame in `layout`
+///   ("nv21", "nv12" or "i420").
+///
+/// `full_range` selects full (0-255) instead of video (16-235) range.
+#[flutter_rust_bridge::frb(sync)]
+pub fn yuv_to_rgba(
+    planes: Vec<LumeYuvPlane>,
+    width: u32,
+    height: u32,
+    layout: String,
+    full_range: bool,
+) -> Result<Vec<u8>> {
+    let layout = YuvLayout::parse(&layout)?;
+    let (cw, ch) = (width.div_ceil(2), height.div_ceil(2));
+    let (y, u, v) = match planes.as_slice() {
+        [frame] => {
+            let bytes = &frame.bytes;
+            let y_size = width as usize * height as usize;
+            let c_size = cw as usize * ch as usize;
+            let y = PlaneView::new("Y", bytes, 0, (width as usize, 1), (width, height))?;
+            let (u_at, v_at, stride) = match layout {
+                YuvLayout::Nv21 => (y_size + 1, y_size, (cw as usize * 2, 2)),
+                YuvLayout::Nv12 => (y_size, y_size + 1, (cw as usize * 2, 2)),
+                YuvLayout::I420 => (y_size, y_size + c_size, (cw as usize, 1)),
+            };
+            let u = PlaneView::new("U", bytes, u_at, stride, (cw, ch))?;
+            let v = PlaneView::new("V", bytes, v_at, stride, (cw, ch))?;
+            (y, u, v)
+        }
+        [y_plane, chroma] => {
+            if layout == YuvLayout::I420 {
+                return Err(anyhow::anyhow!("I420 frames need one or three planes"));
+            }
+            let y = PlaneView::new(
+                "Y",
+                &y_plane.bytes,
+                0,
+                strides(y_plane, width, 1),
+                (width, height),
+            )?;
+            let stride = strides(chroma, cw, 2);
+            let (u_at, v_at) = if layout == YuvLayout::Nv21 {
+                (1, 0)
+            } else {
+                (0, 1)
+            };
+            let u = PlaneView::new("U", &chroma.bytes, u_at, stride, (cw, ch))?;
+            let v = PlaneView::new("V", &chroma.bytes, v_at, stride, (cw, ch))?;
+            (y, u, v)
+        }
+        [y_plane, u_plane, v_plane] => (
+            PlaneView::new(
+                "Y",
+                &y_plane.bytes,
+                0,
+                strides(y_plane, width, 1),
+                (width, height),
+            )?,
+            PlaneView::new("U", &u_plane.bytes, 0, strides(u_plane, cw, 1), (cw, ch))?,
+            PlaneView::new("V", &v_plane.bytes, 0, strides(v_plane, cw, 1), (cw, ch))?,
+        ),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Expected 1 to 3 YUV planes, got {}",
+                planes.len()
+            ))
+        }
+    };
+    let mut out = Vec::with_capacity(width as usize * height as usize * 4);
+    for py in 0..height {
+        for px in 0..width {
+            let rgb = to_rgb(
+                y.at(px, py),
+                u.at(px / 2, py / 2),
+                v.at(px / 2, py / 2),
+                full_range,
+            );
+            out.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+        }
+    }
+    Ok(out)
+}
+
+/// Converts tightly packed RGBA to a single tightly packed 4:2:0 YUV buffer
+/// in `layout` ("nv21", "nv12" or "i420"), e.g. to hand processed frames to
+/// a video encoder. Alpha is ignored; chroma is averaged over 2x2 blocks.
+#[flutter_rust_bridge::frb(sync)]
+pub fn rgba_to_yuv(
+    rgba_bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    layout: String,
+    full_range: bool,
+) -> Result<Vec<u8>> {
+    let layout = YuvLayout::parse(&layout)?;
+    let (w, h) = (width as usize, height as usize);
+    if rgba_bytes.len() != w * h * 4 {
+        return Err(anyhow::anyhow!(
+            "Expected {} bytes of RGBA data for {}x{}, got {}",
+            w * h * 4,
+            width,
+            height,
+            rgba_bytes.len()
+        ));
+    }
+    let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+    let mut out = vec![0u8; w * h + 2 * cw * ch];
+    let (luma, chroma) = out.split_at_mut(w * h);
+    let rgb = |x: usize, y: usize| {
+        let i = (y * w + x) * 4;
+        [rgba_bytes[i], rgba_bytes[i + 1], rgba_bytes[i + 2]].map(i32::from)
+    };
+    for (i, v) in luma.iter_mut().enumerate() {
+        *v = to_yuv(rgb(i % w, i / w), full_range).0.clamp(0, 255) as u8;
+    }
+    for cy in 0..ch {
+        for cx in 0..cw {
+            // Average of the (up to) 2x2 block, rounded.
+            let mut sum = [0; 3];
+            let mut count = 0;
+            for y in (cy * 2)..(cy * 2 + 2).min(h) {
+                for x in (cx * 2)..(cx * 2 + 2).min(w) {
+                    for (s, c) in sum.iter_mut().zip(rgb(x, y)) {
+                        *s += c;
+                    }
+                    count += 1;
+                }
+            }
+            let (_, u, v) = to_yuv(sum.map(|s| (s + count / 2) / count), full_range);
+            let (u, v) = (u.clamp(0, 255) as u8, v.clamp(0, 255) as u8);
+            let i = cy * cw + cx;
+            match layout {
+                YuvLayout::Nv21 => (chroma[i * 2], chroma[i * 2 + 1]) = (v, u),
+                YuvLayout::Nv12 => (chroma[i * 2], chroma[i * 2 + 1]) = (u, v),
+                YuvLayout::I420 => (chroma[i], chroma[cw * ch + i]) = (u, v),
+            }
+        }
+    }
+    Ok(out)
+}
+
+// ---------------------------------------------------------------------------
+// Frame processing
+// ---------------------------------------------------------------------------
+
+/// A `LumeOp` compiled for the size of the frames it will see. Each does the
+/// same as the op on a `DynamicImage`; the blurs round at slightly different
+/// points than the `image` crate's fixed-point filter, so a sample can be
+/// off by one.
+enum Stage {
+    /// Separable filter: `rows` down every column, then `columns` along
+    /// every row. The output is as large as the two tap sets are long.
+    Separable {
+        columns: Taps,
+        rows: Taps,
+    },
+    /// Unsharp mask over a separable blur.
+    Sharpen {
+        columns: Taps,
+        rows: Taps,
+        threshold: i32,
+    },
+    /// Crop to a rectangle already clipped to the frame.
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Grayscale,
+    Brightness(i32),
+    Contrast(f32),
+    Invert,
+    HueRotate(i32),
+    /// Per-channel lookup table, alpha excluded.
+    Lut(Vec<u8>),
+}
+
+/// The 1D kernel `DynamicImage::blur` uses for `sigma`.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let size = ((((sigma - 0.8) / 0.3 + 1.0) * 2.0 + 1.0).max(3.0) as usize) | 1;
+    let mean = (size / 2) as f32;
+    let kernel: Vec<f32> = (0..size)
+        .map(|x| (-0.5 * ((x as f32 - mean) / sigma).powi(2)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter().map(|k| k / sum).collect()
+}
+
+/// `kernel` centered on every one of `len` samples.
+fn kernel_taps(kernel: &[f32], len: u32) -> Taps {
+    let radius = (kernel.len() / 2) as i32;
+    Taps {
+        starts: (0..len as i32).map(|o| o - radius).collect(),
+        weights: (0..len).flat_map(|_| kernel.iter().copied()).collect(),
+        taps: kernel.len(),
+    }
+}
+
+/// Compiles `ops` for `width` x `height` frames, returning the stages and
+/// the size of the frames they produce. Nothing is run, so this is cheap
+/// enough to redo whenever the settings change.
+fn compile(ops: &[LumeOp], width: u32, height: u32) -> Result<(Vec<Stage>, LumeFrameSize)> {
+    let (mut w, mut h) = (width, height);
+    let mut stages = Vec::with_capacity(ops.len());
+    for op in ops {
+        let empty = w == 0 || h == 0;
+        let stage = match *op {
+            LumeOp::Resize {
+                width: nw,
+                height: nh,
+                keep_aspect_ratio,
+            } => {
+                let (nw, nh) = if keep_aspect_ratio && !empty {
+                    helpers::fit_size(w, h, nw, nh)
+                } else {
+                    (nw, nh)
+                };
+                // `image` copies frames that already have the size.
+                if (nw, nh) == (w, h) {
+                    continue;
+                }
+                if empty {
+                    return Err(anyhow::anyhow!("Cannot resize an empty frame"));
+                }
+                let stage = Stage::Separable {
+                    columns: helpers::lanczos3_taps(w, nw),
+                    rows: helpers::lanczos3_taps(h, nh),
+                };
+                (w, h) = (nw, nh);
+                stage
+            }
+            LumeOp::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                // Clipped like `DynamicImage::crop_imm`.
+                let (x, y) = (x.min(w), y.min(h));
+                let (width, height) = (width.min(w - x), height.min(h - y));
+                (w, h) = (width, height);
+                Stage::Crop {
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            }
+            LumeOp::Rotate { degrees } => match degrees % 360 {
+                90 => {
+                    (w, h) = (h, w);
+                    Stage::Rotate90
+                }
+                180 => Stage::Rotate180,
+                270 => {
+                    (w, h) = (h, w);
+                    Stage::Rotate270
+                }
+                0 => continue,
+                _ => return Err(pipeline::unaligned_rotation(degrees)),
+            },
+            LumeOp::FlipHorizontal => Stage::FlipHorizontal,
+            LumeOp::FlipVertical => Stage::FlipVertical,
+            LumeOp::Grayscale => Stage::Grayscale,
+            LumeOp::Brightness { value } => Stage::Brightness(value),
+            LumeOp::Contrast { value } => Stage::Contrast(value),
+            LumeOp::Blur { sigma } => {
+                if !(sigma >= 0.0 && sigma.is_finite()) {
+                    return Err(anyhow::anyhow!("Blur sigma must be 0 or more"));
+                }
+                let kernel = gaussian_kernel(if sigma == 0.0 { 0.8 } else { sigma });
+                Stage::Separable {
+                    columns: kernel_taps(&kernel, w),
+                    rows: kernel_taps(&kernel, h),
+                }
+            }
+            LumeOp::Sharpen { sigma, threshold } => {
+                if !(sigma > 0.0 && sigma.is_finite()) {
+                    return Err(anyhow::anyhow!("Sharpen sigma must be more than 0"));
+                }
+                let kernel = gaussian_kernel(sigma);
+                Stage::Sharpen {
+                    columns: kernel_taps(&kernel, w),
+                    rows: kernel_taps(&kernel, h),
+                    threshold,
+                }
+            }
+            LumeOp::Invert => Stage::Invert,
+            LumeOp::HueRotate { degrees } => Stage::HueRotate(degrees),
+            LumeOp::Levels {
+                black_point,
+                white_point,
+                gamma,
+            } => Stage::Lut(image_ops::levels_lut8(black_point, white_point, gamma)?),
+        };
+        stages.push(stage);
+    }
+    Ok((
+        stages,
+        LumeFrameSize {
+            width: w,
+            height: h,
+        },
+    ))
+}
+
+type View<'a> = ImageBuffer<Rgba<u8>, &'a mut [u8]>;
+
+/// `buf` as a `width` x `height` RGBA image.
+fn view(buf: &mut [u8], (width, height): (u32, u32)) -> View<'_> {
+    ImageBuffer::from_raw(width, height, buf).expect("buffer matches the frame size")
+}
+
+/// Runs a separable filter over the `width` x `height` RGBA `src` into
+/// `dst`, through `floats` between the passes. Returns the output size.
+fn separable(
+    src: &[u8],
+    (width, height): (u32, u32),
+    columns: &Taps,
+    rows: &Taps,
+    floats: &mut Vec<f32>,
+    dst: &mut Vec<u8>,
+) -> (u32, u32) {
+    let (w, h) = (width as usize, height as usize);
+    let (out_w, out_h) = (columns.starts.len(), rows.starts.len());
+    dst.clear();
+    if w == 0 || h == 0 || out_w == 0 || out_h == 0 {
+        return (out_w as u32, out_h as u32);
+    }
+    let at =
+        |start: i32, t: usize, len: usize| (start + t as i32).clamp(0, len as i32 - 1) as usize;
+
+    floats.clear();
+    floats.resize(w * out_h * 4, 0.0);
+    for (oy, row) in floats.chunks_exact_mut(w * 4).enumerate() {
+        for t in 0..rows.taps {
+            let weight = rows.weights[oy * rows.taps + t];
+            if weight == 0.0 {
+                continue;
+            }
+            let sy = at(rows.starts[oy], t, h);
+            for (o, &v) in row.iter_mut().zip(&src[sy * w * 4..(sy + 1) * w * 4]) {
+                *o += weight * v as f32;
+            }
+        }
+    }
+
+    dst.resize(out_w * out_h * 4, 0);
+    for (row, out) in floats
+        .chunks_exact(w * 4)
+        .zip(dst.chunks_exact_mut(out_w * 4))
+    {
+        for (ox, px) in out.chunks_exact_mut(4).enumerate() {
+            let mut sum = [0.0f32; 4];
+            for t in 0..columns.taps {
+                let weight = columns.weights[ox * columns.taps + t];
+                let sx = at(columns.starts[ox], t, w);
+                for (s, &v) in sum.iter_mut().zip(&row[sx * 4..sx * 4 + 4]) {
+                    *s += weight * v;
+                }
+            }
+            for (p, s) in px.iter_mut().zip(sum) {
+                *p = s.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    (out_w as u32, out_h as u32)
+}
+
+impl Stage {
+    /// Runs the stage on the `size` frame in `frame`, leaving the result in
+    /// `frame` (swapping it with `scratch` when the stage cannot work in
+    /// place). Returns the new frame size.
+    fn apply(
+        &self,
+        frame: &mut Vec<u8>,
+        size: (u32, u32),
+        scratch: &mut Vec<u8>,
+        floats: &mut Vec<f32>,
+    ) -> Result<(u32, u32)> {
+        let (w, h) = size;
+        let out = match self {
+            Stage::Separable { columns, rows } => {
+                separable(frame, size, columns, rows, floats, scratch)
+            }
+            Stage::Sharpen {
+                columns,
+                rows,
+                threshold,
+            } => {
+                separable(frame, size, columns, rows, floats, scratch);
+                // As `imageops::unsharpen`: push each sample away from its
+                // blurred value when they differ by more than `threshold`.
+                for (b, &o) in scratch.iter_mut().zip(frame.iter()) {
+                    let diff = o as i32 - *b as i32;
+                    *b = if diff.abs() > *threshold {
+                        (o as i32 + diff).clamp(0, 255) as u8
+                    } else {
+                        o
+                    };
+                }
+                size
+            }
+            Stage::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let (x, width) = (*x as usize, *width as usize);
+                scratch.clear();
+                for row in *y..*y + *height {
+                    let start = (row as usize * w as usize + x) * 4;
+                    scratch.extend_from_slice(&frame[start..start + width * 4]);
+                }
+                (width as u32, *height)
+            }
+            Stage::Rotate90 | Stage::Rotate270 => {
+                scratch.clear();
+                scratch.resize(frame.len(), 0);
+                let src = ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(w, h, frame.as_slice())
+                    .expect("buffer matches the frame size");
+                let mut dst = view(scratch, (h, w));
+                if matches!(self, Stage::Rotate90) {
+                    imageops::rotate90_in(&src, &mut dst)?;
+                } else {
+                    imageops::rotate270_in(&src, &mut dst)?;
+                }
+                (h, w)
+            }
+            in_place => {
+                let mut img = view(frame, size);
+                match in_place {
+                    Stage::Rotate180 => imageops::rotate180_in_place(&mut img),
+                    Stage::FlipHorizontal => imageops::flip_horizontal_in_place(&mut img),
+                    Stage::FlipVertical => imageops::flip_vertical_in_place(&mut img),
+                    Stage::Grayscale => {
+                        for p in img.pixels_mut() {
+                            let luma = p.to_luma_alpha().0[0];
+                            p.0[..3].fill(luma);
+                        }
+                    }
+                    Stage::Brightness(value) => colorops::brighten_in_place(&mut img, *value),
+                    Stage::Contrast(value) => colorops::contrast_in_place(&mut img, *value),
+                    Stage::Invert => imageops::invert(&mut img),
+                    Stage::HueRotate(degrees) => colorops::huerotate_in_place(&mut img, *degrees),
+                    Stage::Lut(lut) => {
+                        for p in img.pixels_mut() {
+                            p.apply_without_alpha(|v| lut[v as usize]);
+                        }
+                    }
+                    _ => unreachable!("handled above"),
+                }
+                return Ok(size);
+            }
+        };
+        std::mem::swap(frame, scratch);
+        Ok(out)
+    }
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn frame_processor_new(
+    ops: Vec<LumeOp>,
+    width: u32,
+    height: u32,
+) -> Result<LumeFrameProcessor> {
+    let (stages, output) = compile(&ops, width, height)?;
+    Ok(LumeFrameProcessor {
+        width,
+        height,
+        stages,
+        output,
+        scratch: Vec::new(),
+        floats: Vec::new(),
+    })
+}
+
+/// Swaps the pipeline without recreating the processor, e.g. while a filter
+/// slider moves. Only the new settings are compiled; no frame is processed.
+/// Invalid ops leave the current ones in place.
+#[flutter_rust_bridge::frb(sync)]
+pub fn frame_processor_set_ops(processor: &mut LumeFrameProcessor, ops: Vec<LumeOp>) -> Result<()> {
+    (processor.stages, processor.output) = compile(&ops, processor.width, processor.height)?;
+    Ok(())
+}
+
+/// Size of the frames `process_frame` returns.
+#[flutter_rust_bridge::frb(sync)]
+pub fn frame_processor_output_size(processor: &LumeFrameProcessor) -> LumeFrameSize {
+    LumeFrameSize {
+        width: processor.output.width,
+        height: processor.output.height,
+    }
+}
+
+fn run(processor: &mut LumeFrameProcessor, rgba_bytes: Vec<u8>) -> Result<RgbaImage> {
+    let mut frame =
+        image_ops::image_from_raw(processor.width, processor.height, rgba_bytes)?.into_raw();
+    let mut size = (processor.width, processor.height);
+    for stage in &processor.stages {
+        size = stage.apply(
+            &mut frame,
+            size,
+            &mut processor.scratch,
+            &mut processor.floats,
+        )?;
+    }
+    Ok(RgbaImage::from_raw(size.0, size.1, frame).expect("stages keep the frame size"))
+}
+
+/// Runs the processor's pipeline on one tightly packed RGBA frame of the
+/// configured size and returns the result as tightly packed RGBA, in the
+/// frame's own buffer when the size allows. YUV camera frames go through
+/// `yuv_to_rgba` first.
+#[flutter_rust_bridge::frb(sync)]
+pub fn process_frame(processor: &mut LumeFrameProcessor, rgba_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    Ok(run(processor, rgba_bytes)?.into_raw())
+}
+
+// ---------------------------------------------------------------------------
+// Texture output
+// ---------------------------------------------------------------------------
+
+/// Tightly packed BGRA copy of `img`, with color multiplied by alpha when
+/// `premultiplied` (as most GPU texture paths expect).
+pub(crate) fn to_bgra(img: DynamicImage, premultiplied: bool) -> LumePixelBuffer {
+    let rgba = img.into_rgba8();
+    let (width, height) = rgba.dimensions();
+    let mut bytes = rgba.into_raw();
+    for px in bytes.chunks_exact_mut(4) {
+        px.swap(0, 2);
+        if premultiplied {
+            let a = px[3] as u32;
+            for c in &mut px[..3] {
+                *c = ((*c as u32 * a + 127) / 255) as u8;
+            }
+        }
+    }
+    LumePixelBuffer {
+        width,
+        height,
+        stride: width * 4,
+        premultiplied,
+        bytes,
+    }
+}
+
+/// Decodes an image straight to a BGRA buffer, so Dart can upload it
+/// without decoding it again or swizzling pixels.
+#[flutter_rust_bridge::frb(sync)]
+pub fn decode_bgra(image_bytes: Vec<u8>, premultiplied: bool) -> Result<LumePixelBuffer> {
+    Ok(to_bgra(helpers::load(&image_bytes)?, premultiplied))
+}
+
+/// `process_frame` returning a BGRA buffer, for live previews drawn through
+/// a texture.
+#[flutter_rust_bridge::frb(sync)]
+pub fn process_frame_bgra(
+    processor: &mut LumeFrameProcessor,
+    rgba_bytes: Vec<u8>,
+    premultiplied: bool,
+) -> Result<LumePixelBuffer> {
+    let frame = run(processor, rgba_bytes)?;
+    Ok(to_bgra(DynamicImage::ImageRgba8(frame), premultiplied))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame whose color only changes between 2x2 blocks, so 4:2:0
+    /// subsampling loses nothing but rounding.
+    fn blocky_frame(width: u32, height: u32) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| {
+                (0..width).flat_map(move |x| {
+                    let (bx, by) = (x / 2, y / 2);
+                    [
+                        (40 + bx * 50) as u8,
+                        (200 - by * 60) as u8,
+                        (90 + bx * by * 20) as u8,
+                        255,
+                    ]
+                })
+            })
+            .collect()
+    }
+
+    /// `rows` rows of `row_len` bytes from `bytes`, each padded to `stride`.
+    fn padded(bytes: &[u8], row_len: usize, rows: usize, stride: usize) -> Vec<u8> {
+        let mut out = vec![0xEE; stride * rows];
+        for r in 0..rows {
+            out[r * stride..r * stride + row_len]
+                .copy_from_slice(&bytes[r * row_len..(r + 1) * row_len]);
+        }
+        out
+    }
+
+    fn plane(bytes: Vec<u8>, row_stride: u32, pixel_stride: u32) -> LumeYuvPlane {
+        LumeYuvPlane {
+            bytes,
+            row_stride,
+            pixel_stride,
+        }
+    }
+
+    fn assert_close(a: &[u8], b: &[u8], tolerance: u8) {
+        assert_eq!(a.len(), b.len());
+        for (i, (x, y)) in a.iter().zip(b).enumerate() {
+            assert!(x.abs_diff(*y) <= tolerance, "byte {}: {} vs {}", i, x, y);
+        }
+    }
+
+    #[test]
+    fn yuv_round_trip_with_odd_size_and_padded_strides() {
+        let (w, h) = (5u32, 3u32);
+        let (ws, hs, cw, ch) = (5, 3, 3, 2);
+        let rgba = blocky_frame(w, h);
+        for full_range in [false, true] {
+            let i420 = rgba_to_yuv(rgba.clone(), w, h, "i420".into(), full_range).unwrap();
+            assert_eq!(i420.len(), ws * hs + 2 * cw * ch);
+            let packed = yuv_to_rgba(
+                vec![plane(i420.clone(), 0, 0)],
+                w,
+                h,
+                "i420".into(),
+                full_range,
+            )
+            .unwrap();
+            assert_close(&packed, &rgba, 2);
+
+            let (y, chroma) = i420.split_at(ws * hs);
+            let (u, v) = chroma.split_at(cw * ch);
+            let planes = vec![
+                plane(padded(y, ws, hs, 8), 8, 1),
+                plane(padded(u, cw, ch, 4), 4, 1),
+                plane(padded(v, cw, ch, 4), 4, 1),
+            ];
+            let three = yuv_to_rgba(planes, w, h, "i420".into(), full_range).unwrap();
+            assert_eq!(three, packed);
+
+            // The same chroma interleaved, with a padded row stride as
+            // Android's camera hands it out.
+            let nv21 = rgba_to_yuv(rgba.clone(), w, h, "nv21".into(), full_range).unwrap();
+            let (y, vu) = nv21.split_at(ws * hs);
+            let planes = vec![
+                plane(padded(y, ws, hs, 8), 8, 1),
+                plane(padded(vu, cw * 2, ch, 8), 8, 2),
+            ];
+            let two = yuv_to_rgba(planes, w, h, "nv21".into(), full_range).unwrap();
+            assert_eq!(two, packed);
+        }
+    }
+
+    #[test]
+    fn yuv_planes_too_small_for_the_frame_are_rejected() {
+        let i420 = rgba_to_yuv(blocky_frame(5, 3), 5, 3, "i420".into(), false).unwrap();
+        let short = i420[..i420.len() - 1].to_vec();
+        assert!(yuv_to_rgba(vec![plane(short, 0, 0)], 5, 3, "i420".into(), false).is_err());
+        // A row stride larger than the plane holds.
+        let y = plane(vec![0; 15], 8, 1);
+        let chroma = plane(vec![128; 12], 0, 2);
+        assert!(yuv_to_rgba(vec![y, chroma], 5, 3, "nv12".into(), false).is_err());
+        assert!(rgba_to_yuv(vec![0; 59], 5, 3, "nv12".into(), false).is_err());
+    }
+
+    #[test]
+    fn decode_bgra_round_trips_rgba() {
+        let rgba = RgbaImage::from_fn(3, 2, |x, y| {
+            image::Rgba([x as u8 * 80, y as u8 * 200, 30, 255 - x as u8 * 100])
+        });
+        let png = helpers::encode(
+            &DynamicImage::ImageRgba8(rgba.clone()),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+        let straight = decode_bgra(png.clone(), false).unwrap();
+        assert_eq!(
+            (straight.width, straight.height, straight.stride),
+            (3, 2, 12)
+        );
+        let mut back = straight.bytes.clone();
+        for px in back.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+        assert_eq!(back, rgba.into_raw());
+
+        let premultiplied = decode_bgra(png, true).unwrap();
+        // (160, 0, 30, 55) premultiplied and swizzled.
+        assert_eq!(&premultiplied.bytes[8..12], &[6, 0, 35, 55]);
+    }
+
+    #[test]
+    fn frame_processor_matches_the_pipeline() {
+        let (w, h) = (9u32, 7u32);
+        let frame: Vec<u8> = (0..w * h * 4).map(|i| (i * 37 % 251) as u8).collect();
+        let ops = vec![
+            LumeOp::Crop {
+                x: 1,
+                y: 1,
+                width: 20,
+                height: 5,
+            },
+            LumeOp::Rotate { degrees: 90 },
+            LumeOp::FlipHorizontal,
+            LumeOp::Brightness { value: 20 },
+            LumeOp::Levels {
+                black_point: 0.1,
+                white_point: 0.9,
+                gamma: 1.2,
+            },
+        ];
+        let expected = pipeline::apply_ops(
+            DynamicImage::ImageRgba8(RgbaImage::from_raw(w, h, frame.clone()).unwrap()),
+            &ops,
+        )
+        .unwrap()
+        .to_rgba8();
+        let mut processor = frame_processor_new(ops, w, h).unwrap();
+        let size = frame_processor_output_size(&processor);
+        assert_eq!((size.width, size.height), expected.dimensions());
+        // The second frame runs on the buffers the first one left behind.
+        for _ in 0..2 {
+            let out = process_frame(&mut processor, frame.clone()).unwrap();
+            assert_eq!(out, expected.as_raw().as_slice());
+        }
+        assert!(
+            frame_processor_set_ops(&mut processor, vec![LumeOp::Rotate { degrees: 45 }]).is_err()
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use crate::gpu;
+
+// ---------------------------------------------------------------------------
+// GPU
+// ---------------------------------------------------------------------------
+
+/// Name of the GPU that runs gaussian blur, convolution, levels and resize
+/// on large images, or `None` when there is none and everything runs on the
+/// CPU. The first call sets the GPU up, so it can take a moment.
+#[flutter_rust_bridge::frb(sync)]
+pub fn gpu_adapter_name() -> Option<String> {
+    gpu::adapter_name()
+}
+
+/// Turns GPU execution on or off (on by default). Results can differ from
+/// the CPU path by one level per channel, from rounding.
+#[flutter_rust_bridge::frb(sync)]
+pub fn set_gpu_enabled(enabled: bool) {
+    gpu::set_enabled(enabled);
+}
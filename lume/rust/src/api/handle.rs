@@ -0,0 +1,281 @@
+use anyhow::Result;
+use image::{DynamicImage, GrayImage, ImageFormat, Luma};
+use imageproc::template_matching::MatchTemplateMethod;
+use std::sync::OnceLock;
+
+use crate::api::frames::{self, LumePixelBuffer};
+use crate::api::image_ops::{self, LumeColor, LumeImageInfo};
+use crate::api::imageproc_ops::{LumePoint, LumeRect};
+use crate::helpers::{self, Image};
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// A decoded image kept alive on the Rust side, so several operations can run
+/// on it without paying the decode cost each time. Intermediates shared by the
+/// analysis functions (grayscale copy, integral image, gradients) are computed
+/// lazily on first use, or eagerly with `precompute`, and then reused.
+#[flutter_rust_bridge::frb(opaque)]
+pub struct LumeHandle {
+    image: DynamicImage,
+    format: ImageFormat,
+    size_bytes: u32,
+    gray: OnceLock<GrayImage>,
+    integral: OnceLock<Image<Luma<u64>>>,
+    gradients: OnceLock<Gradients>,
+}
+
+/// Horizontal and vertical Sobel responses.
+type Gradients = (Image<Luma<i16>>, Image<Luma<i16>>);
+
+impl LumeHandle {
+    pub(crate) fn new(image: DynamicImage, format: ImageFormat, size_bytes: u32) -> Self {
+        LumeHandle {
+            image,
+            format,
+            size_bytes,
+            gray: OnceLock::new(),
+            integral: OnceLock::new(),
+            gradients: OnceLock::new(),
+        }
+    }
+
+    /// Mutable access to the image. Cached intermediates are dropped, since
+    /// they no longer match it.
+    pub(crate) fn image_mut(&mut self) -> &mut DynamicImage {
+        self.gray.take();
+        self.integral.take();
+        self.gradients.take();
+        &mut self.image
+    }
+
+    pub(crate) fn gray(&self) -> &GrayImage {
+        self.gray.get_or_init(|| self.image.to_luma8())
+    }
+
+    pub(crate) fn integral(&self) -> &Image<Luma<u64>> {
+        self.integral
+            .get_or_init(|| imageproc::integral_image::integral_image(self.gray()))
+    }
+
+    pub(crate) fn gradients(&self) -> &Gradients {
+        self.gradients.get_or_init(|| {
+            let gray = self.gray();
+            (
+                imageproc::gradients::horizontal_sobel(gray),
+                imageproc::gradients::vertical_sobel(gray),
+            )
+        })
+    }
+}
+
+pub struct LumeCorner {
+    pub x: u32,
+    pub y: u32,
+    pub score: f32,
+}
+
+pub struct LumeTemplateMatch {
+    pub x: u32,
+    pub y: u32,
+    pub score: f32,
+}
+
+// ---------------------------------------------------------------------------
+// Lifecycle
+// ---------------------------------------------------------------------------
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_open(image_bytes: Vec<u8>) -> Result<LumeHandle> {
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    Ok(LumeHandle::new(img, fmt, image_bytes.len() as u32))
+}
+
+/// Opens a handle on raw RGBA pixels (row by row, no padding), skipping
+/// encoding and decoding altogether. `handle_encode` with an empty format
+/// gives PNG.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_from_raw(width: u32, height: u32, rgba_bytes: Vec<u8>) -> Result<LumeHandle> {
+    let size_bytes = rgba_bytes.len() as u32;
+    let img = image_ops::image_from_raw(width, height, rgba_bytes)?;
+    Ok(LumeHandle::new(
+        DynamicImage::ImageRgba8(img),
+        ImageFormat::Png,
+        size_bytes,
+    ))
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_info(handle: &LumeHandle) -> LumeImageInfo {
+    LumeImageInfo {
+        width: handle.image.width(),
+        height: handle.image.height(),
+        format: helpers::format_to_string(handle.format),
+        size_bytes: handle.size_bytes,
+    }
+}
+
+/// Encodes the handle's image. An empty `format` keeps the source format.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_encode(handle: &LumeHandle, format: String) -> Result<Vec<u8>> {
+    let fmt = if format.is_empty() {
+        handle.format
+    } else {
+        helpers::string_to_format(&format)?
+    };
+    helpers::encode(&handle.image, fmt)
+}
+
+/// The handle's image as a BGRA buffer for a Flutter texture, see
+/// `decode_bgra`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_to_bgra(handle: &LumeHandle, premultiplied: bool) -> LumePixelBuffer {
+    frames::to_bgra(handle.image.clone(), premultiplied)
+}
+
+/// Eagerly computes and caches intermediates on the handle. Supported kinds:
+/// "grayscale", "integral" and "gradients".
+#[flutter_rust_bridge::frb(sync)]
+pub fn precompute(handle: &LumeHandle, kinds: Vec<String>) -> Result<()> {
+    for kind in kinds {
+        match kind.to_lowercase().as_str() {
+            "grayscale" | "gray" | "luma" => {
+                handle.gray();
+            }
+            "integral" => {
+                handle.integral();
+            }
+            "gradients" | "gradient" => {
+                handle.gradients();
+            }
+            other => return Err(anyhow::anyhow!("Unsupported precompute kind: {}", other)),
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Analysis on cached intermediates
+// ---------------------------------------------------------------------------
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_sobel_gradients(handle: &LumeHandle) -> Result<Vec<u8>> {
+    let (gx, gy) = handle.gradients();
+    let out: GrayImage = image::ImageBuffer::from_fn(gx.width(), gx.height(), |x, y| {
+        let h = gx.get_pixel(x, y).0[0] as f32;
+        let v = gy.get_pixel(x, y).0[0] as f32;
+        let magnitude = (h * h + v * v).sqrt().min(u16::MAX as f32) as u16;
+        Luma([(magnitude >> 8) as u8])
+    });
+    helpers::encode(&DynamicImage::ImageLuma8(out), handle.format)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_canny(
+    handle: &LumeHandle,
+    low_threshold: f32,
+    high_threshold: f32,
+) -> Result<Vec<u8>> {
+    let out = imageproc::edges::canny(handle.gray(), low_threshold, high_threshold);
+    helpers::encode(&DynamicImage::ImageLuma8(out), handle.format)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_corners_fast9(handle: &LumeHandle, threshold: u8) -> Vec<LumeCorner> {
+    imageproc::corners::corners_fast9(handle.gray(), threshold)
+        .into_iter()
+        .map(|c| LumeCorner {
+            x: c.x,
+            y: c.y,
+            score: c.score,
+        })
+        .collect()
+}
+
+/// Finds the best position of `template_bytes` in the handle's image.
+/// `method` is one of "sse", "sse_normalized", "ccorr" or "ccorr_normalized";
+/// for the "sse" methods the lowest score wins, otherwise the highest.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_match_template(
+    handle: &LumeHandle,
+    template_bytes: Vec<u8>,
+    method: String,
+) -> Result<LumeTemplateMatch> {
+    let template = helpers::load(&template_bytes)?.to_luma8();
+    let gray = handle.gray();
+    if template.width() > gray.width() || template.height() > gray.height() {
+        return Err(anyhow::anyhow!("Template is larger than the image"));
+    }
+    let (method, lower_is_better) = match method.to_lowercase().as_str() {
+        "sse" => (MatchTemplateMethod::SumOfSquaredErrors, true),
+        "sse_normalized" => (MatchTemplateMethod::SumOfSquaredErrorsNormalized, true),
+        "ccorr" => (MatchTemplateMethod::CrossCorrelation, false),
+        "ccorr_normalized" => (MatchTemplateMethod::CrossCorrelationNormalized, false),
+        other => return Err(anyhow::anyhow!("Unsupported match method: {}", other)),
+    };
+    let scores = imageproc::template_matching::match_template(gray, &template, method);
+    let extremes = imageproc::template_matching::find_extremes(&scores);
+    let ((x, y), score) = if lower_is_better {
+        (extremes.min_value_location, extremes.min_value)
+    } else {
+        (extremes.max_value_location, extremes.max_value)
+    };
+    Ok(LumeTemplateMatch { x, y, score })
+}
+
+/// Mean grayscale intensity of a rectangle, answered in constant time from
+/// the cached integral image.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_region_mean(
+    handle: &LumeHandle,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Result<f64> {
+    let gray = handle.gray();
+    if width == 0
+        || height == 0
+        || x.checked_add(width).is_none_or(|end| end > gray.width())
+        || y.checked_add(height).is_none_or(|end| end > gray.height())
+    {
+        return Err(anyhow::anyhow!("Region is outside the image"));
+    }
+    let [sum] = imageproc::integral_image::sum_image_pixels(
+        handle.integral(),
+        x,
+        y,
+        x + width - 1,
+        y + height - 1,
+    );
+    Ok(sum as f64 / (width as u64 * height as u64) as f64)
+}
+
+// ---------------------------------------------------------------------------
+// Pixel access
+// ---------------------------------------------------------------------------
+
+/// `get_pixels` on the handle's image, without decoding it again.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_get_pixels(handle: &LumeHandle, rect: LumeRect) -> Result<Vec<u8>> {
+    image_ops::read_pixels(&handle.image, &rect)
+}
+
+/// `sample_pixels` on the handle's image, without decoding it again.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_sample_pixels(handle: &LumeHandle, points: Vec<LumePoint>) -> Result<Vec<LumeColor>> {
+    image_ops::sample_points(&handle.image, &points)
+}
+
+/// `set_pixels` on the handle's image, in place. Cached intermediates are
+/// recomputed on next use.
+#[flutter_rust_bridge::frb(sync)]
+pub fn handle_set_pixels(
+    handle: &mut LumeHandle,
+    rect: LumeRect,
+    rgba_data: Vec<u8>,
+) -> Result<()> {
+    image_ops::write_pixels(handle.image_mut(), &rect, &rgba_data)
+}
@@ -1,7 +1,10 @@
 use anyhow::Result;
-use image::{ImageFormat, ImageReader};
+use image::{DynamicImage, GenericImage, GenericImageView, ImageFormat, ImageReader, Pixel};
 use std::io::Cursor;
 
+use crate::api::drawing::{Gradient, LumeGradientStop};
+use crate::api::imageproc_ops::{LumePoint, LumeRect};
+use crate::api::mask::{self, LumeMask};
 use crate::helpers;
 
 // ---------------------------------------------------------------------------
@@ -15,6 +18,83 @@ pub struct LumeImageInfo {
     pub size_bytes: u32,
 }
 
+/// Settings for `resize_with_options`.
+pub struct LumeResizeOptions {
+    pub width: u32,
+    pub height: u32,
+    pub keep_aspect_ratio: bool,
+    /// Resampling filter, named as in `resize_with_filter` (empty for
+    /// Lanczos3).
+    pub filter: String,
+    /// Resamples with colors premultiplied by alpha, so the color of fully
+    /// transparent pixels (often black) does not bleed into the edges of
+    /// logos and stickers as dark fringes. Ignored for opaque images.
+    pub premultiply_alpha: bool,
+    /// Resamples linear light instead of sRGB-encoded values, so fine
+    /// high-contrast detail (text, foliage, stars) keeps its brightness
+    /// instead of darkening. Float images are already linear.
+    pub linear_light: bool,
+}
+
+/// Decoder tolerance settings, see `decode_with_options`.
+pub struct LumeDecodeOptions {
+    pub allow_truncated: bool,
+    pub cmyk_mode: String,
+}
+
+// ---------------------------------------------------------------------------
+// Decoding
+// ---------------------------------------------------------------------------
+
+/// Decodes `image_bytes` with `options` and re-encodes the result as
+/// `format`. Every other function decodes strictly, so run files that need
+/// tolerance through this first:
+/// - `allow_truncated` decodes what is there of a partially downloaded PNG
+///   or JPEG instead of failing. Missing PNG rows come out transparent; JPEG
+///   decoders fill them with gray. Files that are corrupt rather than cut
+///   short still fail.
+/// - `cmyk_mode` picks how CMYK JPEG samples are read: "auto" (inverted when
+///   the file has an Adobe marker, as stored otherwise, which is also what
+///   every other function does), "adobe" (always inverted) or "plain"
+///   (always as stored). Use it when CMYK files from a particular tool come
+///   out as a color negative.
+///
+/// Animated and multi-page files always decode their first frame.
+#[flutter_rust_bridge::frb(sync)]
+pub fn decode_with_options(
+    image_bytes: Vec<u8>,
+    options: LumeDecodeOptions,
+    format: String,
+) -> Result<Vec<u8>> {
+    let cmyk = match options.cmyk_mode.to_lowercase().as_str() {
+        "" | "auto" => helpers::CmykMode::Auto,
+        "adobe" | "inverted" => helpers::CmykMode::Adobe,
+        "plain" => helpers::CmykMode::Plain,
+        other => return Err(anyhow::anyhow!("Unsupported CMYK mode: {}", other)),
+    };
+    let options = helpers::DecodeOptions {
+        allow_truncated: options.allow_truncated,
+        cmyk,
+    };
+    let img = crate::metrics::time_decode(|| helpers::load_with(&image_bytes, &options))?;
+    helpers::encode(&img, helpers::string_to_format(&format)?)
+}
+
+/// Keeps up to `limit` bytes of decoded pixels in memory, keyed by the
+/// content of the encoded input, so repeated calls on the same image (e.g.
+/// while a slider is scrubbed) skip decoding. Least recently used images are
+/// evicted first. 0, the default, turns the cache off and empties it.
+#[flutter_rust_bridge::frb(sync)]
+pub fn set_cache_limit_bytes(limit: u64) {
+    helpers::set_cache_limit(limit.min(usize::MAX as u64) as usize);
+}
+
+/// Drops every cached decoded image, keeping the limit.
+#[flutter_rust_bridge::frb(sync)]
+pub fn clear_cache() {
+    helpers::clear_cache();
+}
+
 // ---------------------------------------------------------------------------
 // Info
 // ---------------------------------------------------------------------------
@@ -22,6 +102,17 @@ pub struct LumeImageInfo {
 #[flutter_rust_bridge::frb(sync)]
 pub fn get_image_info(image_bytes: Vec<u8>) -> Result<LumeImageInfo> {
     let reader = ImageReader::new(Cursor::new(&image_bytes)).with_guessed_format()?;
+    #[cfg(feature = "raw")]
+    if crate::raw::candidate(reader.format()) {
+        if let Some((width, height)) = crate::raw::dimensions(&image_bytes) {
+            return Ok(LumeImageInfo {
+                width,
+                height,
+                format: "raw".to_string(),
+                size_bytes: image_bytes.len() as u32,
+            });
+        }
+    }
     let format = reader
         .format()
         .map(helpers::format_to_string)
@@ -50,6 +141,18 @@ pub fn resize(
     let img = helpers::load(&image_bytes)?;
     let fmt = helpers::detect_format(&image_bytes)?;
 
+    #[cfg(feature = "gpu")]
+    if let DynamicImage::ImageRgba8(rgba) = &img {
+        let (w, h) = if keep_aspect_ratio {
+            helpers::fit_size(rgba.width(), rgba.height(), width, height)
+        } else {
+            (width, height)
+        };
+        if let Some(out) = crate::gpu::resize(rgba, w, h) {
+            return helpers::encode(&DynamicImage::ImageRgba8(out), fmt);
+        }
+    }
+
     let resized = if keep_aspect_ratio {
         img.resize(width, height, image::imageops::FilterType::Lanczos3)
     } else {
@@ -69,36 +172,240 @@ pub fn resize_with_filter(
     let img = helpers::load(&image_bytes)?;
     let fmt = helpers::detect_format(&image_bytes)?;
 
-    let filter_type = match filter.to_lowercase().as_str() {
+    helpers::encode(&img.resize_exact(width, height, filter_type(&filter)), fmt)
+}
+
+/// Resampling filter for `name`; unknown names give Lanczos3.
+fn filter_type(name: &str) -> image::imageops::FilterType {
+    match name.to_lowercase().as_str() {
         "nearest" => image::imageops::FilterType::Nearest,
         "triangle" | "bilinear" => image::imageops::FilterType::Triangle,
         "catmullrom" | "cubic" => image::imageops::FilterType::CatmullRom,
         "gaussian" => image::imageops::FilterType::Gaussian,
         "lanczos" | "lanczos3" => image::imageops::FilterType::Lanczos3,
         _ => image::imageops::FilterType::Lanczos3,
-    };
+    }
+}
 
-    helpers::encode(&img.resize_exact(width, height, filter_type), fmt)
+/// Runs `resample` on a float copy of `img` holding linear-light and/or
+/// alpha-premultiplied colors, and converts the result back to the layout of
+/// `img`. Without either option `resample` gets `img` itself.
+pub(crate) fn resample_float(
+    img: &DynamicImage,
+    linear_light: bool,
+    premultiply_alpha: bool,
+    resample: impl FnOnce(&DynamicImage) -> DynamicImage,
+) -> DynamicImage {
+    let linear_light = linear_light
+        && !matches!(
+            img,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+    let premultiply = premultiply_alpha && img.color().has_alpha();
+    if !linear_light && !premultiply {
+        return resample(img);
+    }
+    let mut float = img.to_rgba32f();
+    for p in float.pixels_mut() {
+        let a = p.0[3];
+        p.apply_without_alpha(|v| {
+            let v = if linear_light {
+                helpers::srgb_to_linear(v)
+            } else {
+                v
+            };
+            if premultiply {
+                v * a
+            } else {
+                v
+            }
+        });
+    }
+    let mut out = resample(&DynamicImage::ImageRgba32F(float)).into_rgba32f();
+    for p in out.pixels_mut() {
+        // Lanczos rings can push alpha slightly out of range.
+        let a = p.0[3].clamp(0.0, 1.0);
+        p.0[3] = a;
+        p.apply_without_alpha(|v| {
+            let v = if !premultiply {
+                v
+            } else if a > 0.0 {
+                (v / a).min(1.0)
+            } else {
+                0.0
+            };
+            if linear_light {
+                helpers::linear_to_srgb(v)
+            } else {
+                v
+            }
+        });
+    }
+    helpers::from_rgba32f(out, img.color())
 }
 
-// ---------------------------------------------------------------------------
-// Crop
-// ---------------------------------------------------------------------------
+pub(crate) fn resize_image(img: &DynamicImage, options: &LumeResizeOptions) -> DynamicImage {
+    let filter = filter_type(&options.filter);
+    let (width, height) = (options.width, options.height);
+    resample_float(
+        img,
+        options.linear_light,
+        options.premultiply_alpha,
+        |img| {
+            if options.keep_aspect_ratio {
+                img.resize(width, height, filter)
+            } else {
+                img.resize_exact(width, height, filter)
+            }
+        },
+    )
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn resize_with_options(image_bytes: Vec<u8>, options: LumeResizeOptions) -> Result<Vec<u8>> {
+    if options.width == 0 || options.height == 0 {
+        return Err(anyhow::anyhow!("Target size must be greater than zero"));
+    }
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    helpers::encode(&resize_image(&img, &options), fmt)
+}
 
+/// Resizes into a `width` x `height` box (Lanczos3). `mode` is:
+/// - "contain": the whole image fits inside the box, keeping its aspect
+///   ratio, so one side may come out shorter;
+/// - "cover": the image fills the box, keeping its aspect ratio, and the
+///   overflow is cropped evenly on both sides;
+/// - "pad": like "contain", centered on a `width` x `height` canvas of
+///   `bg_color`.
+///
+/// `bg_color` is only used by "pad". Alpha is dropped for JPEG.
 #[flutter_rust_bridge::frb(sync)]
-pub fn crop(
+pub fn fit(
     image_bytes: Vec<u8>,
-    x: u32,
-    y: u32,
     width: u32,
     height: u32,
+    mode: String,
+    bg_color: LumeColor,
 ) -> Result<Vec<u8>> {
+    if width == 0 || height == 0 {
+        return Err(anyhow::anyhow!("Target size must be greater than zero"));
+    }
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let filter = image::imageops::FilterType::Lanczos3;
+    let out = match mode.to_lowercase().as_str() {
+        "contain" => img.resize(width, height, filter),
+        "cover" => img.resize_to_fill(width, height, filter),
+        "pad" => {
+            let fitted = img.resize(width, height, filter);
+            let bg = image::Rgba([bg_color.r, bg_color.g, bg_color.b, bg_color.a]);
+            let mut canvas = image::RgbaImage::from_pixel(width, height, bg);
+            let x = (width - fitted.width()) / 2;
+            let y = (height - fitted.height()) / 2;
+            image::imageops::overlay(&mut canvas, &fitted.to_rgba8(), x as i64, y as i64);
+            let canvas = DynamicImage::ImageRgba8(canvas);
+            if fmt == ImageFormat::Jpeg {
+                DynamicImage::ImageRgb8(canvas.to_rgb8())
+            } else {
+                canvas
+            }
+        }
+        other => return Err(anyhow::anyhow!("Unsupported fit mode: {}", other)),
+    };
+    helpers::encode(&out, fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Crop
+// ---------------------------------------------------------------------------
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn crop(image_bytes: Vec<u8>, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u8>> {
     let mut img = helpers::load(&image_bytes)?;
     let fmt = helpers::detect_format(&image_bytes)?;
     let cropped = img.crop(x, y, width, height);
     helpers::encode(&cropped, fmt)
 }
 
+/// Crops every rect out of one decode, e.g. all faces found in a photo or the
+/// prints on a scanned sheet. Rects are clipped to the image; one that lies
+/// entirely outside it is an error. Crops keep the source format.
+#[flutter_rust_bridge::frb(sync)]
+pub fn crop_many(image_bytes: Vec<u8>, rects: Vec<LumeRect>) -> Result<Vec<Vec<u8>>> {
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    rects
+        .iter()
+        .map(|r| {
+            let (x, y, w, h) =
+                helpers::clip_rect(r.x, r.y, r.width, r.height, img.width(), img.height())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Rect at ({}, {}) is outside the image", r.x, r.y)
+                    })?;
+            helpers::encode(&img.crop_imm(x, y, w, h), fmt)
+        })
+        .collect()
+}
+
+/// Where a crop is anchored, as fractions of the cut-away width and height
+/// left of and above it.
+fn gravity_anchor(gravity: &str) -> Result<(f32, f32)> {
+    Ok(match gravity.to_lowercase().as_str() {
+        "center" => (0.5, 0.5),
+        "top" => (0.5, 0.0),
+        "bottom" => (0.5, 1.0),
+        "left" => (0.0, 0.5),
+        "right" => (1.0, 0.5),
+        "top_left" => (0.0, 0.0),
+        "top_right" => (1.0, 0.0),
+        "bottom_left" => (0.0, 1.0),
+        "bottom_right" => (1.0, 1.0),
+        other => return Err(anyhow::anyhow!("Unsupported gravity: {}", other)),
+    })
+}
+
+/// Largest crop of a `width` x `height` image with the `aspect` (width /
+/// height) ratio, placed at `anchor`, as (x, y, width, height).
+fn aspect_crop(width: u32, height: u32, aspect: f32, anchor: (f32, f32)) -> (u32, u32, u32, u32) {
+    let (cw, ch) = if width as f32 / height as f32 > aspect {
+        (
+            ((height as f32 * aspect).round() as u32).clamp(1, width),
+            height,
+        )
+    } else {
+        (
+            width,
+            ((width as f32 / aspect).round() as u32).clamp(1, height),
+        )
+    };
+    let x = ((width - cw) as f32 * anchor.0).round() as u32;
+    let y = ((height - ch) as f32 * anchor.1).round() as u32;
+    (x, y, cw, ch)
+}
+
+/// Crops the largest `ratio_w`:`ratio_h` region, e.g. 16:9 or 1:1, without
+/// resizing. `gravity` picks which part is kept: "center", "top", "bottom",
+/// "left", "right", "top_left", "top_right", "bottom_left" or
+/// "bottom_right".
+#[flutter_rust_bridge::frb(sync)]
+pub fn crop_to_aspect(
+    image_bytes: Vec<u8>,
+    ratio_w: f32,
+    ratio_h: f32,
+    gravity: String,
+) -> Result<Vec<u8>> {
+    let aspect = ratio_w / ratio_h;
+    if !(aspect.is_finite() && aspect > 0.0) {
+        return Err(anyhow::anyhow!("Aspect ratio must be positive"));
+    }
+    let anchor = gravity_anchor(&gravity)?;
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (x, y, w, h) = aspect_crop(img.width(), img.height(), aspect, anchor);
+    helpers::encode(&img.crop_imm(x, y, w, h), fmt)
+}
+
 // ---------------------------------------------------------------------------
 // Rotate & Flip
 // ---------------------------------------------------------------------------
@@ -186,6 +493,166 @@ pub fn huerotate(image_bytes: Vec<u8>, degrees: i32) -> Result<Vec<u8>> {
     helpers::encode(&img.huerotate(degrees), fmt)
 }
 
+/// Maps every non-alpha channel through `lut`, indexed by channel value.
+fn apply_lut<P>(img: &mut helpers::Image<P>, lut: &[P::Subpixel])
+where
+    P: Pixel,
+    P::Subpixel: Into<usize>,
+{
+    for p in img.pixels_mut() {
+        p.apply_without_alpha(|v| lut[v.into()]);
+    }
+}
+
+/// The transfer curve of a levels adjustment on 0-1 values, see `levels`.
+fn levels_curve(
+    black_point: f32,
+    white_point: f32,
+    gamma: f32,
+) -> Result<impl Fn(f32) -> f32 + Copy> {
+    if white_point <= black_point || gamma <= 0.0 {
+        return Err(anyhow::anyhow!(
+            "Invalid levels: need black < white and gamma > 0"
+        ));
+    }
+    Ok(move |t: f32| {
+        ((t - black_point) / (white_point - black_point))
+            .clamp(0.0, 1.0)
+            .powf(1.0 / gamma)
+    })
+}
+
+/// A levels adjustment as a lookup table for 8-bit samples.
+pub(crate) fn levels_lut8(black_point: f32, white_point: f32, gamma: f32) -> Result<Vec<u8>> {
+    let curve = levels_curve(black_point, white_point, gamma)?;
+    Ok((0..=255u32)
+        .map(|v| (curve(v as f32 / 255.0) * 255.0).round() as u8)
+        .collect())
+}
+
+/// Applies a levels adjustment in place, see `levels`.
+pub(crate) fn apply_levels(
+    img: &mut image::DynamicImage,
+    black_point: f32,
+    white_point: f32,
+    gamma: f32,
+) -> Result<()> {
+    let curve = levels_curve(black_point, white_point, gamma)?;
+    let lut8 = levels_lut8(black_point, white_point, gamma)?;
+    let lut16 = || -> Vec<u16> {
+        (0..=65535u32)
+            .map(|v| (curve(v as f32 / 65535.0) * 65535.0).round() as u16)
+            .collect()
+    };
+    match img {
+        image::DynamicImage::ImageLuma8(i) => apply_lut(i, &lut8),
+        image::DynamicImage::ImageLumaA8(i) => apply_lut(i, &lut8),
+        image::DynamicImage::ImageRgb8(i) => apply_lut(i, &lut8),
+        image::DynamicImage::ImageRgba8(i) => {
+            #[cfg(feature = "gpu")]
+            if let Some(out) = crate::gpu::apply_lut(i, &lut8) {
+                *i = out;
+                return Ok(());
+            }
+            apply_lut(i, &lut8)
+        }
+        image::DynamicImage::ImageLuma16(i) => apply_lut(i, &lut16()),
+        image::DynamicImage::ImageLumaA16(i) => apply_lut(i, &lut16()),
+        image::DynamicImage::ImageRgb16(i) => apply_lut(i, &lut16()),
+        image::DynamicImage::ImageRgba16(i) => apply_lut(i, &lut16()),
+        other => {
+            let mut float = other.to_rgba32f();
+            for p in float.pixels_mut() {
+                p.apply_without_alpha(curve);
+            }
+            *other = image::DynamicImage::ImageRgba32F(float);
+        }
+    }
+    Ok(())
+}
+
+/// Levels adjustment: input values at or below `black_point` become black, at
+/// or above `white_point` white, with a `gamma` curve in between (> 1
+/// brightens midtones). Points are normalized to 0-1 so the same call works
+/// at any bit depth; 16-bit images stay 16-bit.
+#[flutter_rust_bridge::frb(sync)]
+pub fn levels(
+    image_bytes: Vec<u8>,
+    black_point: f32,
+    white_point: f32,
+    gamma: f32,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    apply_levels(&mut img, black_point, white_point, gamma)?;
+    helpers::encode(&img, fmt)
+}
+
+// ---------------------------------------------------------------------------
+// HDR
+// ---------------------------------------------------------------------------
+
+/// Maps linear HDR radiance (EXR, Radiance .hdr) to displayable 8-bit sRGB.
+/// `operator` is "reinhard" (on luminance, keeps hues) or "aces" (filmic
+/// curve); `exposure` is in stops and applied first. Float sources come back
+/// as PNG, other inputs keep their format.
+#[flutter_rust_bridge::frb(sync)]
+pub fn tone_map(image_bytes: Vec<u8>, operator: String, exposure: f32) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?;
+    let fmt = match helpers::detect_format(&image_bytes)? {
+        ImageFormat::OpenExr | ImageFormat::Hdr => ImageFormat::Png,
+        other => other,
+    };
+    let aces = match operator.to_lowercase().as_str() {
+        "reinhard" => false,
+        "aces" => true,
+        other => return Err(anyhow::anyhow!("Unsupported tone map operator: {}", other)),
+    };
+    let gain = 2f32.powf(exposure);
+    let to_srgb = |v: f32| {
+        let v = v.clamp(0.0, 1.0);
+        let v = if v <= 0.003_130_8 {
+            12.92 * v
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        };
+        (v * 255.0).round() as u8
+    };
+    // Float formats already hold linear values; 8/16-bit ones are sRGB encoded.
+    let is_linear = matches!(
+        img,
+        image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_)
+    );
+    let to_linear = |v: f32| {
+        if is_linear {
+            v
+        } else if v <= 0.040_45 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let hdr = img.to_rgba32f();
+    let out = image::RgbaImage::from_fn(hdr.width(), hdr.height(), |x, y| {
+        let [r, g, b, a] = hdr.get_pixel(x, y).0;
+        let [r, g, b] = [r, g, b].map(|v| (to_linear(v) * gain).max(0.0));
+        let mapped = if aces {
+            [r, g, b].map(|v| (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14))
+        } else {
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            let scale = if luma > 0.0 { 1.0 / (1.0 + luma) } else { 1.0 };
+            [r * scale, g * scale, b * scale]
+        };
+        image::Rgba([
+            to_srgb(mapped[0]),
+            to_srgb(mapped[1]),
+            to_srgb(mapped[2]),
+            (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+        ])
+    });
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
 // ---------------------------------------------------------------------------
 // Format conversion
 // ---------------------------------------------------------------------------
@@ -197,6 +664,21 @@ pub fn convert_format(image_bytes: Vec<u8>, target_format: String) -> Result<Vec
     helpers::encode(&img, fmt)
 }
 
+/// Develops a camera RAW file (DNG, CR2, NEF, ARW, ...) with `exposure_ev`
+/// stops of exposure compensation and encodes it as `target_format`.
+#[cfg(feature = "raw")]
+#[flutter_rust_bridge::frb(sync)]
+pub fn develop_raw(
+    image_bytes: Vec<u8>,
+    exposure_ev: f32,
+    target_format: String,
+) -> Result<Vec<u8>> {
+    let img = crate::raw::try_decode(&image_bytes, exposure_ev)
+        .ok_or_else(|| anyhow::anyhow!("Not a supported RAW file"))?;
+    let fmt = helpers::string_to_format(&target_format)?;
+    helpers::encode(&img, fmt)
+}
+
 // ---------------------------------------------------------------------------
 // Thumbnail
 // ---------------------------------------------------------------------------
@@ -215,17 +697,162 @@ pub fn thumbnail_exact(image_bytes: Vec<u8>, width: u32, height: u32) -> Result<
     helpers::encode(&img.thumbnail_exact(width, height), fmt)
 }
 
+/// Byte-order aware reads from a TIFF structure; out-of-range reads give
+/// `None`.
+struct TiffReader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> TiffReader<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(TiffReader {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn slice(&self, offset: u32, len: u32) -> Option<&'a [u8]> {
+        let start = offset as usize;
+        self.data.get(start..start.checked_add(len as usize)?)
+    }
+}
+
+/// Collects the JPEG previews referenced from every IFD of a TIFF structure
+/// (the IFD chain and SubIFDs), as found in EXIF blocks and TIFF-based RAW
+/// files.
+fn tiff_previews<'a>(data: &'a [u8], previews: &mut Vec<&'a [u8]>) {
+    let Some(tiff) = TiffReader::new(data) else {
+        return;
+    };
+    let mut pending: Vec<u32> = tiff.u32(4).into_iter().collect();
+    let mut visited = Vec::new();
+    while let Some(ifd) = pending.pop() {
+        // Corrupt files can point IFDs at each other.
+        if ifd == 0 || visited.contains(&ifd) || visited.len() >= 64 {
+            continue;
+        }
+        visited.push(ifd);
+        let base = ifd as usize;
+        let Some(count) = tiff.u16(base) else {
+            continue;
+        };
+        let (mut jpeg_offset, mut jpeg_len) = (None, None);
+        let (mut strip_offset, mut strip_len) = (None, None);
+        let (mut compression, mut subfile_type) = (0, 0);
+        for i in 0..count as usize {
+            let entry = base + 2 + i * 12;
+            let (Some(tag), Some(kind), Some(n)) =
+                (tiff.u16(entry), tiff.u16(entry + 2), tiff.u32(entry + 4))
+            else {
+                break;
+            };
+            // SHORT values sit in the first two bytes of the value field.
+            let value = if kind == 3 {
+                tiff.u16(entry + 8).map(u32::from)
+            } else {
+                tiff.u32(entry + 8)
+            };
+            match tag {
+                0x00FE => subfile_type = value.unwrap_or(0),
+                0x0103 => compression = value.unwrap_or(0),
+                0x0111 if n == 1 => strip_offset = value,
+                0x0117 if n == 1 => strip_len = value,
+                0x0201 => jpeg_offset = value,
+                0x0202 => jpeg_len = value,
+                // SubIFDs: the offset itself, or where the offsets are.
+                0x014A if n == 1 => pending.extend(value),
+                0x014A => {
+                    let at = value.unwrap_or(0) as usize;
+                    pending.extend((0..n.min(16) as usize).filter_map(|k| tiff.u32(at + k * 4)));
+                }
+                _ => {}
+            }
+        }
+        if let (Some(offset), Some(len)) = (jpeg_offset, jpeg_len) {
+            previews.extend(tiff.slice(offset, len));
+        }
+        // Old-style JPEG strips (CR2) and reduced-resolution JPEG strips
+        // (DNG). Full-size compression 7 strips hold lossless raw data.
+        if compression == 6 || (compression == 7 && subfile_type & 1 == 1) {
+            if let (Some(offset), Some(len)) = (strip_offset, strip_len) {
+                previews.extend(tiff.slice(offset, len));
+            }
+        }
+        pending.extend(tiff.u32(base + 2 + count as usize * 12));
+    }
+}
+
+/// Returns the smallest JPEG preview embedded in the file without decoding
+/// the image: the EXIF thumbnail of a JPEG or TIFF, or one of the previews
+/// camera RAW files carry (DNG, CR2, NEF, ARW, ORF, RW2, RAF). Much faster
+/// than `thumbnail` for gallery grids; `None` when there is no usable preview.
+#[flutter_rust_bridge::frb(sync)]
+pub fn extract_embedded_thumbnail(image_bytes: Vec<u8>) -> Option<Vec<u8>> {
+    let mut previews = Vec::new();
+    if image_bytes.starts_with(&[0xFF, 0xD8]) {
+        if let Some(exif) = helpers::jpeg_header(&image_bytes).exif {
+            tiff_previews(exif, &mut previews);
+        }
+    } else if image_bytes.starts_with(b"FUJIFILMCCD-RAW") {
+        // RAF stores the offset and length of a full JPEG (with its own EXIF
+        // thumbnail) at byte 84, big-endian.
+        let read = |at: usize| {
+            Some(u32::from_be_bytes(
+                image_bytes.get(at..at + 4)?.try_into().ok()?,
+            ))
+        };
+        if let (Some(offset), Some(len)) = (read(84), read(88)) {
+            let start = offset as usize;
+            if let Some(jpeg) = image_bytes.get(start..start.saturating_add(len as usize)) {
+                previews.push(jpeg);
+                if let Some(exif) = helpers::jpeg_header(jpeg).exif {
+                    tiff_previews(exif, &mut previews);
+                }
+            }
+        }
+    } else {
+        tiff_previews(&image_bytes, &mut previews);
+    }
+    // Baseline, extended or progressive only; lossless JPEG is raw data.
+    previews
+        .into_iter()
+        .filter(|p| {
+            p.starts_with(&[0xFF, 0xD8]) && matches!(helpers::jpeg_header(p).sof, 0xC0..=0xC2)
+        })
+        .min_by_key(|p| p.len())
+        .map(|p| p.to_vec())
+}
+
 // ---------------------------------------------------------------------------
 // Overlay / Compose
 // ---------------------------------------------------------------------------
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn overlay(
-    base_bytes: Vec<u8>,
-    overlay_bytes: Vec<u8>,
-    x: i64,
-    y: i64,
-) -> Result<Vec<u8>> {
+pub fn overlay(base_bytes: Vec<u8>, overlay_bytes: Vec<u8>, x: i64, y: i64) -> Result<Vec<u8>> {
     let mut base = helpers::load(&base_bytes)?;
     let fmt = helpers::detect_format(&base_bytes)?;
     let top = helpers::load(&overlay_bytes)?;
@@ -233,6 +860,32 @@ pub fn overlay(
     helpers::encode(&base, fmt)
 }
 
+/// Mixes `overlay_bytes` into `base_bytes` through `mask`: where the mask is
+/// set the overlay shows, elsewhere the base. Both images and the mask must
+/// have the same size.
+#[flutter_rust_bridge::frb(sync)]
+pub fn overlay_with_mask(
+    base_bytes: Vec<u8>,
+    overlay_bytes: Vec<u8>,
+    mask: LumeMask,
+) -> Result<Vec<u8>> {
+    let base = helpers::load(&base_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&base_bytes)?;
+    let top = helpers::load(&overlay_bytes)?.to_rgba8();
+    if top.dimensions() != base.dimensions() {
+        return Err(anyhow::anyhow!(
+            "Overlay size {}x{} does not match image size {}x{}",
+            top.width(),
+            top.height(),
+            base.width(),
+            base.height()
+        ));
+    }
+    let mask = mask::to_gray_sized(&mask, base.width(), base.height())?;
+    let out = helpers::blend_with_mask(&base, &top, &mask);
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
 // ---------------------------------------------------------------------------
 // Tile
 // ---------------------------------------------------------------------------
@@ -252,7 +905,7 @@ pub fn tile(image_bytes: Vec<u8>, cols: u32, rows: u32) -> Result<Vec<u8>> {
 }
 
 // ---------------------------------------------------------------------------
-// Create blank image
+// Create blank image / gradient
 // ---------------------------------------------------------------------------
 
 #[flutter_rust_bridge::frb(sync)]
@@ -262,6 +915,341 @@ pub fn create_blank(width: u32, height: u32, r: u8, g: u8, b: u8, a: u8) -> Resu
     helpers::encode(&dyn_img, ImageFormat::Png)
 }
 
+/// Wraps tightly packed RGBA bytes (row by row, no padding) as an image.
+/// Anything but exactly `width * height * 4` bytes is rejected: a longer
+/// buffer is usually padded rows, which would come out sheared.
+pub(crate) fn image_from_raw(
+    width: u32,
+    height: u32,
+    rgba_bytes: Vec<u8>,
+) -> Result<image::RgbaImage> {
+    let expected = width as u64 * height as u64 * 4;
+    if rgba_bytes.len() as u64 != expected {
+        return Err(anyhow::anyhow!(
+            "Expected {} bytes of RGBA data for {}x{}, got {}",
+            expected,
+            width,
+            height,
+            rgba_bytes.len()
+        ));
+    }
+    image::RgbaImage::from_raw(width, height, rgba_bytes)
+        .ok_or_else(|| anyhow::anyhow!("Image of {}x{} is too large", width, height))
+}
+
+/// Encodes raw RGBA pixels (e.g. a camera frame) as `format_out`, so they can
+/// enter the rest of the API without being encoded in Dart first. Alpha is
+/// dropped for JPEG, which cannot store it.
+#[flutter_rust_bridge::frb(sync)]
+pub fn from_raw(
+    width: u32,
+    height: u32,
+    rgba_bytes: Vec<u8>,
+    format_out: String,
+) -> Result<Vec<u8>> {
+    let fmt = helpers::string_to_format(&format_out)?;
+    let img = DynamicImage::ImageRgba8(image_from_raw(width, height, rgba_bytes)?);
+    if fmt == ImageFormat::Jpeg {
+        return helpers::encode(&DynamicImage::ImageRgb8(img.to_rgb8()), fmt);
+    }
+    helpers::encode(&img, fmt)
+}
+
+/// Creates a PNG filled with a gradient through `stops`. `kind` is:
+/// - "linear": along the direction `angle` (degrees clockwise from left to
+///   right, so 90 runs top to bottom), spanning the whole image;
+/// - "radial": from the center (offset 0) to the corners (offset 1);
+/// - "conic": around the center, starting at `angle`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn create_gradient(
+    width: u32,
+    height: u32,
+    stops: Vec<LumeGradientStop>,
+    kind: String,
+    angle: f32,
+) -> Result<Vec<u8>> {
+    let gradient = Gradient::new(&stops)?;
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let offset: Box<dyn Fn(f32, f32) -> f32> = match kind.to_lowercase().as_str() {
+        "linear" => {
+            // Length of the image's projection on the gradient line, so the
+            // first and last stops land on opposite corners (as in CSS).
+            let length = (width as f32 * cos).abs() + (height as f32 * sin).abs();
+            let length = length.max(f32::EPSILON);
+            Box::new(move |dx, dy| (dx * cos + dy * sin) / length + 0.5)
+        }
+        "radial" => {
+            let radius = cx.hypot(cy).max(f32::EPSILON);
+            Box::new(move |dx, dy| dx.hypot(dy) / radius)
+        }
+        "conic" => {
+            let start = angle.to_radians();
+            Box::new(move |dx, dy| {
+                (dy.atan2(dx) - start).rem_euclid(std::f32::consts::TAU) / std::f32::consts::TAU
+            })
+        }
+        other => return Err(anyhow::anyhow!("Unsupported gradient kind: {}", other)),
+    };
+    let img = image::RgbaImage::from_fn(width, height, |x, y| {
+        gradient.at(offset(x as f32 + 0.5 - cx, y as f32 + 0.5 - cy))
+    });
+    helpers::encode(&image::DynamicImage::ImageRgba8(img), ImageFormat::Png)
+}
+
+// ---------------------------------------------------------------------------
+// Noise & patterns
+// ---------------------------------------------------------------------------
+
+/// Lattice permutation shared by Perlin and simplex noise, shuffled by `seed`.
+fn permutation(seed: u64) -> [u8; 512] {
+    let mut rng = helpers::SplitMix64::new(seed);
+    let mut table = [0u8; 256];
+    for (i, v) in table.iter_mut().enumerate() {
+        *v = i as u8;
+    }
+    for i in (1..256).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        table.swap(i, j);
+    }
+    let mut perm = [0u8; 512];
+    for (i, v) in perm.iter_mut().enumerate() {
+        *v = table[i & 255];
+    }
+    perm
+}
+
+/// Dot product of `(x, y)` with one of 8 lattice gradients picked by `hash`.
+fn grad(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => x - y,
+        2 => -x + y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// Classic Perlin noise, roughly in [-1, 1].
+fn perlin(perm: &[u8; 512], x: f32, y: f32) -> f32 {
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let (xf, yf) = (x.floor(), y.floor());
+    let (xi, yi) = ((xf as i32 & 255) as usize, (yf as i32 & 255) as usize);
+    let (x, y) = (x - xf, y - yf);
+    let hash = |i: usize, j: usize| perm[perm[xi + i] as usize + yi + j];
+    let (u, v) = (fade(x), fade(y));
+    let top = lerp(grad(hash(0, 0), x, y), grad(hash(1, 0), x - 1.0, y), u);
+    let bottom = lerp(
+        grad(hash(0, 1), x, y - 1.0),
+        grad(hash(1, 1), x - 1.0, y - 1.0),
+        u,
+    );
+    lerp(top, bottom, v)
+}
+
+/// 2D simplex noise, roughly in [-1, 1]. Fewer directional artifacts than
+/// Perlin noise.
+fn simplex(perm: &[u8; 512], x: f32, y: f32) -> f32 {
+    let f2 = 0.5 * (3f32.sqrt() - 1.0);
+    let g2 = (3.0 - 3f32.sqrt()) / 6.0;
+    let s = (x + y) * f2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * g2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    // Second corner of the triangle containing the point.
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let corners = [
+        (x0, y0, 0, 0),
+        (x0 - i1 as f32 + g2, y0 - j1 as f32 + g2, i1, j1),
+        (x0 - 1.0 + 2.0 * g2, y0 - 1.0 + 2.0 * g2, 1, 1),
+    ];
+    let (ii, jj) = ((i as i32 & 255) as usize, (j as i32 & 255) as usize);
+    let sum: f32 = corners
+        .iter()
+        .map(|&(cx, cy, di, dj)| {
+            let falloff = 0.5 - cx * cx - cy * cy;
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+            let hash = perm[ii + di + perm[jj + dj] as usize];
+            falloff.powi(4) * grad(hash, cx, cy)
+        })
+        .sum();
+    70.0 * sum
+}
+
+/// Creates a grayscale PNG of noise, e.g. for placeholder textures or masks.
+/// `kind` is "perlin", "simplex" or "white". `scale` is the feature size in
+/// pixels: the lattice spacing of Perlin and simplex noise, the size of each
+/// random block for white noise. The same `seed` always gives the same image.
+#[flutter_rust_bridge::frb(sync)]
+pub fn create_noise(
+    width: u32,
+    height: u32,
+    kind: String,
+    scale: f32,
+    seed: u64,
+) -> Result<Vec<u8>> {
+    if scale <= 0.0 {
+        return Err(anyhow::anyhow!("Noise scale must be positive"));
+    }
+    let img = match kind.to_lowercase().as_str() {
+        "white" => {
+            let block = (scale.round() as u32).max(1);
+            let cols = width.div_ceil(block);
+            let mut rng = helpers::SplitMix64::new(seed);
+            let values: Vec<u8> = (0..cols * height.div_ceil(block))
+                .map(|_| (rng.next_u64() >> 56) as u8)
+                .collect();
+            image::GrayImage::from_fn(width, height, |x, y| {
+                image::Luma([values[((y / block) * cols + x / block) as usize]])
+            })
+        }
+        kind @ ("perlin" | "simplex") => {
+            let noise = if kind == "perlin" { perlin } else { simplex };
+            let perm = permutation(seed);
+            image::GrayImage::from_fn(width, height, |x, y| {
+                let v = noise(&perm, (x as f32 + 0.5) / scale, (y as f32 + 0.5) / scale);
+                image::Luma([((v * 0.5 + 0.5) * 255.0).round().clamp(0.0, 255.0) as u8])
+            })
+        }
+        other => return Err(anyhow::anyhow!("Unsupported noise kind: {}", other)),
+    };
+    helpers::encode(&image::DynamicImage::ImageLuma8(img), ImageFormat::Png)
+}
+
+/// Creates a PNG checkerboard of `cell`-pixel squares, e.g. as a backdrop
+/// behind transparent images. Squares cycle through `colors` (usually two)
+/// along rows and columns, starting with the first in the top-left corner.
+#[flutter_rust_bridge::frb(sync)]
+pub fn create_checkerboard(
+    width: u32,
+    height: u32,
+    cell: u32,
+    colors: Vec<LumeColor>,
+) -> Result<Vec<u8>> {
+    if cell == 0 {
+        return Err(anyhow::anyhow!("Checkerboard cell size must be positive"));
+    }
+    if colors.is_empty() {
+        return Err(anyhow::anyhow!("A checkerboard needs at least one color"));
+    }
+    let colors: Vec<image::Rgba<u8>> = colors
+        .iter()
+        .map(|c| image::Rgba([c.r, c.g, c.b, c.a]))
+        .collect();
+    let img = image::RgbaImage::from_fn(width, height, |x, y| {
+        colors[((x / cell + y / cell) as usize) % colors.len()]
+    });
+    helpers::encode(&image::DynamicImage::ImageRgba8(img), ImageFormat::Png)
+}
+
+// ---------------------------------------------------------------------------
+// Test patterns
+// ---------------------------------------------------------------------------
+
+/// Number of black/white spoke pairs of the Siemens star.
+const SIEMENS_SPOKES: u32 = 36;
+
+/// SMPTE EG 1 color bars: seven 75% bars, the reversed blue row under them,
+/// then -I, white, +Q and the PLUGE (sub-black, black, above-black) strip.
+fn smpte_bars(width: u32, height: u32) -> image::RgbaImage {
+    let gray = |v: u8| [v, v, v];
+    const TOP: [[u8; 3]; 7] = [
+        [192, 192, 192],
+        [192, 192, 0],
+        [0, 192, 192],
+        [0, 192, 0],
+        [192, 0, 192],
+        [192, 0, 0],
+        [0, 0, 192],
+    ];
+    let middle = [
+        [0, 0, 192],
+        gray(19),
+        [192, 0, 192],
+        gray(19),
+        [0, 192, 192],
+        gray(19),
+        [192, 192, 192],
+    ];
+    let (top_end, middle_end) = (height * 2 / 3, height * 3 / 4);
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        // Position across the image in units of one top bar.
+        let bars = x as f32 * 7.0 / width as f32;
+        let bar = (bars as usize).min(6);
+        let rgb = if y < top_end {
+            TOP[bar]
+        } else if y < middle_end {
+            middle[bar]
+        } else if bars < 5.0 {
+            [[0, 33, 76], gray(255), [50, 0, 106], gray(19)][(bars / 1.25) as usize]
+        } else if bars < 6.0 {
+            gray([9, 19, 29][((bars - 5.0) * 3.0) as usize])
+        } else {
+            gray(19)
+        };
+        image::Rgba([rgb[0], rgb[1], rgb[2], 255])
+    })
+}
+
+/// Smooth black-to-white ramp on the top half, 11 steps of 10% below.
+fn gray_ramp(width: u32, height: u32) -> image::RgbaImage {
+    let span = width.saturating_sub(1).max(1) as f32;
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let t = x as f32 / span;
+        let t = if y < height / 2 {
+            t
+        } else {
+            (t * 11.0).floor().min(10.0) / 10.0
+        };
+        let v = (t * 255.0).round() as u8;
+        image::Rgba([v, v, v, 255])
+    })
+}
+
+/// Siemens star: black and white wedges meeting at the center, for judging
+/// resolution and focus. Pixels are 4x4 supersampled so the wedges stay
+/// sharp but alias-free until they get too thin to resolve.
+fn siemens_star(width: u32, height: u32) -> image::RgbaImage {
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let radius = cx.min(cy) * 0.95;
+    let wedge = std::f32::consts::PI / SIEMENS_SPOKES as f32;
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let mut black = 0;
+        for sy in 0..4 {
+            for sx in 0..4 {
+                let dx = x as f32 + (sx as f32 + 0.5) / 4.0 - cx;
+                let dy = y as f32 + (sy as f32 + 0.5) / 4.0 - cy;
+                let inside = dx.hypot(dy) <= radius;
+                let sector = (dy.atan2(dx).rem_euclid(std::f32::consts::TAU) / wedge) as u32;
+                if inside && sector.is_multiple_of(2) {
+                    black += 1;
+                }
+            }
+        }
+        let v = 255 - (black * 255 / 16) as u8;
+        image::Rgba([v, v, v, 255])
+    })
+}
+
+/// Creates a PNG test pattern for display calibration and QA. `kind` is
+/// "smpte_bars", "gray_ramp" or "siemens_star".
+#[flutter_rust_bridge::frb(sync)]
+pub fn create_test_pattern(width: u32, height: u32, kind: String) -> Result<Vec<u8>> {
+    let img = match kind.to_lowercase().as_str() {
+        "smpte_bars" => smpte_bars(width, height),
+        "gray_ramp" => gray_ramp(width, height),
+        "siemens_star" => siemens_star(width, height),
+        other => return Err(anyhow::anyhow!("Unsupported test pattern: {}", other)),
+    };
+    helpers::encode(&image::DynamicImage::ImageRgba8(img), ImageFormat::Png)
+}
+
 // ---------------------------------------------------------------------------
 // Extract channel
 // ---------------------------------------------------------------------------
@@ -301,3 +1289,92 @@ pub fn get_pixel(image_bytes: Vec<u8>, x: u32, y: u32) -> Result<LumeColor> {
         a: pixel.0[3],
     })
 }
+
+fn check_region(img: &DynamicImage, rect: &LumeRect) -> Result<()> {
+    let fits = rect.x >= 0
+        && rect.y >= 0
+        && rect.x as u64 + rect.width as u64 <= img.width() as u64
+        && rect.y as u64 + rect.height as u64 <= img.height() as u64;
+    if !fits {
+        return Err(anyhow::anyhow!("Region is outside the image"));
+    }
+    Ok(())
+}
+
+/// RGBA bytes of `rect`, row by row without padding.
+pub(crate) fn read_pixels(img: &DynamicImage, rect: &LumeRect) -> Result<Vec<u8>> {
+    check_region(img, rect)?;
+    let region = img.view(rect.x as u32, rect.y as u32, rect.width, rect.height);
+    Ok(region.pixels().flat_map(|(_, _, p)| p.0).collect())
+}
+
+pub(crate) fn sample_points(img: &DynamicImage, points: &[LumePoint]) -> Result<Vec<LumeColor>> {
+    points
+        .iter()
+        .map(|p| {
+            if p.x < 0 || p.y < 0 || !img.in_bounds(p.x as u32, p.y as u32) {
+                return Err(anyhow::anyhow!(
+                    "Point ({}, {}) is outside the image",
+                    p.x,
+                    p.y
+                ));
+            }
+            let [r, g, b, a] = img.get_pixel(p.x as u32, p.y as u32).0;
+            Ok(LumeColor { r, g, b, a })
+        })
+        .collect()
+}
+
+/// Replaces the pixels of `rect` with `rgba_data`, laid out as `read_pixels`
+/// returns them.
+pub(crate) fn write_pixels(
+    img: &mut DynamicImage,
+    rect: &LumeRect,
+    rgba_data: &[u8],
+) -> Result<()> {
+    check_region(img, rect)?;
+    let expected = rect.width as u64 * rect.height as u64 * 4;
+    if rgba_data.len() as u64 != expected {
+        return Err(anyhow::anyhow!(
+            "Expected {} bytes of RGBA data, got {}",
+            expected,
+            rgba_data.len()
+        ));
+    }
+    if rect.width == 0 {
+        return Ok(());
+    }
+    let (x0, y0) = (rect.x as u32, rect.y as u32);
+    for (i, px) in rgba_data.chunks_exact(4).enumerate() {
+        let (dx, dy) = (i as u32 % rect.width, i as u32 / rect.width);
+        img.put_pixel(x0 + dx, y0 + dy, image::Rgba([px[0], px[1], px[2], px[3]]));
+    }
+    Ok(())
+}
+
+/// Reads a whole rectangle of pixels in one call, e.g. for a loupe or to
+/// average under an eyedropper. Returns RGBA bytes row by row, without
+/// padding (`rect.width * rect.height * 4` bytes).
+#[flutter_rust_bridge::frb(sync)]
+pub fn get_pixels(image_bytes: Vec<u8>, rect: LumeRect) -> Result<Vec<u8>> {
+    read_pixels(&helpers::load(&image_bytes)?, &rect)
+}
+
+/// Colors of the pixels at `points`, in the same order, with one decode for
+/// all of them. Prefer `handle_sample_pixels` for repeated probes on the same
+/// image, e.g. while a color picker moves.
+#[flutter_rust_bridge::frb(sync)]
+pub fn sample_pixels(image_bytes: Vec<u8>, points: Vec<LumePoint>) -> Result<Vec<LumeColor>> {
+    sample_points(&helpers::load(&image_bytes)?, &points)
+}
+
+/// Writes `rgba_data` (RGBA bytes row by row, as `get_pixels` returns them)
+/// into `rect`, e.g. to apply a patch computed in Dart. Pixels are converted
+/// to the image's color type, so alpha is dropped on images without it.
+#[flutter_rust_bridge::frb(sync)]
+pub fn set_pixels(image_bytes: Vec<u8>, rect: LumeRect, rgba_data: Vec<u8>) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    write_pixels(&mut img, &rect, &rgba_data)?;
+    helpers::encode(&img, fmt)
+}
@@ -1,11 +1,14 @@
 use anyhow::Result;
-use image::Rgba;
+use image::{Pixel, Rgba};
 use imageproc::contours::BorderType;
 use imageproc::contrast::ThresholdType;
 use imageproc::distance_transform::Norm as DistNorm;
+use imageproc::morphology::Mask;
 use imageproc::point::Point;
 use imageproc::rect::Rect;
 
+use crate::api::image_ops::{self, LumeColor};
+use crate::api::mask::{self, LumeMask};
 use crate::helpers;
 
 // ===========================================================================
@@ -17,30 +20,252 @@ pub struct LumePoint {
     pub y: i32,
 }
 
+pub struct LumeRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
 pub struct LumeContour {
     pub points: Vec<LumePoint>,
     pub border_type: String,
     pub parent: i32,
 }
 
+/// A concavity of a contour between two consecutive convex hull vertices:
+/// `farthest` is the contour point deepest inside the hull, `depth` its
+/// distance to the hull edge `start`-`end`.
+pub struct LumeConvexityDefect {
+    pub start: LumePoint,
+    pub end: LumePoint,
+    pub farthest: LumePoint,
+    pub depth: f32,
+}
+
+/// Shape measures of a contour, with the contour taken as a polygon through
+/// its pixel centers (as OpenCV does). The bounding box includes the edge
+/// pixels, so `extent` of a filled square is slightly below 1.
+pub struct LumeShapeDescriptors {
+    pub area: f64,
+    pub perimeter: f64,
+    pub hull_area: f64,
+    /// `area / hull_area`.
+    pub solidity: f64,
+    /// `area` over the bounding box area.
+    pub extent: f64,
+    /// Bounding box width over height.
+    pub aspect_ratio: f64,
+    /// Of the ellipse with the same second moments: 0 for a circle,
+    /// approaching 1 for a line.
+    pub eccentricity: f64,
+    pub centroid_x: f64,
+    pub centroid_y: f64,
+    pub bounds: LumeRect,
+}
+
+/// Settings for `blur_with_options`; see `LumeResizeOptions` for the two
+/// flags.
+pub struct LumeBlurOptions {
+    pub sigma: f32,
+    pub linear_light: bool,
+    pub premultiply_alpha: bool,
+}
+
+/// Per-pixel x/y derivatives with their orientation (radians, atan2(gy, gx),
+/// y pointing down). `gx` / `gy` are the raw operator responses.
+pub struct LumeGradientField {
+    pub width: u32,
+    pub height: u32,
+    pub gx: Vec<f32>,
+    pub gy: Vec<f32>,
+    pub orientation: Vec<f32>,
+}
+
+/// Sobel gradient magnitude and orientation (radians) in row-major order.
+pub struct LumeGradients {
+    pub width: u32,
+    pub height: u32,
+    pub magnitude: Vec<f32>,
+    pub orientation: Vec<f32>,
+}
+
+/// Distance of every pixel to the nearest foreground pixel, in row-major
+/// order.
+pub struct LumeDistanceMap {
+    pub width: u32,
+    pub height: u32,
+    pub distances: Vec<f32>,
+    pub max_distance: f32,
+}
+
+/// Number of ink (dark) pixels in each row and each column.
+pub struct LumeProjectionProfiles {
+    pub rows: Vec<u32>,
+    pub cols: Vec<u32>,
+}
+
 // ===========================================================================
 // Filters (imageproc::filter)
 // ===========================================================================
+//
+// The filter, contrast and morphology functions have `*_region` variants
+// that only process `region` (the whole image when `None`) and leave the rest
+// of the image as it was, in its original colors. Filters still read the
+// pixels around the region, so its edges blend into the surroundings; global
+// operations (histogram, Otsu) compute their statistics over the region only.
+
+/// An operation that `filter_region` and `filter_with_border` can run.
+trait Filter: FnOnce(&image::DynamicImage) -> Result<image::DynamicImage> {}
+
+impl<F: FnOnce(&image::DynamicImage) -> Result<image::DynamicImage>> Filter for F {}
+
+/// Decodes `image_bytes`, runs `filter` on the whole image or on `region`
+/// plus `margin` pixels of context, and encodes the result in the source
+/// format. A region outside the image leaves it unchanged.
+fn filter_region(
+    image_bytes: &[u8],
+    region: Option<LumeRect>,
+    margin: u32,
+    filter: impl FnOnce(&image::DynamicImage) -> Result<image::DynamicImage>,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(image_bytes)?;
+    let fmt = helpers::detect_format(image_bytes)?;
+    let Some(r) = region else {
+        return helpers::encode(&filter(&img)?, fmt);
+    };
+    let (w, h) = (img.width(), img.height());
+    let Some((x, y, rw, rh)) = helpers::clip_rect(r.x, r.y, r.width, r.height, w, h) else {
+        return helpers::encode(&img, fmt);
+    };
+    let (px, py) = (x.saturating_sub(margin), y.saturating_sub(margin));
+    let pw = (x + rw).saturating_add(margin).min(w) - px;
+    let ph = (y + rh).saturating_add(margin).min(h) - py;
+    let mut inner = filter(&img.crop_imm(px, py, pw, ph))?.crop_imm(x - px, y - py, rw, rh);
+    let mut out = img;
+    // Filters that work on luma return gray; the rest of the image keeps
+    // its colors.
+    if inner.color() != out.color() {
+        out = image::DynamicImage::ImageRgba8(out.to_rgba8());
+        inner = image::DynamicImage::ImageRgba8(inner.to_rgba8());
+    }
+    image::imageops::replace(&mut out, &inner, x as i64, y as i64);
+    helpers::encode(&out, fmt)
+}
+
+/// `gaussian_blur_f32`, run on the GPU when the `gpu` feature finds one and
+/// the image is large enough to benefit.
+fn gaussian_rgba(img: &image::RgbaImage, sigma: f32) -> image::RgbaImage {
+    #[cfg(feature = "gpu")]
+    if let Some(out) = crate::gpu::gaussian_blur(img, sigma) {
+        return out;
+    }
+    imageproc::filter::gaussian_blur_f32(img, sigma)
+}
 
 #[flutter_rust_bridge::frb(sync)]
 pub fn gaussian_blur(image_bytes: Vec<u8>, sigma: f32) -> Result<Vec<u8>> {
     let img = helpers::load(&image_bytes)?.to_rgba8();
     let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::filter::gaussian_blur_f32(&img, sigma);
+    let out = gaussian_rgba(&img, sigma);
     helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
 }
 
+/// Gaussian blur with the same options as `resize_with_options`: blurring
+/// linear light keeps small bright details (lights, highlights) from being
+/// dimmed and dark halos from forming around them, and premultiplied alpha
+/// keeps transparent pixels from darkening the edges of cut-outs.
 #[flutter_rust_bridge::frb(sync)]
-pub fn median_filter(image_bytes: Vec<u8>, x_radius: u32, y_radius: u32) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
+pub fn blur_with_options(image_bytes: Vec<u8>, options: LumeBlurOptions) -> Result<Vec<u8>> {
+    if options.sigma <= 0.0 {
+        return Err(anyhow::anyhow!("sigma must be greater than zero"));
+    }
+    let img = helpers::load(&image_bytes)?;
     let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::filter::median_filter(&img, x_radius, y_radius);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+    let sigma = options.sigma;
+    let out = image_ops::resample_float(
+        &img,
+        options.linear_light,
+        options.premultiply_alpha,
+        |img| match img {
+            image::DynamicImage::ImageRgba32F(float) => image::DynamicImage::ImageRgba32F(
+                imageproc::filter::gaussian_blur_f32(float, sigma),
+            ),
+            other => {
+                let out = image::DynamicImage::ImageRgba8(gaussian_rgba(&other.to_rgba8(), sigma));
+                if other.color().has_alpha() {
+                    out
+                } else {
+                    image::DynamicImage::ImageRgb8(out.to_rgb8())
+                }
+            }
+        },
+    );
+    helpers::encode(&out, fmt)
+}
+
+/// Gaussian blur limited to `region`; the whole image is blurred when it is
+/// `None`. Pixels just outside the region still feed the blur, so the region
+/// blends into its surroundings instead of showing a hard seam.
+#[flutter_rust_bridge::frb(sync)]
+pub fn blur_region(image_bytes: Vec<u8>, sigma: f32, region: Option<LumeRect>) -> Result<Vec<u8>> {
+    check_sigma(sigma)?;
+    filter_region(&image_bytes, region, gaussian_margin(sigma), |src| {
+        let out = gaussian_rgba(&src.to_rgba8(), sigma);
+        Ok(image::DynamicImage::ImageRgba8(out))
+    })
+}
+
+/// Gaussian blur blended in through a grayscale mask (white = fully blurred,
+/// black = untouched); soft masks give selective-focus transitions.
+#[flutter_rust_bridge::frb(sync)]
+pub fn blur_masked(image_bytes: Vec<u8>, sigma: f32, mask_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let mask = helpers::load_mask(&mask_bytes, img.width(), img.height())?;
+    blur_through_mask(&img, sigma, &mask, fmt)
+}
+
+/// `blur_masked` with a `LumeMask`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn blur_with_mask(image_bytes: Vec<u8>, sigma: f32, mask: LumeMask) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let mask = mask::to_gray_sized(&mask, img.width(), img.height())?;
+    blur_through_mask(&img, sigma, &mask, fmt)
+}
+
+fn blur_through_mask(
+    img: &image::RgbaImage,
+    sigma: f32,
+    mask: &image::GrayImage,
+    fmt: image::ImageFormat,
+) -> Result<Vec<u8>> {
+    if sigma <= 0.0 {
+        return Err(anyhow::anyhow!("sigma must be greater than zero"));
+    }
+    let blurred = gaussian_rgba(img, sigma);
+    let out = helpers::blend_with_mask(img, &blurred, mask);
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn median_filter(image_bytes: Vec<u8>, x_radius: u32, y_radius: u32) -> Result<Vec<u8>> {
+    median_filter_region(image_bytes, x_radius, y_radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn median_filter_region(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, x_radius.max(y_radius), |src| {
+        let img = src.to_luma8();
+        let out = imageproc::filter::median_filter(&img, x_radius, y_radius);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -50,48 +275,581 @@ pub fn bilateral_filter(
     sigma_color: f32,
     sigma_spatial: f32,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out =
-        imageproc::filter::bilateral_filter(&img, window_size, sigma_color, sigma_spatial);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+    bilateral_filter_region(image_bytes, window_size, sigma_color, sigma_spatial, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn bilateral_filter_region(
+    image_bytes: Vec<u8>,
+    window_size: u32,
+    sigma_color: f32,
+    sigma_spatial: f32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, window_size / 2 + 1, |src| {
+        let img = src.to_luma8();
+        let out =
+            imageproc::filter::bilateral_filter(&img, window_size, sigma_color, sigma_spatial);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
 pub fn box_filter(image_bytes: Vec<u8>, x_radius: u32, y_radius: u32) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::filter::box_filter(&img, x_radius, y_radius);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+    box_filter_region(image_bytes, x_radius, y_radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn box_filter_region(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, x_radius.max(y_radius), |src| {
+        let img = src.to_luma8();
+        let out = imageproc::filter::box_filter(&img, x_radius, y_radius);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
 pub fn sharpen3x3(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::filter::sharpen3x3(&img);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+    sharpen3x3_region(image_bytes, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn sharpen3x3_region(image_bytes: Vec<u8>, region: Option<LumeRect>) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 1, |src| {
+        let img = src.to_luma8();
+        let out = imageproc::filter::sharpen3x3(&img);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
 pub fn sharpen_gaussian(image_bytes: Vec<u8>, sigma: f32, amount: f32) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::filter::sharpen_gaussian(&img, sigma, amount);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+    sharpen_gaussian_region(image_bytes, sigma, amount, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn sharpen_gaussian_region(
+    image_bytes: Vec<u8>,
+    sigma: f32,
+    amount: f32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    check_sigma(sigma)?;
+    filter_region(&image_bytes, region, gaussian_margin(sigma), |src| {
+        let img = src.to_luma8();
+        let out = imageproc::filter::sharpen_gaussian(&img, sigma, amount);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
 pub fn laplacian_filter(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::filter::laplacian_filter(&img);
-    // laplacian returns Luma<i16>, convert to Luma<u8> for encoding
-    let converted: image::GrayImage = image::ImageBuffer::from_fn(out.width(), out.height(), |x, y| {
-        let val = out.get_pixel(x, y).0[0];
-        image::Luma([val.unsigned_abs().min(255) as u8])
-    });
-    helpers::encode(&image::DynamicImage::ImageLuma8(converted), fmt)
+    laplacian_filter_region(image_bytes, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn laplacian_filter_region(image_bytes: Vec<u8>, region: Option<LumeRect>) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 1, |src| {
+        let out = imageproc::filter::laplacian_filter(&src.to_luma8());
+        // laplacian returns Luma<i16>, convert to Luma<u8> for encoding
+        let converted: image::GrayImage =
+            image::ImageBuffer::from_fn(out.width(), out.height(), |x, y| {
+                let val = out.get_pixel(x, y).0[0];
+                image::Luma([val.unsigned_abs().min(255) as u8])
+            });
+        Ok(image::DynamicImage::ImageLuma8(converted))
+    })
+}
+
+// Color variants of the filters above, which otherwise work on luma only.
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn median_filter_color(image_bytes: Vec<u8>, x_radius: u32, y_radius: u32) -> Result<Vec<u8>> {
+    median_filter_color_region(image_bytes, x_radius, y_radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn median_filter_color_region(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = median_color_filter(x_radius, y_radius);
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn median_color_filter(x_radius: u32, y_radius: u32) -> (u32, impl Filter) {
+    (x_radius.max(y_radius), move |src: &image::DynamicImage| {
+        let img = src.to_rgba8();
+        let out = imageproc::filter::median_filter(&img, x_radius, y_radius);
+        Ok(image::DynamicImage::ImageRgba8(out))
+    })
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn box_filter_color(image_bytes: Vec<u8>, x_radius: u32, y_radius: u32) -> Result<Vec<u8>> {
+    box_filter_color_region(image_bytes, x_radius, y_radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn box_filter_color_region(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = box_color_filter(x_radius, y_radius);
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn box_color_filter(x_radius: u32, y_radius: u32) -> (u32, impl Filter) {
+    (x_radius.max(y_radius), move |src: &image::DynamicImage| {
+        let out = helpers::map_channels(&src.to_rgba8(), true, |plane| {
+            imageproc::filter::box_filter(plane, x_radius, y_radius)
+        });
+        Ok(image::DynamicImage::ImageRgba8(out))
+    })
+}
+
+/// Bilateral filter on RGB. With `joint` the range weight uses the Euclidean
+/// distance between whole colors, so all channels share the same weights and
+/// no color fringes appear at edges; otherwise each channel is filtered on its
+/// own. Alpha is preserved in both cases.
+#[flutter_rust_bridge::frb(sync)]
+pub fn bilateral_filter_color(
+    image_bytes: Vec<u8>,
+    window_size: u32,
+    sigma_color: f32,
+    sigma_spatial: f32,
+    joint: bool,
+) -> Result<Vec<u8>> {
+    bilateral_filter_color_region(
+        image_bytes,
+        window_size,
+        sigma_color,
+        sigma_spatial,
+        joint,
+        None,
+    )
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn bilateral_filter_color_region(
+    image_bytes: Vec<u8>,
+    window_size: u32,
+    sigma_color: f32,
+    sigma_spatial: f32,
+    joint: bool,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = bilateral_color_filter(window_size, sigma_color, sigma_spatial, joint);
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn bilateral_color_filter(
+    window_size: u32,
+    sigma_color: f32,
+    sigma_spatial: f32,
+    joint: bool,
+) -> (u32, impl Filter) {
+    (window_size / 2 + 1, move |src: &image::DynamicImage| {
+        let img = src.to_rgba8();
+        let out = if joint {
+            joint_bilateral(&img, window_size, sigma_color, sigma_spatial)
+        } else {
+            helpers::map_channels(&img, false, |plane| {
+                imageproc::filter::bilateral_filter(plane, window_size, sigma_color, sigma_spatial)
+            })
+        };
+        Ok(image::DynamicImage::ImageRgba8(out))
+    })
+}
+
+pub(crate) fn joint_bilateral(
+    img: &image::RgbaImage,
+    window_size: u32,
+    sigma_color: f32,
+    sigma_spatial: f32,
+) -> image::RgbaImage {
+    let (w, h) = img.dimensions();
+    let extent = (window_size.max(1) as i32 - 1) / 2;
+    let color_denom = 2.0 * sigma_color.max(f32::EPSILON).powi(2);
+    let spatial_denom = 2.0 * sigma_spatial.max(f32::EPSILON).powi(2);
+    let spatial: Vec<f32> = (-extent..=extent)
+        .flat_map(|dy| (-extent..=extent).map(move |dx| (dx * dx + dy * dy) as f32))
+        .map(|d2| (-d2 / spatial_denom).exp())
+        .collect();
+    image::RgbaImage::from_fn(w, h, |x, y| {
+        let center = img.get_pixel(x, y).0;
+        let mut acc = [0f32; 3];
+        let mut total = 0f32;
+        let mut k = 0;
+        for dy in -extent..=extent {
+            let sy = (y as i32 + dy).clamp(0, h as i32 - 1) as u32;
+            for dx in -extent..=extent {
+                let sx = (x as i32 + dx).clamp(0, w as i32 - 1) as u32;
+                let p = img.get_pixel(sx, sy).0;
+                let d2: f32 = (0..3)
+                    .map(|c| (p[c] as f32 - center[c] as f32).powi(2))
+                    .sum();
+                let weight = spatial[k] * (-d2 / color_denom).exp();
+                for c in 0..3 {
+                    acc[c] += p[c] as f32 * weight;
+                }
+                total += weight;
+                k += 1;
+            }
+        }
+        let f = |v: f32| (v / total).round().clamp(0.0, 255.0) as u8;
+        Rgba([f(acc[0]), f(acc[1]), f(acc[2]), center[3]])
+    })
+}
+
+/// Perona–Malik anisotropic diffusion. Each iteration moves every pixel
+/// towards its 4 neighbours by `lambda` (stable up to 0.25), scaled by a
+/// conductance `exp(-(∇I / kappa)²)` that stops diffusion across edges with a
+/// gradient much larger than `kappa`. Smooths noise while keeping edges.
+#[flutter_rust_bridge::frb(sync)]
+pub fn anisotropic_diffusion(
+    image_bytes: Vec<u8>,
+    iterations: u32,
+    kappa: f32,
+    lambda: f32,
+) -> Result<Vec<u8>> {
+    anisotropic_diffusion_region(image_bytes, iterations, kappa, lambda, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn anisotropic_diffusion_region(
+    image_bytes: Vec<u8>,
+    iterations: u32,
+    kappa: f32,
+    lambda: f32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    // Each iteration spreads values by one pixel.
+    filter_region(&image_bytes, region, iterations, |src| {
+        Ok(image::DynamicImage::ImageRgba8(diffuse(
+            src.to_rgba8(),
+            iterations,
+            kappa,
+            lambda,
+        )))
+    })
+}
+
+fn diffuse(img: image::RgbaImage, iterations: u32, kappa: f32, lambda: f32) -> image::RgbaImage {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let lambda = lambda.clamp(0.0, 0.25);
+    let kappa2 = kappa.max(f32::EPSILON).powi(2);
+    let conductance = |d: f32| (-(d * d) / kappa2).exp() * d;
+
+    let mut planes: Vec<Vec<f32>> = (0..3)
+        .map(|c| img.pixels().map(|p| p.0[c] as f32).collect())
+        .collect();
+    for _ in 0..iterations {
+        for plane in planes.iter_mut() {
+            let src = plane.clone();
+            for y in 0..h {
+                for x in 0..w {
+                    let i = y * w + x;
+                    let v = src[i];
+                    // Neighbours outside the image contribute no flux.
+                    let north = if y > 0 { src[i - w] - v } else { 0.0 };
+                    let south = if y + 1 < h { src[i + w] - v } else { 0.0 };
+                    let west = if x > 0 { src[i - 1] - v } else { 0.0 };
+                    let east = if x + 1 < w { src[i + 1] - v } else { 0.0 };
+                    plane[i] = v + lambda
+                        * (conductance(north)
+                            + conductance(south)
+                            + conductance(west)
+                            + conductance(east));
+                }
+            }
+        }
+    }
+
+    let mut out = img;
+    for (i, p) in out.pixels_mut().enumerate() {
+        for (c, plane) in planes.iter().enumerate() {
+            p.0[c] = plane[i].round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    out
+}
+
+fn luma_f32(img: &image::GrayImage) -> image::ImageBuffer<image::Luma<f32>, Vec<f32>> {
+    image::ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        image::Luma([img.get_pixel(x, y).0[0] as f32])
+    })
+}
+
+/// Maps a signed response to 8 bits with zero at mid-gray (128).
+fn signed_to_gray(values: &image::ImageBuffer<image::Luma<f32>, Vec<f32>>) -> image::GrayImage {
+    image::ImageBuffer::from_fn(values.width(), values.height(), |x, y| {
+        let v = values.get_pixel(x, y).0[0];
+        image::Luma([(128.0 + v).round().clamp(0.0, 255.0) as u8])
+    })
+}
+
+/// Difference of Gaussians, `blur(sigma1) - blur(sigma2)` on luma. The signed
+/// result is offset so 128 means zero response; blobs of size ~sigma show up
+/// as bright (or dark) spots.
+#[flutter_rust_bridge::frb(sync)]
+pub fn difference_of_gaussians(image_bytes: Vec<u8>, sigma1: f32, sigma2: f32) -> Result<Vec<u8>> {
+    difference_of_gaussians_region(image_bytes, sigma1, sigma2, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn difference_of_gaussians_region(
+    image_bytes: Vec<u8>,
+    sigma1: f32,
+    sigma2: f32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = dog_filter(sigma1, sigma2)?;
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn dog_filter(sigma1: f32, sigma2: f32) -> Result<(u32, impl Filter)> {
+    if sigma1 <= 0.0 || sigma2 <= 0.0 {
+        return Err(anyhow::anyhow!("Both sigmas must be greater than zero"));
+    }
+    let margin = gaussian_margin(sigma1.max(sigma2));
+    Ok((margin, move |src: &image::DynamicImage| {
+        let img = src.to_luma8();
+        let values = luma_f32(&img);
+        let a = imageproc::filter::gaussian_blur_f32(&values, sigma1);
+        let b = imageproc::filter::gaussian_blur_f32(&values, sigma2);
+        let diff = image::ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            image::Luma([a.get_pixel(x, y).0[0] - b.get_pixel(x, y).0[0]])
+        });
+        Ok(image::DynamicImage::ImageLuma8(signed_to_gray(&diff)))
+    }))
+}
+
+/// Scale-normalized Laplacian of Gaussian, `sigma² ∇²(G_sigma * I)`, so
+/// responses are comparable across scales. Output uses the same 128-centered
+/// encoding as `difference_of_gaussians`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn laplacian_of_gaussian(image_bytes: Vec<u8>, sigma: f32) -> Result<Vec<u8>> {
+    laplacian_of_gaussian_region(image_bytes, sigma, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn laplacian_of_gaussian_region(
+    image_bytes: Vec<u8>,
+    sigma: f32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = log_filter(sigma)?;
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn log_filter(sigma: f32) -> Result<(u32, impl Filter)> {
+    if sigma <= 0.0 {
+        return Err(anyhow::anyhow!("sigma must be greater than zero"));
+    }
+    Ok((
+        gaussian_margin(sigma) + 1,
+        move |src: &image::DynamicImage| {
+            let blurred = imageproc::filter::gaussian_blur_f32(&luma_f32(&src.to_luma8()), sigma);
+            let kernel = [0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0];
+            let lap = imageproc::filter::Kernel::new(&kernel, 3, 3);
+            let scale = sigma * sigma;
+            let response: image::ImageBuffer<image::Luma<f32>, Vec<f32>> =
+                lap.filter(&blurred, |channel, acc: f32| *channel = acc * scale);
+            Ok(image::DynamicImage::ImageLuma8(signed_to_gray(&response)))
+        },
+    ))
+}
+
+/// Validates a user-supplied kernel and optionally scales it so its weights
+/// sum to 1. Kernels summing to zero (edge detectors) are left untouched.
+fn prepare_kernel(kernel: &[f32], normalize: bool) -> Result<Vec<f32>> {
+    if kernel.is_empty() {
+        return Err(anyhow::anyhow!("Kernel must not be empty"));
+    }
+    let sum: f32 = kernel.iter().sum();
+    if normalize && sum.abs() > f32::EPSILON {
+        Ok(kernel.iter().map(|k| k / sum).collect())
+    } else {
+        Ok(kernel.to_vec())
+    }
+}
+
+/// Copies the alpha channel of `src` onto an RGB result.
+fn with_alpha(rgb: &image::RgbImage, src: &image::RgbaImage) -> image::RgbaImage {
+    image::ImageBuffer::from_fn(rgb.width(), rgb.height(), |x, y| {
+        let [r, g, b] = rgb.get_pixel(x, y).0;
+        Rgba([r, g, b, src.get_pixel(x, y).0[3]])
+    })
+}
+
+/// Correlates the image with an arbitrary row-major kernel (like OpenCV's
+/// `filter2D`, the kernel is not flipped). The color channels are filtered
+/// independently and the alpha channel is preserved.
+#[flutter_rust_bridge::frb(sync)]
+pub fn convolve(
+    image_bytes: Vec<u8>,
+    kernel: Vec<f32>,
+    kernel_width: u32,
+    normalize: bool,
+) -> Result<Vec<u8>> {
+    convolve_region(image_bytes, kernel, kernel_width, normalize, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn convolve_region(
+    image_bytes: Vec<u8>,
+    kernel: Vec<f32>,
+    kernel_width: u32,
+    normalize: bool,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = convolve_filter(&kernel, kernel_width, normalize)?;
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn convolve_filter(
+    kernel: &[f32],
+    kernel_width: u32,
+    normalize: bool,
+) -> Result<(u32, impl Filter)> {
+    if kernel_width == 0 || !kernel.len().is_multiple_of(kernel_width as usize) {
+        return Err(anyhow::anyhow!(
+            "Kernel length {} is not a multiple of kernel_width {}",
+            kernel.len(),
+            kernel_width
+        ));
+    }
+    let data = prepare_kernel(kernel, normalize)?;
+    let kernel_height = (data.len() / kernel_width as usize) as u32;
+    let margin = kernel_width.max(kernel_height) / 2;
+    Ok((margin, move |src: &image::DynamicImage| {
+        let img = src.to_rgba8();
+        #[cfg(feature = "gpu")]
+        if let Some(out) = crate::gpu::convolve(&img, &data, kernel_width, kernel_height) {
+            return Ok(image::DynamicImage::ImageRgba8(out));
+        }
+        let k = imageproc::filter::Kernel::new(&data, kernel_width, kernel_height);
+        let filtered: image::RgbImage = k.filter(&src.to_rgb8(), |channel, acc: f32| {
+            *channel = acc.round().clamp(0.0, 255.0) as u8;
+        });
+        Ok(image::DynamicImage::ImageRgba8(with_alpha(&filtered, &img)))
+    }))
+}
+
+/// Applies a separable kernel as a horizontal pass with `h_kernel` followed by
+/// a vertical pass with `v_kernel`, which is much faster than `convolve` with
+/// the equivalent 2D kernel.
+#[flutter_rust_bridge::frb(sync)]
+pub fn convolve_separable(
+    image_bytes: Vec<u8>,
+    h_kernel: Vec<f32>,
+    v_kernel: Vec<f32>,
+    normalize: bool,
+) -> Result<Vec<u8>> {
+    convolve_separable_region(image_bytes, h_kernel, v_kernel, normalize, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn convolve_separable_region(
+    image_bytes: Vec<u8>,
+    h_kernel: Vec<f32>,
+    v_kernel: Vec<f32>,
+    normalize: bool,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = separable_filter(&h_kernel, &v_kernel, normalize)?;
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn separable_filter(
+    h_kernel: &[f32],
+    v_kernel: &[f32],
+    normalize: bool,
+) -> Result<(u32, impl Filter)> {
+    let h = prepare_kernel(h_kernel, normalize)?;
+    let v = prepare_kernel(v_kernel, normalize)?;
+    let margin = (h.len().max(v.len()) / 2) as u32;
+    Ok((margin, move |src: &image::DynamicImage| {
+        let filtered = imageproc::filter::separable_filter(&src.to_rgb8(), &h, &v);
+        Ok(image::DynamicImage::ImageRgba8(with_alpha(
+            &filtered,
+            &src.to_rgba8(),
+        )))
+    }))
+}
+
+/// Builds a normalized square kernel containing an anti-aliased line of the
+/// given length through its center, rotated `angle` degrees counter-clockwise.
+fn line_kernel(length: u32, angle: f32) -> (Vec<f32>, u32) {
+    let size = length.max(1) | 1;
+    let center = (size / 2) as f32;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let mut data = vec![0.0f32; (size * size) as usize];
+    let half = (length.max(1) as f32 - 1.0) / 2.0;
+    let steps = (length.max(1) * 4) as i32;
+    for i in 0..=steps {
+        let t = -half + 2.0 * half * i as f32 / steps as f32;
+        let (px, py) = (center + t * cos, center - t * sin);
+        let (x0, y0) = (px.floor(), py.floor());
+        let (fx, fy) = (px - x0, py - y0);
+        for (dx, dy, w) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let (x, y) = (x0 as i64 + dx, y0 as i64 + dy);
+            if x >= 0 && y >= 0 && x < size as i64 && y < size as i64 {
+                data[(y * size as i64 + x) as usize] += w;
+            }
+        }
+    }
+    let sum: f32 = data.iter().sum();
+    data.iter_mut().for_each(|v| *v /= sum);
+    (data, size)
+}
+
+/// Simulates camera motion by averaging the image along a line of `length`
+/// pixels oriented `angle` degrees counter-clockwise from the x axis.
+#[flutter_rust_bridge::frb(sync)]
+pub fn motion_blur(image_bytes: Vec<u8>, length: u32, angle: f32) -> Result<Vec<u8>> {
+    motion_blur_region(image_bytes, length, angle, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn motion_blur_region(
+    image_bytes: Vec<u8>,
+    length: u32,
+    angle: f32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = motion_blur_filter(length, angle);
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn motion_blur_filter(length: u32, angle: f32) -> (u32, impl Filter) {
+    (length / 2 + 1, move |src: &image::DynamicImage| {
+        let img = src.to_rgba8();
+        if length <= 1 {
+            return Ok(image::DynamicImage::ImageRgba8(img));
+        }
+        let (data, size) = line_kernel(length, angle);
+        let k = imageproc::filter::Kernel::new(&data, size, size);
+        let out: image::RgbaImage = k.filter(&img, |channel, acc: f32| {
+            *channel = acc.round().clamp(0.0, 255.0) as u8;
+        });
+        Ok(image::DynamicImage::ImageRgba8(out))
+    })
 }
 
 // ===========================================================================
@@ -106,6 +864,62 @@ pub fn canny(image_bytes: Vec<u8>, low_threshold: f32, high_threshold: f32) -> R
     helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
 }
 
+/// Canny thresholds derived from the image itself. `method` is "median"
+/// (thresholds at ±33% of the median intensity) or "otsu" (high at the Otsu
+/// level, low at half of it).
+fn auto_canny_thresholds(img: &image::GrayImage, method: &str) -> Result<(f32, f32)> {
+    match method {
+        "median" => {
+            let mut hist = [0u64; 256];
+            for p in img.pixels() {
+                hist[p.0[0] as usize] += 1;
+            }
+            let half = (img.width() as u64 * img.height() as u64).div_ceil(2);
+            let mut seen = 0;
+            let median = hist
+                .iter()
+                .position(|&count| {
+                    seen += count;
+                    seen >= half
+                })
+                .unwrap_or(0) as f32;
+            Ok(((0.67 * median).max(0.0), (1.33 * median).min(255.0)))
+        }
+        "otsu" => {
+            let level = imageproc::contrast::otsu_level(img) as f32;
+            Ok((0.5 * level, level))
+        }
+        other => Err(anyhow::anyhow!("Unsupported threshold method: {}", other)),
+    }
+}
+
+/// Canny with thresholds picked automatically (see `auto_canny_thresholds`).
+/// With an `overlay_color`, edges are drawn over the original image instead of
+/// returned as a binary map.
+#[flutter_rust_bridge::frb(sync)]
+pub fn canny_auto(
+    image_bytes: Vec<u8>,
+    method: String,
+    overlay_color: Option<LumeColor>,
+) -> Result<Vec<u8>> {
+    let dyn_img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let gray = dyn_img.to_luma8();
+    let (low, high) = auto_canny_thresholds(&gray, &method.to_lowercase())?;
+    let edges = imageproc::edges::canny(&gray, low, high.max(low + 1.0));
+    let Some(color) = overlay_color else {
+        return helpers::encode(&image::DynamicImage::ImageLuma8(edges), fmt);
+    };
+    let mut out = dyn_img.to_rgba8();
+    let ink = Rgba([color.r, color.g, color.b, color.a]);
+    for (p, e) in out.pixels_mut().zip(edges.pixels()) {
+        if e.0[0] > 0 {
+            p.blend(&ink);
+        }
+    }
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
 // ===========================================================================
 // Gradients (imageproc::gradients)
 // ===========================================================================
@@ -116,115 +930,1061 @@ pub fn sobel_gradients(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
     let fmt = helpers::detect_format(&image_bytes)?;
     let out = imageproc::gradients::sobel_gradients(&img);
     // sobel returns Luma<u16>, normalize to Luma<u8>
-    let converted: image::GrayImage = image::ImageBuffer::from_fn(out.width(), out.height(), |x, y| {
-        let val = out.get_pixel(x, y).0[0];
-        image::Luma([(val >> 8) as u8])
-    });
+    let converted: image::GrayImage =
+        image::ImageBuffer::from_fn(out.width(), out.height(), |x, y| {
+            let val = out.get_pixel(x, y).0[0];
+            image::Luma([(val >> 8) as u8])
+        });
     helpers::encode(&image::DynamicImage::ImageLuma8(converted), fmt)
 }
 
-// ===========================================================================
-// Contrast (imageproc::contrast)
-// ===========================================================================
+/// Returns the x kernel, its width, and the factor that turns a response
+/// into a per-pixel intensity difference. The y kernel is its transpose.
+fn gradient_operator(operator: &str) -> Result<(Vec<f32>, u32, f32)> {
+    match operator.to_lowercase().as_str() {
+        "sobel" => Ok((vec![-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0], 3, 4.0)),
+        "scharr" => Ok((
+            vec![-3.0, 0.0, 3.0, -10.0, 0.0, 10.0, -3.0, 0.0, 3.0],
+            3,
+            16.0,
+        )),
+        "prewitt" => Ok((vec![-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0], 3, 3.0)),
+        // Roberts cross works on the diagonals.
+        "roberts" => Ok((vec![-1.0, 0.0, 0.0, 1.0], 2, 1.0)),
+        other => Err(anyhow::anyhow!("Unsupported gradient operator: {}", other)),
+    }
+}
 
-#[flutter_rust_bridge::frb(sync)]
-pub fn adaptive_threshold(image_bytes: Vec<u8>, block_radius: u32) -> Result<Vec<u8>> {
+fn transpose_kernel(kernel: &[f32], size: u32) -> Vec<f32> {
+    let n = size as usize;
+    (0..n * n).map(|i| kernel[(i % n) * n + i / n]).collect()
+}
+
+/// Raw x and y responses of `operator` on a grayscale image, plus its
+/// normalization factor.
+fn gradient_planes(img: &image::GrayImage, operator: &str) -> Result<(Vec<f32>, Vec<f32>, f32)> {
+    let (kx, size, norm) = gradient_operator(operator)?;
+    let ky = if operator.eq_ignore_ascii_case("roberts") {
+        vec![0.0, -1.0, 1.0, 0.0]
+    } else {
+        transpose_kernel(&kx, size)
+    };
+    let values = luma_f32(img);
+    let apply = |k: &[f32]| -> Vec<f32> {
+        let kernel = imageproc::filter::Kernel::new(k, size, size);
+        let out: image::ImageBuffer<image::Luma<f32>, Vec<f32>> =
+            kernel.filter(&values, |channel, acc: f32| *channel = acc);
+        out.into_raw()
+    };
+    Ok((apply(&kx), apply(&ky), norm))
+}
+
+/// Gradient image for `operator` ("sobel", "scharr", "prewitt" or "roberts").
+/// `direction` "x" or "y" returns the signed derivative centered on 128;
+/// "magnitude" returns the gradient length. Both are scaled to per-pixel
+/// intensity differences instead of being shifted down like `sobel_gradients`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn gradients(image_bytes: Vec<u8>, operator: String, direction: String) -> Result<Vec<u8>> {
     let img = helpers::load(&image_bytes)?.to_luma8();
     let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::contrast::adaptive_threshold(&img, block_radius);
+    let (gx, gy, norm) = gradient_planes(&img, &operator)?;
+    let values: Vec<f32> = match direction.to_lowercase().as_str() {
+        "x" | "horizontal" => gx.iter().map(|v| 128.0 + v / norm).collect(),
+        "y" | "vertical" => gy.iter().map(|v| 128.0 + v / norm).collect(),
+        "magnitude" => gx
+            .iter()
+            .zip(&gy)
+            .map(|(x, y)| x.hypot(*y) / norm)
+            .collect(),
+        other => return Err(anyhow::anyhow!("Unsupported gradient direction: {}", other)),
+    };
+    let pixels = values
+        .iter()
+        .map(|v| v.round().clamp(0.0, 255.0) as u8)
+        .collect();
+    let out = image::GrayImage::from_raw(img.width(), img.height(), pixels)
+        .ok_or_else(|| anyhow::anyhow!("Gradient buffer size mismatch"))?;
     helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
 }
 
+/// Full-precision x/y derivatives and orientation for `operator`, in
+/// row-major order, for feature extraction on the Dart side.
+#[flutter_rust_bridge::frb(sync)]
+pub fn gradient_field(image_bytes: Vec<u8>, operator: String) -> Result<LumeGradientField> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let (gx, gy, _) = gradient_planes(&img, &operator)?;
+    let orientation = gx.iter().zip(&gy).map(|(x, y)| y.atan2(*x)).collect();
+    Ok(LumeGradientField {
+        width: img.width(),
+        height: img.height(),
+        gx,
+        gy,
+        orientation,
+    })
+}
+
+/// Full-precision Sobel magnitude and orientation, without the lossy `>> 8`
+/// conversion of `sobel_gradients`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn compute_gradients(image_bytes: Vec<u8>) -> Result<LumeGradients> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let (gx, gy, _) = gradient_planes(&img, "sobel")?;
+    let (magnitude, orientation) = gx
+        .iter()
+        .zip(&gy)
+        .map(|(x, y)| (x.hypot(*y), y.atan2(*x)))
+        .unzip();
+    Ok(LumeGradients {
+        width: img.width(),
+        height: img.height(),
+        magnitude,
+        orientation,
+    })
+}
+
+// ===========================================================================
+// Contrast (imageproc::contrast)
+// ===========================================================================
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn adaptive_threshold(image_bytes: Vec<u8>, block_radius: u32) -> Result<Vec<u8>> {
+    adaptive_threshold_region(image_bytes, block_radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn adaptive_threshold_region(
+    image_bytes: Vec<u8>,
+    block_radius: u32,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, block_radius, |src| {
+        let img = src.to_luma8();
+        let out = imageproc::contrast::adaptive_threshold(&img, block_radius);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
 #[flutter_rust_bridge::frb(sync)]
 pub fn otsu_threshold(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    otsu_threshold_region(image_bytes, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn otsu_threshold_region(image_bytes: Vec<u8>, region: Option<LumeRect>) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 0, |src| {
+        let img = src.to_luma8();
+        let level = imageproc::contrast::otsu_level(&img);
+        let out = imageproc::contrast::threshold(&img, level, ThresholdType::Binary);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+/// `otsu_threshold` as a `LumeMask` (inside = above the level).
+#[flutter_rust_bridge::frb(sync)]
+pub fn otsu_mask(image_bytes: Vec<u8>, packed: bool) -> Result<LumeMask> {
     let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let level = imageproc::contrast::otsu_level(&img);
     let out = imageproc::contrast::threshold(&img, level, ThresholdType::Binary);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+    Ok(mask::from_gray(&out, packed))
 }
 
 #[flutter_rust_bridge::frb(sync)]
 pub fn threshold(image_bytes: Vec<u8>, value: u8, invert: bool) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
+    threshold_region(image_bytes, value, invert, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn threshold_region(
+    image_bytes: Vec<u8>,
+    value: u8,
+    invert: bool,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
     let tt = if invert {
         ThresholdType::BinaryInverted
     } else {
         ThresholdType::Binary
     };
+    filter_region(&image_bytes, region, 0, |src| {
+        let out = imageproc::contrast::threshold(&src.to_luma8(), value, tt);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+/// `threshold` as a `LumeMask` (inside = above `value`, or at or below it
+/// with `invert`).
+#[flutter_rust_bridge::frb(sync)]
+pub fn threshold_mask(
+    image_bytes: Vec<u8>,
+    value: u8,
+    invert: bool,
+    packed: bool,
+) -> Result<LumeMask> {
+    let tt = if invert {
+        ThresholdType::BinaryInverted
+    } else {
+        ThresholdType::Binary
+    };
+    let img = helpers::load(&image_bytes)?.to_luma8();
     let out = imageproc::contrast::threshold(&img, value, tt);
+    Ok(mask::from_gray(&out, packed))
+}
+
+/// Thresholds luma with one of the OpenCV-style `mode`s, `t` being `value`:
+/// - "binary": 255 above `t`, 0 otherwise (same as `threshold`);
+/// - "binary_inverted": 0 above `t`, 255 otherwise;
+/// - "truncate": values above `t` become `t`, the rest are kept;
+/// - "to_zero": values at or below `t` become 0, the rest are kept;
+/// - "to_zero_inverted": values above `t` become 0, the rest are kept.
+#[flutter_rust_bridge::frb(sync)]
+pub fn threshold_with_mode(image_bytes: Vec<u8>, value: u8, mode: String) -> Result<Vec<u8>> {
+    threshold_with_mode_region(image_bytes, value, mode, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn threshold_with_mode_region(
+    image_bytes: Vec<u8>,
+    value: u8,
+    mode: String,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let map: fn(u8, u8) -> u8 = match mode.to_lowercase().as_str() {
+        "binary" => |v, t| if v > t { 255 } else { 0 },
+        "binary_inverted" => |v, t| if v > t { 0 } else { 255 },
+        "truncate" => |v, t| v.min(t),
+        "to_zero" => |v, t| if v > t { v } else { 0 },
+        "to_zero_inverted" => |v, t| if v > t { 0 } else { v },
+        other => return Err(anyhow::anyhow!("Unsupported threshold mode: {}", other)),
+    };
+    filter_region(&image_bytes, region, 0, |src| {
+        let mut img = src.to_luma8();
+        for p in img.pixels_mut() {
+            p.0[0] = map(p.0[0], value);
+        }
+        Ok(image::DynamicImage::ImageLuma8(img))
+    })
+}
+
+/// Zack's triangle method: the level farthest from the line joining the
+/// histogram peak to the end of its longer tail. Suits images with one
+/// dominant background peak and a small foreground, such as cells or text on
+/// an even background, where Otsu's method needs two comparable classes.
+fn triangle_level_of(img: &image::GrayImage) -> u8 {
+    let mut hist = [0i64; 256];
+    for p in img.pixels() {
+        hist[p.0[0] as usize] += 1;
+    }
+    let Some(first) = hist.iter().position(|&c| c > 0) else {
+        return 0;
+    };
+    let last = hist.iter().rposition(|&c| c > 0).unwrap_or(first);
+    let mut left = first.saturating_sub(1);
+    let right = (last + 1).min(255);
+    // First bin holding the maximum count.
+    let mut peak = hist
+        .iter()
+        .enumerate()
+        .fold(0, |best, (i, &c)| if c > hist[best] { i } else { best });
+    // Work on the longer tail, mirrored to the left of the peak if needed.
+    let flip = peak - left < right - peak;
+    if flip {
+        hist.reverse();
+        left = 255 - right;
+        peak = 255 - peak;
+    }
+    let (a, b) = (hist[peak], left as i64 - peak as i64);
+    let mut level = left;
+    let mut best = 0;
+    for (i, &count) in hist.iter().enumerate().take(peak + 1).skip(left + 1) {
+        let dist = a * i as i64 + b * count;
+        if dist > best {
+            best = dist;
+            level = i;
+        }
+    }
+    let level = level.saturating_sub(1);
+    (if flip { 255 - level } else { level }) as u8
+}
+
+/// Binary threshold at the level picked by the triangle method (see
+/// `triangle_level`).
+#[flutter_rust_bridge::frb(sync)]
+pub fn triangle_threshold(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    triangle_threshold_region(image_bytes, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn triangle_threshold_region(
+    image_bytes: Vec<u8>,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 0, |src| {
+        let img = src.to_luma8();
+        let level = triangle_level_of(&img);
+        let out = imageproc::contrast::threshold(&img, level, ThresholdType::Binary);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+/// The level `otsu_threshold` would use, e.g. to show it on a histogram or
+/// reuse it with `threshold_with_mode`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn otsu_level(image_bytes: Vec<u8>) -> Result<u8> {
+    Ok(imageproc::contrast::otsu_level(
+        &helpers::load(&image_bytes)?.to_luma8(),
+    ))
+}
+
+/// The level `triangle_threshold` would use. Pixels above it are
+/// foreground.
+#[flutter_rust_bridge::frb(sync)]
+pub fn triangle_level(image_bytes: Vec<u8>) -> Result<u8> {
+    Ok(triangle_level_of(&helpers::load(&image_bytes)?.to_luma8()))
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn equalize_histogram(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    equalize_histogram_region(image_bytes, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn equalize_histogram_region(
+    image_bytes: Vec<u8>,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 0, |src| {
+        let img = src.to_luma8();
+        let out = imageproc::contrast::equalize_histogram(&img);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn stretch_contrast(
+    image_bytes: Vec<u8>,
+    input_lower: u8,
+    input_upper: u8,
+    output_lower: u8,
+    output_upper: u8,
+) -> Result<Vec<u8>> {
+    stretch_contrast_region(
+        image_bytes,
+        input_lower,
+        input_upper,
+        output_lower,
+        output_upper,
+        None,
+    )
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn stretch_contrast_region(
+    image_bytes: Vec<u8>,
+    input_lower: u8,
+    input_upper: u8,
+    output_lower: u8,
+    output_upper: u8,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 0, |src| {
+        let out = imageproc::contrast::stretch_contrast(
+            &src.to_luma8(),
+            input_lower,
+            input_upper,
+            output_lower,
+            output_upper,
+        );
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+// ===========================================================================
+// Morphology (imageproc::morphology)
+// ===========================================================================
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn dilate(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
+    dilate_region(image_bytes, radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn dilate_region(
+    image_bytes: Vec<u8>,
+    radius: u8,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, radius as u32, |src| {
+        let img = src.to_luma8();
+        let out = imageproc::morphology::dilate(&img, DistNorm::LInf, radius);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn erode(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
+    erode_region(image_bytes, radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn erode_region(image_bytes: Vec<u8>, radius: u8, region: Option<LumeRect>) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, radius as u32, |src| {
+        let img = src.to_luma8();
+        let out = imageproc::morphology::erode(&img, DistNorm::LInf, radius);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn morphological_open(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
+    morphological_open_region(image_bytes, radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn morphological_open_region(
+    image_bytes: Vec<u8>,
+    radius: u8,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 2 * radius as u32, |src| {
+        let img = src.to_luma8();
+        let out = imageproc::morphology::open(&img, DistNorm::LInf, radius);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn morphological_close(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
+    morphological_close_region(image_bytes, radius, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn morphological_close_region(
+    image_bytes: Vec<u8>,
+    radius: u8,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    filter_region(&image_bytes, region, 2 * radius as u32, |src| {
+        let img = src.to_luma8();
+        let out = imageproc::morphology::close(&img, DistNorm::LInf, radius);
+        Ok(image::DynamicImage::ImageLuma8(out))
+    })
+}
+
+/// Builds a structuring element and returns it with its reach (the largest
+/// offset from the anchor). `shape` is "rect", "cross", "ellipse" (all of
+/// size `2 * radius + 1`) or "custom", in which case the non-zero pixels of
+/// `kernel_bytes` form the element, anchored at the kernel's center.
+fn structuring_element(
+    shape: &str,
+    radius: u8,
+    kernel_bytes: Option<&[u8]>,
+) -> Result<(Mask, u32)> {
+    match shape {
+        "rect" | "square" => Ok((Mask::square(radius), radius as u32)),
+        "ellipse" | "disk" => Ok((Mask::disk(radius), radius as u32)),
+        "cross" => {
+            let size = 2 * radius as u32 + 1;
+            let r = radius as u32;
+            let img = image::GrayImage::from_fn(size, size, |x, y| {
+                image::Luma([if x == r || y == r { 255 } else { 0 }])
+            });
+            Ok((Mask::from_image(&img, radius, radius), radius as u32))
+        }
+        "custom" => {
+            let bytes = kernel_bytes
+                .ok_or_else(|| anyhow::anyhow!("Custom shape requires kernel bytes"))?;
+            let kernel = helpers::load(bytes)?.to_luma8();
+            Ok((custom_mask(&kernel, |v| v != 0)?, kernel_reach(&kernel)))
+        }
+        other => Err(anyhow::anyhow!("Unsupported kernel shape: {}", other)),
+    }
+}
+
+/// Mask made of the kernel pixels for which `keep` holds, anchored at the
+/// kernel's center.
+fn custom_mask(kernel: &image::GrayImage, keep: impl Fn(u8) -> bool) -> Result<Mask> {
+    let (w, h) = kernel.dimensions();
+    if w == 0 || h == 0 || w > 511 || h > 511 {
+        return Err(anyhow::anyhow!("Kernel must be between 1x1 and 511x511"));
+    }
+    let selected = image::GrayImage::from_fn(w, h, |x, y| {
+        image::Luma([if keep(kernel.get_pixel(x, y).0[0]) {
+            255
+        } else {
+            0
+        }])
+    });
+    Ok(Mask::from_image(&selected, (w / 2) as u8, (h / 2) as u8))
+}
+
+fn kernel_reach(kernel: &image::GrayImage) -> u32 {
+    kernel.width().max(kernel.height()) / 2
+}
+
+fn subtract(a: &image::GrayImage, b: &image::GrayImage) -> image::GrayImage {
+    image::GrayImage::from_fn(a.width(), a.height(), |x, y| {
+        image::Luma([a.get_pixel(x, y).0[0].saturating_sub(b.get_pixel(x, y).0[0])])
+    })
+}
+
+/// Applies a morphological `operation` with the given mask. Shared by the
+/// binary and grayscale entry points.
+fn apply_morphology(
+    img: &image::GrayImage,
+    operation: &str,
+    mask: &Mask,
+) -> Result<image::GrayImage> {
+    use imageproc::morphology::{
+        grayscale_close, grayscale_dilate, grayscale_erode, grayscale_open,
+    };
+    Ok(match operation {
+        "dilate" => grayscale_dilate(img, mask),
+        "erode" => grayscale_erode(img, mask),
+        "open" => grayscale_open(img, mask),
+        "close" => grayscale_close(img, mask),
+        "gradient" => subtract(&grayscale_dilate(img, mask), &grayscale_erode(img, mask)),
+        "tophat" | "top_hat" => subtract(img, &grayscale_open(img, mask)),
+        "blackhat" | "black_hat" => subtract(&grayscale_close(img, mask), img),
+        other => {
+            return Err(anyhow::anyhow!(
+                "Unsupported morphology operation: {}",
+                other
+            ))
+        }
+    })
+}
+
+/// Binary morphology with a selectable structuring element. Pixels with
+/// non-zero intensity are foreground. `operation` is one of "dilate", "erode",
+/// "open", "close", "gradient", "tophat", "blackhat" or "hit_or_miss"; see
+/// `structuring_element` for `shape`, `radius` and `kernel_bytes`.
+///
+/// "hit_or_miss" needs a custom kernel: white (255) pixels must be foreground,
+/// black (0) pixels must be background and any other value is ignored.
+#[flutter_rust_bridge::frb(sync)]
+pub fn morphology(
+    image_bytes: Vec<u8>,
+    operation: String,
+    shape: String,
+    radius: u8,
+    kernel_bytes: Option<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    morphology_region(image_bytes, operation, shape, radius, kernel_bytes, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn morphology_region(
+    image_bytes: Vec<u8>,
+    operation: String,
+    shape: String,
+    radius: u8,
+    kernel_bytes: Option<Vec<u8>>,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = morphology_filter(&operation, &shape, radius, kernel_bytes.as_deref())?;
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn morphology_filter(
+    operation: &str,
+    shape: &str,
+    radius: u8,
+    kernel_bytes: Option<&[u8]>,
+) -> Result<(u32, impl Filter)> {
+    let binarize = |src: &image::DynamicImage| {
+        let mut img = src.to_luma8();
+        for p in img.pixels_mut() {
+            p.0[0] = if p.0[0] != 0 { 255 } else { 0 };
+        }
+        img
+    };
+    let operation = operation.to_lowercase();
+    if operation == "hit_or_miss" || operation == "hitormiss" {
+        let bytes = match (shape.to_lowercase().as_str(), kernel_bytes) {
+            ("custom", Some(bytes)) => bytes,
+            _ => return Err(anyhow::anyhow!("hit_or_miss requires a custom kernel")),
+        };
+        let kernel = helpers::load(bytes)?.to_luma8();
+        let hits = custom_mask(&kernel, |v| v == 255)?;
+        let misses = custom_mask(&kernel, |v| v == 0)?;
+        Ok((
+            kernel_reach(&kernel),
+            Morphology::HitOrMiss { hits, misses },
+        ))
+    } else {
+        let (mask, reach) = structuring_element(&shape.to_lowercase(), radius, kernel_bytes)?;
+        Ok((2 * reach, Morphology::Element { operation, mask }))
+    }
+    .map(|(margin, morphology)| {
+        (margin, move |src: &image::DynamicImage| {
+            let img = binarize(src);
+            let out = match &morphology {
+                Morphology::HitOrMiss { hits, misses } => {
+                    let fg = imageproc::morphology::grayscale_erode(&img, hits);
+                    let mut inverted = img.clone();
+                    image::imageops::invert(&mut inverted);
+                    let bg = imageproc::morphology::grayscale_erode(&inverted, misses);
+                    image::GrayImage::from_fn(img.width(), img.height(), |x, y| {
+                        image::Luma([fg.get_pixel(x, y).0[0].min(bg.get_pixel(x, y).0[0])])
+                    })
+                }
+                Morphology::Element { operation, mask } => apply_morphology(&img, operation, mask)?,
+            };
+            Ok(image::DynamicImage::ImageLuma8(out))
+        })
+    })
+}
+
+/// A prepared `morphology` operation.
+enum Morphology {
+    Element { operation: String, mask: Mask },
+    HitOrMiss { hits: Mask, misses: Mask },
+}
+
+/// Grayscale morphology: same operations and structuring elements as
+/// `morphology` (except "hit_or_miss"), but on intensities instead of a
+/// binarized image. A large "open" estimates a smooth background, and
+/// "tophat" / "blackhat" subtract it to lift bright / dark detail such as text.
+#[flutter_rust_bridge::frb(sync)]
+pub fn grayscale_morphology(
+    image_bytes: Vec<u8>,
+    operation: String,
+    shape: String,
+    radius: u8,
+    kernel_bytes: Option<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    grayscale_morphology_region(image_bytes, operation, shape, radius, kernel_bytes, None)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn grayscale_morphology_region(
+    image_bytes: Vec<u8>,
+    operation: String,
+    shape: String,
+    radius: u8,
+    kernel_bytes: Option<Vec<u8>>,
+    region: Option<LumeRect>,
+) -> Result<Vec<u8>> {
+    let (margin, filter) =
+        grayscale_morphology_filter(&operation, &shape, radius, kernel_bytes.as_deref())?;
+    filter_region(&image_bytes, region, margin, filter)
+}
+
+fn grayscale_morphology_filter(
+    operation: &str,
+    shape: &str,
+    radius: u8,
+    kernel_bytes: Option<&[u8]>,
+) -> Result<(u32, impl Filter)> {
+    let (mask, reach) = structuring_element(&shape.to_lowercase(), radius, kernel_bytes)?;
+    let operation = operation.to_lowercase();
+    Ok((2 * reach, move |src: &image::DynamicImage| {
+        let out = apply_morphology(&src.to_luma8(), &operation, &mask)?;
+        Ok(image::DynamicImage::ImageLuma8(out))
+    }))
+}
+
+// ===========================================================================
+// Border-aware filters and morphology
+// ===========================================================================
+//
+// Same operations as above, but the image is padded according to
+// `border_mode` ("replicate", "reflect", "wrap" or "constant") before
+// processing and cropped back afterwards. `border_color` is only used by
+// "constant". Every convolution and morphology function has a variant here;
+// edge detectors and gradients keep replicating the edge pixels.
+
+fn parse_border(
+    border_mode: &str,
+    border_color: &LumeColor,
+) -> Result<(helpers::BorderMode, Rgba<u8>)> {
+    let mode = helpers::string_to_border_mode(border_mode)?;
+    let fill = Rgba([
+        border_color.r,
+        border_color.g,
+        border_color.b,
+        border_color.a,
+    ]);
+    Ok((mode, fill))
+}
+
+/// Decodes `image_bytes`, pads it by `margin` pixels following `border_mode`,
+/// runs `filter` on the padded image and crops the result back to the
+/// original size, like `helpers::with_border` for filters that pick their
+/// own pixel type.
+fn filter_with_border(
+    image_bytes: &[u8],
+    margin: u32,
+    border_mode: &str,
+    border_color: &LumeColor,
+    filter: impl Filter,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(image_bytes)?;
+    let fmt = helpers::detect_format(image_bytes)?;
+    let (mode, fill) = parse_border(border_mode, border_color)?;
+    let (w, h) = (img.width(), img.height());
+    let out = if w == 0 || h == 0 {
+        filter(&img)?
+    } else {
+        let padded = helpers::pad(&img.to_rgba8(), margin, mode, fill);
+        filter(&image::DynamicImage::ImageRgba8(padded))?.crop_imm(margin, margin, w, h)
+    };
+    helpers::encode(&out, fmt)
+}
+
+/// Radius covered by the kernel built by `gaussian_blur_f32`.
+fn gaussian_margin(sigma: f32) -> u32 {
+    (2.0 * sigma).ceil().max(1.0) as u32
+}
+
+/// `gaussian_blur_f32` and `sharpen_gaussian` assert a positive sigma;
+/// turn anything else (NaN included) into an error instead of a panic.
+fn check_sigma(sigma: f32) -> Result<()> {
+    if sigma > 0.0 && sigma.is_finite() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("sigma must be greater than zero"))
+    }
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn gaussian_blur_with_border(
+    image_bytes: Vec<u8>,
+    sigma: f32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    check_sigma(sigma)?;
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(&img, gaussian_margin(sigma), mode, fill, |padded| {
+        imageproc::filter::gaussian_blur_f32(padded, sigma)
+    });
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn median_filter_with_border(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let margin = x_radius.max(y_radius);
+    let out = helpers::with_border(&img, margin, mode, fill.to_luma(), |padded| {
+        imageproc::filter::median_filter(padded, x_radius, y_radius)
+    });
+    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn bilateral_filter_with_border(
+    image_bytes: Vec<u8>,
+    window_size: u32,
+    sigma_color: f32,
+    sigma_spatial: f32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let margin = window_size / 2 + 1;
+    let out = helpers::with_border(&img, margin, mode, fill.to_luma(), |padded| {
+        imageproc::filter::bilateral_filter(padded, window_size, sigma_color, sigma_spatial)
+    });
+    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn box_filter_with_border(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let margin = x_radius.max(y_radius);
+    let out = helpers::with_border(&img, margin, mode, fill.to_luma(), |padded| {
+        imageproc::filter::box_filter(padded, x_radius, y_radius)
+    });
+    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn sharpen3x3_with_border(
+    image_bytes: Vec<u8>,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(&img, 1, mode, fill.to_luma(), |padded| {
+        imageproc::filter::sharpen3x3(padded)
+    });
+    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn sharpen_gaussian_with_border(
+    image_bytes: Vec<u8>,
+    sigma: f32,
+    amount: f32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    check_sigma(sigma)?;
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(
+        &img,
+        gaussian_margin(sigma),
+        mode,
+        fill.to_luma(),
+        |padded| imageproc::filter::sharpen_gaussian(padded, sigma, amount),
+    );
+    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn laplacian_filter_with_border(
+    image_bytes: Vec<u8>,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(&img, 1, mode, fill.to_luma(), |padded| {
+        imageproc::filter::laplacian_filter(padded)
+    });
+    let converted: image::GrayImage =
+        image::ImageBuffer::from_fn(out.width(), out.height(), |x, y| {
+            let val = out.get_pixel(x, y).0[0];
+            image::Luma([val.unsigned_abs().min(255) as u8])
+        });
+    helpers::encode(&image::DynamicImage::ImageLuma8(converted), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn dilate_with_border(
+    image_bytes: Vec<u8>,
+    radius: u8,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(&img, radius as u32, mode, fill.to_luma(), |padded| {
+        imageproc::morphology::dilate(padded, DistNorm::LInf, radius)
+    });
+    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn erode_with_border(
+    image_bytes: Vec<u8>,
+    radius: u8,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(&img, radius as u32, mode, fill.to_luma(), |padded| {
+        imageproc::morphology::erode(padded, DistNorm::LInf, radius)
+    });
     helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
 }
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn equalize_histogram(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
+pub fn morphological_open_with_border(
+    image_bytes: Vec<u8>,
+    radius: u8,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
     let img = helpers::load(&image_bytes)?.to_luma8();
     let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::contrast::equalize_histogram(&img);
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(&img, 2 * radius as u32, mode, fill.to_luma(), |padded| {
+        imageproc::morphology::open(padded, DistNorm::LInf, radius)
+    });
     helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
 }
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn stretch_contrast(
+pub fn morphological_close_with_border(
     image_bytes: Vec<u8>,
-    input_lower: u8,
-    input_upper: u8,
-    output_lower: u8,
-    output_upper: u8,
+    radius: u8,
+    border_mode: String,
+    border_color: LumeColor,
 ) -> Result<Vec<u8>> {
     let img = helpers::load(&image_bytes)?.to_luma8();
     let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::contrast::stretch_contrast(
-        &img,
-        input_lower,
-        input_upper,
-        output_lower,
-        output_upper,
-    );
+    let (mode, fill) = parse_border(&border_mode, &border_color)?;
+    let out = helpers::with_border(&img, 2 * radius as u32, mode, fill.to_luma(), |padded| {
+        imageproc::morphology::close(padded, DistNorm::LInf, radius)
+    });
     helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
 }
 
-// ===========================================================================
-// Morphology (imageproc::morphology)
-// ===========================================================================
+#[flutter_rust_bridge::frb(sync)]
+pub fn median_filter_color_with_border(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = median_color_filter(x_radius, y_radius);
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
+}
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn dilate(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::morphology::dilate(&img, DistNorm::LInf, radius);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+pub fn box_filter_color_with_border(
+    image_bytes: Vec<u8>,
+    x_radius: u32,
+    y_radius: u32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = box_color_filter(x_radius, y_radius);
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
 }
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn erode(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::morphology::erode(&img, DistNorm::LInf, radius);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+pub fn bilateral_filter_color_with_border(
+    image_bytes: Vec<u8>,
+    window_size: u32,
+    sigma_color: f32,
+    sigma_spatial: f32,
+    joint: bool,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = bilateral_color_filter(window_size, sigma_color, sigma_spatial, joint);
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
 }
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn morphological_open(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::morphology::open(&img, DistNorm::LInf, radius);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+pub fn difference_of_gaussians_with_border(
+    image_bytes: Vec<u8>,
+    sigma1: f32,
+    sigma2: f32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = dog_filter(sigma1, sigma2)?;
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
 }
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn morphological_close(image_bytes: Vec<u8>, radius: u8) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_luma8();
-    let fmt = helpers::detect_format(&image_bytes)?;
-    let out = imageproc::morphology::close(&img, DistNorm::LInf, radius);
-    helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
+pub fn laplacian_of_gaussian_with_border(
+    image_bytes: Vec<u8>,
+    sigma: f32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = log_filter(sigma)?;
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn convolve_with_border(
+    image_bytes: Vec<u8>,
+    kernel: Vec<f32>,
+    kernel_width: u32,
+    normalize: bool,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = convolve_filter(&kernel, kernel_width, normalize)?;
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn convolve_separable_with_border(
+    image_bytes: Vec<u8>,
+    h_kernel: Vec<f32>,
+    v_kernel: Vec<f32>,
+    normalize: bool,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = separable_filter(&h_kernel, &v_kernel, normalize)?;
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn motion_blur_with_border(
+    image_bytes: Vec<u8>,
+    length: u32,
+    angle: f32,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = motion_blur_filter(length, angle);
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn morphology_with_border(
+    image_bytes: Vec<u8>,
+    operation: String,
+    shape: String,
+    radius: u8,
+    kernel_bytes: Option<Vec<u8>>,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) = morphology_filter(&operation, &shape, radius, kernel_bytes.as_deref())?;
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn grayscale_morphology_with_border(
+    image_bytes: Vec<u8>,
+    operation: String,
+    shape: String,
+    radius: u8,
+    kernel_bytes: Option<Vec<u8>>,
+    border_mode: String,
+    border_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let (margin, filter) =
+        grayscale_morphology_filter(&operation, &shape, radius, kernel_bytes.as_deref())?;
+    filter_with_border(&image_bytes, margin, &border_mode, &border_color, filter)
 }
 
 // ===========================================================================
 // Geometric transformations (imageproc::geometric_transformations)
 // ===========================================================================
 
+/// Interpolation for `name`: "nearest" keeps hard pixel edges (pixel art,
+/// masks), "bilinear" is the fast default and "bicubic" is sharper for
+/// photos.
+fn interpolation(name: &str) -> Result<imageproc::geometric_transformations::Interpolation> {
+    use imageproc::geometric_transformations::Interpolation;
+    match name.to_lowercase().as_str() {
+        "nearest" => Ok(Interpolation::Nearest),
+        "bilinear" => Ok(Interpolation::Bilinear),
+        "bicubic" => Ok(Interpolation::Bicubic),
+        other => Err(anyhow::anyhow!("Unsupported interpolation: {}", other)),
+    }
+}
+
 #[flutter_rust_bridge::frb(sync)]
 pub fn rotate_about_center(
     image_bytes: Vec<u8>,
@@ -234,15 +1994,161 @@ pub fn rotate_about_center(
     bg_b: u8,
     bg_a: u8,
 ) -> Result<Vec<u8>> {
+    let bg = LumeColor {
+        r: bg_r,
+        g: bg_g,
+        b: bg_b,
+        a: bg_a,
+    };
+    rotate_about_center_with_interpolation(image_bytes, theta, "bilinear".to_string(), bg)
+}
+
+/// `rotate_about_center` (`theta` in radians, clockwise) with a choice of
+/// `interpolation`: "nearest", "bilinear" or "bicubic".
+#[flutter_rust_bridge::frb(sync)]
+pub fn rotate_about_center_with_interpolation(
+    image_bytes: Vec<u8>,
+    theta: f32,
+    interpolation: String,
+    bg: LumeColor,
+) -> Result<Vec<u8>> {
+    let interpolation = self::interpolation(&interpolation)?;
     let img = helpers::load(&image_bytes)?.to_rgba8();
     let fmt = helpers::detect_format(&image_bytes)?;
-    let default = Rgba([bg_r, bg_g, bg_b, bg_a]);
-    let interpolation = imageproc::geometric_transformations::Interpolation::Bilinear;
     let out = imageproc::geometric_transformations::rotate_about_center(
         &img,
         theta,
         interpolation,
-        default,
+        Rgba([bg.r, bg.g, bg.b, bg.a]),
+    );
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
+/// Applies a 3x3 projective transform (row-major, mapping input pixel
+/// coordinates to output ones, as returned by `align_images`) into a
+/// `width` x `height` output; 0 for both keeps the input size. Uncovered
+/// areas are filled with `bg`. `interpolation` is "nearest", "bilinear" or
+/// "bicubic".
+#[flutter_rust_bridge::frb(sync)]
+pub fn warp(
+    image_bytes: Vec<u8>,
+    matrix: Vec<f32>,
+    width: u32,
+    height: u32,
+    interpolation: String,
+    bg: LumeColor,
+) -> Result<Vec<u8>> {
+    let matrix: [f32; 9] = matrix
+        .try_into()
+        .map_err(|m: Vec<f32>| anyhow::anyhow!("Expected 9 matrix values, got {}", m.len()))?;
+    let projection = imageproc::geometric_transformations::Projection::from_matrix(matrix)
+        .ok_or_else(|| anyhow::anyhow!("Transform is not invertible"))?;
+    let interpolation = self::interpolation(&interpolation)?;
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = if width == 0 && height == 0 {
+        img.dimensions()
+    } else {
+        (width, height)
+    };
+    let mut out = image::RgbaImage::new(w, h);
+    imageproc::geometric_transformations::warp_into(
+        &img,
+        &projection,
+        interpolation,
+        Rgba([bg.r, bg.g, bg.b, bg.a]),
+        &mut out,
+    );
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
+/// Extracts the `width` x `height` region centered on (`cx`, `cy`) and
+/// rotated by `angle` degrees clockwise as an upright image, resampled once
+/// (bilinear), e.g. to straighten a text line or license plate found with a
+/// rotated bounding box. The patch's x axis runs along the rotated width.
+/// Parts of the region outside the image are transparent.
+#[flutter_rust_bridge::frb(sync)]
+pub fn crop_rotated(
+    image_bytes: Vec<u8>,
+    cx: f32,
+    cy: f32,
+    width: u32,
+    height: u32,
+    angle: f32,
+) -> Result<Vec<u8>> {
+    use imageproc::geometric_transformations::{Interpolation, Projection};
+    if width == 0 || height == 0 {
+        return Err(anyhow::anyhow!("Crop size must be greater than zero"));
+    }
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    // Maps source coordinates into the patch: center on the region, undo
+    // its rotation, move its center to the patch center.
+    let projection = Projection::translate(width as f32 / 2.0, height as f32 / 2.0)
+        * Projection::rotate(-angle.to_radians())
+        * Projection::translate(-cx, -cy);
+    let mut out = image::RgbaImage::new(width, height);
+    imageproc::geometric_transformations::warp_into(
+        &img,
+        &projection,
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+        &mut out,
+    );
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
+/// Rotates by `degrees` clockwise around (`cx`, `cy`). Without `expand` the
+/// output keeps the input size and corners rotated out of it are cut off;
+/// with `expand` the canvas grows to the bounding box of the rotated image,
+/// so nothing is lost (the pivot then only matters for the direction of
+/// rotation, not for where the content lands). Uncovered areas are filled
+/// with `bg`. `interpolation` is "nearest", "bilinear" or "bicubic".
+#[flutter_rust_bridge::frb(sync)]
+pub fn rotate_about_point(
+    image_bytes: Vec<u8>,
+    cx: f32,
+    cy: f32,
+    degrees: f32,
+    expand: bool,
+    interpolation: String,
+    bg: LumeColor,
+) -> Result<Vec<u8>> {
+    use imageproc::geometric_transformations::Projection;
+    let interpolation = self::interpolation(&interpolation)?;
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let rotation = Projection::translate(cx, cy)
+        * Projection::rotate(degrees.to_radians())
+        * Projection::translate(-cx, -cy);
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let (projection, out_w, out_h) = if expand {
+        let corners = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)].map(|(x, y)| rotation * (x, y));
+        let min_x = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min);
+        let max_x = corners
+            .iter()
+            .map(|c| c.0)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let min_y = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min);
+        let max_y = corners
+            .iter()
+            .map(|c| c.1)
+            .fold(f32::NEG_INFINITY, f32::max);
+        (
+            Projection::translate(-min_x, -min_y) * rotation,
+            ((max_x - min_x).round() as u32).max(1),
+            ((max_y - min_y).round() as u32).max(1),
+        )
+    } else {
+        (rotation, img.width(), img.height())
+    };
+    let mut out = image::RgbaImage::new(out_w, out_h);
+    imageproc::geometric_transformations::warp_into(
+        &img,
+        &projection,
+        interpolation,
+        Rgba([bg.r, bg.g, bg.b, bg.a]),
+        &mut out,
     );
     helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
 }
@@ -255,17 +2161,35 @@ pub fn translate(image_bytes: Vec<u8>, tx: i32, ty: i32) -> Result<Vec<u8>> {
     helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
 }
 
+/// Shifts the image by a fractional offset, e.g. to nudge a layer by half a
+/// pixel. Uncovered areas are filled with `bg`. `interpolation` is
+/// "nearest", "bilinear" or "bicubic".
+#[flutter_rust_bridge::frb(sync)]
+pub fn translate_with_interpolation(
+    image_bytes: Vec<u8>,
+    tx: f32,
+    ty: f32,
+    interpolation: String,
+    bg: LumeColor,
+) -> Result<Vec<u8>> {
+    let interpolation = self::interpolation(&interpolation)?;
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let out = imageproc::geometric_transformations::warp(
+        &img,
+        &imageproc::geometric_transformations::Projection::translate(tx, ty),
+        interpolation,
+        Rgba([bg.r, bg.g, bg.b, bg.a]),
+    );
+    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+}
+
 // ===========================================================================
 // Noise (imageproc::noise)
 // ===========================================================================
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn gaussian_noise(
-    image_bytes: Vec<u8>,
-    mean: f64,
-    stddev: f64,
-    seed: u64,
-) -> Result<Vec<u8>> {
+pub fn gaussian_noise(image_bytes: Vec<u8>, mean: f64, stddev: f64, seed: u64) -> Result<Vec<u8>> {
     let img = helpers::load(&image_bytes)?.to_rgba8();
     let fmt = helpers::detect_format(&image_bytes)?;
     let out = imageproc::noise::gaussian_noise(&img, mean, stddev, seed);
@@ -273,11 +2197,7 @@ pub fn gaussian_noise(
 }
 
 #[flutter_rust_bridge::frb(sync)]
-pub fn salt_and_pepper_noise(
-    image_bytes: Vec<u8>,
-    rate: f64,
-    seed: u64,
-) -> Result<Vec<u8>> {
+pub fn salt_and_pepper_noise(image_bytes: Vec<u8>, rate: f64, seed: u64) -> Result<Vec<u8>> {
     let img = helpers::load(&image_bytes)?.to_rgba8();
     let fmt = helpers::detect_format(&image_bytes)?;
     let out = imageproc::noise::salt_and_pepper_noise(&img, rate, seed);
@@ -302,11 +2222,10 @@ pub fn seam_carve_width(image_bytes: Vec<u8>, new_width: u32) -> Result<Vec<u8>>
         let gray = image::DynamicImage::ImageRgba8(current.clone()).to_luma8();
         let energy_u16 = imageproc::gradients::sobel_gradients(&gray);
         // Convert Luma<u16> → Luma<u8> for find_vertical_seam
-        let energy: image::GrayImage = image::ImageBuffer::from_fn(
-            energy_u16.width(),
-            energy_u16.height(),
-            |x, y| image::Luma([(energy_u16.get_pixel(x, y).0[0] >> 8) as u8]),
-        );
+        let energy: image::GrayImage =
+            image::ImageBuffer::from_fn(energy_u16.width(), energy_u16.height(), |x, y| {
+                image::Luma([(energy_u16.get_pixel(x, y).0[0] >> 8) as u8])
+            });
         let seam = imageproc::seam_carving::find_vertical_seam(&energy);
         current = imageproc::seam_carving::remove_vertical_seam(&current, &seam);
     }
@@ -316,6 +2235,25 @@ pub fn seam_carve_width(image_bytes: Vec<u8>, new_width: u32) -> Result<Vec<u8>>
 // ===========================================================================
 // Drawing (imageproc::drawing)
 // ===========================================================================
+//
+// Shapes are drawn into a coverage mask and the color is composited through
+// it, so colors with alpha below 255 blend with the image instead of
+// replacing its pixels.
+
+const INK: image::Luma<u8> = image::Luma([255]);
+
+fn draw_blended(
+    image_bytes: &[u8],
+    color: Rgba<u8>,
+    draw: impl FnOnce(&mut image::GrayImage),
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(image_bytes)?;
+    let mut mask = image::GrayImage::new(img.width(), img.height());
+    draw(&mut mask);
+    helpers::paint_over(&mut img, &mask, 1.0, |_, _| color);
+    helpers::encode(&image::DynamicImage::ImageRgba8(img), fmt)
+}
 
 #[flutter_rust_bridge::frb(sync)]
 pub fn draw_line(
@@ -329,16 +2267,15 @@ pub fn draw_line(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_line_segment(
-        &img,
-        (x1 as f32, y1 as f32),
-        (x2 as f32, y2 as f32),
-        color,
-    );
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_line_segment_mut(
+            mask,
+            (x1 as f32, y1 as f32),
+            (x2 as f32, y2 as f32),
+            INK,
+        );
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -353,17 +2290,16 @@ pub fn draw_antialiased_line(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_antialiased_line_segment(
-        &img,
-        (x1, y1),
-        (x2, y2),
-        color,
-        imageproc::pixelops::interpolate,
-    );
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_antialiased_line_segment_mut(
+            mask,
+            (x1, y1),
+            (x2, y2),
+            INK,
+            imageproc::pixelops::interpolate,
+        );
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -378,12 +2314,11 @@ pub fn draw_hollow_rect(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
     let rect = Rect::at(x, y).of_size(width, height);
-    let out = imageproc::drawing::draw_hollow_rect(&img, rect, color);
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_hollow_rect_mut(mask, rect, INK);
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -398,12 +2333,11 @@ pub fn draw_filled_rect(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
     let rect = Rect::at(x, y).of_size(width, height);
-    let out = imageproc::drawing::draw_filled_rect(&img, rect, color);
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_filled_rect_mut(mask, rect, INK);
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -417,11 +2351,10 @@ pub fn draw_hollow_circle(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_hollow_circle(&img, (cx, cy), radius, color);
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_hollow_circle_mut(mask, (cx, cy), radius, INK);
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -435,11 +2368,10 @@ pub fn draw_filled_circle(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_filled_circle(&img, (cx, cy), radius, color);
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_filled_circle_mut(mask, (cx, cy), radius, INK);
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -454,17 +2386,16 @@ pub fn draw_hollow_ellipse(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_hollow_ellipse(
-        &img,
-        (cx, cy),
-        width_radius,
-        height_radius,
-        color,
-    );
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_hollow_ellipse_mut(
+            mask,
+            (cx, cy),
+            width_radius,
+            height_radius,
+            INK,
+        );
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -479,17 +2410,16 @@ pub fn draw_filled_ellipse(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_filled_ellipse(
-        &img,
-        (cx, cy),
-        width_radius,
-        height_radius,
-        color,
-    );
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_filled_ellipse_mut(
+            mask,
+            (cx, cy),
+            width_radius,
+            height_radius,
+            INK,
+        );
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -501,12 +2431,11 @@ pub fn draw_filled_polygon(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
     let pts: Vec<Point<i32>> = points.iter().map(|p| Point::new(p.x, p.y)).collect();
-    let out = imageproc::drawing::draw_polygon(&img, &pts, color);
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_polygon_mut(mask, &pts, INK);
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -518,12 +2447,14 @@ pub fn draw_hollow_polygon(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let mut img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let pts: Vec<Point<f32>> = points.iter().map(|p| Point::new(p.x as f32, p.y as f32)).collect();
-    imageproc::drawing::draw_hollow_polygon_mut(&mut img, &pts, color);
-    helpers::encode(&image::DynamicImage::ImageRgba8(img), fmt)
+    let pts: Vec<Point<f32>> = points
+        .iter()
+        .map(|p| Point::new(p.x as f32, p.y as f32))
+        .collect();
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_hollow_polygon_mut(mask, &pts, INK);
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -542,18 +2473,17 @@ pub fn draw_cubic_bezier(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_cubic_bezier_curve(
-        &img,
-        (start_x, start_y),
-        (end_x, end_y),
-        (ctrl1_x, ctrl1_y),
-        (ctrl2_x, ctrl2_y),
-        color,
-    );
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_cubic_bezier_curve_mut(
+            mask,
+            (start_x, start_y),
+            (end_x, end_y),
+            (ctrl1_x, ctrl1_y),
+            (ctrl2_x, ctrl2_y),
+            INK,
+        );
+    })
 }
 
 #[flutter_rust_bridge::frb(sync)]
@@ -566,11 +2496,10 @@ pub fn draw_cross(
     b: u8,
     a: u8,
 ) -> Result<Vec<u8>> {
-    let img = helpers::load(&image_bytes)?.to_rgba8();
-    let fmt = helpers::detect_format(&image_bytes)?;
     let color = Rgba([r, g, b, a]);
-    let out = imageproc::drawing::draw_cross(&img, color, cx, cy);
-    helpers::encode(&image::DynamicImage::ImageRgba8(out), fmt)
+    draw_blended(&image_bytes, color, |mask| {
+        imageproc::drawing::draw_cross_mut(mask, INK, cx, cy);
+    })
 }
 
 // ===========================================================================
@@ -598,6 +2527,161 @@ pub fn find_contours(image_bytes: Vec<u8>) -> Result<Vec<LumeContour>> {
         .collect())
 }
 
+fn contour_points(contour: &LumeContour) -> Result<Vec<Point<i32>>> {
+    if contour.points.is_empty() {
+        return Err(anyhow::anyhow!("Contour has no points"));
+    }
+    Ok(contour
+        .points
+        .iter()
+        .map(|p| Point::new(p.x, p.y))
+        .collect())
+}
+
+fn distance_to_line(p: Point<i32>, a: Point<i32>, b: Point<i32>) -> f64 {
+    let (dx, dy) = ((b.x - a.x) as f64, (b.y - a.y) as f64);
+    let (px, py) = ((p.x - a.x) as f64, (p.y - a.y) as f64);
+    let len = dx.hypot(dy);
+    if len == 0.0 {
+        return px.hypot(py);
+    }
+    (dx * py - dy * px).abs() / len
+}
+
+/// The concavities of `contour` (e.g. from `find_contours`), in contour
+/// order: for each pair of consecutive hull vertices, the contour point
+/// farthest inside the hull. Defects of zero depth are left out; the ones
+/// between fingers of a hand are the deepest.
+#[flutter_rust_bridge::frb(sync)]
+pub fn convexity_defects(contour: LumeContour) -> Result<Vec<LumeConvexityDefect>> {
+    let points = contour_points(&contour)?;
+    let hull = imageproc::geometry::convex_hull(points.clone());
+    // Hull vertices at their first position along the contour, so the
+    // contour stretches between consecutive ones are the concavities.
+    let mut vertices: Vec<usize> = hull
+        .iter()
+        .filter_map(|v| points.iter().position(|p| p == v))
+        .collect();
+    vertices.sort_unstable();
+    vertices.dedup();
+    if vertices.len() < 3 {
+        return Ok(Vec::new());
+    }
+    let n = points.len();
+    let to_lume = |p: Point<i32>| LumePoint { x: p.x, y: p.y };
+    let mut defects = Vec::new();
+    for (k, &start) in vertices.iter().enumerate() {
+        let end = vertices[(k + 1) % vertices.len()];
+        let (a, b) = (points[start], points[end]);
+        let span = (end + n - start) % n;
+        let deepest = (1..span)
+            .map(|j| points[(start + j) % n])
+            .map(|p| (p, distance_to_line(p, a, b)))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((farthest, depth)) = deepest.filter(|d| d.1 > 0.0) {
+            defects.push(LumeConvexityDefect {
+                start: to_lume(a),
+                end: to_lume(b),
+                farthest: to_lume(farthest),
+                depth: depth as f32,
+            });
+        }
+    }
+    Ok(defects)
+}
+
+/// Area, perimeter, solidity, extent, aspect ratio and eccentricity of
+/// `contour`, the usual features for classifying blobs (leaves, parts,
+/// hand poses) found with `find_contours`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn shape_descriptors(contour: LumeContour) -> Result<LumeShapeDescriptors> {
+    let points = contour_points(&contour)?;
+    let area = imageproc::geometry::contour_area(&points);
+    let perimeter = imageproc::geometry::arc_length(&points, true);
+    let hull = imageproc::geometry::convex_hull(points.clone());
+    let hull_area = imageproc::geometry::contour_area(&hull);
+
+    let min_x = points.iter().map(|p| p.x).min().unwrap_or(0);
+    let max_x = points.iter().map(|p| p.x).max().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.y).min().unwrap_or(0);
+    let max_y = points.iter().map(|p| p.y).max().unwrap_or(0);
+    let (bw, bh) = ((max_x - min_x + 1) as u32, (max_y - min_y + 1) as u32);
+
+    // Second moments of the polygon (Green's theorem); degenerate contours
+    // such as lines have no area, so fall back to those of the points.
+    let (mut m00, mut m10, mut m01) = (0.0, 0.0, 0.0);
+    let (mut m20, mut m11, mut m02) = (0.0, 0.0, 0.0);
+    for (i, p) in points.iter().enumerate() {
+        let q = points[(i + 1) % points.len()];
+        let (x0, y0, x1, y1) = (p.x as f64, p.y as f64, q.x as f64, q.y as f64);
+        let c = x0 * y1 - x1 * y0;
+        m00 += c / 2.0;
+        m10 += (x0 + x1) * c / 6.0;
+        m01 += (y0 + y1) * c / 6.0;
+        m20 += (x0 * x0 + x0 * x1 + x1 * x1) * c / 12.0;
+        m02 += (y0 * y0 + y0 * y1 + y1 * y1) * c / 12.0;
+        m11 += (x0 * y1 + 2.0 * x0 * y0 + 2.0 * x1 * y1 + x1 * y0) * c / 24.0;
+    }
+    let (cx, cy, mu20, mu11, mu02) = if m00.abs() > f64::EPSILON {
+        let (cx, cy) = (m10 / m00, m01 / m00);
+        (
+            cx,
+            cy,
+            m20 / m00 - cx * cx,
+            m11 / m00 - cx * cy,
+            m02 / m00 - cy * cy,
+        )
+    } else {
+        let n = points.len() as f64;
+        let cx = points.iter().map(|p| p.x as f64).sum::<f64>() / n;
+        let cy = points.iter().map(|p| p.y as f64).sum::<f64>() / n;
+        let moment = |f: &dyn Fn(f64, f64) -> f64| {
+            points
+                .iter()
+                .map(|p| f(p.x as f64 - cx, p.y as f64 - cy))
+                .sum::<f64>()
+                / n
+        };
+        (
+            cx,
+            cy,
+            moment(&|x, _| x * x),
+            moment(&|x, y| x * y),
+            moment(&|_, y| y * y),
+        )
+    };
+    let spread = ((mu20 - mu02).powi(2) + 4.0 * mu11 * mu11).sqrt();
+    let major = (mu20 + mu02 + spread) / 2.0;
+    let minor = ((mu20 + mu02 - spread) / 2.0).max(0.0);
+    let eccentricity = if major > 0.0 {
+        (1.0 - minor / major).sqrt()
+    } else {
+        0.0
+    };
+
+    Ok(LumeShapeDescriptors {
+        area,
+        perimeter,
+        hull_area,
+        solidity: if hull_area > 0.0 {
+            area / hull_area
+        } else {
+            0.0
+        },
+        extent: area / (bw as f64 * bh as f64),
+        aspect_ratio: bw as f64 / bh as f64,
+        eccentricity,
+        centroid_x: cx,
+        centroid_y: cy,
+        bounds: LumeRect {
+            x: min_x,
+            y: min_y,
+            width: bw,
+            height: bh,
+        },
+    })
+}
+
 // ===========================================================================
 // Distance transform (imageproc::distance_transform)
 // ===========================================================================
@@ -609,3 +2693,125 @@ pub fn distance_transform(image_bytes: Vec<u8>) -> Result<Vec<u8>> {
     let out = imageproc::distance_transform::distance_transform(&img, DistNorm::LInf);
     helpers::encode(&image::DynamicImage::ImageLuma8(out), fmt)
 }
+
+fn dist_norm(name: &str) -> Result<DistNorm> {
+    match name.to_lowercase().as_str() {
+        "l1" => Ok(DistNorm::L1),
+        "l2" => Ok(DistNorm::L2),
+        "linf" => Ok(DistNorm::LInf),
+        other => Err(anyhow::anyhow!("Unsupported norm: {}", other)),
+    }
+}
+
+/// Unclamped distances to the nearest non-zero pixel. L2 is exact
+/// Euclidean; L1 and LInf are exact city-block and chessboard distances
+/// from a two-pass chamfer scan.
+fn distances(img: &image::GrayImage, norm: DistNorm) -> Result<Vec<f32>> {
+    if img.pixels().all(|p| p.0[0] == 0) {
+        return Err(anyhow::anyhow!("Image has no foreground pixels"));
+    }
+    if let DistNorm::L2 = norm {
+        let squared = imageproc::distance_transform::euclidean_squared_distance_transform(img);
+        return Ok(squared.pixels().map(|p| p.0[0].sqrt() as f32).collect());
+    }
+    let diagonal = matches!(norm, DistNorm::LInf);
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut d: Vec<u32> = img
+        .pixels()
+        .map(|p| if p.0[0] > 0 { 0 } else { u32::MAX / 2 })
+        .collect();
+    // Neighbors already visited by a scan going forward (dx, dy); the
+    // backward scan uses the mirrored ones.
+    let mut neighbors = vec![(-1, 0), (0, -1)];
+    if diagonal {
+        neighbors.extend([(-1, -1), (1, -1)]);
+    }
+    for backward in [false, true] {
+        let sign = if backward { -1 } else { 1 };
+        for i in 0..w * h {
+            let i = if backward { w * h - 1 - i } else { i };
+            let (x, y) = ((i % w) as i64, (i / w) as i64);
+            for &(dx, dy) in &neighbors {
+                let (nx, ny) = (x + dx * sign, y + dy * sign);
+                if nx >= 0 && ny >= 0 && (nx as usize) < w && (ny as usize) < h {
+                    let n = d[ny as usize * w + nx as usize] + 1;
+                    d[i] = d[i].min(n);
+                }
+            }
+        }
+    }
+    Ok(d.into_iter().map(|v| v as f32).collect())
+}
+
+/// Distance transform with a choice of `norm` ("l1", "l2" or "linf") and
+/// output depth, for masks larger than the 255 px `distance_transform` can
+/// represent. `bit_depth` is 8 or 16; distances are stored as is and clamped
+/// to the depth's range, or scaled so the largest one is white with
+/// `normalize`. Encoded as PNG.
+#[flutter_rust_bridge::frb(sync)]
+pub fn distance_transform_with_norm(
+    image_bytes: Vec<u8>,
+    norm: String,
+    bit_depth: u8,
+    normalize: bool,
+) -> Result<Vec<u8>> {
+    let max_value = match bit_depth {
+        8 => u8::MAX as f32,
+        16 => u16::MAX as f32,
+        other => return Err(anyhow::anyhow!("Unsupported bit depth: {}", other)),
+    };
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let d = distances(&img, dist_norm(&norm)?)?;
+    let largest = d.iter().cloned().fold(0.0, f32::max);
+    let scale = if normalize && largest > 0.0 {
+        max_value / largest
+    } else {
+        1.0
+    };
+    let values = d.iter().map(|v| (v * scale).round().min(max_value));
+    let (w, h) = img.dimensions();
+    let out = if bit_depth == 8 {
+        let buf = image::GrayImage::from_raw(w, h, values.map(|v| v as u8).collect());
+        buf.map(image::DynamicImage::ImageLuma8)
+    } else {
+        let buf = image::ImageBuffer::from_raw(w, h, values.map(|v| v as u16).collect());
+        buf.map(image::DynamicImage::ImageLuma16)
+    };
+    let out = out.ok_or_else(|| anyhow::anyhow!("Distance buffer size mismatch"))?;
+    helpers::encode(&out, image::ImageFormat::Png)
+}
+
+/// Real-valued distances to the nearest foreground (non-zero) pixel, e.g. to
+/// seed a watershed or plan paths around obstacles. `norm` is "l1", "l2"
+/// (exact Euclidean) or "linf".
+#[flutter_rust_bridge::frb(sync)]
+pub fn distance_transform_values(image_bytes: Vec<u8>, norm: String) -> Result<LumeDistanceMap> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let distances = distances(&img, dist_norm(&norm)?)?;
+    Ok(LumeDistanceMap {
+        width: img.width(),
+        height: img.height(),
+        max_distance: distances.iter().cloned().fold(0.0, f32::max),
+        distances,
+    })
+}
+
+// ===========================================================================
+// Projection profiles
+// ===========================================================================
+
+/// Counts ink pixels (luma below 128) per row and per column, the usual
+/// starting point for segmenting lines and columns of binarized text.
+#[flutter_rust_bridge::frb(sync)]
+pub fn projection_profiles(image_bytes: Vec<u8>) -> Result<LumeProjectionProfiles> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let mut rows = vec![0u32; img.height() as usize];
+    let mut cols = vec![0u32; img.width() as usize];
+    for (x, y, p) in img.enumerate_pixels() {
+        if p.0[0] < 128 {
+            rows[y as usize] += 1;
+            cols[x as usize] += 1;
+        }
+    }
+    Ok(LumeProjectionProfiles { rows, cols })
+}
@@ -0,0 +1,394 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use std::f64::consts::PI;
+
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// A detected line segment from (`x1`, `y1`) to (`x2`, `y2`), in pixels of
+/// the input image. `width` is the thickness of the supporting region and
+/// `log_nfa` the detection confidence (-log10 of the expected number of
+/// false alarms; higher is more certain, anything returned is above 0).
+pub struct LumeSegment {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+    pub width: f32,
+    pub log_nfa: f32,
+}
+
+// ---------------------------------------------------------------------------
+// Line segment detection (LSD)
+// ---------------------------------------------------------------------------
+//
+// Follows R. Grompone von Gioi et al., "LSD: a Line Segment Detector"
+// (IPOL 2012): pixels are grouped into line-support regions of similar
+// gradient orientation, each region is approximated by a rectangle, and the
+// rectangle is kept when the number of aligned pixels inside it is unlikely
+// to happen by chance (a contrario validation). There are no parameters to
+// tune; the defaults below are the paper's.
+
+/// The image is first scaled down by this factor, which removes staircase
+/// effects of aliased edges.
+const SCALE: f64 = 0.8;
+/// Gradient quantization error bound.
+const QUANT: f64 = 2.0;
+/// Orientation tolerance, in radians (22.5 degrees).
+const ANGLE_TOLERANCE: f64 = PI / 8.0;
+/// Minimum share of region pixels in its rectangle.
+const MIN_DENSITY: f64 = 0.7;
+
+struct Region {
+    pixels: Vec<(usize, usize)>,
+    angle: f64,
+}
+
+struct Rect {
+    x1: f64,
+    y1: f64,
+    x2: f64,
+    y2: f64,
+    width: f64,
+    theta: f64,
+}
+
+/// Absolute difference of two angles, in [0, pi].
+fn angle_diff(a: f64, b: f64) -> f64 {
+    let d = (a - b).rem_euclid(2.0 * PI);
+    d.min(2.0 * PI - d)
+}
+
+/// ln(gamma(x)), with Windschitl's approximation for large values and
+/// Lanczos' otherwise.
+fn log_gamma(x: f64) -> f64 {
+    if x > 15.0 {
+        return 0.918938533204673 + (x - 0.5) * x.ln() - x
+            + 0.5 * x * (x * (1.0 / x).sinh() + 1.0 / (810.0 * x.powi(6))).ln();
+    }
+    const Q: [f64; 7] = [
+        75122.6331530,
+        80916.6278952,
+        36308.2951477,
+        8687.24529705,
+        1168.92649479,
+        83.8676043424,
+        2.50662827511,
+    ];
+    let mut a = (x + 0.5) * (x + 5.5).ln() - (x + 5.5);
+    let mut b = 0.0;
+    for (n, q) in Q.iter().enumerate() {
+        a -= (x + n as f64).ln();
+        b += q * x.powi(n as i32);
+    }
+    a + b.ln()
+}
+
+/// -log10 of the number of false alarms for `k` aligned pixels out of `n`,
+/// each aligned with probability `p`, among `10^log_nt` tests.
+fn log_nfa(n: usize, k: usize, p: f64, log_nt: f64) -> f64 {
+    if n == 0 || k == 0 {
+        return -log_nt;
+    }
+    if n == k {
+        return -log_nt - n as f64 * p.log10();
+    }
+    let (nf, kf) = (n as f64, k as f64);
+    let log_term = log_gamma(nf + 1.0) - log_gamma(kf + 1.0) - log_gamma(nf - kf + 1.0)
+        + kf * p.ln()
+        + (nf - kf) * (1.0 - p).ln();
+    let mut term = log_term.exp();
+    if term == 0.0 {
+        return if kf > nf * p {
+            -log_term / std::f64::consts::LN_10 - log_nt
+        } else {
+            -log_nt
+        };
+    }
+    // Sum the binomial tail until the remaining terms are negligible.
+    let mut tail = term;
+    for i in k + 1..=n {
+        let bin_term = (n - i + 1) as f64 / i as f64;
+        let mult = bin_term * p / (1.0 - p);
+        term *= mult;
+        tail += term;
+        if bin_term < 1.0 {
+            let err = term * ((1.0 - mult.powi((n - i + 1) as i32)) / (1.0 - mult) - 1.0);
+            if err < 0.1 * (-tail.log10() - log_nt).abs() * tail {
+                break;
+            }
+        }
+    }
+    -tail.log10() - log_nt
+}
+
+struct Field {
+    width: usize,
+    height: usize,
+    /// Level-line angle (along the edge) of each pixel, `None` where the
+    /// gradient is too weak to define one.
+    angles: Vec<Option<f64>>,
+    magnitudes: Vec<f64>,
+}
+
+impl Field {
+    fn new(values: &[f64], width: usize, height: usize) -> Field {
+        let threshold = QUANT / ANGLE_TOLERANCE.sin();
+        let mut angles = vec![None; width * height];
+        let mut magnitudes = vec![0.0; width * height];
+        // 2x2 differences; the last row and column have no gradient.
+        for y in 0..height.saturating_sub(1) {
+            for x in 0..width.saturating_sub(1) {
+                let i = y * width + x;
+                let (a, b) = (values[i], values[i + 1]);
+                let (c, d) = (values[i + width], values[i + width + 1]);
+                let gx = (b + d - a - c) / 2.0;
+                let gy = (c + d - a - b) / 2.0;
+                let magnitude = gx.hypot(gy);
+                magnitudes[i] = magnitude;
+                if magnitude > threshold {
+                    angles[i] = Some(gx.atan2(-gy));
+                }
+            }
+        }
+        Field {
+            width,
+            height,
+            angles,
+            magnitudes,
+        }
+    }
+
+    fn aligned(&self, x: usize, y: usize, angle: f64) -> bool {
+        self.angles[y * self.width + x].is_some_and(|a| angle_diff(a, angle) <= ANGLE_TOLERANCE)
+    }
+}
+
+/// Grows a region of similarly oriented pixels from `seed`.
+fn grow_region(field: &Field, used: &mut [bool], seed: (usize, usize)) -> Region {
+    let (w, h) = (field.width, field.height);
+    used[seed.1 * w + seed.0] = true;
+    let first = field.angles[seed.1 * w + seed.0].unwrap_or(0.0);
+    let (mut sum_cos, mut sum_sin) = (first.cos(), first.sin());
+    let mut region = Region {
+        pixels: vec![seed],
+        angle: first,
+    };
+    let mut i = 0;
+    while i < region.pixels.len() {
+        let (px, py) = region.pixels[i];
+        for ny in py.saturating_sub(1)..=(py + 1).min(h - 1) {
+            for nx in px.saturating_sub(1)..=(px + 1).min(w - 1) {
+                let n = ny * w + nx;
+                if used[n] || !field.aligned(nx, ny, region.angle) {
+                    continue;
+                }
+                used[n] = true;
+                region.pixels.push((nx, ny));
+                let a = field.angles[n].unwrap_or(0.0);
+                sum_cos += a.cos();
+                sum_sin += a.sin();
+                region.angle = sum_sin.atan2(sum_cos);
+            }
+        }
+        i += 1;
+    }
+    region
+}
+
+/// The smallest rectangle along the region's principal axis (weighted by
+/// gradient magnitude) that holds all its pixels.
+fn region_rect(field: &Field, region: &Region) -> Rect {
+    let weight = |&(x, y): &(usize, usize)| field.magnitudes[y * field.width + x];
+    let total: f64 = region.pixels.iter().map(weight).sum();
+    let (cx, cy) = region.pixels.iter().fold((0.0, 0.0), |(sx, sy), p| {
+        (sx + p.0 as f64 * weight(p), sy + p.1 as f64 * weight(p))
+    });
+    let (cx, cy) = (cx / total, cy / total);
+
+    let (mut ixx, mut iyy, mut ixy) = (0.0, 0.0, 0.0);
+    for p in &region.pixels {
+        let (dx, dy) = (p.0 as f64 - cx, p.1 as f64 - cy);
+        ixx += weight(p) * dy * dy;
+        iyy += weight(p) * dx * dx;
+        ixy -= weight(p) * dx * dy;
+    }
+    let lambda = 0.5 * (ixx + iyy - ((ixx - iyy).powi(2) + 4.0 * ixy * ixy).sqrt());
+    let mut theta = if ixx.abs() > iyy.abs() {
+        (lambda - ixx).atan2(ixy)
+    } else {
+        ixy.atan2(lambda - iyy)
+    };
+    if angle_diff(theta, region.angle) > ANGLE_TOLERANCE {
+        theta += PI;
+    }
+
+    let (dx, dy) = (theta.cos(), theta.sin());
+    let (mut l_min, mut l_max, mut w_min, mut w_max) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+    for p in &region.pixels {
+        let (px, py) = (p.0 as f64 - cx, p.1 as f64 - cy);
+        let l = px * dx + py * dy;
+        let w = -px * dy + py * dx;
+        l_min = l_min.min(l);
+        l_max = l_max.max(l);
+        w_min = w_min.min(w);
+        w_max = w_max.max(w);
+    }
+    // Center the rectangle across the region.
+    let shift = (w_min + w_max) / 2.0;
+    let (cx, cy) = (cx - shift * dy, cy + shift * dx);
+    Rect {
+        x1: cx + l_min * dx,
+        y1: cy + l_min * dy,
+        x2: cx + l_max * dx,
+        y2: cy + l_max * dy,
+        width: (w_max - w_min).max(1.0),
+        theta,
+    }
+}
+
+/// Pixels inside `rect` and how many of them are aligned with it.
+fn rect_counts(field: &Field, rect: &Rect) -> (usize, usize) {
+    let (dx, dy) = (rect.theta.cos(), rect.theta.sin());
+    let length = (rect.x2 - rect.x1).hypot(rect.y2 - rect.y1);
+    let half = rect.width / 2.0;
+    let pad = half + 1.0;
+    let clamp_x = |v: f64| v.clamp(0.0, field.width as f64 - 1.0) as usize;
+    let clamp_y = |v: f64| v.clamp(0.0, field.height as f64 - 1.0) as usize;
+    let (x0, x1) = (
+        clamp_x(rect.x1.min(rect.x2) - pad),
+        clamp_x(rect.x1.max(rect.x2) + pad),
+    );
+    let (y0, y1) = (
+        clamp_y(rect.y1.min(rect.y2) - pad),
+        clamp_y(rect.y1.max(rect.y2) + pad),
+    );
+    let (mut n, mut k) = (0, 0);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let (px, py) = (x as f64 - rect.x1, y as f64 - rect.y1);
+            let l = px * dx + py * dy;
+            let w = -px * dy + py * dx;
+            if l < -0.5 || l > length + 0.5 || w.abs() > half {
+                continue;
+            }
+            n += 1;
+            if field.aligned(x, y, rect.theta) {
+                k += 1;
+            }
+        }
+    }
+    (n, k)
+}
+
+fn rect_nfa(field: &Field, rect: &Rect, log_nt: f64) -> f64 {
+    let (n, k) = rect_counts(field, rect);
+    log_nfa(n, k, ANGLE_TOLERANCE / PI, log_nt)
+}
+
+fn density(region: &Region, rect: &Rect) -> f64 {
+    let length = (rect.x2 - rect.x1).hypot(rect.y2 - rect.y1);
+    region.pixels.len() as f64 / (length * rect.width).max(1.0)
+}
+
+/// Drops region pixels far from the seed until the region fills its
+/// rectangle well enough, as happens when a region wraps around a curve.
+fn refine(field: &Field, mut region: Region, mut rect: Rect) -> Option<(Region, Rect)> {
+    let seed = region.pixels[0];
+    let dist = |p: &(usize, usize)| (p.0 as f64 - seed.0 as f64).hypot(p.1 as f64 - seed.1 as f64);
+    let mut radius = region.pixels.iter().map(dist).fold(0.0, f64::max);
+    while density(&region, &rect) < MIN_DENSITY {
+        radius *= 0.75;
+        region.pixels.retain(|p| dist(p) <= radius);
+        if region.pixels.len() < 2 {
+            return None;
+        }
+        rect = region_rect(field, &region);
+    }
+    Some((region, rect))
+}
+
+/// Narrows `rect` while that makes it more significant.
+fn improve(field: &Field, mut rect: Rect, log_nt: f64) -> (Rect, f64) {
+    let mut best = rect_nfa(field, &rect, log_nt);
+    for _ in 0..5 {
+        if rect.width - 0.5 < 1.0 {
+            break;
+        }
+        let narrower = Rect {
+            width: rect.width - 0.5,
+            ..rect
+        };
+        let nfa = rect_nfa(field, &narrower, log_nt);
+        if nfa <= best {
+            break;
+        }
+        best = nfa;
+        rect = narrower;
+    }
+    (rect, best)
+}
+
+/// Detects straight line segments with the LSD algorithm and returns their
+/// endpoints, unlike Hough transforms which only give infinite lines. Suited
+/// to finding document edges, building facades or any straight structure;
+/// segments follow edges with a consistent side of dark and light, so the
+/// two borders of a thin line come back as two segments.
+#[flutter_rust_bridge::frb(sync)]
+pub fn detect_line_segments(image_bytes: Vec<u8>) -> Result<Vec<LumeSegment>> {
+    let img = helpers::load(&image_bytes)?.to_luma8();
+    let (w, h) = img.dimensions();
+    let blurred = imageproc::filter::gaussian_blur_f32(&img, (0.6 / SCALE) as f32);
+    let (sw, sh) = (
+        ((w as f64 * SCALE).round() as u32).max(2),
+        ((h as f64 * SCALE).round() as u32).max(2),
+    );
+    let small = image::imageops::resize(&blurred, sw, sh, FilterType::Triangle);
+    let values: Vec<f64> = small.pixels().map(|p| p.0[0] as f64).collect();
+    let field = Field::new(&values, sw as usize, sh as usize);
+
+    let p = ANGLE_TOLERANCE / PI;
+    let log_nt = 5.0 * ((sw as f64).log10() + (sh as f64).log10()) / 2.0 + 11f64.log10();
+    let min_region = (-log_nt / p.log10()) as usize;
+
+    // Seeds in order of decreasing gradient magnitude.
+    let mut seeds: Vec<usize> = (0..field.angles.len())
+        .filter(|&i| field.angles[i].is_some())
+        .collect();
+    seeds.sort_by(|&a, &b| field.magnitudes[b].total_cmp(&field.magnitudes[a]));
+
+    let mut used = vec![false; field.angles.len()];
+    let mut segments = Vec::new();
+    for seed in seeds {
+        if used[seed] {
+            continue;
+        }
+        let region = grow_region(&field, &mut used, (seed % field.width, seed / field.width));
+        if region.pixels.len() < min_region {
+            continue;
+        }
+        let rect = region_rect(&field, &region);
+        let Some((_, rect)) = refine(&field, region, rect) else {
+            continue;
+        };
+        let (rect, nfa) = improve(&field, rect, log_nt);
+        if nfa <= 0.0 {
+            continue;
+        }
+        // Gradients sit between pixels; move to pixel centers and back to
+        // input coordinates.
+        let to_input = |v: f64| ((v + 0.5) / SCALE) as f32;
+        segments.push(LumeSegment {
+            x1: to_input(rect.x1),
+            y1: to_input(rect.y1),
+            x2: to_input(rect.x2),
+            y2: to_input(rect.y2),
+            width: (rect.width / SCALE) as f32,
+            log_nfa: nfa as f32,
+        });
+    }
+    Ok(segments)
+}
@@ -0,0 +1,278 @@
+use anyhow::Result;
+use image::{DynamicImage, GrayImage, ImageFormat, Luma, Rgba, RgbaImage};
+use imageproc::distance_transform::Norm;
+use std::collections::VecDeque;
+
+use crate::api::image_ops::LumeColor;
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// A mask as raw pixels, so masks move between calls without being encoded
+/// as images. With `packed`, `data` holds 1 bit per pixel (1 = inside),
+/// most significant bit first, each row padded to a whole byte; otherwise
+/// one byte per pixel (255 = inside, 0 = outside, values in between for
+/// soft edges). Packed masks are 8 times smaller than 8-bit ones, which is
+/// what thresholding and segmentation produce anyway.
+pub struct LumeMask {
+    pub width: u32,
+    pub height: u32,
+    pub packed: bool,
+    pub data: Vec<u8>,
+}
+
+// ---------------------------------------------------------------------------
+// Conversion
+// ---------------------------------------------------------------------------
+
+fn packed_row_bytes(width: u32) -> usize {
+    width.div_ceil(8) as usize
+}
+
+/// `img` as a mask; packing keeps pixels of 128 and above.
+pub(crate) fn from_gray(img: &GrayImage, packed: bool) -> LumeMask {
+    let (width, height) = img.dimensions();
+    let data = if packed {
+        let stride = packed_row_bytes(width);
+        let mut data = vec![0u8; stride * height as usize];
+        for (x, y, p) in img.enumerate_pixels() {
+            if p.0[0] >= 128 {
+                data[y as usize * stride + x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        data
+    } else {
+        img.as_raw().clone()
+    };
+    LumeMask {
+        width,
+        height,
+        packed,
+        data,
+    }
+}
+
+/// The mask as 8-bit grayscale (255 = inside).
+pub(crate) fn to_gray(mask: &LumeMask) -> Result<GrayImage> {
+    let (width, height) = (mask.width, mask.height);
+    let stride = if mask.packed {
+        packed_row_bytes(width)
+    } else {
+        width as usize
+    };
+    let expected = stride * height as usize;
+    if mask.data.len() != expected {
+        return Err(anyhow::anyhow!(
+            "Mask data is {} bytes, expected {} for {}x{}",
+            mask.data.len(),
+            expected,
+            width,
+            height
+        ));
+    }
+    if !mask.packed {
+        return GrayImage::from_raw(width, height, mask.data.clone())
+            .ok_or_else(|| anyhow::anyhow!("Mask buffer size mismatch"));
+    }
+    Ok(GrayImage::from_fn(width, height, |x, y| {
+        let byte = mask.data[y as usize * stride + x as usize / 8];
+        Luma([if byte & (0x80 >> (x % 8)) != 0 {
+            255
+        } else {
+            0
+        }])
+    }))
+}
+
+/// `to_gray` for a mask applied to a `width` x `height` image.
+pub(crate) fn to_gray_sized(mask: &LumeMask, width: u32, height: u32) -> Result<GrayImage> {
+    if (mask.width, mask.height) != (width, height) {
+        return Err(anyhow::anyhow!(
+            "Mask is {}x{} but the image is {}x{}",
+            mask.width,
+            mask.height,
+            width,
+            height
+        ));
+    }
+    to_gray(mask)
+}
+
+/// Reads a mask from an encoded image (its luma), e.g. one painted in Dart.
+#[flutter_rust_bridge::frb(sync)]
+pub fn mask_from_image(mask_bytes: Vec<u8>, packed: bool) -> Result<LumeMask> {
+    Ok(from_gray(&helpers::load(&mask_bytes)?.to_luma8(), packed))
+}
+
+/// Encodes a mask as a grayscale PNG: 1-bit for packed masks, which keeps
+/// the file tiny, 8-bit otherwise.
+#[flutter_rust_bridge::frb(sync)]
+pub fn mask_to_png(mask: LumeMask) -> Result<Vec<u8>> {
+    if !mask.packed {
+        return helpers::encode(&DynamicImage::ImageLuma8(to_gray(&mask)?), ImageFormat::Png);
+    }
+    // Rejects data of the wrong length before the encoder sees it.
+    to_gray(&mask)?;
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, mask.width, mask.height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&mask.data)?;
+    writer.finish()?;
+    Ok(bytes)
+}
+
+// ---------------------------------------------------------------------------
+// Morphology
+// ---------------------------------------------------------------------------
+
+/// Morphological reconstruction by dilation of `marker` under `mask`
+/// (8-connected), with the hybrid raster scan and queue algorithm of
+/// L. Vincent, "Morphological Grayscale Reconstruction in Image Analysis"
+/// (1993). Both images must have the same size.
+fn reconstruct_gray(marker: &GrayImage, mask: &GrayImage) -> GrayImage {
+    let (w, h) = (mask.width() as usize, mask.height() as usize);
+    let m = mask.as_raw();
+    let mut out: Vec<u8> = marker
+        .as_raw()
+        .iter()
+        .zip(m)
+        .map(|(&a, &b)| a.min(b))
+        .collect();
+    // Neighbors already visited in a forward scan; a backward scan visits
+    // the mirrored ones.
+    const CAUSAL: [(isize, isize); 4] = [(-1, -1), (0, -1), (1, -1), (-1, 0)];
+    let at = |x: usize, y: usize, (dx, dy): (isize, isize)| {
+        let (nx, ny) = (x as isize + dx, y as isize + dy);
+        (nx >= 0 && ny >= 0 && (nx as usize) < w && (ny as usize) < h)
+            .then(|| ny as usize * w + nx as usize)
+    };
+
+    for y in 0..h {
+        for x in 0..w {
+            let i = y * w + x;
+            let v = CAUSAL
+                .iter()
+                .filter_map(|&d| at(x, y, d))
+                .fold(out[i], |v, n| v.max(out[n]));
+            out[i] = v.min(m[i]);
+        }
+    }
+    let mut queue = VecDeque::new();
+    for y in (0..h).rev() {
+        for x in (0..w).rev() {
+            let i = y * w + x;
+            let v = CAUSAL
+                .iter()
+                .filter_map(|&(dx, dy)| at(x, y, (-dx, -dy)))
+                .fold(out[i], |v, n| v.max(out[n]));
+            out[i] = v.min(m[i]);
+            let grows = CAUSAL
+                .iter()
+                .filter_map(|&(dx, dy)| at(x, y, (-dx, -dy)))
+                .any(|n| out[n] < out[i] && out[n] < m[n]);
+            if grows {
+                queue.push_back((x, y));
+            }
+        }
+    }
+    while let Some((x, y)) = queue.pop_front() {
+        let i = y * w + x;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let Some(n) = at(x, y, (dx, dy)) else {
+                    continue;
+                };
+                if out[n] < out[i] && out[n] != m[n] {
+                    out[n] = out[i].min(m[n]);
+                    queue.push_back((n % w, n / w));
+                }
+            }
+        }
+    }
+    GrayImage::from_raw(w as u32, h as u32, out).expect("buffer matches dimensions")
+}
+
+/// Grows `marker` inside `mask`: every connected part of `mask` that a
+/// marker touches comes back whole, the others disappear. Keeps, for
+/// example, only the blobs of a threshold mask that contain a seed. Works
+/// on soft masks too (grayscale reconstruction). The result is packed when
+/// `mask` is.
+#[flutter_rust_bridge::frb(sync)]
+pub fn reconstruct(marker: LumeMask, mask: LumeMask) -> Result<LumeMask> {
+    let bounds = to_gray(&mask)?;
+    let seeds = to_gray_sized(&marker, mask.width, mask.height)?;
+    Ok(from_gray(&reconstruct_gray(&seeds, &bounds), mask.packed))
+}
+
+/// Fills the holes of `mask`: areas outside it that do not reach the image
+/// border become inside. The result is packed when `mask` is.
+#[flutter_rust_bridge::frb(sync)]
+pub fn fill_holes(mask: LumeMask) -> Result<LumeMask> {
+    let img = to_gray(&mask)?;
+    let (w, h) = img.dimensions();
+    // Reconstruct the outside from the border, then everything it does not
+    // reach is a hole.
+    let mut outside = img.clone();
+    image::imageops::invert(&mut outside);
+    let border = GrayImage::from_fn(w, h, |x, y| {
+        if x == 0 || y == 0 || x == w - 1 || y == h - 1 {
+            *outside.get_pixel(x, y)
+        } else {
+            Luma([0])
+        }
+    });
+    let mut filled = reconstruct_gray(&border, &outside);
+    image::imageops::invert(&mut filled);
+    Ok(from_gray(&filled, mask.packed))
+}
+
+// ---------------------------------------------------------------------------
+// Outline
+// ---------------------------------------------------------------------------
+
+/// The pixels of `mask` within `thickness` pixels of its edge, as 255. The
+/// image border does not count as an edge, so selections running off the
+/// image are not outlined there.
+fn border_gray(mask: &LumeMask, thickness: u8) -> Result<GrayImage> {
+    if thickness == 0 {
+        return Err(anyhow::anyhow!("Outline thickness must be at least 1"));
+    }
+    let inside = imageproc::contrast::threshold(
+        &to_gray(mask)?,
+        127,
+        imageproc::contrast::ThresholdType::Binary,
+    );
+    let core = imageproc::morphology::erode(&inside, Norm::LInf, thickness);
+    Ok(GrayImage::from_fn(mask.width, mask.height, |x, y| {
+        let edge = inside.get_pixel(x, y).0[0] > 0 && core.get_pixel(x, y).0[0] == 0;
+        Luma([if edge { 255 } else { 0 }])
+    }))
+}
+
+/// The inner boundary of `mask`, `thickness` pixels wide. The result is
+/// packed when `mask` is.
+#[flutter_rust_bridge::frb(sync)]
+pub fn mask_border(mask: LumeMask, thickness: u8) -> Result<LumeMask> {
+    Ok(from_gray(&border_gray(&mask, thickness)?, mask.packed))
+}
+
+/// A transparent PNG of the mask size with the boundary of `mask` stroked in
+/// `color`, `thickness` pixels wide on the inside, to draw selection edges
+/// over the photo.
+#[flutter_rust_bridge::frb(sync)]
+pub fn mask_outline(mask: LumeMask, thickness: u8, color: LumeColor) -> Result<Vec<u8>> {
+    let border = border_gray(&mask, thickness)?;
+    let stroke = Rgba([color.r, color.g, color.b, color.a]);
+    let out = RgbaImage::from_fn(mask.width, mask.height, |x, y| {
+        if border.get_pixel(x, y).0[0] > 0 {
+            stroke
+        } else {
+            Rgba([0, 0, 0, 0])
+        }
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), ImageFormat::Png)
+}
@@ -0,0 +1,225 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageFormat, Luma, RgbImage};
+use std::io::Cursor;
+use tract_onnx::prelude::*;
+
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Model loading
+// ---------------------------------------------------------------------------
+
+/// Input size used when a matting model does not declare a fixed one
+/// (U²-Net's native resolution).
+const MATTING_INPUT_SIZE: usize = 320;
+const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+type Plan = TypedRunnableModel<TypedModel>;
+
+fn load_model(model_bytes: &[u8]) -> Result<InferenceModel> {
+    tract_onnx::onnx().model_for_read(&mut Cursor::new(model_bytes))
+}
+
+/// Height and width of the first input when the model fixes them.
+fn declared_input_size(model: &InferenceModel) -> Option<(usize, usize)> {
+    let shape = model
+        .input_fact(0)
+        .ok()?
+        .shape
+        .as_concrete_finite()
+        .ok()??;
+    match shape.as_slice() {
+        [_, _, h, w] => Some((*h, *w)),
+        _ => None,
+    }
+}
+
+/// Optimizes the model for a 1x3xHxW float input.
+fn compile(model: InferenceModel, height: usize, width: usize) -> Result<Plan> {
+    model
+        .with_input_fact(0, f32::fact([1, 3, height, width]).into())?
+        .into_optimized()?
+        .into_runnable()
+}
+
+/// NCHW tensor from an RGB image scaled to [0, 1] and then normalized per
+/// channel.
+fn to_tensor(img: &RgbImage, mean: [f32; 3], std: [f32; 3]) -> Tensor {
+    let (w, h) = img.dimensions();
+    tract_ndarray::Array4::from_shape_fn((1, 3, h as usize, w as usize), |(_, c, y, x)| {
+        (img.get_pixel(x as u32, y as u32).0[c] as f32 / 255.0 - mean[c]) / std[c]
+    })
+    .into()
+}
+
+// ---------------------------------------------------------------------------
+// Background removal
+// ---------------------------------------------------------------------------
+
+/// Cuts out the subject with a salient-object / portrait-matting ONNX model
+/// (U²-Net, MODNet and similar: ImageNet-normalized RGB in, one matte out).
+/// The matte becomes the alpha channel, so the result is always a PNG.
+#[flutter_rust_bridge::frb(sync)]
+pub fn remove_background(image_bytes: Vec<u8>, model_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?;
+    let model = load_model(&model_bytes)?;
+    let (mh, mw) = declared_input_size(&model).unwrap_or((MATTING_INPUT_SIZE, MATTING_INPUT_SIZE));
+    let plan = compile(model, mh, mw)?;
+
+    let input = image::imageops::resize(&img.to_rgb8(), mw as u32, mh as u32, FilterType::Triangle);
+    let outputs = plan.run(tvec!(to_tensor(&input, IMAGENET_MEAN, IMAGENET_STD).into()))?;
+    let matte = outputs[0].to_array_view::<f32>()?;
+    let shape = matte.shape();
+    if shape.len() < 2 {
+        return Err(anyhow::anyhow!(
+            "Unexpected model output shape: {:?}",
+            shape
+        ));
+    }
+    let (oh, ow) = (shape[shape.len() - 2], shape[shape.len() - 1]);
+    let values: Vec<f32> = matte.iter().take(oh * ow).cloned().collect();
+
+    // Stretch the matte to the full range; raw U²-Net outputs rarely reach 0 and 1.
+    let lo = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let hi = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = (hi - lo).max(1e-6);
+    let small = GrayImage::from_fn(ow as u32, oh as u32, |x, y| {
+        let v = (values[y as usize * ow + x as usize] - lo) / range;
+        Luma([(v * 255.0).round() as u8])
+    });
+    let alpha = image::imageops::resize(&small, img.width(), img.height(), FilterType::Triangle);
+
+    let mut out = img.to_rgba8();
+    for (p, a) in out.pixels_mut().zip(alpha.pixels()) {
+        p.0[3] = (p.0[3] as u16 * a.0[0] as u16 / 255) as u8;
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(out), ImageFormat::Png)
+}
+
+// ---------------------------------------------------------------------------
+// Super-resolution
+// ---------------------------------------------------------------------------
+
+/// Tile edge fed to super-resolution models without a fixed input size, and
+/// the margin each tile shares with its neighbours to hide seams.
+const UPSCALE_TILE: usize = 128;
+const UPSCALE_OVERLAP: usize = 8;
+
+/// Start offsets of tiles of `tile` pixels covering `len`, each overlapping
+/// the previous one by `2 * overlap`. The last tile is clamped to the end.
+fn tile_starts(len: usize, tile: usize, overlap: usize) -> Vec<usize> {
+    let step = (tile - 2 * overlap).max(1);
+    let mut starts = Vec::new();
+    let mut x = 0;
+    loop {
+        if x + tile >= len {
+            starts.push(len.saturating_sub(tile));
+            return starts;
+        }
+        starts.push(x);
+        x += step;
+    }
+}
+
+/// Upscales with an ESRGAN-class ONNX model (RGB in [0, 1] in, scaled RGB
+/// out). The image is processed in overlapping tiles so memory stays bounded
+/// on large photos. If `factor` differs from the model's native scale the
+/// result is resized to match; alpha is scaled conventionally.
+#[flutter_rust_bridge::frb(sync)]
+pub fn upscale(image_bytes: Vec<u8>, factor: u32, model_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    if factor == 0 {
+        return Err(anyhow::anyhow!("Upscale factor must be at least 1"));
+    }
+    let rgb = img.to_rgb8();
+    let (w, h) = (rgb.width() as usize, rgb.height() as usize);
+    if w == 0 || h == 0 {
+        return Err(anyhow::anyhow!("Image is empty"));
+    }
+    let model = load_model(&model_bytes)?;
+    let (th, tw) = declared_input_size(&model).unwrap_or((UPSCALE_TILE, UPSCALE_TILE));
+    if th == 0 || tw == 0 {
+        return Err(anyhow::anyhow!("Model declares an empty input size"));
+    }
+    let plan = compile(model, th, tw)?;
+    let overlap = UPSCALE_OVERLAP.min(th.min(tw) / 4);
+
+    // Replicate edges so every tile is full size, even for tiny images.
+    let (pw, ph) = (w.max(tw), h.max(th));
+    let padded = RgbImage::from_fn(pw as u32, ph as u32, |x, y| {
+        *rgb.get_pixel(x.min(w as u32 - 1), y.min(h as u32 - 1))
+    });
+
+    let xs = tile_starts(pw, tw, overlap);
+    let ys = tile_starts(ph, th, overlap);
+    let mut canvas: Option<(RgbImage, usize)> = None;
+    for (yi, &y0) in ys.iter().enumerate() {
+        for (xi, &x0) in xs.iter().enumerate() {
+            let tile =
+                image::imageops::crop_imm(&padded, x0 as u32, y0 as u32, tw as u32, th as u32)
+                    .to_image();
+            let outputs = plan.run(tvec!(to_tensor(&tile, [0.0; 3], [1.0; 3]).into()))?;
+            let out = outputs[0].to_array_view::<f32>()?;
+            // Both output sides must be the same whole multiple of the tile,
+            // or the copy below would read past the output.
+            let scale = match *out.shape() {
+                [batch, 3, oh, ow]
+                    if batch > 0 && oh >= th && oh % th == 0 && ow == oh / th * tw =>
+                {
+                    oh / th
+                }
+                ref shape => {
+                    return Err(anyhow::anyhow!(
+                        "Unexpected model output shape: {:?}",
+                        shape
+                    ))
+                }
+            };
+            let (canvas, canvas_scale) = canvas.get_or_insert_with(|| {
+                (
+                    RgbImage::new((pw * scale) as u32, (ph * scale) as u32),
+                    scale,
+                )
+            });
+            if *canvas_scale != scale {
+                return Err(anyhow::anyhow!("Model output scale differs between tiles"));
+            }
+
+            // Keep only the tile's core; the overlap goes to the neighbour.
+            let left = if xi == 0 { 0 } else { overlap };
+            let top = if yi == 0 { 0 } else { overlap };
+            let right = if xi + 1 == xs.len() { 0 } else { overlap };
+            let bottom = if yi + 1 == ys.len() { 0 } else { overlap };
+            for ty in top * scale..(th - bottom) * scale {
+                for tx in left * scale..(tw - right) * scale {
+                    let px = image::Rgb(std::array::from_fn(|c| {
+                        (out[[0, c, ty, tx]].clamp(0.0, 1.0) * 255.0).round() as u8
+                    }));
+                    canvas.put_pixel((x0 * scale + tx) as u32, (y0 * scale + ty) as u32, px);
+                }
+            }
+        }
+    }
+    let (canvas, scale) = canvas.ok_or_else(|| anyhow::anyhow!("Image is empty"))?;
+
+    let native =
+        image::imageops::crop_imm(&canvas, 0, 0, (w * scale) as u32, (h * scale) as u32).to_image();
+    let (ow, oh) = (w as u32 * factor, h as u32 * factor);
+    let rgb_out = if scale as u32 == factor {
+        native
+    } else {
+        image::imageops::resize(&native, ow, oh, FilterType::Lanczos3)
+    };
+    if !img.color().has_alpha() {
+        return helpers::encode(&DynamicImage::ImageRgb8(rgb_out), fmt);
+    }
+    let alpha = image::imageops::resize(&img.to_rgba8(), ow, oh, FilterType::CatmullRom);
+    let mut out = image::RgbaImage::new(ow, oh);
+    for ((o, c), a) in out.pixels_mut().zip(rgb_out.pixels()).zip(alpha.pixels()) {
+        *o = image::Rgba([c.0[0], c.0[1], c.0[2], a.0[3]]);
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
@@ -1,3 +1,27 @@
 pub mod simple;
 pub mod image_ops;
 pub mod imageproc_ops;
+pub mod handle;
+pub mod benchmark;
+pub mod effects;
+pub mod selection;
+pub mod analysis;
+pub mod barcode;
+pub mod registration;
+pub mod export;
+pub mod pipeline;
+pub mod session;
+pub mod drawing;
+pub mod visualize;
+pub mod frames;
+pub mod mask;
+pub mod lines;
+pub mod calibration;
+#[cfg(feature = "svg")]
+pub mod svg;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+#[cfg(feature = "ml")]
+pub mod ml;
+#[cfg(feature = "gpu")]
+pub mod gpu;
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat};
+use pdfium_render::prelude::*;
+
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Rendering
+// ---------------------------------------------------------------------------
+
+/// Renders one page (0-based) of a PDF to a PNG at `dpi` dots per inch (72
+/// gives one pixel per PDF point). Uses the pdfium library bundled with the
+/// app, so the result can go straight into the drawing and filter APIs.
+#[flutter_rust_bridge::frb(sync)]
+pub fn render_pdf_page(pdf_bytes: Vec<u8>, page_index: u32, dpi: f32) -> Result<Vec<u8>> {
+    if dpi <= 0.0 {
+        return Err(anyhow::anyhow!("DPI must be positive"));
+    }
+    let pdfium = Pdfium::new(Pdfium::bind_to_system_library()?);
+    let document = pdfium.load_pdf_from_byte_slice(&pdf_bytes, None)?;
+    let pages = document.pages();
+    let index = u16::try_from(page_index)
+        .ok()
+        .filter(|&i| i < pages.len())
+        .ok_or_else(|| anyhow::anyhow!("Page {} is out of range", page_index))?;
+    let page = pages.get(index)?;
+    let width = (page.width().to_inches() * dpi).round().max(1.0) as i32;
+    let height = (page.height().to_inches() * dpi).round().max(1.0) as i32;
+    let config = PdfRenderConfig::new()
+        .set_target_width(width)
+        .set_target_height(height);
+    let img = page.render_with_config(&config)?.as_image();
+    helpers::encode(&DynamicImage::ImageRgba8(img.to_rgba8()), ImageFormat::Png)
+}
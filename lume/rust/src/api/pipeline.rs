@@ -0,0 +1,395 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::api::image_ops;
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// One step of an edit pipeline. Each variant does the same as the standalone
+/// function of the same name, without the decode and encode around it.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum LumeOp {
+    Resize {
+        width: u32,
+        height: u32,
+        keep_aspect_ratio: bool,
+    },
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Clockwise, in multiples of 90 degrees.
+    Rotate {
+        degrees: u32,
+    },
+    FlipHorizontal,
+    FlipVertical,
+    Grayscale,
+    Brightness {
+        value: i32,
+    },
+    Contrast {
+        value: f32,
+    },
+    Blur {
+        sigma: f32,
+    },
+    Sharpen {
+        sigma: f32,
+        threshold: i32,
+    },
+    Invert,
+    HueRotate {
+        degrees: i32,
+    },
+    Levels {
+        black_point: f32,
+        white_point: f32,
+        gamma: f32,
+    },
+}
+
+/// Serialized form of a pipeline. `version` lets future readers migrate old
+/// recipes when ops change.
+#[derive(Serialize, Deserialize)]
+struct PipelineJson {
+    version: u32,
+    ops: Vec<LumeOp>,
+}
+
+const PIPELINE_JSON_VERSION: u32 = 1;
+
+/// A batch item: encoded image bytes, or a path to read them from.
+pub enum LumeBatchInput {
+    Bytes(Vec<u8>),
+    Path(String),
+}
+
+/// Outcome of one batch item: the encoded result (in the input's format) or
+/// the error message.
+pub struct LumeBatchResult {
+    pub bytes: Option<Vec<u8>>,
+    pub error: Option<String>,
+}
+
+/// Progress of a running `process_batch`, shared with Dart so it can poll
+/// while the batch runs. Use a new tracker for each batch.
+#[flutter_rust_bridge::frb(opaque)]
+pub struct LumeBatchTracker {
+    total: AtomicUsize,
+    /// Indices of finished items, in completion order, with their success.
+    finished: Mutex<Vec<(u32, bool)>>,
+}
+
+pub struct LumeBatchProgress {
+    pub total: u32,
+    pub completed: u32,
+    pub failed: u32,
+    /// Indices of finished items, in completion order.
+    pub finished: Vec<u32>,
+}
+
+// ---------------------------------------------------------------------------
+// Pipeline
+// ---------------------------------------------------------------------------
+
+/// `LumeOp::Rotate` only turns by quarter turns; anything else is refused
+/// rather than silently skipped.
+pub(crate) fn unaligned_rotation(degrees: u32) -> anyhow::Error {
+    anyhow::anyhow!("Rotation must be a multiple of 90 degrees, got {}", degrees)
+}
+
+pub(crate) fn apply_op(img: DynamicImage, op: &LumeOp) -> Result<DynamicImage> {
+    Ok(match *op {
+        LumeOp::Resize {
+            width,
+            height,
+            keep_aspect_ratio: true,
+        } => img.resize(width, height, FilterType::Lanczos3),
+        LumeOp::Resize { width, height, .. } => {
+            img.resize_exact(width, height, FilterType::Lanczos3)
+        }
+        LumeOp::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => img.crop_imm(x, y, width, height),
+        LumeOp::Rotate { degrees } => match degrees % 360 {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            0 => img,
+            _ => return Err(unaligned_rotation(degrees)),
+        },
+        LumeOp::FlipHorizontal => img.fliph(),
+        LumeOp::FlipVertical => img.flipv(),
+        LumeOp::Grayscale => img.grayscale(),
+        LumeOp::Brightness { value } => img.brighten(value),
+        LumeOp::Contrast { value } => img.adjust_contrast(value),
+        LumeOp::Blur { sigma } => img.blur(sigma),
+        LumeOp::Sharpen { sigma, threshold } => img.unsharpen(sigma, threshold),
+        LumeOp::Invert => {
+            let mut img = img;
+            img.invert();
+            img
+        }
+        LumeOp::HueRotate { degrees } => img.huerotate(degrees),
+        LumeOp::Levels {
+            black_point,
+            white_point,
+            gamma,
+        } => {
+            let mut img = img;
+            image_ops::apply_levels(&mut img, black_point, white_point, gamma)?;
+            img
+        }
+    })
+}
+
+/// `op` adjusted for an image scaled by `factor`: pixel sizes, offsets and
+/// blur radii scale with it, so the result matches a downscaled render of the
+/// full-size result.
+pub(crate) fn scale_op(op: &LumeOp, factor: f32) -> LumeOp {
+    let px = |v: u32| (v as f32 * factor).round() as u32;
+    match *op {
+        LumeOp::Resize {
+            width,
+            height,
+            keep_aspect_ratio,
+        } => LumeOp::Resize {
+            width: px(width).max(1),
+            height: px(height).max(1),
+            keep_aspect_ratio,
+        },
+        LumeOp::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => LumeOp::Crop {
+            x: px(x),
+            y: px(y),
+            width: px(width).max(1),
+            height: px(height).max(1),
+        },
+        LumeOp::Blur { sigma } => LumeOp::Blur {
+            sigma: sigma * factor,
+        },
+        LumeOp::Sharpen { sigma, threshold } => LumeOp::Sharpen {
+            sigma: sigma * factor,
+            threshold,
+        },
+        ref other => other.clone(),
+    }
+}
+
+pub(crate) fn apply_ops(img: DynamicImage, ops: &[LumeOp]) -> Result<DynamicImage> {
+    ops.iter().try_fold(img, apply_op)
+}
+
+/// Decodes `bytes`, runs `ops` and encodes the result in the same format.
+#[flutter_rust_bridge::frb(sync)]
+pub fn apply_pipeline(image_bytes: Vec<u8>, ops: Vec<LumeOp>) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    helpers::encode(&apply_ops(img, &ops)?, fmt)
+}
+
+/// Serializes a pipeline to JSON, so edit recipes can be saved, shared and
+/// re-applied later with `pipeline_from_json`. Example:
+/// `{"version":1,"ops":[{"op":"resize","width":800,"height":600,
+/// "keep_aspect_ratio":true},{"op":"grayscale"}]}`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn pipeline_to_json(ops: Vec<LumeOp>) -> Result<String> {
+    Ok(serde_json::to_string(&PipelineJson {
+        version: PIPELINE_JSON_VERSION,
+        ops,
+    })?)
+}
+
+/// Parses a pipeline written by `pipeline_to_json`. Fails on unknown ops,
+/// missing parameters or a newer format version.
+#[flutter_rust_bridge::frb(sync)]
+pub fn pipeline_from_json(json: String) -> Result<Vec<LumeOp>> {
+    let pipeline: PipelineJson =
+        serde_json::from_str(&json).map_err(|e| anyhow::anyhow!("Invalid pipeline JSON: {}", e))?;
+    if pipeline.version > PIPELINE_JSON_VERSION {
+        return Err(anyhow::anyhow!(
+            "Unsupported pipeline version: {}",
+            pipeline.version
+        ));
+    }
+    Ok(pipeline.ops)
+}
+
+// ---------------------------------------------------------------------------
+// Batch
+// ---------------------------------------------------------------------------
+
+fn process_item(input: &LumeBatchInput, ops: &[LumeOp]) -> Result<Vec<u8>> {
+    let read;
+    let bytes = match input {
+        LumeBatchInput::Bytes(bytes) => bytes,
+        LumeBatchInput::Path(path) => {
+            read =
+                std::fs::read(path).map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path, e))?;
+            &read
+        }
+    };
+    let img = helpers::load(bytes)?;
+    let fmt = helpers::detect_format(bytes)?;
+    helpers::encode(&apply_ops(img, ops)?, fmt)
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn batch_tracker_new() -> LumeBatchTracker {
+    LumeBatchTracker {
+        total: AtomicUsize::new(0),
+        finished: Mutex::new(Vec::new()),
+    }
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn batch_progress(tracker: &LumeBatchTracker) -> LumeBatchProgress {
+    let finished = tracker.finished.lock().unwrap_or_else(|e| e.into_inner());
+    LumeBatchProgress {
+        total: tracker.total.load(Ordering::Relaxed) as u32,
+        completed: finished.len() as u32,
+        failed: finished.iter().filter(|(_, ok)| !ok).count() as u32,
+        finished: finished.iter().map(|(index, _)| *index).collect(),
+    }
+}
+
+/// Runs the same pipeline over many images on `parallelism` worker threads
+/// (0 uses one per CPU core), paying the FFI and scheduling cost once for the
+/// whole batch. Results come back in input order; a failing item does not
+/// stop the others. Poll `batch_progress` on `tracker` while it runs to
+/// report per-item progress.
+pub fn process_batch(
+    inputs: Vec<LumeBatchInput>,
+    ops: Vec<LumeOp>,
+    parallelism: u32,
+    tracker: &LumeBatchTracker,
+) -> Vec<LumeBatchResult> {
+    let total = inputs.len();
+    tracker.total.store(total, Ordering::Relaxed);
+    let workers = match parallelism {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n as usize,
+    }
+    .clamp(1, total.max(1));
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<LumeBatchResult>>> =
+        Mutex::new((0..total).map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= total {
+                    break;
+                }
+                let result = match process_item(&inputs[index], &ops) {
+                    Ok(bytes) => LumeBatchResult {
+                        bytes: Some(bytes),
+                        error: None,
+                    },
+                    Err(e) => LumeBatchResult {
+                        bytes: None,
+                        error: Some(e.to_string()),
+                    },
+                };
+                let success = result.bytes.is_some();
+                results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(result);
+                tracker
+                    .finished
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push((index as u32, success));
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .map(|r| r.expect("every index is processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn every_op() -> Vec<LumeOp> {
+        vec![
+            LumeOp::Resize {
+                width: 640,
+                height: 480,
+                keep_aspect_ratio: true,
+            },
+            LumeOp::Crop {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 4,
+            },
+            LumeOp::Rotate { degrees: 270 },
+            LumeOp::FlipHorizontal,
+            LumeOp::FlipVertical,
+            LumeOp::Grayscale,
+            LumeOp::Brightness { value: -12 },
+            LumeOp::Contrast { value: 7.5 },
+            LumeOp::Blur { sigma: 1.25 },
+            LumeOp::Sharpen {
+                sigma: 0.5,
+                threshold: 3,
+            },
+            LumeOp::Invert,
+            LumeOp::HueRotate { degrees: -90 },
+            LumeOp::Levels {
+                black_point: 0.05,
+                white_point: 0.95,
+                gamma: 1.1,
+            },
+        ]
+    }
+
+    #[test]
+    fn pipeline_json_round_trips_every_op() {
+        let json = pipeline_to_json(every_op()).unwrap();
+        assert!(json.starts_with(&format!("{{\"version\":{}", PIPELINE_JSON_VERSION)));
+        assert!(json.contains(r#"{"op":"rotate","degrees":270}"#));
+        let ops = pipeline_from_json(json.clone()).unwrap();
+        assert_eq!(ops.len(), every_op().len());
+        assert_eq!(pipeline_to_json(ops).unwrap(), json);
+    }
+
+    #[test]
+    fn pipeline_json_rejects_newer_versions_and_unknown_ops() {
+        let newer = format!(r#"{{"version":{},"ops":[]}}"#, PIPELINE_JSON_VERSION + 1);
+        assert!(pipeline_from_json(newer).is_err());
+        assert!(pipeline_from_json(r#"{"version":1,"ops":[{"op":"melt"}]}"#.into()).is_err());
+        assert!(pipeline_from_json(r#"{"version":1,"ops":[{"op":"blur"}]}"#.into()).is_err());
+    }
+
+    #[test]
+    fn rotations_off_the_quarter_turns_are_rejected() {
+        let img = DynamicImage::new_rgba8(3, 2);
+        let turned = apply_op(img.clone(), &LumeOp::Rotate { degrees: 450 }).unwrap();
+        assert_eq!((turned.width(), turned.height()), (2, 3));
+        assert!(apply_op(img, &LumeOp::Rotate { degrees: 45 }).is_err());
+    }
+}
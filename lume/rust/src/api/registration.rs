@@ -0,0 +1,788 @@
+use anyhow::Result;
+use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::binary_descriptors::{brief::brief, match_binary_descriptors, BinaryDescriptor};
+use imageproc::geometric_transformations::{Interpolation, Projection};
+use imageproc::point::Point;
+
+use crate::helpers::{self, SplitMix64};
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// Result of `align_images`. `transform` is a row-major 3x3 affine matrix
+/// mapping coordinates in the moving image to coordinates in the reference.
+pub struct LumeAlignment {
+    pub image: Vec<u8>,
+    pub transform: Vec<f32>,
+}
+
+// ---------------------------------------------------------------------------
+// Linear algebra
+// ---------------------------------------------------------------------------
+
+/// Solves `a * x = b` by Gaussian elimination with partial pivoting.
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let f = a[row][col] / a[col][col];
+            let (upper, lower) = a.split_at_mut(row);
+            for (v, p) in lower[0][col..].iter_mut().zip(&upper[col][col..]) {
+                *v -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let s: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - s) / a[row][row];
+    }
+    Some(x)
+}
+
+/// 2x3 affine matrix, row-major.
+type Affine = [f64; 6];
+
+/// A point and its counterpart in another image.
+type Correspondence = ((f64, f64), (f64, f64));
+
+const IDENTITY: Affine = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+fn apply(m: &Affine, x: f64, y: f64) -> (f64, f64) {
+    (m[0] * x + m[1] * y + m[2], m[3] * x + m[4] * y + m[5])
+}
+
+fn invert_affine(m: &Affine) -> Option<Affine> {
+    let det = m[0] * m[4] - m[1] * m[3];
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let (a, b, c, d) = (m[4] / det, -m[1] / det, -m[3] / det, m[0] / det);
+    Some([a, b, -(a * m[2] + b * m[5]), c, d, -(c * m[2] + d * m[5])])
+}
+
+/// Least-squares affine fit mapping `from` points onto `to` points.
+fn fit_affine(pairs: &[Correspondence]) -> Option<Affine> {
+    let mut ata = vec![vec![0.0; 3]; 3];
+    let mut atx = vec![0.0; 3];
+    let mut aty = vec![0.0; 3];
+    for &((fx, fy), (tx, ty)) in pairs {
+        let row = [fx, fy, 1.0];
+        for i in 0..3 {
+            for j in 0..3 {
+                ata[i][j] += row[i] * row[j];
+            }
+            atx[i] += row[i] * tx;
+            aty[i] += row[i] * ty;
+        }
+    }
+    let top = solve(ata.clone(), atx)?;
+    let bottom = solve(ata, aty)?;
+    Some([top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]])
+}
+
+// ---------------------------------------------------------------------------
+// ECC (intensity-based) alignment
+// ---------------------------------------------------------------------------
+
+const ECC_ITERATIONS: usize = 50;
+const ECC_EPSILON: f64 = 1e-4;
+/// Coarsest pyramid level keeps at least this many pixels on its short side.
+const ECC_MIN_LEVEL_SIDE: u32 = 48;
+
+struct FloatImage {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+impl FloatImage {
+    fn from_gray(img: &GrayImage) -> Self {
+        FloatImage {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            data: img.pixels().map(|p| p.0[0] as f64).collect(),
+        }
+    }
+
+    fn at(&self, x: usize, y: usize) -> f64 {
+        self.data[y * self.width + x]
+    }
+
+    /// Bilinear sample, or `None` outside the image.
+    fn sample(&self, x: f64, y: f64) -> Option<f64> {
+        if x < 0.0 || y < 0.0 || x > (self.width - 1) as f64 || y > (self.height - 1) as f64 {
+            return None;
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let top = self.at(x0, y0) * (1.0 - fx) + self.at(x1, y0) * fx;
+        let bottom = self.at(x0, y1) * (1.0 - fx) + self.at(x1, y1) * fx;
+        Some(top * (1.0 - fy) + bottom * fy)
+    }
+
+    /// Central-difference derivatives along x and y.
+    fn gradients(&self) -> (FloatImage, FloatImage) {
+        let (w, h) = (self.width, self.height);
+        let mut gx = vec![0.0; w * h];
+        let mut gy = vec![0.0; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                let (xl, xr) = (x.saturating_sub(1), (x + 1).min(w - 1));
+                let (yu, yd) = (y.saturating_sub(1), (y + 1).min(h - 1));
+                gx[y * w + x] = (self.at(xr, y) - self.at(xl, y)) / (xr - xl).max(1) as f64;
+                gy[y * w + x] = (self.at(x, yd) - self.at(x, yu)) / (yd - yu).max(1) as f64;
+            }
+        }
+        (
+            FloatImage {
+                width: w,
+                height: h,
+                data: gx,
+            },
+            FloatImage {
+                width: w,
+                height: h,
+                data: gy,
+            },
+        )
+    }
+}
+
+/// Gaussian pyramid, finest level first.
+fn pyramid(img: &GrayImage) -> Vec<GrayImage> {
+    let mut levels = vec![imageproc::filter::gaussian_blur_f32(img, 1.0)];
+    while levels.len() < 5 {
+        let last = &levels[levels.len() - 1];
+        if last.width().min(last.height()) / 2 < ECC_MIN_LEVEL_SIDE {
+            break;
+        }
+        let half = image::imageops::resize(
+            last,
+            last.width() / 2,
+            last.height() / 2,
+            image::imageops::FilterType::Triangle,
+        );
+        levels.push(half);
+    }
+    levels
+}
+
+/// One pyramid level of ECC maximization (Evangelidis & Psarakis, 2008).
+/// `warp` maps reference coordinates into the moving image and is refined in
+/// place.
+fn ecc_level(reference: &FloatImage, moving: &FloatImage, warp: &mut Affine) {
+    let (gx, gy) = moving.gradients();
+    for _ in 0..ECC_ITERATIONS {
+        // Sample the warped moving image and its gradients on the reference grid.
+        let mut samples = Vec::with_capacity(reference.data.len());
+        for y in 0..reference.height {
+            for x in 0..reference.width {
+                let (wx, wy) = apply(warp, x as f64, y as f64);
+                if let (Some(i), Some(dx), Some(dy)) =
+                    (moving.sample(wx, wy), gx.sample(wx, wy), gy.sample(wx, wy))
+                {
+                    samples.push((x as f64, y as f64, reference.at(x, y), i, dx, dy));
+                }
+            }
+        }
+        if samples.len() < 16 {
+            return;
+        }
+        let n = samples.len() as f64;
+        let t_mean = samples.iter().map(|s| s.2).sum::<f64>() / n;
+        let i_mean = samples.iter().map(|s| s.3).sum::<f64>() / n;
+
+        let mut hessian = vec![vec![0.0; 6]; 6];
+        let mut img_proj = vec![0.0; 6];
+        let mut tmp_proj = vec![0.0; 6];
+        let (mut ii, mut ti) = (0.0, 0.0);
+        for &(x, y, t, i, dx, dy) in &samples {
+            let (t, i) = (t - t_mean, i - i_mean);
+            let g = [dx * x, dx * y, dx, dy * x, dy * y, dy];
+            for r in 0..6 {
+                for c in 0..6 {
+                    hessian[r][c] += g[r] * g[c];
+                }
+                img_proj[r] += g[r] * i;
+                tmp_proj[r] += g[r] * t;
+            }
+            ii += i * i;
+            ti += t * i;
+        }
+
+        let Some(h_img) = solve(hessian.clone(), img_proj.clone()) else {
+            return;
+        };
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>();
+        let lambda_n = ii - dot(&img_proj, &h_img);
+        let lambda_d = ti - dot(&tmp_proj, &h_img);
+        if lambda_d.abs() < 1e-12 {
+            return;
+        }
+        let lambda = lambda_n / lambda_d;
+        let error_proj: Vec<f64> = tmp_proj
+            .iter()
+            .zip(&img_proj)
+            .map(|(t, i)| lambda * t - i)
+            .collect();
+        let Some(delta) = solve(hessian, error_proj) else {
+            return;
+        };
+        for (w, d) in warp.iter_mut().zip(&delta) {
+            *w += d;
+        }
+        if dot(&delta, &delta).sqrt() < ECC_EPSILON {
+            return;
+        }
+    }
+}
+
+/// Coarse-to-fine ECC. Returns the affine map from moving to reference
+/// coordinates.
+fn align_ecc(reference: &GrayImage, moving: &GrayImage) -> Result<Affine> {
+    let ref_levels = pyramid(reference);
+    let mov_levels = pyramid(moving);
+    let levels = ref_levels.len().min(mov_levels.len());
+    let mut warp = IDENTITY;
+    for level in (0..levels).rev() {
+        let r = FloatImage::from_gray(&ref_levels[level]);
+        let m = FloatImage::from_gray(&mov_levels[level]);
+        ecc_level(&r, &m, &mut warp);
+        if level > 0 {
+            // Translation doubles when moving to the next finer level.
+            warp[2] *= 2.0;
+            warp[5] *= 2.0;
+        }
+    }
+    invert_affine(&warp).ok_or_else(|| anyhow::anyhow!("Alignment did not converge"))
+}
+
+// ---------------------------------------------------------------------------
+// Feature-based alignment
+// ---------------------------------------------------------------------------
+
+const MAX_KEYPOINTS: usize = 800;
+const RANSAC_ITERATIONS: usize = 1000;
+const RANSAC_INLIER_PX: f64 = 3.0;
+
+/// Strongest FAST corners far enough from the border for BRIEF patches.
+fn keypoints(img: &GrayImage) -> Vec<Point<u32>> {
+    let (w, h) = img.dimensions();
+    let mut corners = imageproc::corners::corners_fast9(img, 20);
+    corners.retain(|c| c.x >= 17 && c.y >= 17 && c.x + 17 < w && c.y + 17 < h);
+    corners.sort_by(|a, b| b.score.total_cmp(&a.score));
+    corners.truncate(MAX_KEYPOINTS);
+    corners.into_iter().map(|c| Point::new(c.x, c.y)).collect()
+}
+
+/// FAST + BRIEF matches filtered by RANSAC. Returns the affine map from
+/// moving to reference coordinates.
+fn align_features(reference: &GrayImage, moving: &GrayImage) -> Result<Affine> {
+    let no_match = || anyhow::anyhow!("Not enough feature matches to align the images");
+    let (ref_desc, pairs) =
+        brief(reference, &keypoints(reference), 256, None).map_err(|e| anyhow::anyhow!(e))?;
+    let (mov_desc, _) =
+        brief(moving, &keypoints(moving), 256, Some(&pairs)).map_err(|e| anyhow::anyhow!(e))?;
+    let matches: Vec<Correspondence> = match_binary_descriptors(&mov_desc, &ref_desc, 48, Some(0))
+        .into_iter()
+        .map(|(m, r)| {
+            let (m, r) = (m.position(), r.position());
+            ((m.x as f64, m.y as f64), (r.x as f64, r.y as f64))
+        })
+        .collect();
+    if matches.len() < 3 {
+        return Err(no_match());
+    }
+
+    let mut rng = SplitMix64::new(0x5EED);
+    let mut best: Vec<usize> = Vec::new();
+    for _ in 0..RANSAC_ITERATIONS {
+        let sample: Vec<_> = (0..3)
+            .map(|_| matches[(rng.next_u64() % matches.len() as u64) as usize])
+            .collect();
+        let Some(model) = fit_affine(&sample) else {
+            continue;
+        };
+        let inliers: Vec<usize> = (0..matches.len())
+            .filter(|&k| {
+                let ((fx, fy), (tx, ty)) = matches[k];
+                let (px, py) = apply(&model, fx, fy);
+                (px - tx).hypot(py - ty) <= RANSAC_INLIER_PX
+            })
+            .collect();
+        if inliers.len() > best.len() {
+            best = inliers;
+        }
+    }
+    if best.len() < 6 {
+        return Err(no_match());
+    }
+    let inliers: Vec<_> = best.iter().map(|&k| matches[k]).collect();
+    fit_affine(&inliers).ok_or_else(no_match)
+}
+
+// ---------------------------------------------------------------------------
+// Public API
+// ---------------------------------------------------------------------------
+
+/// Estimates the affine map from `moving` to `reference` coordinates with
+/// "ecc" (intensity-based, sub-pixel, for small motions like burst frames) or
+/// "feature" (corner matching with RANSAC, tolerates larger motions).
+pub(crate) fn estimate_alignment(
+    reference: &GrayImage,
+    moving: &GrayImage,
+    method: &str,
+) -> Result<[f32; 9]> {
+    let m = match method {
+        "ecc" => align_ecc(reference, moving)?,
+        "feature" | "features" => align_features(reference, moving)?,
+        other => return Err(anyhow::anyhow!("Unsupported alignment method: {}", other)),
+    };
+    Ok([
+        m[0] as f32,
+        m[1] as f32,
+        m[2] as f32,
+        m[3] as f32,
+        m[4] as f32,
+        m[5] as f32,
+        0.0,
+        0.0,
+        1.0,
+    ])
+}
+
+/// Warps `moving` into a `width` x `height` reference frame. Areas the moving
+/// image does not cover become transparent.
+pub(crate) fn warp_to_reference(
+    moving: &RgbaImage,
+    transform: [f32; 9],
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage> {
+    let projection = Projection::from_matrix(transform)
+        .ok_or_else(|| anyhow::anyhow!("Transform is not invertible"))?;
+    let mut out = RgbaImage::new(width, height);
+    imageproc::geometric_transformations::warp_into(
+        moving,
+        &projection,
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+        &mut out,
+    );
+    Ok(out)
+}
+
+/// Aligns `moving_bytes` onto `reference_bytes` with an affine transform (see
+/// `estimate_alignment` for `method`). Returns the warped moving image, at
+/// the reference's size and in the moving image's format, plus the transform.
+#[flutter_rust_bridge::frb(sync)]
+pub fn align_images(
+    reference_bytes: Vec<u8>,
+    moving_bytes: Vec<u8>,
+    method: String,
+) -> Result<LumeAlignment> {
+    let reference = helpers::load(&reference_bytes)?;
+    let moving = helpers::load(&moving_bytes)?;
+    let fmt = helpers::detect_format(&moving_bytes)?;
+    let transform = estimate_alignment(
+        &reference.to_luma8(),
+        &moving.to_luma8(),
+        &method.to_lowercase(),
+    )?;
+    let warped = warp_to_reference(
+        &moving.to_rgba8(),
+        transform,
+        reference.width(),
+        reference.height(),
+    )?;
+    Ok(LumeAlignment {
+        image: helpers::encode(&DynamicImage::ImageRgba8(warped), fmt)?,
+        transform: transform.to_vec(),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Stacking
+// ---------------------------------------------------------------------------
+
+/// A frame ready for stacking: premultiplied so that bilinear warping does
+/// not blend colour with the transparent border, plus how much of each pixel
+/// the warp covered (`None` when it covers everything).
+struct StackFrame {
+    pixels: RgbaImage,
+    coverage: Option<GrayImage>,
+}
+
+impl StackFrame {
+    fn new(frame: &DynamicImage, warp: Option<([f32; 9], u32, u32)>) -> Result<StackFrame> {
+        let mut pixels = frame.to_rgba8();
+        for p in pixels.pixels_mut() {
+            let a = p.0[3] as u32;
+            for c in &mut p.0[..3] {
+                *c = ((*c as u32 * a + 127) / 255) as u8;
+            }
+        }
+        let Some((transform, w, h)) = warp else {
+            return Ok(StackFrame {
+                pixels,
+                coverage: None,
+            });
+        };
+        let projection = Projection::from_matrix(transform)
+            .ok_or_else(|| anyhow::anyhow!("Transform is not invertible"))?;
+        let mut coverage = GrayImage::new(w, h);
+        imageproc::geometric_transformations::warp_into(
+            &GrayImage::from_pixel(pixels.width(), pixels.height(), Luma([255])),
+            &projection,
+            Interpolation::Bilinear,
+            Luma([0]),
+            &mut coverage,
+        );
+        Ok(StackFrame {
+            pixels: warp_to_reference(&pixels, transform, w, h)?,
+            coverage: Some(coverage),
+        })
+    }
+
+    /// Coverage (0 to 1), premultiplied colour and alpha at `(x, y)`.
+    fn sample(&self, x: u32, y: u32) -> (f32, [f32; 4]) {
+        let k = self
+            .coverage
+            .as_ref()
+            .map_or(1.0, |c| c.get_pixel(x, y).0[0] as f32 / 255.0);
+        (k, self.pixels.get_pixel(x, y).0.map(|v| v as f32))
+    }
+}
+
+/// The value at which `samples` (value, weight) reach half their total
+/// weight, or 0 when they weigh nothing.
+fn weighted_median(samples: &mut [(f32, f32)]) -> f32 {
+    samples.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+    let half = samples.iter().map(|s| s.1).sum::<f32>() / 2.0;
+    let mut seen = 0.0;
+    for &(value, weight) in samples.iter() {
+        seen += weight;
+        if seen > half {
+            return value;
+        }
+    }
+    samples.last().map_or(0.0, |s| s.0)
+}
+
+/// Merges a burst into one low-noise frame. `method` is "mean" or "median"
+/// (slower, but rejects outliers such as passers-by). With `align_method`
+/// ("ecc" or "feature") every frame is first aligned onto the first one.
+/// Samples count by how much of the pixel a warped frame covers and colours
+/// by their alpha, so uncovered borders and transparent pixels do not darken
+/// the stack.
+#[flutter_rust_bridge::frb(sync)]
+pub fn stack_frames(
+    images: Vec<Vec<u8>>,
+    method: String,
+    align_method: Option<String>,
+) -> Result<Vec<u8>> {
+    let median = match method.to_lowercase().as_str() {
+        "mean" | "average" => false,
+        "median" => true,
+        other => return Err(anyhow::anyhow!("Unsupported stacking method: {}", other)),
+    };
+    let first = images
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("No frames to stack"))?;
+    let reference = helpers::load(first)?;
+    let fmt = helpers::detect_format(first)?;
+    let (w, h) = (reference.width(), reference.height());
+    let reference_gray = reference.to_luma8();
+
+    let mut frames = vec![StackFrame::new(&reference, None)?];
+    for bytes in &images[1..] {
+        let frame = helpers::load(bytes)?;
+        let warp = match &align_method {
+            Some(m) => Some((
+                estimate_alignment(&reference_gray, &frame.to_luma8(), &m.to_lowercase())?,
+                w,
+                h,
+            )),
+            None if frame.width() == w && frame.height() == h => None,
+            None => return Err(anyhow::anyhow!("All frames must have the same size")),
+        };
+        frames.push(StackFrame::new(&frame, warp)?);
+    }
+
+    let mut samples: Vec<(f32, f32)> = Vec::with_capacity(frames.len());
+    let out = RgbaImage::from_fn(w, h, |x, y| {
+        let mut px = [0.0f32; 4];
+        if median {
+            // Alpha weighs by coverage, colours by covered alpha.
+            samples.clear();
+            samples.extend(
+                frames
+                    .iter()
+                    .map(|f| f.sample(x, y))
+                    .filter(|&(k, _)| k > 0.0)
+                    .map(|(k, p)| (p[3] / k, k)),
+            );
+            px[3] = weighted_median(&mut samples);
+            for c in 0..3 {
+                samples.clear();
+                samples.extend(
+                    frames
+                        .iter()
+                        .map(|f| f.sample(x, y).1)
+                        .filter(|p| p[3] > 0.0)
+                        .map(|p| (p[c] * 255.0 / p[3], p[3])),
+                );
+                px[c] = weighted_median(&mut samples);
+            }
+        } else {
+            let (mut coverage, mut sum) = (0.0, [0.0f32; 4]);
+            for (k, p) in frames.iter().map(|f| f.sample(x, y)) {
+                coverage += k;
+                for (s, v) in sum.iter_mut().zip(p) {
+                    *s += v;
+                }
+            }
+            if coverage > 0.0 {
+                px[3] = sum[3] / coverage;
+            }
+            if sum[3] > 0.0 {
+                for c in 0..3 {
+                    px[c] = sum[c] * 255.0 / sum[3];
+                }
+            }
+        }
+        Rgba(px.map(|v| v.round().clamp(0.0, 255.0) as u8))
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Stabilization
+// ---------------------------------------------------------------------------
+
+/// `a` after `b`.
+fn compose(a: &Affine, b: &Affine) -> Affine {
+    [
+        a[0] * b[0] + a[1] * b[3],
+        a[0] * b[1] + a[1] * b[4],
+        a[0] * b[2] + a[1] * b[5] + a[2],
+        a[3] * b[0] + a[4] * b[3],
+        a[3] * b[1] + a[4] * b[4],
+        a[3] * b[2] + a[4] * b[5] + a[5],
+    ]
+}
+
+/// Translation, rotation and log scale of the similarity closest to `m`,
+/// the camera path quantities that get smoothed.
+fn similarity_params(m: &Affine) -> [f64; 4] {
+    let (a, b) = ((m[0] + m[4]) / 2.0, (m[3] - m[1]) / 2.0);
+    [m[2], m[5], b.atan2(a), a.hypot(b).ln()]
+}
+
+fn similarity(p: &[f64; 4]) -> Affine {
+    let s = p[3].exp();
+    let (cos, sin) = (s * p[2].cos(), s * p[2].sin());
+    [cos, -sin, p[0], sin, cos, p[1]]
+}
+
+/// Mean of camera path samples. Angles average on the circle, or a path
+/// crossing +-180 degrees would swing the other way round.
+fn mean_params(window: &[[f64; 4]]) -> [f64; 4] {
+    let mut mean = [0.0; 4];
+    let (mut sin, mut cos) = (0.0, 0.0);
+    for p in window {
+        for (m, v) in mean.iter_mut().zip(p) {
+            *m += v / window.len() as f64;
+        }
+        sin += p[2].sin();
+        cos += p[2].cos();
+    }
+    mean[2] = sin.atan2(cos);
+    mean
+}
+
+/// Estimates per-frame transforms that stabilize a sequence of stills (e.g.
+/// a handheld timelapse): the camera path is tracked frame to frame with
+/// `method` ("ecc" or "feature", see `align_images`), smoothed with a moving
+/// average over `smoothing_radius` frames on each side, and each frame gets
+/// the row-major 3x3 transform from its coordinates onto the smoothed path.
+/// A radius larger than the sequence locks every frame onto the average
+/// view. A frame that cannot be aligned with the one before it (a cut, a
+/// blurred or featureless frame) is taken as not having moved. Apply them
+/// with `apply_transform_crop`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn estimate_stabilization(
+    frames: Vec<Vec<u8>>,
+    method: String,
+    smoothing_radius: u32,
+) -> Result<Vec<Vec<f32>>> {
+    let method = method.to_lowercase();
+    if !matches!(method.as_str(), "ecc" | "feature" | "features") {
+        return Err(anyhow::anyhow!("Unsupported alignment method: {}", method));
+    }
+    let mut previous: Option<GrayImage> = None;
+    // Map from each frame onto the first one.
+    let mut path: Vec<Affine> = Vec::with_capacity(frames.len());
+    for bytes in &frames {
+        let gray = helpers::load(bytes)?.to_luma8();
+        let to_first = match (&previous, path.last()) {
+            (Some(prev), Some(last)) => match estimate_alignment(prev, &gray, &method) {
+                Ok(t) => compose(last, &[t[0], t[1], t[2], t[3], t[4], t[5]].map(f64::from)),
+                Err(_) => *last,
+            },
+            _ => IDENTITY,
+        };
+        path.push(to_first);
+        previous = Some(gray);
+    }
+
+    // The camera motion is the inverse of the map onto the first frame.
+    let camera: Vec<[f64; 4]> = path
+        .iter()
+        .map(|m| invert_affine(m).map(|inv| similarity_params(&inv)))
+        .collect::<Option<_>>()
+        .ok_or_else(|| anyhow::anyhow!("Frame motion is not invertible"))?;
+    let radius = smoothing_radius as usize;
+    path.iter()
+        .enumerate()
+        .map(|(i, to_first)| {
+            let window = &camera[i.saturating_sub(radius)..(i + radius + 1).min(camera.len())];
+            let t = compose(&similarity(&mean_params(window)), to_first);
+            Ok([t[0], t[1], t[2], t[3], t[4], t[5], 0.0, 0.0, 1.0]
+                .map(|v| v as f32)
+                .to_vec())
+        })
+        .collect()
+}
+
+/// Warps a frame with a 3x3 row-major `transform` (from
+/// `estimate_stabilization` or `align_images`), then keeps the central
+/// `crop_ratio` (0-1] of each side and scales it back to the frame size,
+/// which hides the uncovered borders that stabilizing leaves.
+#[flutter_rust_bridge::frb(sync)]
+pub fn apply_transform_crop(
+    image_bytes: Vec<u8>,
+    transform: Vec<f32>,
+    crop_ratio: f32,
+) -> Result<Vec<u8>> {
+    let transform: [f32; 9] = transform
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Transform must have 9 values"))?;
+    if !(crop_ratio > 0.0 && crop_ratio <= 1.0) {
+        return Err(anyhow::anyhow!("Crop ratio must be in (0, 1]"));
+    }
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = (img.width(), img.height());
+    let warped = warp_to_reference(&img.to_rgba8(), transform, w, h)?;
+    let (cw, ch) = (
+        ((w as f32 * crop_ratio).round() as u32).max(1),
+        ((h as f32 * crop_ratio).round() as u32).max(1),
+    );
+    let cropped = image::imageops::crop_imm(&warped, (w - cw) / 2, (h - ch) / 2, cw, ch).to_image();
+    let out = image::imageops::resize(&cropped, w, h, image::imageops::FilterType::Lanczos3);
+    helpers::encode(&DynamicImage::ImageRgba8(out), fmt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageFormat;
+
+    fn png(img: RgbaImage) -> Vec<u8> {
+        helpers::encode(&DynamicImage::ImageRgba8(img), ImageFormat::Png).unwrap()
+    }
+
+    fn textured() -> RgbaImage {
+        RgbaImage::from_fn(96, 96, |x, y| {
+            let v = (((x / 8 + y / 8) % 2) * 150 + (x * 3 + y) % 60) as u8;
+            Rgba([v, v, v, 255])
+        })
+    }
+
+    #[test]
+    fn weighted_median_picks_the_heavier_half() {
+        assert_eq!(weighted_median(&mut []), 0.0);
+        assert_eq!(
+            weighted_median(&mut [(5.0, 1.0), (1.0, 1.0), (9.0, 1.0)]),
+            5.0
+        );
+        assert_eq!(weighted_median(&mut [(1.0, 1.0), (9.0, 3.0)]), 9.0);
+    }
+
+    #[test]
+    fn transparent_samples_do_not_darken_the_stack() {
+        let opaque = RgbaImage::from_pixel(4, 4, Rgba([200, 100, 50, 255]));
+        let holed = RgbaImage::from_fn(4, 4, |x, _| {
+            if x < 2 {
+                Rgba([0, 0, 0, 0])
+            } else {
+                Rgba([100, 50, 24, 255])
+            }
+        });
+        let frames = vec![png(opaque), png(holed)];
+        let mean = stack_frames(frames.clone(), "mean".into(), None).unwrap();
+        let mean = helpers::load(&mean).unwrap().to_rgba8();
+        assert_eq!(mean.get_pixel(0, 0).0, [200, 100, 50, 128]);
+        assert_eq!(mean.get_pixel(3, 0).0, [150, 75, 37, 255]);
+        let median = stack_frames(frames, "median".into(), None).unwrap();
+        let median = helpers::load(&median).unwrap().to_rgba8();
+        assert_eq!(median.get_pixel(0, 0).0, [200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn stacking_checks_the_method_before_decoding() {
+        let err = stack_frames(vec![vec![1, 2, 3]], "sum".into(), None).unwrap_err();
+        assert!(err.to_string().contains("stacking method"));
+    }
+
+    #[test]
+    fn aligned_stack_keeps_the_reference_edges() {
+        let reference = textured();
+        let shifted =
+            RgbaImage::from_fn(96, 96, |x, y| *reference.get_pixel(x.saturating_sub(3), y));
+        let out = stack_frames(
+            vec![png(reference.clone()), png(shifted)],
+            "mean".into(),
+            Some("ecc".into()),
+        )
+        .unwrap();
+        let out = helpers::load(&out).unwrap().to_rgba8();
+        for (a, b) in out.pixels().zip(reference.pixels()) {
+            assert!(a.0[0].abs_diff(b.0[0]) <= 2);
+        }
+    }
+
+    #[test]
+    fn unalignable_frames_count_as_still() {
+        let blank = RgbaImage::from_pixel(96, 96, Rgba([9, 9, 9, 255]));
+        let frames = vec![png(textured()), png(blank), png(textured())];
+        let transforms = estimate_stabilization(frames, "feature".into(), 1).unwrap();
+        assert_eq!(transforms.len(), 3);
+        for t in &transforms {
+            for (v, identity) in t.iter().zip([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]) {
+                assert!((v - identity).abs() < 1e-3, "{:?}", t);
+            }
+        }
+        assert!(estimate_stabilization(vec![], "optical".into(), 1).is_err());
+    }
+
+    #[test]
+    fn path_angles_average_on_the_circle() {
+        let mean = mean_params(&[[2.0, 0.0, 3.1, 0.0], [4.0, 0.0, -3.1, 0.0]]);
+        assert_eq!(mean[0], 3.0);
+        assert!((mean[2].abs() - std::f64::consts::PI).abs() < 1e-9);
+        let mean = mean_params(&[[0.0, 0.0, 0.2, 0.0], [0.0, 0.0, 0.4, 0.0]]);
+        assert!((mean[2] - 0.3).abs() < 1e-9);
+    }
+}
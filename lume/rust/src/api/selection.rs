@@ -0,0 +1,713 @@
+use anyhow::Result;
+use image::{DynamicImage, GrayImage, Luma, RgbImage, Rgba, RgbaImage};
+
+use crate::api::image_ops::LumeColor;
+use crate::api::imageproc_ops::LumeRect;
+use crate::api::mask::{self, LumeMask};
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Flood fill
+// ---------------------------------------------------------------------------
+
+/// True when every channel of `p` is within `tolerance` of `seed`.
+fn is_similar(seed: &Rgba<u8>, p: &Rgba<u8>, tolerance: u8) -> bool {
+    seed.0
+        .iter()
+        .zip(p.0.iter())
+        .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+}
+
+fn check_seed(img: &RgbaImage, x: u32, y: u32) -> Result<()> {
+    if x >= img.width() || y >= img.height() {
+        return Err(anyhow::anyhow!("Seed point is outside the image"));
+    }
+    Ok(())
+}
+
+/// Mask (255 = selected) of the 4-connected region around (x, y) whose pixels
+/// are similar to the seed pixel.
+fn flood_region(img: &RgbaImage, x: u32, y: u32, tolerance: u8) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let seed = *img.get_pixel(x, y);
+    let mut mask = GrayImage::new(w, h);
+    let mut stack = vec![(x, y)];
+    mask.put_pixel(x, y, Luma([255]));
+    while let Some((cx, cy)) = stack.pop() {
+        let neighbors = [
+            (cx.wrapping_sub(1), cy),
+            (cx + 1, cy),
+            (cx, cy.wrapping_sub(1)),
+            (cx, cy + 1),
+        ];
+        for (nx, ny) in neighbors {
+            if nx < w
+                && ny < h
+                && mask.get_pixel(nx, ny).0[0] == 0
+                && is_similar(&seed, img.get_pixel(nx, ny), tolerance)
+            {
+                mask.put_pixel(nx, ny, Luma([255]));
+                stack.push((nx, ny));
+            }
+        }
+    }
+    mask
+}
+
+/// Paint-bucket fill: the contiguous region around (x, y) whose channels are
+/// all within `tolerance` of the seed pixel is replaced by `fill_color`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn flood_fill(
+    image_bytes: Vec<u8>,
+    x: u32,
+    y: u32,
+    tolerance: u8,
+    fill_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    check_seed(&img, x, y)?;
+    let mask = flood_region(&img, x, y, tolerance);
+    let fill = Rgba([fill_color.r, fill_color.g, fill_color.b, fill_color.a]);
+    for (p, m) in img.pixels_mut().zip(mask.pixels()) {
+        if m.0[0] > 0 {
+            *p = fill;
+        }
+    }
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
+
+/// Same region as `flood_fill`, returned as a grayscale mask
+/// (255 = inside the region, 0 = outside).
+#[flutter_rust_bridge::frb(sync)]
+pub fn flood_fill_mask(image_bytes: Vec<u8>, x: u32, y: u32, tolerance: u8) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    check_seed(&img, x, y)?;
+    let mask = flood_region(&img, x, y, tolerance);
+    helpers::encode(&DynamicImage::ImageLuma8(mask), fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Magic wand
+// ---------------------------------------------------------------------------
+
+/// Magic-wand selection around (x, y). With `contiguous` only the connected
+/// region is selected (as in `flood_fill_mask`), otherwise every pixel in the
+/// image within `tolerance` of the seed. The mask can be passed straight to
+/// the `*_masked` functions.
+#[flutter_rust_bridge::frb(sync)]
+pub fn select_similar(
+    image_bytes: Vec<u8>,
+    x: u32,
+    y: u32,
+    tolerance: u8,
+    contiguous: bool,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let mask = similar_region(&img, x, y, tolerance, contiguous)?;
+    helpers::encode(&DynamicImage::ImageLuma8(mask), fmt)
+}
+
+/// `select_similar` as a `LumeMask`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn select_similar_mask(
+    image_bytes: Vec<u8>,
+    x: u32,
+    y: u32,
+    tolerance: u8,
+    contiguous: bool,
+    packed: bool,
+) -> Result<LumeMask> {
+    let img = helpers::load(&image_bytes)?.to_rgba8();
+    let mask = similar_region(&img, x, y, tolerance, contiguous)?;
+    Ok(mask::from_gray(&mask, packed))
+}
+
+fn similar_region(
+    img: &RgbaImage,
+    x: u32,
+    y: u32,
+    tolerance: u8,
+    contiguous: bool,
+) -> Result<GrayImage> {
+    check_seed(img, x, y)?;
+    if contiguous {
+        return Ok(flood_region(img, x, y, tolerance));
+    }
+    let seed = *img.get_pixel(x, y);
+    Ok(GrayImage::from_fn(img.width(), img.height(), |px, py| {
+        Luma([if is_similar(&seed, img.get_pixel(px, py), tolerance) {
+            255
+        } else {
+            0
+        }])
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// Foreground extraction (GrabCut)
+// ---------------------------------------------------------------------------
+
+/// Per-pixel label used while segmenting.
+#[derive(Clone, Copy, PartialEq)]
+enum Label {
+    Background,
+    ProbableBackground,
+    ProbableForeground,
+    Foreground,
+}
+
+impl Label {
+    fn is_foreground(self) -> bool {
+        matches!(self, Label::ProbableForeground | Label::Foreground)
+    }
+
+    fn is_fixed(self) -> bool {
+        matches!(self, Label::Background | Label::Foreground)
+    }
+}
+
+/// Longest side of the image the segmentation runs on. The resulting mask is
+/// scaled back up to the input size.
+const GRABCUT_MAX_SIDE: u32 = 320;
+const GMM_COMPONENTS: usize = 5;
+
+struct Gaussian {
+    mean: [f64; 3],
+    inv_cov: [[f64; 3]; 3],
+    /// ln(weight) - ln(det(cov)) / 2.
+    log_scale: f64,
+}
+
+/// Gaussian mixture color model.
+struct Gmm(Vec<Gaussian>);
+
+impl Gmm {
+    /// Fits one Gaussian per component from `samples` and their component
+    /// `assignments`. Empty components are dropped.
+    fn fit(samples: &[[f64; 3]], assignments: &[usize]) -> Gmm {
+        let mut components = Vec::new();
+        for k in 0..GMM_COMPONENTS {
+            let members: Vec<&[f64; 3]> = samples
+                .iter()
+                .zip(assignments)
+                .filter(|(_, &a)| a == k)
+                .map(|(s, _)| s)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            let n = members.len() as f64;
+            let mut mean = [0.0; 3];
+            for m in &members {
+                for c in 0..3 {
+                    mean[c] += m[c] / n;
+                }
+            }
+            let mut cov = [[0.0; 3]; 3];
+            for m in &members {
+                for i in 0..3 {
+                    for j in 0..3 {
+                        cov[i][j] += (m[i] - mean[i]) * (m[j] - mean[j]) / n;
+                    }
+                }
+            }
+            // Regularize so flat regions do not produce singular covariances.
+            for (i, row) in cov.iter_mut().enumerate() {
+                row[i] += 1.0;
+            }
+            let (inv_cov, det) = invert3(&cov);
+            components.push(Gaussian {
+                mean,
+                inv_cov,
+                log_scale: (n / samples.len() as f64).ln() - 0.5 * det.ln(),
+            });
+        }
+        Gmm(components)
+    }
+
+    fn component_log_likelihood(g: &Gaussian, z: &[f64; 3]) -> f64 {
+        let d = [z[0] - g.mean[0], z[1] - g.mean[1], z[2] - g.mean[2]];
+        let mut mahalanobis = 0.0;
+        for i in 0..3 {
+            for j in 0..3 {
+                mahalanobis += d[i] * g.inv_cov[i][j] * d[j];
+            }
+        }
+        g.log_scale - 0.5 * mahalanobis
+    }
+
+    fn most_likely_component(&self, z: &[f64; 3]) -> usize {
+        let mut best = (0, f64::NEG_INFINITY);
+        for (k, g) in self.0.iter().enumerate() {
+            let l = Self::component_log_likelihood(g, z);
+            if l > best.1 {
+                best = (k, l);
+            }
+        }
+        best.0
+    }
+
+    fn neg_log_likelihood(&self, z: &[f64; 3]) -> f64 {
+        let logs: Vec<f64> = self
+            .0
+            .iter()
+            .map(|g| Self::component_log_likelihood(g, z))
+            .collect();
+        let max = logs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        -(max + logs.iter().map(|l| (l - max).exp()).sum::<f64>().ln())
+    }
+}
+
+/// Inverse and determinant of a symmetric positive-definite 3x3 matrix.
+fn invert3(m: &[[f64; 3]; 3]) -> ([[f64; 3]; 3], f64) {
+    let c00 = m[1][1] * m[2][2] - m[1][2] * m[2][1];
+    let c01 = m[1][2] * m[2][0] - m[1][0] * m[2][2];
+    let c02 = m[1][0] * m[2][1] - m[1][1] * m[2][0];
+    let det = m[0][0] * c00 + m[0][1] * c01 + m[0][2] * c02;
+    let inv = [
+        [
+            c00 / det,
+            (m[0][2] * m[2][1] - m[0][1] * m[2][2]) / det,
+            (m[0][1] * m[1][2] - m[0][2] * m[1][1]) / det,
+        ],
+        [
+            c01 / det,
+            (m[0][0] * m[2][2] - m[0][2] * m[2][0]) / det,
+            (m[0][2] * m[1][0] - m[0][0] * m[1][2]) / det,
+        ],
+        [
+            c02 / det,
+            (m[0][1] * m[2][0] - m[0][0] * m[2][1]) / det,
+            (m[0][0] * m[1][1] - m[0][1] * m[1][0]) / det,
+        ],
+    ];
+    (inv, det)
+}
+
+/// Initial component assignment with a few rounds of k-means.
+fn kmeans_assign(samples: &[[f64; 3]]) -> Vec<usize> {
+    let k = GMM_COMPONENTS.min(samples.len());
+    let mut centers: Vec<[f64; 3]> = (0..k).map(|i| samples[i * samples.len() / k]).collect();
+    let mut assignments = vec![0; samples.len()];
+    for _ in 0..10 {
+        for (a, s) in assignments.iter_mut().zip(samples) {
+            let dist = |c: &[f64; 3]| (0..3).map(|i| (s[i] - c[i]).powi(2)).sum::<f64>();
+            *a = (0..k)
+                .min_by(|&x, &y| dist(&centers[x]).total_cmp(&dist(&centers[y])))
+                .unwrap_or(0);
+        }
+        let mut sums = vec![([0.0; 3], 0usize); k];
+        for (a, s) in assignments.iter().zip(samples) {
+            let (sum, n) = &mut sums[*a];
+            for (acc, v) in sum.iter_mut().zip(s) {
+                *acc += v;
+            }
+            *n += 1;
+        }
+        for (center, (sum, n)) in centers.iter_mut().zip(sums) {
+            if n > 0 {
+                *center = sum.map(|v| v / n as f64);
+            }
+        }
+    }
+    assignments
+}
+
+/// Fits a color model to the pixels whose label matches `foreground`. For
+/// later iterations `previous` refines the component assignment.
+fn fit_model(
+    pixels: &[[f64; 3]],
+    labels: &[Label],
+    foreground: bool,
+    previous: Option<&Gmm>,
+) -> Result<Gmm> {
+    let samples: Vec<[f64; 3]> = pixels
+        .iter()
+        .zip(labels)
+        .filter(|(_, l)| l.is_foreground() == foreground)
+        .map(|(p, _)| *p)
+        .collect();
+    if samples.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Selection leaves no {} pixels",
+            if foreground {
+                "foreground"
+            } else {
+                "background"
+            }
+        ));
+    }
+    let assignments = match previous {
+        Some(gmm) if !gmm.0.is_empty() => samples
+            .iter()
+            .map(|s| gmm.most_likely_component(s))
+            .collect(),
+        _ => kmeans_assign(&samples),
+    };
+    Ok(Gmm::fit(&samples, &assignments))
+}
+
+/// Dinic max-flow on a graph stored as paired forward/backward edges, so the
+/// reverse of edge `e` is `e ^ 1`.
+struct FlowGraph {
+    adjacency: Vec<Vec<usize>>,
+    to: Vec<usize>,
+    capacity: Vec<f64>,
+}
+
+impl FlowGraph {
+    fn new(nodes: usize) -> Self {
+        FlowGraph {
+            adjacency: vec![Vec::new(); nodes],
+            to: Vec::new(),
+            capacity: Vec::new(),
+        }
+    }
+
+    fn add_edge(&mut self, a: usize, b: usize, forward: f64, backward: f64) {
+        self.adjacency[a].push(self.to.len());
+        self.to.push(b);
+        self.capacity.push(forward);
+        self.adjacency[b].push(self.to.len());
+        self.to.push(a);
+        self.capacity.push(backward);
+    }
+
+    fn levels(&self, source: usize) -> Vec<i32> {
+        let mut level = vec![-1; self.adjacency.len()];
+        let mut queue = std::collections::VecDeque::from([source]);
+        level[source] = 0;
+        while let Some(u) = queue.pop_front() {
+            for &e in &self.adjacency[u] {
+                let v = self.to[e];
+                if level[v] < 0 && self.capacity[e] > 1e-9 {
+                    level[v] = level[u] + 1;
+                    queue.push_back(v);
+                }
+            }
+        }
+        level
+    }
+
+    /// Runs max-flow and returns which nodes stay on the source side of the
+    /// minimum cut.
+    fn min_cut(&mut self, source: usize, sink: usize) -> Vec<bool> {
+        loop {
+            let mut level = self.levels(source);
+            if level[sink] < 0 {
+                return level.iter().map(|&l| l >= 0).collect();
+            }
+            let mut next = vec![0usize; self.adjacency.len()];
+            let mut path: Vec<usize> = Vec::new();
+            let mut u = source;
+            loop {
+                if u == sink {
+                    let flow = path
+                        .iter()
+                        .map(|&e| self.capacity[e])
+                        .fold(f64::INFINITY, f64::min);
+                    for &e in &path {
+                        self.capacity[e] -= flow;
+                        self.capacity[e ^ 1] += flow;
+                    }
+                    // Resume from the tail of the first saturated edge.
+                    let cut = path
+                        .iter()
+                        .position(|&e| self.capacity[e] <= 1e-9)
+                        .unwrap_or(0);
+                    path.truncate(cut);
+                    u = path.last().map_or(source, |&e| self.to[e]);
+                    continue;
+                }
+                let mut advanced = false;
+                while next[u] < self.adjacency[u].len() {
+                    let e = self.adjacency[u][next[u]];
+                    let v = self.to[e];
+                    if self.capacity[e] > 1e-9 && level[v] == level[u] + 1 {
+                        path.push(e);
+                        u = v;
+                        advanced = true;
+                        break;
+                    }
+                    next[u] += 1;
+                }
+                if advanced {
+                    continue;
+                }
+                if u == source {
+                    break;
+                }
+                // Dead end: drop the node from this phase and back up.
+                level[u] = -1;
+                let e = path.pop().unwrap_or(0);
+                u = self.to[e ^ 1];
+                next[u] += 1;
+            }
+        }
+    }
+}
+
+/// One graph cut over the working image: unknown pixels are relabelled to
+/// whichever side minimises color-model cost plus edge-aware smoothness.
+fn graph_cut(pixels: &[[f64; 3]], labels: &mut [Label], width: usize, fg: &Gmm, bg: &Gmm) {
+    const GAMMA: f64 = 50.0;
+    let height = pixels.len() / width;
+    let diff2 = |a: usize, b: usize| {
+        (0..3)
+            .map(|c| (pixels[a][c] - pixels[b][c]).powi(2))
+            .sum::<f64>()
+    };
+
+    let mut total = 0.0;
+    let mut count = 0usize;
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width {
+                total += diff2(i, i + 1);
+                count += 1;
+            }
+            if y + 1 < height {
+                total += diff2(i, i + width);
+                count += 1;
+            }
+        }
+    }
+    let beta = if total > 0.0 {
+        count as f64 / (2.0 * total)
+    } else {
+        0.0
+    };
+
+    let n = pixels.len();
+    let (source, sink) = (n, n + 1);
+    let hard = 1e9;
+    let mut graph = FlowGraph::new(n + 2);
+    for i in 0..n {
+        let (to_source, to_sink) = match labels[i] {
+            Label::Foreground => (hard, 0.0),
+            Label::Background => (0.0, hard),
+            _ => (
+                bg.neg_log_likelihood(&pixels[i]),
+                fg.neg_log_likelihood(&pixels[i]),
+            ),
+        };
+        graph.add_edge(source, i, to_source, 0.0);
+        graph.add_edge(i, sink, to_sink, 0.0);
+        let (x, y) = (i % width, i / width);
+        if x + 1 < width {
+            let w = GAMMA * (-beta * diff2(i, i + 1)).exp();
+            graph.add_edge(i, i + 1, w, w);
+        }
+        if y + 1 < height {
+            let w = GAMMA * (-beta * diff2(i, i + width)).exp();
+            graph.add_edge(i, i + width, w, w);
+        }
+    }
+
+    let source_side = graph.min_cut(source, sink);
+    for (label, in_fg) in labels.iter_mut().zip(&source_side) {
+        if !label.is_fixed() {
+            *label = if *in_fg {
+                Label::ProbableForeground
+            } else {
+                Label::ProbableBackground
+            };
+        }
+    }
+}
+
+/// Initial labels from the rectangle and/or scribble mask.
+fn initial_labels(
+    width: u32,
+    height: u32,
+    rect: Option<&LumeRect>,
+    scribbles: Option<&GrayImage>,
+) -> Result<Vec<Label>> {
+    let mut labels = match rect {
+        Some(r) => {
+            let (rx, ry, rw, rh) =
+                helpers::clip_rect(r.x, r.y, r.width, r.height, width, height)
+                    .ok_or_else(|| anyhow::anyhow!("Rectangle is outside the image"))?;
+            let mut labels = vec![Label::Background; (width * height) as usize];
+            for y in ry..ry + rh {
+                for x in rx..rx + rw {
+                    labels[(y * width + x) as usize] = Label::ProbableForeground;
+                }
+            }
+            labels
+        }
+        None => vec![Label::ProbableBackground; (width * height) as usize],
+    };
+    if let Some(scribbles) = scribbles {
+        for (label, p) in labels.iter_mut().zip(scribbles.pixels()) {
+            match p.0[0] {
+                0..=63 => *label = Label::Background,
+                193..=255 => *label = Label::Foreground,
+                _ => {}
+            }
+        }
+    }
+    Ok(labels)
+}
+
+/// GrabCut-style foreground extraction without any model download. The
+/// object is marked with a `rect` (everything outside is background), a
+/// `scribble_bytes` mask of the image's size (dark = background, bright =
+/// foreground, mid-gray = unknown), or both. Color models and a graph cut are
+/// alternated `iterations` times; the result is a mask (255 = foreground).
+#[flutter_rust_bridge::frb(sync)]
+pub fn extract_foreground(
+    image_bytes: Vec<u8>,
+    rect: Option<LumeRect>,
+    scribble_bytes: Option<Vec<u8>>,
+    iterations: u32,
+) -> Result<Vec<u8>> {
+    let img = helpers::load(&image_bytes)?.to_rgb8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let (w, h) = img.dimensions();
+    let scribbles = scribble_bytes
+        .map(|bytes| helpers::load_mask(&bytes, w, h))
+        .transpose()?;
+    let mask = grab_cut(&img, rect.as_ref(), scribbles.as_ref(), iterations)?;
+    helpers::encode(&DynamicImage::ImageLuma8(mask), fmt)
+}
+
+/// `extract_foreground` with masks passed as `LumeMask`. Scribbles need an
+/// unpacked mask to mark unknown areas (mid-gray); in a packed one every
+/// pixel is either foreground or background.
+#[flutter_rust_bridge::frb(sync)]
+pub fn extract_foreground_mask(
+    image_bytes: Vec<u8>,
+    rect: Option<LumeRect>,
+    scribbles: Option<LumeMask>,
+    iterations: u32,
+    packed: bool,
+) -> Result<LumeMask> {
+    let img = helpers::load(&image_bytes)?.to_rgb8();
+    let (w, h) = img.dimensions();
+    let scribbles = scribbles
+        .map(|m| mask::to_gray_sized(&m, w, h))
+        .transpose()?;
+    let mask = grab_cut(&img, rect.as_ref(), scribbles.as_ref(), iterations)?;
+    Ok(mask::from_gray(&mask, packed))
+}
+
+fn grab_cut(
+    img: &RgbImage,
+    rect: Option<&LumeRect>,
+    scribbles: Option<&GrayImage>,
+    iterations: u32,
+) -> Result<GrayImage> {
+    let (w, h) = img.dimensions();
+    if rect.is_none() && scribbles.is_none() {
+        return Err(anyhow::anyhow!(
+            "Either a rectangle or scribbles are required"
+        ));
+    }
+    let full_labels = initial_labels(w, h, rect, scribbles)?;
+
+    // Segment a downscaled copy; the graph grows with the pixel count.
+    let scale = (GRABCUT_MAX_SIDE as f64 / w.max(h) as f64).min(1.0);
+    let sw = ((w as f64 * scale).round() as u32).max(1);
+    let sh = ((h as f64 * scale).round() as u32).max(1);
+    let small = image::imageops::resize(img, sw, sh, image::imageops::FilterType::Triangle);
+    let pixels: Vec<[f64; 3]> = small.pixels().map(|p| p.0.map(f64::from)).collect();
+    let mut labels: Vec<Label> = (0..sw * sh)
+        .map(|i| {
+            let x = ((i % sw) as u64 * w as u64 / sw as u64) as u32;
+            let y = ((i / sw) as u64 * h as u64 / sh as u64) as u32;
+            full_labels[(y * w + x) as usize]
+        })
+        .collect();
+
+    let mut fg: Option<Gmm> = None;
+    let mut bg: Option<Gmm> = None;
+    for _ in 0..iterations.max(1) {
+        let fg_model = fit_model(&pixels, &labels, true, fg.as_ref())?;
+        let bg_model = fit_model(&pixels, &labels, false, bg.as_ref())?;
+        graph_cut(&pixels, &mut labels, sw as usize, &fg_model, &bg_model);
+        fg = Some(fg_model);
+        bg = Some(bg_model);
+    }
+
+    let small_mask = GrayImage::from_fn(sw, sh, |x, y| {
+        Luma([if labels[(y * sw + x) as usize].is_foreground() {
+            255
+        } else {
+            0
+        }])
+    });
+    let upscaled =
+        image::imageops::resize(&small_mask, w, h, image::imageops::FilterType::Triangle);
+    let mask = GrayImage::from_fn(w, h, |x, y| {
+        let v = match full_labels[(y * w + x) as usize] {
+            Label::Foreground => 255,
+            Label::Background => 0,
+            _ if upscaled.get_pixel(x, y).0[0] >= 128 => 255,
+            _ => 0,
+        };
+        Luma([v])
+    });
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Left half red, right half blue, with a red square inside the blue half
+    /// that does not touch the left half.
+    fn two_regions() -> Vec<u8> {
+        let img = RgbaImage::from_fn(8, 6, |x, y| {
+            if x < 4 || (x == 6 && y == 2) {
+                Rgba([220, 20, 20, 255])
+            } else {
+                Rgba([20, 20, 220, 255])
+            }
+        });
+        helpers::encode(&DynamicImage::ImageRgba8(img), image::ImageFormat::Png).unwrap()
+    }
+
+    #[test]
+    fn flood_fill_stays_in_the_connected_region() {
+        let mask = flood_fill_mask(two_regions(), 0, 0, 10).unwrap();
+        let mask = helpers::load(&mask).unwrap().to_luma8();
+        for (x, y, p) in mask.enumerate_pixels() {
+            assert_eq!(p.0[0] == 255, x < 4, "pixel ({x}, {y})");
+        }
+
+        let all = select_similar(two_regions(), 0, 0, 10, false).unwrap();
+        let all = helpers::load(&all).unwrap().to_luma8();
+        assert_eq!(all.get_pixel(6, 2).0[0], 255);
+        assert!(flood_fill_mask(two_regions(), 8, 0, 10).is_err());
+    }
+
+    #[test]
+    fn grab_cut_separates_the_marked_object() {
+        let img = RgbaImage::from_fn(24, 24, |x, y| {
+            if (8..16).contains(&x) && (8..16).contains(&y) {
+                Rgba([240, 200, 30, 255])
+            } else {
+                Rgba([30, 60, 200, 255])
+            }
+        });
+        let bytes =
+            helpers::encode(&DynamicImage::ImageRgba8(img), image::ImageFormat::Png).unwrap();
+        let rect = LumeRect {
+            x: 4,
+            y: 4,
+            width: 16,
+            height: 16,
+        };
+        let mask = extract_foreground(bytes.clone(), Some(rect), None, 3).unwrap();
+        let mask = helpers::load(&mask).unwrap().to_luma8();
+        assert_eq!(mask.get_pixel(12, 12).0[0], 255);
+        assert_eq!(mask.get_pixel(5, 5).0[0], 0);
+        assert_eq!(mask.get_pixel(0, 0).0[0], 0);
+        assert!(extract_foreground(bytes, None, None, 3).is_err());
+    }
+}
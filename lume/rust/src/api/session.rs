@@ -0,0 +1,318 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+
+use crate::api::pipeline::{self, LumeOp};
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Structs
+// ---------------------------------------------------------------------------
+
+/// Rendered images are kept after every this many ops, so undo replays at
+/// most this many ops instead of the whole history.
+const SNAPSHOT_INTERVAL: usize = 4;
+
+/// A non-destructive edit session, the editing counterpart of `LumeHandle`:
+/// the decoded original plus the list of applied ops. History steps are
+/// stored as ops, not images, so an editor keeps one decoded image (plus a
+/// few snapshots) on the Rust side instead of an encoded copy per step in
+/// Dart.
+///
+/// With a preview size set, ops run on a downscaled proxy for interactive
+/// feedback, and `session_render_full` replays them on the original.
+#[flutter_rust_bridge::frb(opaque)]
+pub struct LumeSession {
+    format: ImageFormat,
+    /// The full-size original while previewing; `None` when `snapshots[0]`
+    /// is the original itself.
+    full: Option<DynamicImage>,
+    /// Proxy size relative to the original (1 without a preview).
+    preview_scale: f32,
+    /// Every op applied so far, including undone ones that can be redone.
+    history: Vec<LumeOp>,
+    /// How many ops of `history` are currently applied.
+    applied: usize,
+    /// Renders after `n` ops, for `n` multiple of `SNAPSHOT_INTERVAL`
+    /// (`snapshots[0]` is the original, or the proxy while previewing).
+    snapshots: Vec<DynamicImage>,
+    current: DynamicImage,
+}
+
+impl LumeSession {
+    fn state(&self) -> LumeSessionState {
+        LumeSessionState {
+            width: self.current.width(),
+            height: self.current.height(),
+            applied: self.applied as u32,
+            can_undo: self.applied > 0,
+            can_redo: self.applied < self.history.len(),
+        }
+    }
+
+    /// `op` as it runs on the working image (the proxy while previewing).
+    fn working_op(&self, op: &LumeOp) -> LumeOp {
+        if self.preview_scale < 1.0 {
+            pipeline::scale_op(op, self.preview_scale)
+        } else {
+            op.clone()
+        }
+    }
+
+    fn apply(&self, img: DynamicImage, op: &LumeOp) -> Result<DynamicImage> {
+        pipeline::apply_op(img, &self.working_op(op))
+    }
+
+    /// Re-renders `current` for `self.applied` ops from the nearest snapshot.
+    fn rerender(&mut self) -> Result<()> {
+        let base = (self.applied / SNAPSHOT_INTERVAL).min(self.snapshots.len() - 1);
+        let start = base * SNAPSHOT_INTERVAL;
+        let mut img = self.snapshots[base].clone();
+        for op in &self.history[start..self.applied] {
+            img = self.apply(img, op)?;
+        }
+        self.current = img;
+        Ok(())
+    }
+
+    /// Makes `next` the current render, one op further into `history`.
+    fn advance(&mut self, next: DynamicImage) {
+        self.current = next;
+        self.applied += 1;
+        if self.applied.is_multiple_of(SNAPSHOT_INTERVAL) {
+            self.snapshots.truncate(self.applied / SNAPSHOT_INTERVAL);
+            self.snapshots.push(self.current.clone());
+        }
+    }
+}
+
+/// `width` and `height` are those of the working image, the proxy while
+/// previewing.
+pub struct LumeSessionState {
+    pub width: u32,
+    pub height: u32,
+    /// Number of ops currently applied.
+    pub applied: u32,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Session
+// ---------------------------------------------------------------------------
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_open(image_bytes: Vec<u8>) -> Result<LumeSession> {
+    let original = helpers::load(&image_bytes)?;
+    let format = helpers::detect_format(&image_bytes)?;
+    Ok(LumeSession {
+        current: original.clone(),
+        snapshots: vec![original],
+        full: None,
+        preview_scale: 1.0,
+        format,
+        history: Vec::new(),
+        applied: 0,
+    })
+}
+
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_state(session: &LumeSession) -> LumeSessionState {
+    session.state()
+}
+
+/// Applies `op` on top of the current state. Ops that were undone are
+/// discarded, as in any editor. A failing op leaves the session unchanged.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_apply(session: &mut LumeSession, op: LumeOp) -> Result<LumeSessionState> {
+    let next = session.apply(session.current.clone(), &op)?;
+    session.history.truncate(session.applied);
+    session
+        .snapshots
+        .truncate(session.applied / SNAPSHOT_INTERVAL + 1);
+    session.history.push(op);
+    session.advance(next);
+    Ok(session.state())
+}
+
+/// Steps back one op. Does nothing when there is nothing to undo.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_undo(session: &mut LumeSession) -> Result<LumeSessionState> {
+    if session.applied > 0 {
+        session.applied -= 1;
+        session.rerender()?;
+    }
+    Ok(session.state())
+}
+
+/// Re-applies the last undone op. Does nothing when there is nothing to redo.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_redo(session: &mut LumeSession) -> Result<LumeSessionState> {
+    if let Some(op) = session.history.get(session.applied) {
+        let next = session.apply(session.current.clone(), op)?;
+        session.advance(next);
+    }
+    Ok(session.state())
+}
+
+/// The currently applied ops, e.g. to save them with `pipeline_to_json`.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_ops(session: &LumeSession) -> Vec<LumeOp> {
+    session.history[..session.applied].to_vec()
+}
+
+/// Encodes the current state, at preview size while previewing. An empty
+/// `format` keeps the source format.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_export(session: &LumeSession, format: String) -> Result<Vec<u8>> {
+    let fmt = if format.is_empty() {
+        session.format
+    } else {
+        helpers::string_to_format(&format)?
+    };
+    helpers::encode(&session.current, fmt)
+}
+
+/// Drops every op and goes back to the original image.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_reset(session: &mut LumeSession) -> LumeSessionState {
+    session.history.clear();
+    session.applied = 0;
+    session.snapshots.truncate(1);
+    session.current = session.snapshots[0].clone();
+    session.state()
+}
+
+// ---------------------------------------------------------------------------
+// Preview
+// ---------------------------------------------------------------------------
+
+/// Runs further edits on a proxy whose longer side is at most `max_dim`, so
+/// slider adjustments stay interactive on large photos. Already applied ops
+/// are re-rendered on the proxy; sizes, offsets and blur radii in ops keep
+/// referring to the full-size image. 0 (or a size at least as large as the
+/// original) turns the preview off.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_set_preview_size(
+    session: &mut LumeSession,
+    max_dim: u32,
+) -> Result<LumeSessionState> {
+    let original = match session.full.take() {
+        Some(original) => original,
+        None => session.snapshots.swap_remove(0),
+    };
+    let longest = original.width().max(original.height());
+    let base = if max_dim == 0 || max_dim >= longest {
+        session.preview_scale = 1.0;
+        original
+    } else {
+        let proxy = original.resize(max_dim, max_dim, FilterType::Triangle);
+        session.preview_scale = proxy.width() as f32 / original.width() as f32;
+        session.full = Some(original);
+        proxy
+    };
+    session.current = base.clone();
+    session.snapshots = vec![base];
+    let applied = std::mem::take(&mut session.applied);
+    for i in 0..applied {
+        let next = session.apply(session.current.clone(), &session.history[i])?;
+        session.advance(next);
+    }
+    Ok(session.state())
+}
+
+/// Replays the applied ops on the full-size original and encodes the result.
+/// An empty `format` keeps the source format.
+#[flutter_rust_bridge::frb(sync)]
+pub fn session_render_full(session: &LumeSession, format: String) -> Result<Vec<u8>> {
+    let fmt = if format.is_empty() {
+        session.format
+    } else {
+        helpers::string_to_format(&format)?
+    };
+    if session.full.is_none() {
+        return helpers::encode(&session.current, fmt);
+    }
+    let original = session
+        .full
+        .clone()
+        .unwrap_or_else(|| session.snapshots[0].clone());
+    let out = pipeline::apply_ops(original, &session.history[..session.applied])?;
+    helpers::encode(&out, fmt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image() -> Vec<u8> {
+        let img = image::RgbaImage::from_fn(6, 4, |x, y| {
+            image::Rgba([x as u8 * 40, y as u8 * 60, 100, 255])
+        });
+        helpers::encode(&DynamicImage::ImageRgba8(img), ImageFormat::Png).unwrap()
+    }
+
+    fn expected(bytes: &[u8], ops: &[LumeOp]) -> Vec<u8> {
+        let img = pipeline::apply_ops(helpers::load(bytes).unwrap(), ops).unwrap();
+        img.to_rgba8().into_raw()
+    }
+
+    #[test]
+    fn undo_and_redo_replay_from_snapshots() {
+        let bytes = test_image();
+        let mut session = session_open(bytes.clone()).unwrap();
+        let ops: Vec<LumeOp> = (0..2 * SNAPSHOT_INTERVAL + 1)
+            .map(|i| match i % 3 {
+                0 => LumeOp::Rotate { degrees: 90 },
+                1 => LumeOp::Brightness { value: 10 },
+                _ => LumeOp::FlipHorizontal,
+            })
+            .collect();
+        for op in &ops {
+            session_apply(&mut session, op.clone()).unwrap();
+        }
+        assert_eq!(session.snapshots.len(), 3);
+
+        // Undo across both snapshot boundaries, checking every step.
+        for applied in (0..ops.len()).rev() {
+            let state = session_undo(&mut session).unwrap();
+            assert_eq!(state.applied as usize, applied);
+            assert_eq!(
+                session.current.to_rgba8().into_raw(),
+                expected(&bytes, &ops[..applied])
+            );
+        }
+        assert!(!session_undo(&mut session).unwrap().can_undo);
+
+        for applied in 1..=SNAPSHOT_INTERVAL + 1 {
+            let state = session_redo(&mut session).unwrap();
+            assert_eq!(state.applied as usize, applied);
+        }
+        assert_eq!(
+            session.current.to_rgba8().into_raw(),
+            expected(&bytes, &ops[..SNAPSHOT_INTERVAL + 1])
+        );
+
+        // A new op drops the redo history.
+        let state = session_apply(&mut session, LumeOp::Invert).unwrap();
+        assert!(!state.can_redo);
+        let mut kept = ops[..SNAPSHOT_INTERVAL + 1].to_vec();
+        kept.push(LumeOp::Invert);
+        assert_eq!(session_ops(&session).len(), kept.len());
+        session_undo(&mut session).unwrap();
+        session_undo(&mut session).unwrap();
+        assert_eq!(
+            session.current.to_rgba8().into_raw(),
+            expected(&bytes, &kept[..SNAPSHOT_INTERVAL])
+        );
+    }
+
+    #[test]
+    fn failing_op_leaves_the_session_unchanged() {
+        let mut session = session_open(test_image()).unwrap();
+        session_apply(&mut session, LumeOp::Grayscale).unwrap();
+        assert!(session_apply(&mut session, LumeOp::Rotate { degrees: 45 }).is_err());
+        let state = session_state(&session);
+        assert_eq!((state.applied, state.can_redo), (1, false));
+    }
+}
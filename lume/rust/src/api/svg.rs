@@ -0,0 +1,48 @@
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
+
+use crate::api::image_ops::LumeColor;
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Rasterization
+// ---------------------------------------------------------------------------
+
+/// Renders an SVG document to a PNG over `bg_color` (use alpha 0 for a
+/// transparent background). A zero `width` or `height` is derived from the
+/// other one and the document's aspect ratio; both zero keep its intrinsic
+/// size. Text is drawn only with fonts embedded as paths.
+#[flutter_rust_bridge::frb(sync)]
+pub fn rasterize_svg(
+    svg_bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    bg_color: LumeColor,
+) -> Result<Vec<u8>> {
+    let tree = usvg::Tree::from_data(&svg_bytes, &usvg::Options::default())?;
+    let size = tree.size();
+    let (sw, sh) = (size.width(), size.height());
+    let (w, h) = match (width, height) {
+        (0, 0) => (sw.ceil() as u32, sh.ceil() as u32),
+        (0, h) => (((h as f32 * sw / sh).round() as u32).max(1), h),
+        (w, 0) => (w, ((w as f32 * sh / sw).round() as u32).max(1)),
+        (w, h) => (w, h),
+    };
+    let mut pixmap =
+        tiny_skia::Pixmap::new(w, h).ok_or_else(|| anyhow::anyhow!("Invalid output size"))?;
+    pixmap.fill(tiny_skia::Color::from_rgba8(
+        bg_color.r, bg_color.g, bg_color.b, bg_color.a,
+    ));
+    let transform = tiny_skia::Transform::from_scale(w as f32 / sw, h as f32 / sh);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // tiny-skia stores premultiplied alpha.
+    let out = RgbaImage::from_fn(w, h, |x, y| {
+        let p = pixmap.pixel(x, y).map(|p| p.demultiply());
+        p.map_or(Rgba([0, 0, 0, 0]), |c| {
+            Rgba([c.red(), c.green(), c.blue(), c.alpha()])
+        })
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(out), ImageFormat::Png)
+}
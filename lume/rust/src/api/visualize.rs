@@ -0,0 +1,262 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgba};
+
+use crate::helpers;
+
+// ---------------------------------------------------------------------------
+// Colormaps
+// ---------------------------------------------------------------------------
+
+/// Scientific colormaps, from low (0) to high (1) values.
+#[derive(Clone, Copy)]
+enum Colormap {
+    Viridis,
+    Inferno,
+    Jet,
+    Hot,
+}
+
+/// Polynomial fits of matplotlib's viridis and inferno, per channel from the
+/// constant term up.
+const VIRIDIS: [[f32; 7]; 3] = [
+    [
+        0.277_727_3,
+        0.105_093,
+        -0.330_861_8,
+        -4.634_230_5,
+        6.228_27,
+        4.776_385,
+        -5.435_456,
+    ],
+    [
+        0.005_407_3,
+        1.404_613_5,
+        0.214_847_6,
+        -5.799_101,
+        14.179_933,
+        -13.745_145,
+        4.645_853,
+    ],
+    [
+        0.334_099_8,
+        1.384_590_2,
+        0.095_095_2,
+        -19.332_441,
+        56.690_55,
+        -65.353_03,
+        26.312_435,
+    ],
+];
+const INFERNO: [[f32; 7]; 3] = [
+    [
+        0.000_218_9,
+        0.106_513_4,
+        11.602_493,
+        -41.703_996,
+        77.162_94,
+        -71.319_43,
+        25.131_126,
+    ],
+    [
+        0.001_651,
+        0.563_956_4,
+        -3.972_854,
+        17.436_4,
+        -33.402_36,
+        32.626_064,
+        -12.242_669,
+    ],
+    [
+        -0.019_480_9,
+        3.932_712_4,
+        -15.942_394,
+        44.354_145,
+        -81.807_31,
+        73.209_52,
+        -23.070_325,
+    ],
+];
+
+impl Colormap {
+    fn parse(name: &str) -> Result<Colormap> {
+        match name.to_lowercase().as_str() {
+            "viridis" => Ok(Colormap::Viridis),
+            "inferno" => Ok(Colormap::Inferno),
+            "jet" => Ok(Colormap::Jet),
+            "hot" => Ok(Colormap::Hot),
+            other => Err(anyhow::anyhow!("Unsupported colormap: {}", other)),
+        }
+    }
+
+    /// Color for `t`, clamped to [0, 1].
+    fn at(self, t: f32) -> [u8; 3] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let poly = |coeffs: &[f32; 7]| coeffs.iter().rev().fold(0.0, |acc, c| acc * t + c);
+        let rgb = match self {
+            Colormap::Viridis => VIRIDIS.map(|c| poly(&c)),
+            Colormap::Inferno => INFERNO.map(|c| poly(&c)),
+            // Blue, cyan, yellow, red ramps centered at 1/4, 2/4, 3/4.
+            Colormap::Jet => [3.0, 2.0, 1.0].map(|center| 1.5 - (4.0 * t - center).abs()),
+            // Black through red and yellow to white, one channel at a time.
+            Colormap::Hot => [0.0, 1.0, 2.0].map(|start| 3.0 * t - start),
+        };
+        rgb.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Heatmaps & false color
+// ---------------------------------------------------------------------------
+
+/// Colorizes a row-major `width` x `height` grid of `values` (e.g. a saliency
+/// map or ML attention scores) and blends it over the image at `opacity`
+/// (0-1). The grid is stretched over the whole image with bilinear
+/// interpolation, and values are normalized so the lowest maps to the start
+/// of `colormap` (see `apply_colormap`) and the highest to its end.
+#[flutter_rust_bridge::frb(sync)]
+pub fn overlay_heatmap(
+    image_bytes: Vec<u8>,
+    values: Vec<f32>,
+    width: u32,
+    height: u32,
+    colormap: String,
+    opacity: f32,
+) -> Result<Vec<u8>> {
+    let colormap = Colormap::parse(&colormap)?;
+    if values.len() as u64 != width as u64 * height as u64 || values.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Expected {}x{} heatmap values, got {}",
+            width,
+            height,
+            values.len()
+        ));
+    }
+    let mut img = helpers::load(&image_bytes)?.to_rgba8();
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let finite = || values.iter().copied().filter(|v| v.is_finite());
+    let min = finite().fold(f32::INFINITY, f32::min);
+    let max = finite().fold(f32::NEG_INFINITY, f32::max);
+    let range = if max > min { max - min } else { 1.0 };
+    let grid: ImageBuffer<Luma<f32>, Vec<f32>> = ImageBuffer::from_vec(
+        width,
+        height,
+        values.iter().map(|v| (v - min) / range).collect(),
+    )
+    .ok_or_else(|| anyhow::anyhow!("Invalid heatmap size"))?;
+    let grid = image::imageops::resize(&grid, img.width(), img.height(), FilterType::Triangle);
+    let coverage = GrayImage::from_pixel(img.width(), img.height(), Luma([255]));
+    helpers::paint_over(&mut img, &coverage, opacity, |x, y| {
+        let [r, g, b] = colormap.at(grid.get_pixel(x, y).0[0]);
+        Rgba([r, g, b, 255])
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(img), fmt)
+}
+
+/// Maps the image's luma through `colormap`, e.g. to visualize a distance
+/// transform, depth map or thermal data: "viridis", "inferno", "jet" or
+/// "hot". Black maps to the start of the colormap and white to its end;
+/// alpha is kept.
+#[flutter_rust_bridge::frb(sync)]
+pub fn apply_colormap(image_bytes: Vec<u8>, colormap: String) -> Result<Vec<u8>> {
+    let colormap = Colormap::parse(&colormap)?;
+    let img = helpers::load(&image_bytes)?;
+    let fmt = helpers::detect_format(&image_bytes)?;
+    let lut: Vec<[u8; 3]> = (0..=255).map(|v| colormap.at(v as f32 / 255.0)).collect();
+    let luma = img.to_luma_alpha8();
+    let out = if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(
+            img.width(),
+            img.height(),
+            |x, y| {
+                let [v, a] = luma.get_pixel(x, y).0;
+                let [r, g, b] = lut[v as usize];
+                Rgba([r, g, b, a])
+            },
+        ))
+    } else {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(
+            img.width(),
+            img.height(),
+            |x, y| image::Rgb(lut[luma.get_pixel(x, y).0[0] as usize]),
+        ))
+    };
+    helpers::encode(&out, fmt)
+}
+
+// ---------------------------------------------------------------------------
+// Charts
+// ---------------------------------------------------------------------------
+
+/// Bin counts for each of `width` columns: the bins falling in the column
+/// are merged with `max`, so narrow spikes survive narrow charts.
+fn chart_columns(bins: &[u32; 256], width: u32) -> Vec<u32> {
+    (0..width as usize)
+        .map(|x| {
+            let start = x * 256 / width as usize;
+            let end = ((x + 1) * 256 / width as usize).max(start + 1);
+            bins[start..end].iter().copied().max().unwrap_or(0)
+        })
+        .collect()
+}
+
+/// Renders the image's histogram as a `width` x `height` PNG chart on a
+/// transparent background, so editor UIs can show it as is. `style` is:
+/// - "luma": one light gray chart of the luminance;
+/// - "rgb": the red, green and blue charts overlaid additively, so where
+///   all three overlap the chart is white.
+///
+/// Bars are scaled so the tallest bin fills the height.
+#[flutter_rust_bridge::frb(sync)]
+pub fn render_histogram(
+    image_bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    style: String,
+) -> Result<Vec<u8>> {
+    if width == 0 || height == 0 {
+        return Err(anyhow::anyhow!("Histogram chart size must be positive"));
+    }
+    let img = helpers::load(&image_bytes)?;
+    // Channel histograms with the color each is drawn in.
+    let channels: Vec<([u32; 256], [u8; 3])> = match style.to_lowercase().as_str() {
+        "luma" => {
+            let hist = imageproc::stats::histogram(&img.to_luma8());
+            vec![(hist.channels[0], [200, 200, 200])]
+        }
+        "rgb" => {
+            let hist = imageproc::stats::histogram(&img.to_rgb8());
+            let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+            hist.channels.into_iter().zip(colors).collect()
+        }
+        other => return Err(anyhow::anyhow!("Unsupported histogram style: {}", other)),
+    };
+    let columns: Vec<Vec<u32>> = channels
+        .iter()
+        .map(|(bins, _)| chart_columns(bins, width))
+        .collect();
+    let peak = columns.iter().flatten().copied().max().unwrap_or(0).max(1) as f32;
+    // Bar heights in pixels, per channel and column.
+    let bars: Vec<Vec<u32>> = columns
+        .iter()
+        .map(|c| {
+            c.iter()
+                .map(|&n| (n as f32 / peak * height as f32).round() as u32)
+                .collect()
+        })
+        .collect();
+    let chart = image::RgbaImage::from_fn(width, height, |x, y| {
+        let mut rgb = [0u8; 3];
+        let mut covered = false;
+        for ((_, color), bar) in channels.iter().zip(&bars) {
+            if height - y <= bar[x as usize] {
+                covered = true;
+                for (v, c) in rgb.iter_mut().zip(color) {
+                    *v = v.saturating_add(*c);
+                }
+            }
+        }
+        Rgba([rgb[0], rgb[1], rgb[2], if covered { 255 } else { 0 }])
+    });
+    helpers::encode(&DynamicImage::ImageRgba8(chart), image::ImageFormat::Png)
+}
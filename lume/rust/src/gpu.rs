@@ -0,0 +1,532 @@
+//! GPU execution of heavy filters (`gpu` feature) through wgpu compute
+//! shaders. Every entry point returns `None` when the GPU cannot or should
+//! not run the job (no adapter, GPU disabled, image too small to be worth
+//! the transfers, buffer limits exceeded, or any wgpu error), and callers
+//! then fall back to their CPU path.
+
+use image::RgbaImage;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use wgpu::util::DeviceExt;
+
+use crate::helpers::{self, Taps};
+
+/// Below this many pixels, uploading and reading back costs more than the
+/// CPU needs for the whole filter.
+const MIN_PIXELS: u64 = 512 * 512;
+/// Side of the 2D workgroups of the image shaders.
+const TILE: u32 = 16;
+/// Invocations per workgroup of the per-pixel shaders.
+const LINE: u32 = 256;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+
+/// One pass of a separable filter: output pixel `o` along the pass axis is
+/// the weighted sum of `taps` source pixels from `starts[o]`, with
+/// `weights[o * taps ..]`. Source coordinates are clamped to the image.
+/// RGBA8 pixels travel packed in a u32; the intermediate is f32 so the
+/// second pass does not see rounded values.
+const RESAMPLE_SHADER: &str = r#"
+struct Params {
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    taps: u32,
+    horizontal: u32,
+    pad0: u32,
+    pad1: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> starts: array<i32>;
+@group(0) @binding(2) var<storage, read> weights: array<f32>;
+@group(0) @binding(3) var<storage, read> src: array<SRC_TYPE>;
+@group(0) @binding(4) var<storage, read_write> dst: array<DST_TYPE>;
+
+@compute @workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.dst_width || id.y >= params.dst_height) {
+        return;
+    }
+    let horizontal = params.horizontal == 1u;
+    let o = select(id.y, id.x, horizontal);
+    let start = starts[o];
+    var acc = vec4<f32>(0.0);
+    for (var k = 0u; k < params.taps; k = k + 1u) {
+        let s = start + i32(k);
+        var i: u32;
+        if (horizontal) {
+            i = id.y * params.src_width + u32(clamp(s, 0, i32(params.src_width) - 1));
+        } else {
+            i = u32(clamp(s, 0, i32(params.src_height) - 1)) * params.src_width + id.x;
+        }
+        acc = acc + weights[o * params.taps + k] * LOAD;
+    }
+    dst[id.y * params.dst_width + id.x] = STORE;
+}
+"#;
+
+/// 2D correlation of the color channels, as `imageproc::filter::Kernel`
+/// does it: anchored at the kernel center, clamped at the borders. Alpha is
+/// kept.
+const CONVOLVE_SHADER: &str = r#"
+struct Params {
+    width: u32,
+    height: u32,
+    kernel_width: u32,
+    kernel_height: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> weights: array<f32>;
+@group(0) @binding(2) var<storage, read> src: array<u32>;
+@group(0) @binding(3) var<storage, read_write> dst: array<u32>;
+
+@compute @workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+    let cx = i32(params.kernel_width / 2u);
+    let cy = i32(params.kernel_height / 2u);
+    var acc = vec3<f32>(0.0);
+    for (var ky = 0u; ky < params.kernel_height; ky = ky + 1u) {
+        let y = u32(clamp(i32(id.y) + i32(ky) - cy, 0, i32(params.height) - 1));
+        for (var kx = 0u; kx < params.kernel_width; kx = kx + 1u) {
+            let x = u32(clamp(i32(id.x) + i32(kx) - cx, 0, i32(params.width) - 1));
+            let w = weights[ky * params.kernel_width + kx];
+            acc = acc + w * unpack4x8unorm(src[y * params.width + x]).rgb;
+        }
+    }
+    let i = id.y * params.width + id.x;
+    dst[i] = pack4x8unorm(vec4<f32>(acc, unpack4x8unorm(src[i]).a));
+}
+"#;
+
+/// Maps the color channels of packed RGBA8 pixels through a 256-entry LUT,
+/// in place. Pixels are numbered row by row over a 2D dispatch, `row`
+/// invocations per dispatch row.
+const LUT_SHADER: &str = r#"
+struct Params {
+    row: u32,
+    len: u32,
+    pad0: u32,
+    pad1: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> lut: array<u32>;
+@group(0) @binding(2) var<storage, read_write> pixels: array<u32>;
+
+@compute @workgroup_size(256)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.y * params.row + id.x;
+    if (i >= params.len) {
+        return;
+    }
+    let p = pixels[i];
+    let r = lut[p & 0xffu];
+    let g = lut[(p >> 8u) & 0xffu];
+    let b = lut[(p >> 16u) & 0xffu];
+    pixels[i] = r | (g << 8u) | (b << 16u) | (p & 0xff000000u);
+}
+"#;
+
+struct Gpu {
+    name: String,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    limits: wgpu::Limits,
+    /// Packed RGBA8 in, f32 out: first pass of a separable filter.
+    resample_first: wgpu::ComputePipeline,
+    /// f32 in, packed RGBA8 out: second pass.
+    resample_second: wgpu::ComputePipeline,
+    convolve: wgpu::ComputePipeline,
+    lut: wgpu::ComputePipeline,
+}
+
+fn to_bytes<T: Copy, const N: usize>(values: &[T], f: impl Fn(T) -> [u8; N]) -> Vec<u8> {
+    values.iter().flat_map(|&v| f(v)).collect()
+}
+
+fn init() -> Option<Gpu> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        force_fallback_adapter: false,
+        compatible_surface: None,
+    }))?;
+    let info = adapter.get_info();
+    // Software rasterizers are slower than the CPU paths they would replace.
+    if info.device_type == wgpu::DeviceType::Cpu {
+        return None;
+    }
+    let limits = adapter.limits();
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("lume"),
+            required_features: wgpu::Features::empty(),
+            required_limits: limits.clone(),
+            memory_hints: wgpu::MemoryHints::Performance,
+        },
+        None,
+    ))
+    .ok()?;
+    // Errors are caught per job with error scopes; by default wgpu panics
+    // on the ones nobody catches.
+    device.on_uncaptured_error(Box::new(|_| {}));
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let pipeline = |label: &str, source: String| {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+    };
+    let resample = |src: &str, dst: &str, load: &str, store: &str| {
+        RESAMPLE_SHADER
+            .replace("SRC_TYPE", src)
+            .replace("DST_TYPE", dst)
+            .replace("LOAD", load)
+            .replace("STORE", store)
+    };
+    let resample_first = pipeline(
+        "resample_first",
+        resample("u32", "vec4<f32>", "unpack4x8unorm(src[i])", "acc"),
+    );
+    let resample_second = pipeline(
+        "resample_second",
+        resample("vec4<f32>", "u32", "src[i]", "pack4x8unorm(acc)"),
+    );
+    let convolve = pipeline("convolve", CONVOLVE_SHADER.to_string());
+    let lut = pipeline("lut", LUT_SHADER.to_string());
+    if pollster::block_on(device.pop_error_scope()).is_some() {
+        return None;
+    }
+    Some(Gpu {
+        name: info.name,
+        device,
+        queue,
+        limits,
+        resample_first,
+        resample_second,
+        convolve,
+        lut,
+    })
+}
+
+fn gpu() -> Option<&'static Gpu> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    GPU.get_or_init(init).as_ref()
+}
+
+impl Gpu {
+    /// Whether buffers of `sizes` bytes can be bound as storage.
+    fn fits(&self, sizes: &[u64]) -> bool {
+        let max =
+            (self.limits.max_storage_buffer_binding_size as u64).min(self.limits.max_buffer_size);
+        sizes.iter().all(|&s| s > 0 && s <= max)
+    }
+
+    fn upload(&self, contents: &[u8], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents,
+                usage,
+            })
+    }
+
+    fn uniform(&self, values: &[u32]) -> wgpu::Buffer {
+        self.upload(
+            &to_bytes(values, u32::to_le_bytes),
+            wgpu::BufferUsages::UNIFORM,
+        )
+    }
+
+    fn storage(&self, contents: &[u8]) -> wgpu::Buffer {
+        self.upload(contents, wgpu::BufferUsages::STORAGE)
+    }
+
+    fn output(&self, size: u64) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Records a dispatch of `pipeline` with `buffers` bound in order.
+    fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::ComputePipeline,
+        buffers: &[&wgpu::Buffer],
+        (x, y): (u32, u32),
+    ) {
+        let entries: Vec<wgpu::BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(x, y, 1);
+    }
+
+    /// Runs the dispatches `record` adds and reads back the buffer it
+    /// returns, or `None` if wgpu reported an error on the way.
+    fn run(
+        &self,
+        size: u64,
+        record: impl FnOnce(&mut wgpu::CommandEncoder) -> wgpu::Buffer,
+    ) -> Option<Vec<u8>> {
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let result = record(&mut encoder);
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_buffer_to_buffer(&result, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |mapped| {
+            let _ = tx.send(mapped);
+        });
+        let _ = self.device.poll(wgpu::Maintain::Wait);
+        let mapped = rx.recv().ok().and_then(|r| r.ok());
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let out_of_memory = pollster::block_on(self.device.pop_error_scope());
+        mapped?;
+        if validation.is_some() || out_of_memory.is_some() {
+            return None;
+        }
+        let bytes = slice.get_mapped_range().to_vec();
+        staging.unmap();
+        Some(bytes)
+    }
+
+    /// Two separable passes: `first` along x into a `dst_width` x height
+    /// intermediate, then `second` along y into `dst_width` x `dst_height`.
+    fn separable(
+        &self,
+        img: &RgbaImage,
+        (dst_width, dst_height): (u32, u32),
+        first: &Taps,
+        second: &Taps,
+    ) -> Option<RgbaImage> {
+        let (width, height) = img.dimensions();
+        let mid_size = dst_width as u64 * height as u64 * 16;
+        let out_size = dst_width as u64 * dst_height as u64 * 4;
+        let src_size = img.as_raw().len() as u64;
+        if !self.fits(&[src_size, mid_size, out_size]) {
+            return None;
+        }
+        let bytes = self.run(out_size, |encoder| {
+            let src = self.storage(img.as_raw());
+            let mid = self.output(mid_size);
+            let out = self.output(out_size);
+            // `sizes` is (src_width, src_height, dst_width, dst_height).
+            let mut record = |pipeline: &wgpu::ComputePipeline,
+                              pass: &Taps,
+                              input: &wgpu::Buffer,
+                              output: &wgpu::Buffer,
+                              sizes: [u32; 4],
+                              horizontal: bool| {
+                let [_, _, w, h] = sizes;
+                let params = self.uniform(&[
+                    sizes[0],
+                    sizes[1],
+                    w,
+                    h,
+                    pass.taps as u32,
+                    horizontal as u32,
+                    0,
+                    0,
+                ]);
+                let starts = self.storage(&to_bytes(&pass.starts, i32::to_le_bytes));
+                let weights = self.storage(&to_bytes(&pass.weights, f32::to_le_bytes));
+                self.dispatch(
+                    encoder,
+                    pipeline,
+                    &[&params, &starts, &weights, input, output],
+                    (w.div_ceil(TILE), h.div_ceil(TILE)),
+                );
+            };
+            record(
+                &self.resample_first,
+                first,
+                &src,
+                &mid,
+                [width, height, dst_width, height],
+                true,
+            );
+            record(
+                &self.resample_second,
+                second,
+                &mid,
+                &out,
+                [dst_width, height, dst_width, dst_height],
+                false,
+            );
+            out
+        })?;
+        RgbaImage::from_raw(dst_width, dst_height, bytes)
+    }
+}
+
+fn worth_it(pixels: u64) -> bool {
+    pixels >= MIN_PIXELS
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Name of the adapter in use, initializing it on first call.
+pub fn adapter_name() -> Option<String> {
+    gpu().map(|gpu| gpu.name.clone())
+}
+
+/// Same kernel as `imageproc::filter::gaussian_blur_f32`.
+pub fn gaussian_blur(img: &RgbaImage, sigma: f32) -> Option<RgbaImage> {
+    if !sigma.is_finite() || sigma <= 0.0 || !worth_it(img.width() as u64 * img.height() as u64) {
+        return None;
+    }
+    let gpu = gpu()?;
+    let radius = (2.0 * sigma).ceil() as usize;
+    let mut kernel: Vec<f32> = (0..=2 * radius)
+        .map(|i| {
+            let x = i as f32 - radius as f32;
+            (-x * x / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|k| *k /= sum);
+    let pass = |size: u32| Taps {
+        starts: (0..size as i32).map(|o| o - radius as i32).collect(),
+        weights: (0..size).flat_map(|_| kernel.iter().copied()).collect(),
+        taps: kernel.len(),
+    };
+    gpu.separable(
+        img,
+        img.dimensions(),
+        &pass(img.width()),
+        &pass(img.height()),
+    )
+}
+
+/// Lanczos3 resize to exactly `width` x `height`.
+pub fn resize(img: &RgbaImage, width: u32, height: u32) -> Option<RgbaImage> {
+    let (src_w, src_h) = img.dimensions();
+    let pixels = (src_w as u64 * src_h as u64).max(width as u64 * height as u64);
+    if (width, height) == (src_w, src_h) || width == 0 || height == 0 || !worth_it(pixels) {
+        return None;
+    }
+    gpu()?.separable(
+        img,
+        (width, height),
+        &helpers::lanczos3_taps(src_w, width),
+        &helpers::lanczos3_taps(src_h, height),
+    )
+}
+
+/// Correlates the color channels with a row-major kernel, keeping alpha.
+pub fn convolve(
+    img: &RgbaImage,
+    kernel: &[f32],
+    kernel_width: u32,
+    kernel_height: u32,
+) -> Option<RgbaImage> {
+    let (width, height) = img.dimensions();
+    if !worth_it(width as u64 * height as u64) {
+        return None;
+    }
+    let gpu = gpu()?;
+    let size = img.as_raw().len() as u64;
+    if !gpu.fits(&[size, kernel.len() as u64 * 4]) {
+        return None;
+    }
+    let bytes = gpu.run(size, |encoder| {
+        let params = gpu.uniform(&[width, height, kernel_width, kernel_height]);
+        let weights = gpu.storage(&to_bytes(kernel, f32::to_le_bytes));
+        let src = gpu.storage(img.as_raw());
+        let out = gpu.output(size);
+        gpu.dispatch(
+            encoder,
+            &gpu.convolve,
+            &[&params, &weights, &src, &out],
+            (width.div_ceil(TILE), height.div_ceil(TILE)),
+        );
+        out
+    })?;
+    RgbaImage::from_raw(width, height, bytes)
+}
+
+/// Maps the color channels through `lut` (256 entries), keeping alpha.
+pub fn apply_lut(img: &RgbaImage, lut: &[u8]) -> Option<RgbaImage> {
+    let len = img.width() as u64 * img.height() as u64;
+    if lut.len() != 256 || !worth_it(len) {
+        return None;
+    }
+    let gpu = gpu()?;
+    let size = len * 4;
+    let groups = len.div_ceil(LINE as u64);
+    let max_groups = gpu.limits.max_compute_workgroups_per_dimension as u64;
+    let groups_x = groups.min(max_groups);
+    let groups_y = groups.div_ceil(groups_x);
+    if !gpu.fits(&[size]) || groups_y > max_groups {
+        return None;
+    }
+    let bytes = gpu.run(size, |encoder| {
+        let row = (groups_x * LINE as u64) as u32;
+        let params = gpu.uniform(&[row, len as u32, 0, 0]);
+        let table: Vec<u32> = lut.iter().map(|&v| v as u32).collect();
+        let table = gpu.storage(&to_bytes(&table, u32::to_le_bytes));
+        let pixels = gpu.upload(
+            img.as_raw(),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        );
+        gpu.dispatch(
+            encoder,
+            &gpu.lut,
+            &[&params, &table, &pixels],
+            (groups_x as u32, groups_y as u32),
+        );
+        pixels
+    })?;
+    RgbaImage::from_raw(img.width(), img.height(), bytes)
+}
@@ -1,26 +1,406 @@
 use anyhow::Result;
-use image::{DynamicImage, ImageFormat, ImageReader};
+use image::{
+    ColorType, DynamicImage, GrayImage, ImageBuffer, ImageError, ImageFormat, ImageReader, Luma,
+    Pixel, RgbImage, Rgba, Rgba32FImage, RgbaImage,
+};
+use std::hash::Hasher;
 use std::io::Cursor;
+use std::sync::Mutex;
+
+pub type Image<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+/// How the samples of a CMYK JPEG are interpreted.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum CmykMode {
+    /// Inverted when the file has an Adobe APP14 marker, plain otherwise.
+    #[default]
+    Auto,
+    /// Inverted, as Photoshop and most Adobe tools write them.
+    Adobe,
+    /// As stored, as libjpeg-based encoders write them.
+    Plain,
+}
+
+/// Decoder tolerance settings for `load_with`; `load` uses the defaults
+/// (strict, automatic CMYK handling).
+#[derive(Clone, Copy, Default)]
+pub struct DecodeOptions {
+    pub allow_truncated: bool,
+    pub cmyk: CmykMode,
+}
 
 pub fn load(bytes: &[u8]) -> Result<DynamicImage> {
-    Ok(ImageReader::new(Cursor::new(bytes))
-        .with_guessed_format()?
-        .decode()?)
+    crate::metrics::time_decode(|| load_cached(bytes))
+}
+
+fn load_cached(bytes: &[u8]) -> Result<DynamicImage> {
+    if DECODE_CACHE.lock().unwrap_or_else(|e| e.into_inner()).limit == 0 {
+        return load_with(bytes, &DecodeOptions::default());
+    }
+    let key = content_key(bytes);
+    if let Some(img) = cache_get(key) {
+        return Ok(img);
+    }
+    let img = load_with(bytes, &DecodeOptions::default())?;
+    cache_put(key, &img);
+    Ok(img)
+}
+
+pub fn load_with(bytes: &[u8], options: &DecodeOptions) -> Result<DynamicImage> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let format = reader.format();
+    #[cfg(feature = "raw")]
+    if crate::raw::candidate(format) && crate::raw::is_raw(bytes) {
+        if let Some(img) = crate::raw::try_decode(bytes, 0.0) {
+            return Ok(img);
+        }
+    }
+    if format == Some(ImageFormat::Jpeg) {
+        let header = jpeg_header(bytes);
+        let plain = match options.cmyk {
+            CmykMode::Auto => !header.adobe,
+            CmykMode::Adobe => false,
+            CmykMode::Plain => true,
+        };
+        // The default decoder always assumes inverted CMYK.
+        if header.components == 4 && plain {
+            return decode_plain_cmyk(bytes);
+        }
+    }
+    let err = match reader.decode() {
+        Ok(img) => return Ok(img),
+        Err(err) => err,
+    };
+    let truncated = match &err {
+        ImageError::IoError(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+        // JPEG decoders report a cut-off file as bad scan data; what sets it
+        // apart from a corrupt one is the missing end-of-image marker.
+        _ => format == Some(ImageFormat::Jpeg) && !bytes.ends_with(&[0xFF, 0xD9]),
+    };
+    match format {
+        Some(ImageFormat::Png) if truncated && options.allow_truncated => decode_partial_png(bytes),
+        Some(ImageFormat::Jpeg) if truncated && options.allow_truncated => {
+            // Progressive JPEGs cut before the end of a scan only need the
+            // end-of-image marker the decoder is waiting for.
+            let mut patched = bytes.to_vec();
+            patched.extend_from_slice(&[0xFF, 0xD9]);
+            Ok(image::load_from_memory_with_format(
+                &patched,
+                ImageFormat::Jpeg,
+            )?)
+        }
+        _ if truncated => Err(anyhow::anyhow!(
+            "Image data is truncated (decode_with_options with allow_truncated keeps what is there)"
+        )),
+        _ => Err(err.into()),
+    }
 }
 
+// ---------------------------------------------------------------------------
+// Decode cache
+// ---------------------------------------------------------------------------
+
+/// Hash and length of encoded image bytes.
+type CacheKey = (u64, usize);
+
+struct CacheEntry {
+    key: CacheKey,
+    image: DynamicImage,
+    size: usize,
+}
+
+/// Decoded images of recent `load` calls, least recently used first.
+struct DecodeCache {
+    /// Budget for the decoded pixels in bytes; 0 turns the cache off.
+    limit: usize,
+    used: usize,
+    entries: Vec<CacheEntry>,
+}
+
+static DECODE_CACHE: Mutex<DecodeCache> = Mutex::new(DecodeCache {
+    limit: 0,
+    used: 0,
+    entries: Vec::new(),
+});
+
+fn content_key(bytes: &[u8]) -> CacheKey {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    hasher.write(bytes);
+    (hasher.finish(), bytes.len())
+}
+
+impl DecodeCache {
+    fn evict_to(&mut self, budget: usize) {
+        while self.used > budget && !self.entries.is_empty() {
+            self.used -= self.entries.remove(0).size;
+        }
+    }
+}
+
+fn cache_get(key: CacheKey) -> Option<DynamicImage> {
+    let mut cache = DECODE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let index = cache.entries.iter().position(|e| e.key == key)?;
+    let entry = cache.entries.remove(index);
+    let image = entry.image.clone();
+    cache.entries.push(entry);
+    Some(image)
+}
+
+fn cache_put(key: CacheKey, image: &DynamicImage) {
+    let size = image.as_bytes().len();
+    let mut cache = DECODE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    // Another thread may have decoded the same bytes meanwhile.
+    if size > cache.limit || cache.entries.iter().any(|e| e.key == key) {
+        return;
+    }
+    let budget = cache.limit - size;
+    cache.evict_to(budget);
+    cache.used += size;
+    cache.entries.push(CacheEntry {
+        key,
+        image: image.clone(),
+        size,
+    });
+}
+
+/// Sets the decode cache budget, evicting least recently used images to fit.
+pub fn set_cache_limit(limit: usize) {
+    let mut cache = DECODE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.limit = limit;
+    cache.evict_to(limit);
+}
+
+pub fn clear_cache() {
+    let mut cache = DECODE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.entries.clear();
+    cache.used = 0;
+}
+
+/// What the markers before the first scan of a JPEG say about it.
+pub struct JpegHeader<'a> {
+    /// Start-of-frame marker (0xC0 baseline, 0xC2 progressive, 0xC3
+    /// lossless, ...), or 0 when there is none.
+    pub sof: u8,
+    pub components: u8,
+    /// Whether the file carries an Adobe APP14 marker.
+    pub adobe: bool,
+    /// TIFF structure of the EXIF APP1 segment.
+    pub exif: Option<&'a [u8]>,
+}
+
+pub fn jpeg_header(bytes: &[u8]) -> JpegHeader<'_> {
+    let mut header = JpegHeader {
+        sof: 0,
+        components: 0,
+        adobe: false,
+        exif: None,
+    };
+    let mut i = 2;
+    while i + 4 <= bytes.len() && bytes[i] == 0xFF {
+        let marker = bytes[i + 1];
+        let len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        // The length counts its own two bytes; anything shorter is corrupt.
+        if len < 2 {
+            break;
+        }
+        let segment = &bytes[(i + 4).min(bytes.len())..(i + 2 + len).min(bytes.len())];
+        match marker {
+            0xE1 if segment.starts_with(b"Exif\0\0") => header.exif = Some(&segment[6..]),
+            0xEE if segment.starts_with(b"Adobe") => header.adobe = true,
+            // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC).
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                header.sof = marker;
+                header.components = segment.get(5).copied().unwrap_or(0);
+                break;
+            }
+            0xDA => break,
+            _ => {}
+        }
+        i += 2 + len;
+    }
+    header
+}
+
+fn decode_plain_cmyk(bytes: &[u8]) -> Result<DynamicImage> {
+    use zune_core::colorspace::ColorSpace;
+    let options = zune_core::options::DecoderOptions::default()
+        .set_strict_mode(false)
+        .jpeg_set_out_colorspace(ColorSpace::CMYK);
+    let mut decoder = zune_jpeg::JpegDecoder::new_with_options(
+        zune_core::bytestream::ZCursor::new(bytes),
+        options,
+    );
+    let cmyk = decoder.decode()?;
+    let (w, h) = decoder
+        .dimensions()
+        .ok_or_else(|| anyhow::anyhow!("Could not read JPEG dimensions"))?;
+    let rgb = cmyk
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let k = 255 - p[3] as u32;
+            [0, 1, 2].map(|c| (((255 - p[c] as u32) * k + 127) / 255) as u8)
+        })
+        .collect();
+    RgbImage::from_raw(w as u32, h as u32, rgb)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| anyhow::anyhow!("Unexpected CMYK buffer size"))
+}
+
+/// Decodes as many rows of a truncated PNG as are present. Missing rows of a
+/// plain PNG come out transparent; an interlaced PNG shows its completed
+/// passes upscaled, like a browser would while it loads.
+fn decode_partial_png(bytes: &[u8]) -> Result<DynamicImage> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let (color, _) = reader.output_color_type();
+    let (width, height) = (reader.info().width, reader.info().height);
+    let channels = color.samples();
+    let stride = width as usize * channels;
+    let mut buf = vec![0; stride * height as usize];
+    let mut rows = 0;
+    while let Ok(Some(row)) = reader.next_interlaced_row() {
+        match row.interlace() {
+            png::InterlaceInfo::Null(_) => {
+                buf[rows * stride..(rows + 1) * stride].copy_from_slice(row.data());
+            }
+            png::InterlaceInfo::Adam7(info) => {
+                png::splat_interlaced_row(&mut buf, stride, row.data(), info, channels as u8 * 8);
+            }
+        }
+        rows += 1;
+    }
+    if rows == 0 {
+        return Err(anyhow::anyhow!("No image data before the end of the file"));
+    }
+    let img = match color {
+        png::ColorType::Grayscale => {
+            GrayImage::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+        }
+        png::ColorType::GrayscaleAlpha => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+        }
+        png::ColorType::Rgb => RgbImage::from_raw(width, height, buf).map(DynamicImage::ImageRgb8),
+        _ => RgbaImage::from_raw(width, height, buf).map(DynamicImage::ImageRgba8),
+    }
+    .ok_or_else(|| anyhow::anyhow!("Unexpected PNG buffer size"))?;
+    if reader.info().interlaced || rows >= height as usize {
+        return Ok(img);
+    }
+    let mut out = img.to_rgba8();
+    for y in rows as u32..height {
+        for x in 0..width {
+            out.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+        }
+    }
+    Ok(DynamicImage::ImageRgba8(out))
+}
+
+/// Container format of `bytes`. RAW files have no encoder: TIFF-based ones
+/// (DNG, NEF, CR2, ...) report TIFF, and with the `raw` feature any other RAW
+/// container reports JPEG, so results of operations on them stay encodable.
 pub fn detect_format(bytes: &[u8]) -> Result<ImageFormat> {
-    ImageReader::new(Cursor::new(bytes))
+    let format = ImageReader::new(Cursor::new(bytes))
         .with_guessed_format()?
-        .format()
-        .ok_or_else(|| anyhow::anyhow!("Could not detect image format"))
+        .format();
+    #[cfg(feature = "raw")]
+    if format.is_none() && crate::raw::is_raw(bytes) {
+        return Ok(ImageFormat::Jpeg);
+    }
+    format.ok_or_else(|| anyhow::anyhow!("Could not detect image format"))
 }
 
+/// Encodes `img`, converting pixels only when the target format needs it:
+/// EXR and HDR store floats, PNG and TIFF keep 16-bit (floats become 16-bit
+/// there), and every other format gets 8-bit.
 pub fn encode(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    crate::metrics::time_encode(|| encode_uncounted(img, format))
+}
+
+fn encode_uncounted(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let is_float = matches!(
+        img,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    );
+    let is_16bit = matches!(
+        img,
+        DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_)
+    );
+    let keeps_16bit = matches!(format, ImageFormat::Png | ImageFormat::Tiff);
+    let converted = match format {
+        ImageFormat::OpenExr if !is_float => Some(DynamicImage::ImageRgba32F(img.to_rgba32f())),
+        ImageFormat::Hdr if !matches!(img, DynamicImage::ImageRgb32F(_)) => {
+            Some(DynamicImage::ImageRgb32F(img.to_rgb32f()))
+        }
+        ImageFormat::OpenExr | ImageFormat::Hdr => None,
+        _ if is_float && keeps_16bit => Some(DynamicImage::ImageRgba16(img.to_rgba16())),
+        _ if is_float => Some(DynamicImage::ImageRgba8(img.to_rgba8())),
+        _ if is_16bit && !keeps_16bit => Some(to_8bit(img)),
+        _ => None,
+    };
     let mut buf: Vec<u8> = Vec::new();
-    img.write_to(&mut Cursor::new(&mut buf), format)?;
+    converted
+        .as_ref()
+        .unwrap_or(img)
+        .write_to(&mut Cursor::new(&mut buf), format)?;
     Ok(buf)
 }
 
+/// 8-bit copy of `img` with the same channel layout.
+pub fn to_8bit(img: &DynamicImage) -> DynamicImage {
+    match img {
+        DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma8(img.to_luma8()),
+        DynamicImage::ImageLumaA16(_) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgb32F(_) => {
+            DynamicImage::ImageRgb8(img.to_rgb8())
+        }
+        DynamicImage::ImageRgba16(_) | DynamicImage::ImageRgba32F(_) => {
+            DynamicImage::ImageRgba8(img.to_rgba8())
+        }
+        other => other.clone(),
+    }
+}
+
+/// Decodes an sRGB-encoded value (0-1) to linear light.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.040_45 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear-light value as sRGB, clamped to 0-1.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts a float working copy back to the channel layout and depth of
+/// `color`.
+pub fn from_rgba32f(img: Rgba32FImage, color: ColorType) -> DynamicImage {
+    let img = DynamicImage::ImageRgba32F(img);
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(img.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(img.to_rgb8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(img.to_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(img.to_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(img.to_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(img.to_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(img.to_rgb32f()),
+        _ => img,
+    }
+}
+
 pub fn format_to_string(fmt: ImageFormat) -> String {
     match fmt {
         ImageFormat::Png => "png",
@@ -30,6 +410,8 @@ pub fn format_to_string(fmt: ImageFormat) -> String {
         ImageFormat::Bmp => "bmp",
         ImageFormat::Tiff => "tiff",
         ImageFormat::Ico => "ico",
+        ImageFormat::OpenExr => "exr",
+        ImageFormat::Hdr => "hdr",
         _ => "unknown",
     }
     .to_string()
@@ -44,6 +426,320 @@ pub fn string_to_format(s: &str) -> Result<ImageFormat> {
         "bmp" => Ok(ImageFormat::Bmp),
         "tiff" | "tif" => Ok(ImageFormat::Tiff),
         "ico" => Ok(ImageFormat::Ico),
+        "exr" | "openexr" => Ok(ImageFormat::OpenExr),
+        "hdr" | "rgbe" => Ok(ImageFormat::Hdr),
         other => Err(anyhow::anyhow!("Unsupported format: {}", other)),
     }
 }
+
+// ---------------------------------------------------------------------------
+// Border handling
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BorderMode {
+    Replicate,
+    Reflect,
+    Wrap,
+    Constant,
+}
+
+pub fn string_to_border_mode(s: &str) -> Result<BorderMode> {
+    match s.to_lowercase().as_str() {
+        "replicate" | "clamp" | "edge" => Ok(BorderMode::Replicate),
+        "reflect" | "mirror" => Ok(BorderMode::Reflect),
+        "wrap" | "repeat" => Ok(BorderMode::Wrap),
+        "constant" => Ok(BorderMode::Constant),
+        other => Err(anyhow::anyhow!("Unsupported border mode: {}", other)),
+    }
+}
+
+/// Maps an out-of-range coordinate back into `0..len`, or `None` when the
+/// constant color should be used instead.
+fn border_index(i: i64, len: i64, mode: BorderMode) -> Option<u32> {
+    if (0..len).contains(&i) {
+        return Some(i as u32);
+    }
+    let mapped = match mode {
+        BorderMode::Constant => return None,
+        BorderMode::Replicate => i.clamp(0, len - 1),
+        BorderMode::Wrap => i.rem_euclid(len),
+        BorderMode::Reflect => {
+            // Mirror without repeating the edge pixel (dcb|abcd|cba).
+            if len == 1 {
+                0
+            } else {
+                let period = 2 * (len - 1);
+                let m = i.rem_euclid(period);
+                if m < len {
+                    m
+                } else {
+                    period - m
+                }
+            }
+        }
+    };
+    Some(mapped as u32)
+}
+
+/// Returns a copy of `img` with `margin` extra pixels on every side, filled
+/// according to `mode`.
+pub fn pad<P: Pixel>(img: &Image<P>, margin: u32, mode: BorderMode, fill: P) -> Image<P> {
+    let (w, h) = img.dimensions();
+    let m = margin as i64;
+    ImageBuffer::from_fn(w + 2 * margin, h + 2 * margin, |x, y| {
+        let sx = border_index(x as i64 - m, w as i64, mode);
+        let sy = border_index(y as i64 - m, h as i64, mode);
+        match (sx, sy) {
+            (Some(sx), Some(sy)) => *img.get_pixel(sx, sy),
+            _ => fill,
+        }
+    })
+}
+
+/// Pads `img`, runs `op` on the padded copy and crops the result back to the
+/// original size, so `op` never sees the real image edges.
+pub fn with_border<P, Q, F>(
+    img: &Image<P>,
+    margin: u32,
+    mode: BorderMode,
+    fill: P,
+    op: F,
+) -> Image<Q>
+where
+    P: Pixel,
+    Q: Pixel + 'static,
+    F: FnOnce(&Image<P>) -> Image<Q>,
+{
+    let (w, h) = img.dimensions();
+    if w == 0 || h == 0 {
+        return op(img);
+    }
+    let out = op(&pad(img, margin, mode, fill));
+    image::imageops::crop_imm(&out, margin, margin, w, h).to_image()
+}
+
+// ---------------------------------------------------------------------------
+// Resampling
+// ---------------------------------------------------------------------------
+
+/// Weights of one separable filter pass along an axis: output `o` is the sum
+/// over `t < taps` of input `starts[o] + t` (clamped to the edges) times
+/// `weights[o * taps + t]`.
+pub struct Taps {
+    pub starts: Vec<i32>,
+    pub weights: Vec<f32>,
+    pub taps: usize,
+}
+
+/// Size `DynamicImage::resize` picks to fit `width` x `height` within
+/// `max_width` x `max_height` keeping the aspect ratio.
+pub fn fit_size(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    let ratio = (max_width as f64 / width as f64).min(max_height as f64 / height as f64);
+    let side = |v: u32| ((v as f64 * ratio).round() as u64).clamp(1, u32::MAX as u64) as u32;
+    (side(width), side(height))
+}
+
+/// Lanczos3 taps for resampling `src` pixels to `dst`, computed as the
+/// `image` crate does, so results match `FilterType::Lanczos3`.
+pub fn lanczos3_taps(src: u32, dst: u32) -> Taps {
+    let sinc = |x: f32| {
+        if x == 0.0 {
+            1.0
+        } else {
+            let a = x * std::f32::consts::PI;
+            a.sin() / a
+        }
+    };
+    let lanczos3 = |x: f32| {
+        if x.abs() < 3.0 {
+            sinc(x) * sinc(x / 3.0)
+        } else {
+            0.0
+        }
+    };
+    let ratio = src as f32 / dst as f32;
+    let scale = ratio.max(1.0);
+    let support = 3.0 * scale;
+    let ranges: Vec<(i64, i64, f32)> = (0..dst)
+        .map(|o| {
+            let center = (o as f32 + 0.5) * ratio;
+            let left = ((center - support).floor() as i64).clamp(0, src as i64 - 1);
+            let right = ((center + support).ceil() as i64).clamp(left + 1, src as i64);
+            (left, right, center - 0.5)
+        })
+        .collect();
+    let taps = ranges
+        .iter()
+        .map(|(l, r, _)| (r - l) as usize)
+        .max()
+        .unwrap_or(1);
+    let mut weights = Vec::with_capacity(ranges.len() * taps);
+    for &(left, right, center) in &ranges {
+        let row: Vec<f32> = (left..right)
+            .map(|i| lanczos3((i as f32 - center) / scale))
+            .collect();
+        let sum: f32 = row.iter().sum();
+        weights.extend(row.iter().map(|w| w / sum));
+        weights.resize(weights.len() + taps - row.len(), 0.0);
+    }
+    Taps {
+        starts: ranges.iter().map(|&(left, _, _)| left as i32).collect(),
+        weights,
+        taps,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Channels
+// ---------------------------------------------------------------------------
+
+/// Runs a grayscale operation on each color channel of `img` independently.
+/// When `include_alpha` is false the alpha channel is copied through.
+pub fn map_channels<F>(img: &RgbaImage, include_alpha: bool, f: F) -> RgbaImage
+where
+    F: Fn(&GrayImage) -> GrayImage,
+{
+    let (w, h) = img.dimensions();
+    let channels = if include_alpha { 4 } else { 3 };
+    let mut out = img.clone();
+    for c in 0..channels {
+        let plane = GrayImage::from_fn(w, h, |x, y| Luma([img.get_pixel(x, y).0[c]]));
+        let filtered = f(&plane);
+        for (x, y, p) in out.enumerate_pixels_mut() {
+            p.0[c] = filtered.get_pixel(x, y).0[0];
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Regions
+// ---------------------------------------------------------------------------
+
+/// Intersects a rectangle with the `img_width` × `img_height` image bounds and
+/// returns it as (x, y, width, height), or `None` if nothing is left.
+pub fn clip_rect(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    img_width: u32,
+    img_height: u32,
+) -> Option<(u32, u32, u32, u32)> {
+    let x0 = (x as i64).clamp(0, img_width as i64);
+    let y0 = (y as i64).clamp(0, img_height as i64);
+    let x1 = (x as i64 + width as i64).clamp(0, img_width as i64);
+    let y1 = (y as i64 + height as i64).clamp(0, img_height as i64);
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some((x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32))
+}
+
+// ---------------------------------------------------------------------------
+// Masks & blending
+// ---------------------------------------------------------------------------
+
+pub fn lerp_rgba(a: &Rgba<u8>, b: &Rgba<u8>, t: f32) -> Rgba<u8> {
+    let mut out = [0u8; 4];
+    for (o, (&x, &y)) in out.iter_mut().zip(a.0.iter().zip(b.0.iter())) {
+        *o = (x as f32 + (y as f32 - x as f32) * t)
+            .round()
+            .clamp(0.0, 255.0) as u8;
+    }
+    Rgba(out)
+}
+
+/// Decodes a grayscale mask and checks it matches the image size.
+pub fn load_mask(mask_bytes: &[u8], width: u32, height: u32) -> Result<GrayImage> {
+    let mask = load(mask_bytes)?.to_luma8();
+    if mask.dimensions() != (width, height) {
+        return Err(anyhow::anyhow!(
+            "Mask is {}x{} but the image is {}x{}",
+            mask.width(),
+            mask.height(),
+            width,
+            height
+        ));
+    }
+    Ok(mask)
+}
+
+/// Mixes `top` over `base` with per-pixel weights from `mask`
+/// (255 = only `top`, 0 = only `base`).
+pub fn blend_with_mask(base: &RgbaImage, top: &RgbaImage, mask: &GrayImage) -> RgbaImage {
+    RgbaImage::from_fn(base.width(), base.height(), |x, y| {
+        let t = mask.get_pixel(x, y).0[0] as f32 / 255.0;
+        lerp_rgba(base.get_pixel(x, y), top.get_pixel(x, y), t)
+    })
+}
+
+/// Composites `paint` over `img` with "source over", weighted per pixel by
+/// `coverage` (255 = fully covered) and overall by `opacity` (0-1). Unlike
+/// drawing the color directly, semi-transparent colors let the image show
+/// through.
+pub fn paint_over<F>(img: &mut RgbaImage, coverage: &GrayImage, opacity: f32, paint: F)
+where
+    F: Fn(u32, u32) -> Rgba<u8>,
+{
+    let opacity = opacity.clamp(0.0, 1.0);
+    for (x, y, p) in img.enumerate_pixels_mut() {
+        let c = coverage.get_pixel(x, y).0[0];
+        if c == 0 {
+            continue;
+        }
+        let src = paint(x, y).0;
+        let sa = src[3] as f32 / 255.0 * c as f32 / 255.0 * opacity;
+        if sa <= 0.0 {
+            continue;
+        }
+        let da = p.0[3] as f32 / 255.0;
+        let oa = sa + da * (1.0 - sa);
+        for (d, s) in p.0.iter_mut().zip(src).take(3) {
+            let v = (s as f32 * sa + *d as f32 * da * (1.0 - sa)) / oa;
+            *d = v.round().clamp(0.0, 255.0) as u8;
+        }
+        p.0[3] = (oa * 255.0).round() as u8;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Random numbers
+// ---------------------------------------------------------------------------
+
+/// Small deterministic PRNG (SplitMix64) for seeded effects.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jpeg_header_stops_at_short_segment_length() {
+        let bytes = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let header = jpeg_header(&bytes);
+        assert_eq!(header.sof, 0);
+        assert!(header.exif.is_none());
+        assert!(load(&bytes).is_err());
+    }
+}
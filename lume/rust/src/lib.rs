@@ -1,3 +1,8 @@
 pub mod api;
 mod frb_generated;
+#[cfg(feature = "gpu")]
+mod gpu;
 mod helpers;
+mod metrics;
+#[cfg(feature = "raw")]
+mod raw;
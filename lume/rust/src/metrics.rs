@@ -0,0 +1,173 @@
+//! Per-thread call metrics: time spent decoding and encoding while
+//! recording, and (with the `metrics` feature) peak heap use through a
+//! counting global allocator. Each thread records on its own, so calls made
+//! elsewhere at the same time do not show up in another caller's numbers.
+
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+/// What one thread has recorded since `begin`.
+struct Recording {
+    started: Instant,
+    decode: Duration,
+    encode: Duration,
+    decodes: u32,
+    encodes: u32,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+pub struct Snapshot {
+    pub total: Duration,
+    pub decode: Duration,
+    pub encode: Duration,
+    pub decodes: u32,
+    pub encodes: u32,
+    /// Highest heap use by this thread above its level at `begin`, or `None`
+    /// without the `metrics` feature.
+    pub peak_bytes: Option<u64>,
+}
+
+fn timed<T>(add: impl FnOnce(&mut Recording, Duration), f: impl FnOnce() -> T) -> T {
+    if !ACTIVE.with(Cell::get) {
+        return f();
+    }
+    let t = Instant::now();
+    let out = f();
+    let elapsed = t.elapsed();
+    RECORDING.with_borrow_mut(|recording| {
+        if let Some(recording) = recording {
+            add(recording, elapsed);
+        }
+    });
+    out
+}
+
+pub fn time_decode<T>(f: impl FnOnce() -> T) -> T {
+    timed(
+        |r, d| {
+            r.decode += d;
+            r.decodes += 1;
+        },
+        f,
+    )
+}
+
+pub fn time_encode<T>(f: impl FnOnce() -> T) -> T {
+    timed(
+        |r, d| {
+            r.encode += d;
+            r.encodes += 1;
+        },
+        f,
+    )
+}
+
+/// Starts recording on the current thread from zero.
+pub fn begin() {
+    #[cfg(feature = "metrics")]
+    heap::reset_peak();
+    RECORDING.set(Some(Recording {
+        started: Instant::now(),
+        decode: Duration::ZERO,
+        encode: Duration::ZERO,
+        decodes: 0,
+        encodes: 0,
+    }));
+    ACTIVE.set(true);
+}
+
+/// Stops recording on the current thread and returns what was recorded
+/// since `begin`, or `None` when the thread was not recording.
+pub fn end() -> Option<Snapshot> {
+    let recording = RECORDING.take()?;
+    ACTIVE.set(false);
+    #[cfg(feature = "metrics")]
+    let peak_bytes = Some(heap::peak_above_baseline());
+    #[cfg(not(feature = "metrics"))]
+    let peak_bytes = None;
+    Some(Snapshot {
+        total: recording.started.elapsed(),
+        decode: recording.decode,
+        encode: recording.encode,
+        decodes: recording.decodes,
+        encodes: recording.encodes,
+        peak_bytes,
+    })
+}
+
+#[cfg(feature = "metrics")]
+mod heap {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    // Bytes allocated minus bytes freed by this thread. Memory allocated on
+    // one thread and freed on another moves the counts of both, which is
+    // why these are signed.
+    thread_local! {
+        static CURRENT: Cell<isize> = const { Cell::new(0) };
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+        static BASELINE: Cell<isize> = const { Cell::new(0) };
+    }
+
+    /// The system allocator, counting each thread's live bytes and their
+    /// high-water mark.
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                moved(layout.size() as isize);
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                moved(layout.size() as isize);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            moved(-(layout.size() as isize));
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new = System.realloc(ptr, layout, new_size);
+            if !new.is_null() {
+                moved(new_size as isize - layout.size() as isize);
+            }
+            new
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    fn moved(size: isize) {
+        // `try_with` because threads still allocate while their locals are
+        // being torn down.
+        let _ = CURRENT.try_with(|current| {
+            let now = current.get() + size;
+            current.set(now);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+        });
+    }
+
+    pub fn reset_peak() {
+        let now = CURRENT.with(Cell::get);
+        BASELINE.set(now);
+        PEAK.set(now);
+    }
+
+    pub fn peak_above_baseline() -> u64 {
+        (PEAK.with(Cell::get) - BASELINE.with(Cell::get)).max(0) as u64
+    }
+}
@@ -0,0 +1,176 @@
+//! RAW camera file support (`raw` feature): decoding through rawloader and a
+//! basic development pipeline (black/white levels, white balance, bilinear
+//! demosaic, exposure, sRGB gamma).
+
+use image::metadata::Orientation as ImageOrientation;
+use image::{DynamicImage, ImageFormat, RgbImage};
+use rawloader::{Orientation, RawImage, RawImageData};
+use std::io::Cursor;
+
+/// Whether a file `image` guessed as `format` may be RAW: one whose magic
+/// bytes `image` does not know (CRW, RAF, ORF, RW2, ...), or TIFF, which
+/// most RAW formats (DNG, NEF, CR2, ARW, ...) are built on. `image` would
+/// decode the small preview in the first IFD of those, so they have to go
+/// to rawloader first; `is_raw` tells them apart from ordinary TIFFs without
+/// decoding any pixels.
+pub fn candidate(format: Option<ImageFormat>) -> bool {
+    matches!(format, None | Some(ImageFormat::Tiff))
+}
+
+/// Decodes `bytes` as a camera RAW file, or `None` if rawloader does not
+/// recognise it.
+fn decode(bytes: &[u8]) -> Option<RawImage> {
+    rawloader::decode(&mut Cursor::new(bytes)).ok()
+}
+
+/// Parses only the metadata of a RAW file: rawloader's dummy decode fills
+/// in everything but the pixel data, which it leaves empty.
+fn probe(bytes: &[u8]) -> Option<RawImage> {
+    rawloader::decode_dummy(&mut Cursor::new(bytes)).ok()
+}
+
+/// Whether rawloader recognises `bytes`, from its metadata only.
+pub fn is_raw(bytes: &[u8]) -> bool {
+    probe(bytes).is_some()
+}
+
+/// The EXIF-style orientation of a RAW file in `image`'s terms.
+fn orientation(raw: &RawImage) -> ImageOrientation {
+    match raw.orientation {
+        Orientation::HorizontalFlip => ImageOrientation::FlipHorizontal,
+        Orientation::Rotate180 => ImageOrientation::Rotate180,
+        Orientation::VerticalFlip => ImageOrientation::FlipVertical,
+        Orientation::Transpose => ImageOrientation::Rotate90FlipH,
+        Orientation::Rotate90 => ImageOrientation::Rotate90,
+        Orientation::Transverse => ImageOrientation::Rotate270FlipH,
+        Orientation::Rotate270 => ImageOrientation::Rotate270,
+        _ => ImageOrientation::NoTransforms,
+    }
+}
+
+/// Size left after the file's crop margins, or `None` when they do not fit
+/// in the sensor area (a malformed file).
+fn cropped_size(raw: &RawImage) -> Option<(usize, usize)> {
+    let [top, right, bottom, left] = raw.crops;
+    Some((
+        raw.width.checked_sub(left)?.checked_sub(right)?,
+        raw.height.checked_sub(top)?.checked_sub(bottom)?,
+    ))
+}
+
+/// Output dimensions of a RAW file, cropped and oriented as `try_decode`
+/// returns it, read from the metadata without decoding the pixels.
+pub fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let raw = probe(bytes)?;
+    let (w, h) = cropped_size(&raw)?;
+    let (w, h) = (w as u32, h as u32);
+    let swapped = matches!(
+        orientation(&raw),
+        ImageOrientation::Rotate90
+            | ImageOrientation::Rotate270
+            | ImageOrientation::Rotate90FlipH
+            | ImageOrientation::Rotate270FlipH
+    );
+    Some(if swapped { (h, w) } else { (w, h) })
+}
+
+/// Decodes and develops a RAW file with the given exposure compensation in
+/// stops, or `None` if `bytes` is not RAW (or is malformed).
+pub fn try_decode(bytes: &[u8], exposure_ev: f32) -> Option<DynamicImage> {
+    decode(bytes).and_then(|raw| develop(&raw, exposure_ev))
+}
+
+/// White balance multipliers normalised to green, falling back to neutral
+/// when the file has none.
+fn white_balance(raw: &RawImage) -> [f32; 4] {
+    let wb = raw.wb_coeffs;
+    if wb[1].is_finite() && wb[1] > 0.0 && wb[..3].iter().all(|v| v.is_finite() && *v > 0.0) {
+        let fourth = if wb[3].is_finite() && wb[3] > 0.0 {
+            wb[3]
+        } else {
+            wb[1]
+        };
+        [wb[0] / wb[1], 1.0, wb[2] / wb[1], fourth / wb[1]]
+    } else {
+        [1.0; 4]
+    }
+}
+
+fn develop(raw: &RawImage, exposure_ev: f32) -> Option<DynamicImage> {
+    let (w, h) = (raw.width, raw.height);
+    let (cw, ch) = cropped_size(raw)?;
+    let values: Vec<f32> = match &raw.data {
+        RawImageData::Integer(data) => data.iter().map(|&v| v as f32).collect(),
+        RawImageData::Float(data) => data.clone(),
+    };
+    let wb = white_balance(raw);
+    let normalize = |v: f32, c: usize| {
+        let black = raw.blacklevels[c] as f32;
+        let white = (raw.whitelevels[c] as f32).max(black + 1.0);
+        ((v - black) / (white - black)).max(0.0) * wb[c]
+    };
+
+    // Linear RGB per pixel.
+    let mut rgb = vec![[0.0f32; 3]; w * h];
+    if raw.cpp >= 3 {
+        for (i, px) in rgb.iter_mut().enumerate() {
+            for (c, out) in px.iter_mut().enumerate() {
+                *out = normalize(values[i * raw.cpp + c], c);
+            }
+        }
+    } else {
+        // Bayer-style mosaic; the fourth CFA color (emerald etc.) counts as green.
+        let color = |row: usize, col: usize| raw.cfa.color_at(row, col).min(3);
+        let plane: Vec<f32> = (0..w * h)
+            .map(|i| normalize(values[i], color(i / w, i % w)))
+            .collect();
+        for y in 0..h {
+            for x in 0..w {
+                let mut sums = [0.0f32; 3];
+                let mut counts = [0u32; 3];
+                for ny in y.saturating_sub(1)..(y + 2).min(h) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(w) {
+                        let c = match color(ny, nx) {
+                            3 => 1,
+                            c => c,
+                        };
+                        sums[c] += plane[ny * w + nx];
+                        counts[c] += 1;
+                    }
+                }
+                let own = match color(y, x) {
+                    3 => 1,
+                    c => c,
+                };
+                for (c, out) in rgb[y * w + x].iter_mut().enumerate() {
+                    *out = if c == own {
+                        plane[y * w + x]
+                    } else if counts[c] > 0 {
+                        sums[c] / counts[c] as f32
+                    } else {
+                        0.0
+                    };
+                }
+            }
+        }
+    }
+
+    let gain = 2f32.powf(exposure_ev);
+    let srgb = |v: f32| {
+        let v = (v * gain).clamp(0.0, 1.0);
+        let g = if v <= 0.003_130_8 {
+            12.92 * v
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        };
+        (g * 255.0).round() as u8
+    };
+    let [top, _, _, left] = raw.crops;
+    let out = RgbImage::from_fn(cw as u32, ch as u32, |x, y| {
+        let px = rgb[(y as usize + top) * w + x as usize + left];
+        image::Rgb([srgb(px[0]), srgb(px[1]), srgb(px[2])])
+    });
+    let mut img = DynamicImage::ImageRgb8(out);
+    img.apply_orientation(orientation(raw));
+    Some(img)
+}